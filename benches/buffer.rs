@@ -0,0 +1,84 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rust_uniswap_task::buffer::ReorganizingBuffer;
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+const DEPTH: usize = 5;
+const BLOCK_COUNT: u64 = 1000;
+const REORG_INTERVAL: u64 = 5;
+
+// Counts heap allocations so each scenario can report its allocation rate
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Offsets pushed for `BLOCK_COUNT` new blocks, going back `reorg_depth` blocks at every interval
+fn offsets(reorg_depth: u64) -> Vec<u64> {
+	let mut offsets = Vec::new();
+	for offset in 1..=BLOCK_COUNT {
+		offsets.push(offset);
+		if reorg_depth > 0 && offset % REORG_INTERVAL == 0 {
+			offsets.extend(offset + 1 - reorg_depth..=offset);
+		}
+	}
+	offsets
+}
+
+fn items(offsets: &[u64]) -> Vec<(u64, Vec<u64>)> {
+	offsets.iter().map(|offset| (*offset, vec![*offset; 2])).collect()
+}
+
+fn push_all(items: Vec<(u64, Vec<u64>)>) -> ReorganizingBuffer<u64> {
+	let mut buffer = ReorganizingBuffer::new(DEPTH);
+	for item in items {
+		buffer.push(item).unwrap();
+	}
+	buffer
+}
+
+fn report_allocations(name: &str, offsets: &[u64]) {
+	let items = items(offsets);
+	let before = ALLOCATIONS.load(Ordering::Relaxed);
+	drop(push_all(items));
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+	println!(
+		"push/{}: {:.3} allocations per push",
+		name,
+		allocations as f64 / offsets.len() as f64
+	);
+}
+
+fn push(c: &mut Criterion) {
+	let scenarios = [("sequential", offsets(0)), ("reorg_1", offsets(1)), ("reorg_3", offsets(3))];
+
+	let mut group = c.benchmark_group("push");
+	for (name, offsets) in scenarios {
+		report_allocations(name, &offsets);
+
+		group.throughput(Throughput::Elements(offsets.len() as u64));
+		group.bench_function(name, |b| {
+			b.iter_batched(|| items(&offsets), push_all, BatchSize::SmallInput)
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, push);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rust_uniswap_task::{
+	event::TokenPair,
+	parser::SwapParser,
+	testing::{swap_event_abi, SwapLogBuilder},
+};
+use std::time::Duration;
+use web3::{
+	ethabi::Event,
+	types::{Address, Log, H256, U256},
+};
+
+const BATCH_SIZE: usize = 100;
+
+// Amounts and pool state typical of the mainnet DAI/USDC 0.01% pool around a price of 1.0
+fn swap_log(swap_event: &Event, log_index: u64) -> Log {
+	let router: Address = "0xe592427a0aece92de3edee1f18e0157c05861564".parse().unwrap();
+
+	let mut log = SwapLogBuilder::new(swap_event)
+		.block_number(18_000_000)
+		.transaction_hash(H256::repeat_byte(0xcd))
+		.log_index(log_index)
+		.sender(router)
+		.recipient(router)
+		.amount0(25_000_000_000_000_000_000_000)
+		.amount1(-24_987_654_321)
+		.sqrt_price_x96(U256::from_dec_str("79220240490215316061937").unwrap())
+		.liquidity(1_153_478_293_418_735_284_412)
+		.tick(-276_326)
+		.build();
+	log.block_hash = Some(H256::repeat_byte(0xab));
+	log
+}
+
+fn parse(c: &mut Criterion) {
+	let swap_event = &swap_event_abi();
+	let pair = &TokenPair::dai_usdc();
+
+	let mut group = c.benchmark_group("parse");
+
+	group.throughput(Throughput::Elements(1));
+	group.bench_function("single", |b| {
+		b.iter_batched(
+			|| swap_log(swap_event, 0),
+			|log| SwapParser::parse_log_only(log, swap_event, pair).unwrap(),
+			BatchSize::SmallInput,
+		)
+	});
+
+	group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+	group.bench_function("batch", |b| {
+		b.iter_batched(
+			|| (0..BATCH_SIZE as u64).map(|i| swap_log(swap_event, i)).collect(),
+			|logs| SwapParser::parse_batch(logs, swap_event, pair).unwrap(),
+			BatchSize::SmallInput,
+		)
+	});
+
+	group.finish();
+}
+
+// Changes within 5% are treated as noise, anything beyond is reported as a regression
+fn config() -> Criterion {
+	Criterion::default()
+		.noise_threshold(0.05)
+		.significance_level(0.01)
+		.measurement_time(Duration::from_secs(10))
+}
+
+criterion_group! {
+	name = benches;
+	config = config();
+	targets = parse
+}
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+	// Use the bundled protoc so building doesn't depend on a system installation
+	std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+	tonic_build::configure()
+		.build_client(false)
+		.protoc_arg("--experimental_allow_proto3_optional")
+		.compile(&["proto/pool_monitor.proto"], &["proto"])?;
+
+	Ok(())
+}
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_uniswap_task::buffer::ReorganizingBuffer;
+
+// Deeper buffers don't exercise any additional arithmetic
+const MAX_DEPTH: u8 = 16;
+
+fuzz_target!(|input: (u8, Vec<(u64, Vec<u8>)>)| {
+	let (depth, items) = input;
+	let mut buffer = ReorganizingBuffer::new((depth % MAX_DEPTH).into());
+
+	for item in items {
+		let _ = buffer.push(item);
+	}
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_uniswap_task::{
+	event::TokenPair,
+	parser::SwapParser,
+	testing::{swap_event_abi, SwapLogBuilder},
+};
+use web3::types::Bytes;
+
+fuzz_target!(|data: &[u8]| {
+	let swap_event = swap_event_abi();
+
+	// Keep the valid Swap topics so parsing gets past the signature check
+	let mut log = SwapLogBuilder::new(&swap_event).build();
+	log.data = Bytes(data.to_vec());
+
+	// Malformed data must be reported as an error, never as a panic
+	let _ = SwapParser::parse_log_only(log, &swap_event, &TokenPair::dai_usdc());
+});
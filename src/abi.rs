@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use std::{collections::HashMap, path::Path};
+use web3::{
+	ethabi::{Contract, Event},
+	types::{H160, H256},
+};
+
+use crate::parser::Protocol;
+
+const POOL_ABI: &[u8] = include_bytes!("contracts/uniswap_pool_abi.json");
+const V2_PAIR_ABI: &[u8] = include_bytes!("contracts/uniswap_v2_pair_abi.json");
+
+#[derive(Debug, Clone)]
+pub struct ContractAbi {
+	pub abi: Contract,
+	pub swap_event: Event,
+}
+
+impl ContractAbi {
+	// Fails unless the ABI contains a Swap event
+	pub fn from_json(json: &[u8]) -> Result<ContractAbi> {
+		let abi = Contract::load(json)?;
+		let swap_event = abi.event("Swap").context("Missing Swap event in pool ABI")?.clone();
+
+		Ok(ContractAbi { abi, swap_event })
+	}
+
+	pub fn from_file(path: &Path) -> Result<ContractAbi> {
+		let json = std::fs::read(path)
+			.with_context(|| format!("Failed to read ABI file '{}'", path.display()))?;
+		ContractAbi::from_json(&json)
+			.with_context(|| format!("Failed to parse ABI file '{}'", path.display()))
+	}
+
+	// Embedded ABI of the protocol's pools
+	pub fn embedded(protocol: Protocol) -> Result<ContractAbi> {
+		match protocol {
+			Protocol::V2 => ContractAbi::from_json(V2_PAIR_ABI),
+			Protocol::V3 => ContractAbi::from_json(POOL_ABI),
+		}
+	}
+}
+
+// ABIs of the monitored pools by pool address
+#[derive(Debug, Clone, Default)]
+pub struct AbiRegistry {
+	entries: HashMap<H160, ContractAbi>,
+}
+
+impl AbiRegistry {
+	pub fn new() -> AbiRegistry {
+		AbiRegistry::default()
+	}
+
+	// Embedded Uniswap V3 pool ABI
+	pub fn load_v3_pool(&mut self, address: H160) -> Result<()> {
+		self.entries.insert(address, ContractAbi::embedded(Protocol::V3)?);
+		Ok(())
+	}
+
+	// Embedded Uniswap V2 pair ABI
+	pub fn load_v2_pair(&mut self, address: H160) -> Result<()> {
+		self.entries.insert(address, ContractAbi::embedded(Protocol::V2)?);
+		Ok(())
+	}
+
+	// Embedded ABI of the protocol's pools
+	pub fn load_embedded(&mut self, protocol: Protocol, address: H160) -> Result<()> {
+		match protocol {
+			Protocol::V2 => self.load_v2_pair(address),
+			Protocol::V3 => self.load_v3_pool(address),
+		}
+	}
+
+	pub fn load_from_file(&mut self, address: H160, path: &Path) -> Result<()> {
+		self.entries.insert(address, ContractAbi::from_file(path)?);
+		Ok(())
+	}
+
+	pub fn get(&self, address: H160) -> Option<&ContractAbi> {
+		self.entries.get(&address)
+	}
+
+	// Distinct Swap event signatures of all pools
+	pub fn swap_topics(&self) -> Vec<H256> {
+		let mut topics: Vec<_> =
+			self.entries.values().map(|abi| abi.swap_event.signature()).collect();
+		topics.sort();
+		topics.dedup();
+		topics
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::{swap_event_abi, v2_swap_event_abi};
+
+	// Pool ABI whose Swap event differs from the Uniswap V3 one in its amount types
+	fn custom_abi() -> String {
+		String::from_utf8(POOL_ABI.to_vec())
+			.unwrap()
+			.replace("\"int256\"", "\"int128\"")
+	}
+
+	mod load_v3_pool {
+		use super::*;
+
+		#[test]
+		fn embedded() {
+			let mut registry = AbiRegistry::new();
+			registry.load_v3_pool(H160::from([1; 20])).unwrap();
+
+			assert_eq!(registry.get(H160::from([1; 20])).unwrap().swap_event, swap_event_abi());
+			assert!(registry.get(H160::from([2; 20])).is_none());
+		}
+	}
+
+	mod load_embedded {
+		use super::*;
+
+		#[test]
+		fn protocols() {
+			let mut registry = AbiRegistry::new();
+			registry.load_embedded(Protocol::V2, H160::from([1; 20])).unwrap();
+			registry.load_embedded(Protocol::V3, H160::from([2; 20])).unwrap();
+
+			assert_eq!(registry.get(H160::from([1; 20])).unwrap().swap_event, v2_swap_event_abi());
+			assert_eq!(registry.get(H160::from([2; 20])).unwrap().swap_event, swap_event_abi());
+		}
+	}
+
+	mod load_from_file {
+		use super::*;
+
+		#[test]
+		fn custom() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("pool_abi.json");
+			std::fs::write(&path, custom_abi()).unwrap();
+
+			let mut registry = AbiRegistry::new();
+			registry.load_from_file(H160::from([1; 20]), &path).unwrap();
+
+			let abi = registry.get(H160::from([1; 20])).unwrap();
+			assert_ne!(abi.swap_event.signature(), swap_event_abi().signature());
+			assert_eq!(abi.swap_event.name, "Swap");
+		}
+
+		#[test]
+		fn missing_file() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("missing.json");
+
+			let err = AbiRegistry::new().load_from_file(H160::zero(), &path).unwrap_err();
+
+			assert_eq!(err.to_string(), format!("Failed to read ABI file '{}'", path.display()));
+		}
+
+		#[test]
+		fn invalid_file() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("pool_abi.json");
+			std::fs::write(&path, "{").unwrap();
+
+			let err = AbiRegistry::new().load_from_file(H160::zero(), &path).unwrap_err();
+
+			assert_eq!(err.to_string(), format!("Failed to parse ABI file '{}'", path.display()));
+		}
+
+		#[test]
+		fn without_swap_event() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("factory_abi.json");
+			std::fs::write(&path, include_bytes!("contracts/uniswap_factory_abi.json")).unwrap();
+
+			let err = AbiRegistry::new().load_from_file(H160::zero(), &path).unwrap_err();
+
+			assert_eq!(
+				format!("{:#}", err),
+				format!(
+					"Failed to parse ABI file '{}': Missing Swap event in pool ABI: Invalid name: Swap",
+					path.display()
+				)
+			);
+		}
+	}
+
+	mod swap_topics {
+		use super::*;
+
+		#[test]
+		fn distinct() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("pool_abi.json");
+			std::fs::write(&path, custom_abi()).unwrap();
+
+			let mut registry = AbiRegistry::new();
+			registry.load_v3_pool(H160::from([1; 20])).unwrap();
+			registry.load_v3_pool(H160::from([2; 20])).unwrap();
+			registry.load_from_file(H160::from([3; 20]), &path).unwrap();
+
+			let topics = registry.swap_topics();
+			assert_eq!(topics.len(), 2);
+			assert!(topics.contains(&swap_event_abi().signature()));
+		}
+	}
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::{collections::HashMap, path::Path};
+use web3::types::H160;
+
+// Human-readable names of known addresses, e.g. routers and exchange wallets
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressBook {
+	labels: HashMap<H160, String>,
+}
+
+impl AddressBook {
+	pub fn new(labels: HashMap<H160, String>) -> AddressBook {
+		AddressBook { labels }
+	}
+
+	// Reads a TOML table of labels keyed by address, e.g.
+	// "0xE592427A0AEce92De3Edee1F18E0157C05861564" = "Uniswap V3 Router"
+	pub fn load_from_toml(path: &Path) -> Result<AddressBook> {
+		let contents = std::fs::read_to_string(path)
+			.with_context(|| format!("Failed to read address book '{}'", path.display()))?;
+
+		let labels = toml::from_str(&contents)
+			.with_context(|| format!("Failed to parse address book '{}'", path.display()))?;
+		Ok(AddressBook { labels })
+	}
+
+	pub fn label(&self, addr: &H160) -> Option<&str> {
+		self.labels.get(addr).map(String::as_str)
+	}
+
+	pub fn len(&self) -> usize {
+		self.labels.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.labels.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod load_from_toml {
+		use super::*;
+
+		#[test]
+		fn labels() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("addresses.toml");
+			std::fs::write(
+				&path,
+				"\"0xE592427A0AEce92De3Edee1F18E0157C05861564\" = \"Uniswap V3 Router\"\n\
+				 \"0x0101010101010101010101010101010101010101\" = \"Coinbase 7\"\n",
+			)
+			.unwrap();
+
+			let book = AddressBook::load_from_toml(&path).unwrap();
+
+			let router: H160 = "e592427a0aece92de3edee1f18e0157c05861564".parse().unwrap();
+			assert_eq!(book.len(), 2);
+			assert_eq!(book.label(&router), Some("Uniswap V3 Router"));
+			assert_eq!(book.label(&H160::repeat_byte(1)), Some("Coinbase 7"));
+			assert_eq!(book.label(&H160::repeat_byte(2)), None);
+		}
+
+		#[test]
+		fn invalid_address() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("addresses.toml");
+			std::fs::write(&path, "\"0x01\" = \"Too short\"\n").unwrap();
+
+			let err = AddressBook::load_from_toml(&path).unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				format!("Failed to parse address book '{}'", path.display())
+			);
+		}
+	}
+}
@@ -0,0 +1,1272 @@
+use rust_decimal::{prelude::ToPrimitive, Decimal, MathematicalOps};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::event::{SwapAmounts, SwapDirection, SwapEvent, TokenPair};
+
+#[derive(Debug)]
+pub struct TwapAccumulator {
+	window: usize,
+	rates: VecDeque<(u64, Decimal)>,
+}
+
+impl TwapAccumulator {
+	pub fn new(window: usize) -> TwapAccumulator {
+		TwapAccumulator { window, rates: VecDeque::with_capacity(window) }
+	}
+
+	// Swaps without an exchange rate (zero DAI amount) are ignored
+	pub fn push(&mut self, block_number: u64, event: &SwapEvent) {
+		if self.window == 0 {
+			return;
+		}
+
+		if let Some(rate) = event.exchange_rate() {
+			if self.rates.len() == self.window {
+				self.rates.pop_front();
+			}
+			self.rates.push_back((block_number, rate));
+		}
+	}
+
+	pub fn twap(&self) -> Option<Decimal> {
+		if self.rates.is_empty() {
+			return None;
+		}
+
+		let sum = self
+			.rates
+			.iter()
+			.try_fold(Decimal::ZERO, |sum, (_, rate)| sum.checked_add(*rate))?;
+		sum.checked_div(Decimal::from(self.rates.len()))
+	}
+}
+
+// Average of the last `window` values, kept as a running sum
+#[derive(Debug, Clone)]
+pub struct RollingAverage {
+	window: usize,
+	values: VecDeque<Decimal>,
+	sum: Decimal,
+}
+
+impl RollingAverage {
+	pub fn new(window: usize) -> RollingAverage {
+		RollingAverage { window, values: VecDeque::with_capacity(window), sum: Decimal::ZERO }
+	}
+
+	pub fn push(&mut self, value: Decimal) {
+		if self.window == 0 {
+			return;
+		}
+
+		if self.values.len() == self.window {
+			if let Some(oldest) = self.values.pop_front() {
+				self.sum -= oldest;
+			}
+		}
+		self.values.push_back(value);
+		self.sum += value;
+	}
+
+	// None until the window is full
+	pub fn average(&self) -> Option<Decimal> {
+		match self.is_full() {
+			true => self.sum.checked_div(Decimal::from(self.window)),
+			false => None,
+		}
+	}
+
+	pub fn is_full(&self) -> bool {
+		self.values.len() == self.window
+	}
+}
+
+// Exchange rate in USDC per DAI, derived from the swaps seen so far
+pub trait PriceOracle: Send + Sync {
+	fn current_price(&self) -> Option<Decimal>;
+
+	fn update(&mut self, event: &SwapEvent);
+}
+
+// Average exchange rate of the last `window` swaps
+#[derive(Debug, Clone)]
+pub struct TwapOracle {
+	pub window: usize,
+	accumulator: RollingAverage,
+}
+
+impl TwapOracle {
+	pub fn new(window: usize) -> TwapOracle {
+		TwapOracle { window, accumulator: RollingAverage::new(window) }
+	}
+}
+
+impl PriceOracle for TwapOracle {
+	// None until `window` swaps with an exchange rate were seen
+	fn current_price(&self) -> Option<Decimal> {
+		self.accumulator.average()
+	}
+
+	fn update(&mut self, event: &SwapEvent) {
+		if let Some(rate) = event.exchange_rate() {
+			self.accumulator.push(rate);
+		}
+	}
+}
+
+// Exchange rate of the most recent swap
+#[derive(Debug, Clone, Default)]
+pub struct SpotOracle {
+	last: Option<Decimal>,
+}
+
+impl PriceOracle for SpotOracle {
+	fn current_price(&self) -> Option<Decimal> {
+		self.last
+	}
+
+	fn update(&mut self, event: &SwapEvent) {
+		if let Some(rate) = event.exchange_rate() {
+			self.last = Some(rate);
+		}
+	}
+}
+
+// Realized slippage of swaps against a reference exchange rate in USDC per DAI
+#[derive(Debug, Clone)]
+pub struct SlippageTracker {
+	pub reference_price: Decimal,
+}
+
+impl SlippageTracker {
+	// Uses the oracle's current price as reference, None while it has none
+	pub fn from_oracle(oracle: &dyn PriceOracle) -> Option<SlippageTracker> {
+		Some(SlippageTracker { reference_price: oracle.current_price()? })
+	}
+
+	// Uses the current TWAP as reference, None while it has no rates
+	pub fn from_twap(twap: &TwapAccumulator) -> Option<SlippageTracker> {
+		Some(SlippageTracker { reference_price: twap.twap()? })
+	}
+
+	// Deviation of the swap's exchange rate from the reference in percent, None for swaps without
+	// an exchange rate (zero DAI amount) or a zero reference
+	pub fn measure(&self, event: &SwapEvent) -> Option<Decimal> {
+		let deviation = (event.exchange_rate()? - self.reference_price).abs();
+		Some(deviation.checked_div(self.reference_price)? * Decimal::ONE_HUNDRED)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolHealthStatus {
+	Healthy,
+	StalePrice { blocks_since_last_swap: u64 },
+	// The last swap left no liquidity in range of the current tick
+	ZeroLiquidity,
+}
+
+// Flags pools whose price may be stale because they haven't had a swap in a while
+#[derive(Debug, Clone)]
+pub struct PoolHealthMonitor {
+	pub max_blocks_without_swap: u64,
+	// Until the first swap, blocks are counted from the first checked block
+	last_swap_block: Option<u64>,
+	// Unknown for V2 swaps, which carry no pool state
+	liquidity: Option<u128>,
+}
+
+impl PoolHealthMonitor {
+	pub fn new(max_blocks_without_swap: u64) -> PoolHealthMonitor {
+		PoolHealthMonitor { max_blocks_without_swap, last_swap_block: None, liquidity: None }
+	}
+
+	pub fn record(&mut self, event: &SwapEvent) {
+		if let Some(block_number) = event.block_number {
+			self.last_swap_block = Some(block_number);
+		}
+		if !event.sqrt_price_x96.is_zero() {
+			self.liquidity = Some(event.liquidity);
+		}
+	}
+
+	pub fn check(&mut self, current_block: u64) -> PoolHealthStatus {
+		if self.liquidity == Some(0) {
+			return PoolHealthStatus::ZeroLiquidity;
+		}
+
+		let last_swap_block = *self.last_swap_block.get_or_insert(current_block);
+		// Reorganizations may go back before the last swap
+		let blocks_since_last_swap = current_block.saturating_sub(last_swap_block);
+		match blocks_since_last_swap > self.max_blocks_without_swap {
+			true => PoolHealthStatus::StalePrice { blocks_since_last_swap },
+			false => PoolHealthStatus::Healthy,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLagWarning {
+	pub confirmed: u64,
+	pub latest: u64,
+	pub lag: u64,
+}
+
+// Flags confirmed blocks falling behind the chain head, e.g. because of slow RPC requests or
+// database writes. The confirmation depth counts towards the lag.
+#[derive(Debug, Clone)]
+pub struct BlockLagMonitor {
+	pub max_lag_blocks: u64,
+	last_confirmed: u64,
+	latest_seen: u64,
+}
+
+impl BlockLagMonitor {
+	pub fn new(max_lag_blocks: u64) -> BlockLagMonitor {
+		BlockLagMonitor { max_lag_blocks, last_confirmed: 0, latest_seen: 0 }
+	}
+
+	pub fn update(&mut self, confirmed: u64, latest: u64) -> Option<BlockLagWarning> {
+		self.last_confirmed = confirmed;
+		self.latest_seen = latest;
+
+		let lag = self.lag();
+		(lag > self.max_lag_blocks).then_some(BlockLagWarning { confirmed, latest, lag })
+	}
+
+	// Reorganizations may go back before the last confirmed block
+	pub fn lag(&self) -> u64 {
+		self.latest_seen.saturating_sub(self.last_confirmed)
+	}
+}
+
+// Token0 volume of the swaps in each direction
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct VolumeRatio {
+	pub token0_to_token1_volume: Decimal,
+	pub token1_to_token0_volume: Decimal,
+}
+
+impl VolumeRatio {
+	// Above 1 when more token0 is sold than bought, `None` without token1 to token0 volume
+	pub fn ratio(&self) -> Option<Decimal> {
+		self.token0_to_token1_volume.checked_div(self.token1_to_token0_volume)
+	}
+
+	// Equal volumes count as token0 to token1
+	pub fn dominant_direction(&self) -> SwapDirection {
+		match self.token0_to_token1_volume >= self.token1_to_token0_volume {
+			true => SwapDirection::Token0ToToken1,
+			false => SwapDirection::Token1ToToken0,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct VolumeSnapshot {
+	pub block_number: u64,
+	pub swap_count: usize,
+	pub total_token0_volume: Decimal,
+	pub total_token1_volume: Decimal,
+	// Positive when more token0 flowed into the pools than out of them
+	pub net_token0_flow: Decimal,
+	pub dominant_direction: Option<SwapDirection>,
+	pub volume_ratio: VolumeRatio,
+	// Annualized fee return in percent, only estimated when the pools' TVL is known
+	pub fee_apr: Option<Decimal>,
+}
+
+// Block number is taken from the first event, callers of empty blocks should set it themselves
+pub fn aggregate(events: &[SwapEvent]) -> VolumeSnapshot {
+	let mut total_token0_volume = Decimal::ZERO;
+	let mut total_token1_volume = Decimal::ZERO;
+	let mut volume_ratio = VolumeRatio::default();
+
+	for event in events {
+		total_token0_volume += event.amounts.token0_amount;
+		total_token1_volume += event.amounts.token1_amount;
+		match event.direction {
+			SwapDirection::Token0ToToken1 =>
+				volume_ratio.token0_to_token1_volume += event.amounts.token0_amount,
+			SwapDirection::Token1ToToken0 =>
+				volume_ratio.token1_to_token0_volume += event.amounts.token0_amount,
+		}
+	}
+
+	let net_token0_flow =
+		volume_ratio.token0_to_token1_volume - volume_ratio.token1_to_token0_volume;
+
+	let dominant_direction = match net_token0_flow.cmp(&Decimal::ZERO) {
+		std::cmp::Ordering::Greater => Some(SwapDirection::Token0ToToken1),
+		std::cmp::Ordering::Less => Some(SwapDirection::Token1ToToken0),
+		std::cmp::Ordering::Equal => None,
+	};
+
+	VolumeSnapshot {
+		block_number: events.first().and_then(|event| event.block_number).unwrap_or_default(),
+		swap_count: events.len(),
+		total_token0_volume,
+		total_token1_volume,
+		net_token0_flow,
+		dominant_direction,
+		volume_ratio,
+		fee_apr: None,
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct WhaleDetector {
+	pub token1_threshold: Decimal,
+}
+
+impl WhaleDetector {
+	pub fn is_whale(&self, event: &SwapEvent) -> bool {
+		event.amounts.token1_amount >= self.token1_threshold
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SandwichCandidate<'a> {
+	pub front_run: &'a SwapEvent,
+	pub victim: &'a SwapEvent,
+	pub back_run: &'a SwapEvent,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SandwichDetector;
+
+impl SandwichDetector {
+	// Expects the swaps of a single block in log order. A candidate is a swap surrounded by two
+	// swaps of another sender, the first in the same direction as it and the second reversing the
+	// first.
+	pub fn detect<'a>(&self, events: &'a [SwapEvent]) -> Vec<SandwichCandidate<'a>> {
+		events
+			.windows(3)
+			.filter_map(|window| {
+				let [front_run, victim, back_run] = window else { return None };
+
+				let is_sandwich = front_run.sender == back_run.sender &&
+					front_run.sender != victim.sender &&
+					front_run.direction == victim.direction &&
+					front_run.direction != back_run.direction;
+
+				is_sandwich.then_some(SandwichCandidate { front_run, victim, back_run })
+			})
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArbitrageDirection {
+	// DAI is cheaper in pool A, buy it there and sell it in pool B
+	BuyAToSellB,
+	// DAI is cheaper in pool B, buy it there and sell it in pool A
+	BuyBToSellA,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArbitrageOpportunity {
+	// USDC per DAI implied by the compared swaps
+	pub pool_a_price: Decimal,
+	pub pool_b_price: Decimal,
+	// Price difference relative to the lower price
+	pub spread_bps: u32,
+	pub direction: ArbitrageDirection,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbitrageDetector {
+	pub threshold_bps: u32,
+}
+
+impl ArbitrageDetector {
+	// Reports spreads above the threshold between swaps of two different pools
+	pub fn check(&self, pool_a: &SwapEvent, pool_b: &SwapEvent) -> Option<ArbitrageOpportunity> {
+		if pool_a.pool == pool_b.pool {
+			return None;
+		}
+
+		let pool_a_price = pool_a.exchange_rate()?;
+		let pool_b_price = pool_b.exchange_rate()?;
+
+		let (low, high, direction) = match pool_a_price <= pool_b_price {
+			true => (pool_a_price, pool_b_price, ArbitrageDirection::BuyAToSellB),
+			false => (pool_b_price, pool_a_price, ArbitrageDirection::BuyBToSellA),
+		};
+		let spread_bps = (high - low)
+			.checked_mul(Decimal::from(10_000))?
+			.checked_div(low)?
+			.trunc()
+			.to_u32()?;
+
+		(spread_bps > self.threshold_bps).then_some(ArbitrageOpportunity {
+			pool_a_price,
+			pool_b_price,
+			spread_bps,
+			direction,
+		})
+	}
+}
+
+#[derive(Debug)]
+pub struct NetFlowTracker {
+	window: usize,
+	history: VecDeque<SwapEvent>,
+	// Minimal net USDC flow, in either direction, for the window to count as biased
+	bias_threshold: Decimal,
+}
+
+impl NetFlowTracker {
+	pub fn new(window: usize, bias_threshold: Decimal) -> NetFlowTracker {
+		NetFlowTracker { window, history: VecDeque::with_capacity(window), bias_threshold }
+	}
+
+	pub fn push(&mut self, event: SwapEvent) {
+		if self.window == 0 {
+			return;
+		}
+
+		if self.history.len() == self.window {
+			self.history.pop_front();
+		}
+		self.history.push_back(event);
+	}
+
+	// Flows from the pool's point of view, positive amounts flowed into the pool
+	pub fn net_flow(&self) -> SwapAmounts {
+		let pair = self
+			.history
+			.front()
+			.map_or_else(TokenPair::dai_usdc, |event| event.amounts.pair.clone());
+		let mut net =
+			SwapAmounts { token0_amount: Decimal::ZERO, token1_amount: Decimal::ZERO, pair };
+
+		for event in &self.history {
+			match event.direction {
+				SwapDirection::Token0ToToken1 => {
+					net.token0_amount += event.amounts.token0_amount;
+					net.token1_amount -= event.amounts.token1_amount;
+				},
+				SwapDirection::Token1ToToken0 => {
+					net.token0_amount -= event.amounts.token0_amount;
+					net.token1_amount += event.amounts.token1_amount;
+				},
+			}
+		}
+
+		net
+	}
+
+	pub fn direction_bias(&self) -> Option<SwapDirection> {
+		let net_usdc = self.net_flow().token1_amount;
+
+		if -net_usdc > self.bias_threshold {
+			Some(SwapDirection::Token0ToToken1)
+		} else if net_usdc > self.bias_threshold {
+			Some(SwapDirection::Token1ToToken0)
+		} else {
+			None
+		}
+	}
+}
+
+// A day of mainnet blocks
+pub const BLOCKS_PER_DAY: u64 = 7200;
+
+// Annualized fee return of the liquidity in percent, from the daily volume fees are charged on and
+// the fee tier in hundredths of a basis point. Zero without liquidity.
+pub fn fee_apr(
+	daily_fee_volume: Decimal,
+	total_liquidity_usd: Decimal,
+	fee_tier_bps: u32,
+) -> Decimal {
+	let daily_fees = daily_fee_volume * Decimal::from(fee_tier_bps) / Decimal::from(1_000_000);
+	// Dividing last avoids rounding the intermediate fee rate
+	(daily_fees * Decimal::from(365) * Decimal::ONE_HUNDRED)
+		.checked_div(total_liquidity_usd)
+		.unwrap_or_default()
+}
+
+// USDC volume of the last `window` blocks per fee tier, extrapolated to a day of blocks for
+// fee APR estimates
+#[derive(Debug)]
+pub struct FeeVolumeTracker {
+	window: u64,
+	// Block number, fee tier and volume of each swap, oldest first
+	volumes: VecDeque<(u64, u32, Decimal)>,
+	first_block: Option<u64>,
+	last_block: Option<u64>,
+}
+
+impl FeeVolumeTracker {
+	pub fn new(window: u64) -> FeeVolumeTracker {
+		FeeVolumeTracker {
+			window: window.max(1),
+			volumes: VecDeque::new(),
+			first_block: None,
+			last_block: None,
+		}
+	}
+
+	// Expects every block in order, including blocks without swaps
+	pub fn record(&mut self, block_number: u64, volumes: impl IntoIterator<Item = (u32, Decimal)>) {
+		self.first_block.get_or_insert(block_number);
+		self.last_block = Some(block_number);
+		self.volumes
+			.extend(volumes.into_iter().map(|(fee_tier, volume)| (block_number, fee_tier, volume)));
+
+		let window_start = (block_number + 1).saturating_sub(self.window);
+		while self
+			.volumes
+			.front()
+			.is_some_and(|(block_number, ..)| *block_number < window_start)
+		{
+			self.volumes.pop_front();
+		}
+	}
+
+	// Combined APR of all fee tiers, None before the first block
+	pub fn fee_apr(&self, total_liquidity_usd: Decimal) -> Option<Decimal> {
+		let blocks = (self.last_block? - self.first_block? + 1).min(self.window);
+
+		let mut volumes = BTreeMap::<u32, Decimal>::new();
+		for (_, fee_tier, volume) in &self.volumes {
+			*volumes.entry(*fee_tier).or_default() += volume;
+		}
+
+		Some(
+			volumes
+				.into_iter()
+				.map(|(fee_tier, volume)| {
+					let daily_volume =
+						volume * Decimal::from(BLOCKS_PER_DAY) / Decimal::from(blocks);
+					fee_apr(daily_volume, total_liquidity_usd, fee_tier)
+				})
+				.sum(),
+		)
+	}
+}
+
+// Value lost by a full-range position against holding its tokens, as a fraction, e.g. -0.0572
+// when the price doubles. Zero if the price ratio is undefined (zero entry price) or negative.
+pub fn impermanent_loss(entry_price: Decimal, current_price: Decimal) -> Decimal {
+	let loss = || {
+		let ratio = current_price.checked_div(entry_price)?;
+		Some(Decimal::TWO * ratio.sqrt()? / (Decimal::ONE + ratio) - Decimal::ONE)
+	};
+
+	loss().unwrap_or_default()
+}
+
+// Like `impermanent_loss` for a position concentrated between the lower and upper price. None
+// while the current price is outside the range, or if the entry price is.
+pub fn impermanent_loss_in_range(
+	entry_price: Decimal,
+	lower_price: Decimal,
+	upper_price: Decimal,
+	current_price: Decimal,
+) -> Option<Decimal> {
+	let range = lower_price..=upper_price;
+	if lower_price <= Decimal::ZERO ||
+		!range.contains(&entry_price) ||
+		!range.contains(&current_price)
+	{
+		return None;
+	}
+
+	let (sqrt_entry, sqrt_lower, sqrt_upper, sqrt_current) =
+		(entry_price.sqrt()?, lower_price.sqrt()?, upper_price.sqrt()?, current_price.sqrt()?);
+
+	// Per unit of liquidity, in units of the quote token
+	let position = Decimal::TWO * sqrt_current - sqrt_lower - current_price / sqrt_upper;
+	let held = current_price / sqrt_entry + sqrt_entry - sqrt_lower - current_price / sqrt_upper;
+
+	Some(position.checked_div(held)? - Decimal::ONE)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+
+	fn swap(dai: i64, usdc: i64) -> SwapEvent {
+		SwapEventBuilder::default()
+			.token0_amount(Decimal::from(dai))
+			.token1_amount(Decimal::from(usdc))
+			.build()
+	}
+
+	mod twap {
+		use super::*;
+
+		#[test]
+		fn empty() {
+			assert_eq!(TwapAccumulator::new(3).twap(), None);
+		}
+
+		#[test]
+		fn partial_window() {
+			let mut accumulator = TwapAccumulator::new(3);
+			accumulator.push(1, &swap(100, 99));
+			accumulator.push(2, &swap(100, 101));
+
+			assert_eq!(accumulator.twap(), Some(Decimal::ONE));
+		}
+
+		#[test]
+		fn rolling_window() {
+			let mut accumulator = TwapAccumulator::new(3);
+			for (block_number, usdc) in [(1, 90), (2, 100), (3, 101), (4, 102)] {
+				accumulator.push(block_number, &swap(100, usdc));
+			}
+
+			assert_eq!(accumulator.twap(), Some(Decimal::new(101, 2)));
+		}
+
+		#[test]
+		fn skips_zero_dai() {
+			let mut accumulator = TwapAccumulator::new(3);
+			accumulator.push(1, &swap(100, 98));
+			accumulator.push(2, &swap(0, 100));
+
+			assert_eq!(accumulator.twap(), Some(Decimal::new(98, 2)));
+		}
+
+		#[test]
+		fn zero_window() {
+			let mut accumulator = TwapAccumulator::new(0);
+			accumulator.push(1, &swap(100, 98));
+
+			assert_eq!(accumulator.twap(), None);
+		}
+	}
+
+	mod rolling_average {
+		use super::*;
+
+		#[test]
+		fn full_window() {
+			let mut average = RollingAverage::new(3);
+
+			let averages: Vec<_> = [1, 2, 3, 4]
+				.into_iter()
+				.map(|value| {
+					average.push(Decimal::from(value));
+					average.average()
+				})
+				.collect();
+
+			assert_eq!(
+				averages,
+				vec![None, None, Some(Decimal::new(200, 2)), Some(Decimal::new(300, 2))]
+			);
+			assert!(average.is_full());
+		}
+
+		#[test]
+		fn zero_window() {
+			let mut average = RollingAverage::new(0);
+			average.push(Decimal::ONE);
+
+			assert!(average.is_full());
+			assert_eq!(average.average(), None);
+		}
+	}
+
+	mod twap_oracle {
+		use super::*;
+
+		#[test]
+		fn average_of_window() {
+			let mut oracle = TwapOracle::new(2);
+			oracle.update(&swap(100, 98));
+			assert_eq!(oracle.current_price(), None);
+
+			// Swaps without an exchange rate are skipped
+			oracle.update(&swap(0, 98));
+			oracle.update(&swap(100, 100));
+			oracle.update(&swap(100, 104));
+
+			assert_eq!(oracle.current_price(), Some(Decimal::new(102, 2)));
+		}
+	}
+
+	mod spot_oracle {
+		use super::*;
+
+		#[test]
+		fn most_recent() {
+			let mut oracle = SpotOracle::default();
+			assert_eq!(oracle.current_price(), None);
+
+			oracle.update(&swap(100, 98));
+			oracle.update(&swap(100, 101));
+			oracle.update(&swap(0, 98));
+
+			assert_eq!(oracle.current_price(), Some(Decimal::new(101, 2)));
+		}
+	}
+
+	mod pool_health_monitor {
+		use super::*;
+		use web3::types::U256;
+
+		fn swap(block_number: u64, liquidity: u128) -> SwapEvent {
+			SwapEventBuilder::default()
+				.block_number(block_number)
+				.sqrt_price_x96(U256::one() << 96)
+				.liquidity(liquidity)
+				.build()
+		}
+
+		#[test]
+		fn stale_price() {
+			let mut monitor = PoolHealthMonitor::new(2);
+
+			assert_eq!(monitor.check(100), PoolHealthStatus::Healthy);
+			assert_eq!(
+				monitor.check(103),
+				PoolHealthStatus::StalePrice { blocks_since_last_swap: 3 }
+			);
+
+			monitor.record(&swap(104, 1_000));
+			assert_eq!(monitor.check(104), PoolHealthStatus::Healthy);
+			assert_eq!(monitor.check(106), PoolHealthStatus::Healthy);
+			assert_eq!(
+				monitor.check(107),
+				PoolHealthStatus::StalePrice { blocks_since_last_swap: 3 }
+			);
+		}
+
+		#[test]
+		fn zero_liquidity() {
+			let mut monitor = PoolHealthMonitor::new(2);
+
+			monitor.record(&swap(100, 0));
+			assert_eq!(monitor.check(100), PoolHealthStatus::ZeroLiquidity);
+
+			monitor.record(&swap(101, 1_000));
+			assert_eq!(monitor.check(101), PoolHealthStatus::Healthy);
+		}
+
+		#[test]
+		fn v2_swaps() {
+			let mut monitor = PoolHealthMonitor::new(2);
+
+			monitor.record(&SwapEvent { sqrt_price_x96: U256::zero(), ..swap(100, 0) });
+
+			assert_eq!(monitor.check(100), PoolHealthStatus::Healthy);
+		}
+	}
+
+	mod block_lag_monitor {
+		use super::*;
+
+		#[test]
+		fn within_max_lag() {
+			let mut monitor = BlockLagMonitor::new(10);
+
+			assert_eq!(monitor.update(100, 105), None);
+			assert_eq!(monitor.update(100, 110), None);
+			assert_eq!(monitor.lag(), 10);
+		}
+
+		#[test]
+		fn exceeds_max_lag() {
+			let mut monitor = BlockLagMonitor::new(10);
+
+			assert_eq!(
+				monitor.update(100, 111),
+				Some(BlockLagWarning { confirmed: 100, latest: 111, lag: 11 })
+			);
+			assert_eq!(monitor.update(106, 111), None);
+		}
+
+		#[test]
+		fn reorganized_below_confirmed() {
+			let mut monitor = BlockLagMonitor::new(0);
+
+			assert_eq!(monitor.update(100, 99), None);
+			assert_eq!(monitor.lag(), 0);
+		}
+	}
+
+	mod slippage_tracker {
+		use super::*;
+
+		#[test]
+		fn measure() {
+			let tracker = SlippageTracker { reference_price: Decimal::ONE };
+
+			assert_eq!(tracker.measure(&swap(100, 98)), Some(Decimal::new(200, 2)));
+			assert_eq!(tracker.measure(&swap(100, 101)), Some(Decimal::new(100, 2)));
+			assert_eq!(tracker.measure(&swap(0, 101)), None);
+		}
+
+		#[test]
+		fn zero_reference() {
+			let tracker = SlippageTracker { reference_price: Decimal::ZERO };
+
+			assert_eq!(tracker.measure(&swap(100, 98)), None);
+		}
+
+		#[test]
+		fn from_oracle() {
+			let mut oracle = SpotOracle::default();
+			assert!(SlippageTracker::from_oracle(&oracle).is_none());
+
+			oracle.update(&swap(100, 98));
+
+			let tracker = SlippageTracker::from_oracle(&oracle).unwrap();
+			assert_eq!(tracker.measure(&swap(100, 98)), Some(Decimal::ZERO));
+		}
+
+		#[test]
+		fn from_twap() {
+			let mut twap = TwapAccumulator::new(2);
+			assert!(SlippageTracker::from_twap(&twap).is_none());
+
+			twap.push(1, &swap(100, 98));
+			twap.push(2, &swap(100, 102));
+
+			let tracker = SlippageTracker::from_twap(&twap).unwrap();
+			assert_eq!(tracker.reference_price, Decimal::ONE);
+		}
+	}
+
+	mod aggregate {
+		use super::*;
+
+		fn directed_swap(direction: SwapDirection, dai: i64, usdc: i64) -> SwapEvent {
+			SwapEvent { block_number: Some(42), direction, ..swap(dai, usdc) }
+		}
+
+		#[test]
+		fn empty_block() {
+			let snapshot = aggregate(&[]);
+
+			assert_eq!(
+				snapshot,
+				VolumeSnapshot {
+					block_number: 0,
+					swap_count: 0,
+					total_token0_volume: Decimal::ZERO,
+					total_token1_volume: Decimal::ZERO,
+					net_token0_flow: Decimal::ZERO,
+					dominant_direction: None,
+					volume_ratio: VolumeRatio::default(),
+					fee_apr: None,
+				}
+			);
+		}
+
+		#[test]
+		fn single_direction() {
+			let snapshot = aggregate(&[
+				directed_swap(SwapDirection::Token1ToToken0, 100, 101),
+				directed_swap(SwapDirection::Token1ToToken0, 50, 51),
+			]);
+
+			assert_eq!(
+				snapshot,
+				VolumeSnapshot {
+					block_number: 42,
+					swap_count: 2,
+					total_token0_volume: Decimal::from(150),
+					total_token1_volume: Decimal::from(152),
+					net_token0_flow: Decimal::from(-150),
+					dominant_direction: Some(SwapDirection::Token1ToToken0),
+					volume_ratio: VolumeRatio {
+						token0_to_token1_volume: Decimal::ZERO,
+						token1_to_token0_volume: Decimal::from(150),
+					},
+					fee_apr: None,
+				}
+			);
+		}
+
+		#[test]
+		fn mixed_directions() {
+			let snapshot = aggregate(&[
+				directed_swap(SwapDirection::Token0ToToken1, 100, 99),
+				directed_swap(SwapDirection::Token1ToToken0, 30, 31),
+				directed_swap(SwapDirection::Token0ToToken1, 20, 19),
+			]);
+
+			assert_eq!(
+				snapshot,
+				VolumeSnapshot {
+					block_number: 42,
+					swap_count: 3,
+					total_token0_volume: Decimal::from(150),
+					total_token1_volume: Decimal::from(149),
+					net_token0_flow: Decimal::from(90),
+					dominant_direction: Some(SwapDirection::Token0ToToken1),
+					volume_ratio: VolumeRatio {
+						token0_to_token1_volume: Decimal::from(120),
+						token1_to_token0_volume: Decimal::from(30),
+					},
+					fee_apr: None,
+				}
+			);
+		}
+
+		#[test]
+		fn balanced_flow() {
+			let snapshot = aggregate(&[
+				directed_swap(SwapDirection::Token0ToToken1, 100, 99),
+				directed_swap(SwapDirection::Token1ToToken0, 100, 101),
+			]);
+
+			assert_eq!(snapshot.net_token0_flow, Decimal::ZERO);
+			assert_eq!(snapshot.dominant_direction, None);
+		}
+	}
+
+	mod volume_ratio {
+		use super::*;
+
+		fn volume_ratio(token0_to_token1: i64, token1_to_token0: i64) -> VolumeRatio {
+			VolumeRatio {
+				token0_to_token1_volume: Decimal::from(token0_to_token1),
+				token1_to_token0_volume: Decimal::from(token1_to_token0),
+			}
+		}
+
+		#[test]
+		fn ratio() {
+			assert_eq!(volume_ratio(150, 100).ratio(), Some(Decimal::new(15, 1)));
+			assert_eq!(volume_ratio(0, 100).ratio(), Some(Decimal::ZERO));
+		}
+
+		#[test]
+		fn without_token1_to_token0_volume() {
+			assert_eq!(volume_ratio(100, 0).ratio(), None);
+			assert_eq!(VolumeRatio::default().ratio(), None);
+		}
+
+		#[test]
+		fn dominant_direction() {
+			assert_eq!(volume_ratio(150, 100).dominant_direction(), SwapDirection::Token0ToToken1);
+			assert_eq!(volume_ratio(100, 150).dominant_direction(), SwapDirection::Token1ToToken0);
+			assert_eq!(volume_ratio(100, 100).dominant_direction(), SwapDirection::Token0ToToken1);
+		}
+	}
+
+	mod is_whale {
+		use super::*;
+
+		#[test]
+		fn threshold_boundary() {
+			let detector = WhaleDetector { token1_threshold: Decimal::new(10_000_000, 2) };
+
+			assert!(!detector.is_whale(&swap(100_000, 99_999)));
+			assert!(detector.is_whale(&swap(100_000, 100_000)));
+			assert!(detector.is_whale(&swap(100_000, 100_001)));
+		}
+	}
+
+	mod sandwich_detector {
+		use super::*;
+		use web3::types::Address;
+
+		fn swap_by(sender: u8, direction: SwapDirection) -> SwapEvent {
+			SwapEvent { sender: Address::from([sender; 20]), direction, ..swap(100, 100) }
+		}
+
+		#[test]
+		fn sandwich() {
+			let events = [
+				swap_by(1, SwapDirection::Token1ToToken0),
+				swap_by(2, SwapDirection::Token1ToToken0),
+				swap_by(1, SwapDirection::Token0ToToken1),
+			];
+
+			assert_eq!(
+				SandwichDetector.detect(&events),
+				vec![SandwichCandidate {
+					front_run: &events[0],
+					victim: &events[1],
+					back_run: &events[2],
+				}]
+			);
+		}
+
+		#[test]
+		fn within_longer_block() {
+			let events = [
+				swap_by(3, SwapDirection::Token0ToToken1),
+				swap_by(1, SwapDirection::Token0ToToken1),
+				swap_by(2, SwapDirection::Token0ToToken1),
+				swap_by(1, SwapDirection::Token1ToToken0),
+				swap_by(4, SwapDirection::Token1ToToken0),
+			];
+
+			let candidates = SandwichDetector.detect(&events);
+
+			assert_eq!(candidates.len(), 1);
+			assert_eq!(candidates[0].victim, &events[2]);
+		}
+
+		#[test]
+		fn no_sandwich() {
+			let not_reversed = [
+				swap_by(1, SwapDirection::Token0ToToken1),
+				swap_by(2, SwapDirection::Token0ToToken1),
+				swap_by(1, SwapDirection::Token0ToToken1),
+			];
+			let same_sender = [
+				swap_by(1, SwapDirection::Token0ToToken1),
+				swap_by(1, SwapDirection::Token0ToToken1),
+				swap_by(1, SwapDirection::Token1ToToken0),
+			];
+			let opposite_victim = [
+				swap_by(1, SwapDirection::Token0ToToken1),
+				swap_by(2, SwapDirection::Token1ToToken0),
+				swap_by(1, SwapDirection::Token1ToToken0),
+			];
+
+			assert_eq!(SandwichDetector.detect(&not_reversed), vec![]);
+			assert_eq!(SandwichDetector.detect(&same_sender), vec![]);
+			assert_eq!(SandwichDetector.detect(&opposite_victim), vec![]);
+			assert_eq!(SandwichDetector.detect(&not_reversed[..2]), vec![]);
+		}
+	}
+
+	mod arbitrage_detector {
+		use super::*;
+		use web3::types::Address;
+
+		fn pool_swap(pool: u8, dai: i64, usdc: i64) -> SwapEvent {
+			SwapEvent { pool: Address::from([pool; 20]), ..swap(dai, usdc) }
+		}
+
+		#[test]
+		fn above_threshold() {
+			let detector = ArbitrageDetector { threshold_bps: 10 };
+
+			assert_eq!(
+				detector.check(&pool_swap(1, 1000, 999), &pool_swap(2, 1000, 1002)),
+				Some(ArbitrageOpportunity {
+					pool_a_price: Decimal::new(999, 3),
+					pool_b_price: Decimal::new(1002, 3),
+					spread_bps: 30,
+					direction: ArbitrageDirection::BuyAToSellB,
+				})
+			);
+			assert_eq!(
+				detector
+					.check(&pool_swap(1, 1000, 1002), &pool_swap(2, 1000, 999))
+					.map(|opportunity| opportunity.direction),
+				Some(ArbitrageDirection::BuyBToSellA)
+			);
+		}
+
+		#[test]
+		fn within_threshold() {
+			let detector = ArbitrageDetector { threshold_bps: 30 };
+
+			assert_eq!(detector.check(&pool_swap(1, 1000, 999), &pool_swap(2, 1000, 1002)), None);
+		}
+
+		#[test]
+		fn same_pool() {
+			let detector = ArbitrageDetector { threshold_bps: 0 };
+
+			assert_eq!(detector.check(&pool_swap(1, 1000, 900), &pool_swap(1, 1000, 1100)), None);
+		}
+
+		#[test]
+		fn missing_price() {
+			let detector = ArbitrageDetector { threshold_bps: 0 };
+
+			assert_eq!(detector.check(&pool_swap(1, 0, 900), &pool_swap(2, 1000, 1100)), None);
+		}
+	}
+
+	mod net_flow_tracker {
+		use super::*;
+
+		fn directed_swap(direction: SwapDirection, dai: i64, usdc: i64) -> SwapEvent {
+			SwapEvent { direction, ..swap(dai, usdc) }
+		}
+
+		#[test]
+		fn empty() {
+			let tracker = NetFlowTracker::new(4, Decimal::ZERO);
+
+			assert_eq!(tracker.net_flow(), SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::ZERO));
+			assert_eq!(tracker.direction_bias(), None);
+		}
+
+		#[test]
+		fn alternating() {
+			let mut tracker = NetFlowTracker::new(4, Decimal::TEN);
+			for _ in 0..3 {
+				tracker.push(directed_swap(SwapDirection::Token0ToToken1, 100, 99));
+				tracker.push(directed_swap(SwapDirection::Token1ToToken0, 100, 101));
+			}
+
+			assert_eq!(tracker.net_flow(), SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::from(4)));
+			assert_eq!(tracker.direction_bias(), None);
+		}
+
+		#[test]
+		fn lopsided() {
+			let mut tracker = NetFlowTracker::new(4, Decimal::TEN);
+			tracker.push(directed_swap(SwapDirection::Token1ToToken0, 500, 501));
+			for _ in 0..4 {
+				tracker.push(directed_swap(SwapDirection::Token0ToToken1, 100, 99));
+			}
+
+			assert_eq!(
+				tracker.net_flow(),
+				SwapAmounts::dai_usdc(Decimal::from(400), Decimal::from(-396))
+			);
+			assert_eq!(tracker.direction_bias(), Some(SwapDirection::Token0ToToken1));
+		}
+
+		#[test]
+		fn lopsided_usdc_inflow() {
+			let mut tracker = NetFlowTracker::new(4, Decimal::TEN);
+			tracker.push(directed_swap(SwapDirection::Token1ToToken0, 100, 101));
+			tracker.push(directed_swap(SwapDirection::Token1ToToken0, 100, 101));
+
+			assert_eq!(tracker.direction_bias(), Some(SwapDirection::Token1ToToken0));
+		}
+
+		#[test]
+		fn within_threshold() {
+			let mut tracker = NetFlowTracker::new(4, Decimal::TEN);
+			tracker.push(directed_swap(SwapDirection::Token0ToToken1, 10, 10));
+
+			assert_eq!(tracker.direction_bias(), None);
+		}
+	}
+
+	mod impermanent_loss {
+		use super::*;
+
+		fn loss(entry_price: i64, current_price: i64) -> Decimal {
+			impermanent_loss(Decimal::from(entry_price), Decimal::from(current_price)).round_dp(4)
+		}
+
+		#[test]
+		fn known_values() {
+			assert_eq!(loss(100, 200), Decimal::new(-572, 4));
+			assert_eq!(loss(100, 50), Decimal::new(-572, 4));
+			assert_eq!(loss(100, 1000), Decimal::new(-4250, 4));
+		}
+
+		#[test]
+		fn unchanged_price() {
+			assert_eq!(loss(100, 100), Decimal::ZERO);
+		}
+
+		#[test]
+		fn zero_entry_price() {
+			assert_eq!(loss(0, 100), Decimal::ZERO);
+		}
+	}
+
+	mod impermanent_loss_in_range {
+		use super::*;
+
+		fn loss(entry_price: i64, current_price: i64) -> Option<Decimal> {
+			impermanent_loss_in_range(
+				Decimal::from(entry_price),
+				Decimal::from(50),
+				Decimal::from(200),
+				Decimal::from(current_price),
+			)
+			.map(|loss| loss.round_dp(4))
+		}
+
+		#[test]
+		fn amplified() {
+			// Full-range positions lose 5.72% at 2x and 0.5x
+			assert_eq!(loss(100, 200), Some(Decimal::new(-1953, 4)));
+			assert_eq!(loss(100, 50), Some(Decimal::new(-1953, 4)));
+			assert_eq!(loss(100, 150), Some(Decimal::new(-690, 4)));
+			assert_eq!(loss(100, 100), Some(Decimal::ZERO));
+		}
+
+		#[test]
+		fn out_of_range() {
+			assert_eq!(loss(100, 201), None);
+			assert_eq!(loss(100, 49), None);
+			assert_eq!(loss(300, 100), None);
+		}
+
+		#[test]
+		fn wide_range() {
+			let loss = impermanent_loss_in_range(
+				Decimal::from(100),
+				Decimal::new(1, 9),
+				Decimal::from(1_000_000_000_000u64),
+				Decimal::from(200),
+			);
+
+			assert_eq!(loss.map(|loss| loss.round_dp(4)), Some(Decimal::new(-572, 4)));
+		}
+	}
+	mod fee_apr {
+		use super::*;
+
+		#[test]
+		fn known_values() {
+			// $10M daily volume at 0.01% on $50M of liquidity
+			assert_eq!(
+				fee_apr(Decimal::from(10_000_000), Decimal::from(50_000_000), 100),
+				Decimal::new(73, 2)
+			);
+			// $1M daily volume at 0.3% on $10M of liquidity
+			assert_eq!(
+				fee_apr(Decimal::from(1_000_000), Decimal::from(10_000_000), 3000),
+				Decimal::new(1095, 2)
+			);
+		}
+
+		#[test]
+		fn without_liquidity() {
+			assert_eq!(fee_apr(Decimal::from(1_000_000), Decimal::ZERO, 100), Decimal::ZERO);
+		}
+	}
+
+	mod fee_volume_tracker {
+		use super::*;
+
+		#[test]
+		fn extrapolates_to_day() {
+			let mut tracker = FeeVolumeTracker::new(BLOCKS_PER_DAY);
+			assert_eq!(tracker.fee_apr(Decimal::ONE), None);
+
+			// $1,000 at 0.01% in each of two blocks is $7.2M a day
+			tracker.record(100, [(100, Decimal::from(1_000))]);
+			tracker.record(101, [(100, Decimal::from(600)), (100, Decimal::from(400))]);
+
+			assert_eq!(tracker.fee_apr(Decimal::from(7_200_000)), Some(Decimal::new(365, 2)));
+		}
+
+		#[test]
+		fn fee_tiers() {
+			let mut tracker = FeeVolumeTracker::new(BLOCKS_PER_DAY);
+
+			tracker.record(100, [(100, Decimal::from(1_000)), (500, Decimal::from(1_000))]);
+
+			// 0.01% and 0.05% of $7.2M a day each
+			assert_eq!(tracker.fee_apr(Decimal::from(7_200_000)), Some(Decimal::new(2190, 2)));
+		}
+
+		#[test]
+		fn window() {
+			let mut tracker = FeeVolumeTracker::new(2);
+
+			tracker.record(100, [(100, Decimal::from(5_000))]);
+			tracker.record(101, []);
+			tracker.record(102, [(100, Decimal::from(2_000))]);
+
+			// Only block 102 of the last two blocks had volume
+			assert_eq!(tracker.fee_apr(Decimal::from(7_200_000)), Some(Decimal::new(365, 2)));
+		}
+	}
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use axum::{
+	extract::{Query, State},
+	response::{IntoResponse, Response},
+	routing::get,
+	Json, Router,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::VecDeque,
+	future::Future,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+use tower_http::trace::TraceLayer;
+
+use crate::{analytics::VolumeSnapshot, event::SwapEvent, output::JsonSwapEvent, server};
+
+pub const DEFAULT_API_ADDR: &str = "127.0.0.1:8080";
+pub const RECENT_SWAPS_CAPACITY: usize = 1000;
+
+// Most recently confirmed swaps and volume statistics, shared with the API server
+#[derive(Debug)]
+pub struct RecentSwaps {
+	capacity: usize,
+	inner: Mutex<RecentSwapsInner>,
+}
+
+#[derive(Debug, Default)]
+struct RecentSwapsInner {
+	events: VecDeque<SwapEvent>,
+	latest_block: Option<VolumeSnapshot>,
+	totals: CumulativeTotals,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CumulativeTotals {
+	pub block_count: u64,
+	pub swap_count: u64,
+	pub token0_volume: Decimal,
+	pub token1_volume: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Stats {
+	pub latest_block: Option<VolumeSnapshot>,
+	pub totals: CumulativeTotals,
+}
+
+impl RecentSwaps {
+	pub fn new(capacity: usize) -> RecentSwaps {
+		RecentSwaps { capacity, inner: Mutex::default() }
+	}
+
+	pub fn record(&self, snapshot: VolumeSnapshot, events: &[SwapEvent]) {
+		let mut inner = self.inner.lock().unwrap();
+
+		for event in events {
+			if inner.events.len() == self.capacity {
+				inner.events.pop_front();
+			}
+			if self.capacity > 0 {
+				inner.events.push_back(event.clone());
+			}
+		}
+
+		inner.totals.block_count += 1;
+		inner.totals.swap_count += snapshot.swap_count as u64;
+		inner.totals.token0_volume += snapshot.total_token0_volume;
+		inner.totals.token1_volume += snapshot.total_token1_volume;
+		inner.latest_block = Some(snapshot);
+	}
+
+	// Latest swaps, oldest first, optionally only those confirmed at or after `since_block`
+	pub fn swaps(&self, limit: Option<usize>, since_block: Option<u64>) -> Vec<SwapEvent> {
+		let inner = self.inner.lock().unwrap();
+
+		let mut events: Vec<_> = inner
+			.events
+			.iter()
+			.rev()
+			.filter(|event| since_block.map_or(true, |since| event.block_number >= Some(since)))
+			.take(limit.unwrap_or(usize::MAX))
+			.cloned()
+			.collect();
+		events.reverse();
+		events
+	}
+
+	pub fn stats(&self) -> Stats {
+		let inner = self.inner.lock().unwrap();
+		Stats { latest_block: inner.latest_block.clone(), totals: inner.totals.clone() }
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapsQuery {
+	limit: Option<usize>,
+	since_block: Option<u64>,
+}
+
+pub fn router(recent: Arc<RecentSwaps>) -> Router {
+	Router::new()
+		.route("/swaps", get(swaps))
+		.route("/stats", get(stats))
+		.layer(TraceLayer::new_for_http())
+		.with_state(recent)
+}
+
+pub fn serve(
+	addr: SocketAddr,
+	recent: Arc<RecentSwaps>,
+) -> Result<impl Future<Output = hyper::Result<()>>> {
+	Ok(axum::Server::from_tcp(server::bind(addr)?)?.serve(router(recent).into_make_service()))
+}
+
+async fn swaps(
+	State(recent): State<Arc<RecentSwaps>>,
+	Query(query): Query<SwapsQuery>,
+) -> Response {
+	let events = recent.swaps(query.limit, query.since_block);
+	Json(events.iter().map(JsonSwapEvent::from).collect::<Vec<_>>()).into_response()
+}
+
+async fn stats(State(recent): State<Arc<RecentSwaps>>) -> Json<Stats> {
+	Json(recent.stats())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{analytics, event::SwapEventBuilder};
+	use axum::{
+		body::Body,
+		http::{Request, StatusCode},
+	};
+	use tower::ServiceExt;
+
+	fn swap(block_number: u64, dai: i64) -> SwapEvent {
+		SwapEventBuilder::default()
+			.block_number(block_number)
+			.token0_amount(Decimal::from(dai))
+			.token1_amount(Decimal::from(dai))
+			.build()
+	}
+
+	fn record(recent: &RecentSwaps, events: &[SwapEvent]) {
+		recent.record(analytics::aggregate(events), events);
+	}
+
+	mod swaps {
+		use super::*;
+
+		#[test]
+		fn capacity() {
+			let recent = RecentSwaps::new(2);
+			record(&recent, &[swap(1, 10), swap(1, 11)]);
+			record(&recent, &[swap(2, 12)]);
+
+			assert_eq!(recent.swaps(None, None), vec![swap(1, 11), swap(2, 12)]);
+		}
+
+		#[test]
+		fn limit_and_since_block() {
+			let recent = RecentSwaps::new(10);
+			record(&recent, &[swap(1, 10)]);
+			record(&recent, &[swap(2, 11), swap(2, 12)]);
+			record(&recent, &[swap(3, 13)]);
+
+			assert_eq!(recent.swaps(Some(2), None), vec![swap(2, 12), swap(3, 13)]);
+			assert_eq!(recent.swaps(None, Some(2)), vec![swap(2, 11), swap(2, 12), swap(3, 13)]);
+			assert_eq!(recent.swaps(Some(1), Some(4)), vec![]);
+		}
+	}
+
+	mod stats {
+		use super::*;
+
+		#[test]
+		fn totals() {
+			let recent = RecentSwaps::new(10);
+			record(&recent, &[swap(1, 10), swap(1, 5)]);
+			record(&recent, &[]);
+
+			let stats = recent.stats();
+
+			assert_eq!(stats.latest_block.unwrap().swap_count, 0);
+			assert_eq!(
+				stats.totals,
+				CumulativeTotals {
+					block_count: 2,
+					swap_count: 2,
+					token0_volume: Decimal::from(15),
+					token1_volume: Decimal::from(15),
+				}
+			);
+		}
+	}
+
+	mod router {
+		use super::*;
+
+		async fn get(recent: Arc<RecentSwaps>, uri: &str) -> (StatusCode, serde_json::Value) {
+			let request = Request::get(uri).body(Body::empty()).unwrap();
+			let response = router(recent).oneshot(request).await.unwrap();
+
+			let status = response.status();
+			let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+			(status, serde_json::from_slice(&body).unwrap_or_default())
+		}
+
+		#[tokio::test]
+		async fn swaps() {
+			let recent = Arc::new(RecentSwaps::new(10));
+			record(&recent, &[swap(1, 10)]);
+			record(&recent, &[swap(2, 11)]);
+
+			let (status, body) = get(recent, "/swaps?limit=5&since_block=2").await;
+
+			assert_eq!(status, StatusCode::OK);
+			assert_eq!(body.as_array().unwrap().len(), 1);
+			assert_eq!(body[0]["block_number"], 2);
+			assert_eq!(body[0]["token0_amount"], "11");
+		}
+
+		#[tokio::test]
+		async fn stats() {
+			let recent = Arc::new(RecentSwaps::new(10));
+			record(&recent, &[swap(7, 10)]);
+
+			let (status, body) = get(recent, "/stats").await;
+
+			assert_eq!(status, StatusCode::OK);
+			assert_eq!(body["latest_block"]["block_number"], 7);
+			assert_eq!(body["totals"]["swap_count"], 1);
+			assert_eq!(body["totals"]["token0_volume"], "10");
+		}
+	}
+}
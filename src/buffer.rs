@@ -1,36 +1,73 @@
 use std::collections::VecDeque;
 
 #[derive(Debug)]
-pub(crate) struct ReorganizingBuffer<Value> {
-	pub(crate) depth: usize,
+pub struct ReorganizingBuffer<Value> {
+	pub depth: usize,
 	queue: VecDeque<(u64, Vec<Value>)>,
+	// Offset of the last item that left the queue, it can no longer be reorganized
+	confirmed_offset: Option<u64>,
 }
 
 #[derive(Debug)]
-pub(crate) enum ReorganizingBufferError {
+pub enum ReorganizingBufferError {
 	MissingOffset(u64),
 	DepthExceeded(u64),
+	// Snapshot offsets must be strictly increasing
+	UnorderedOffset(u64),
+	// Number of items of a snapshot longer than the depth allows
+	SnapshotTooLarge(usize),
 }
 
 impl<Value> ReorganizingBuffer<Value> {
-	pub(crate) fn new(depth: usize) -> ReorganizingBuffer<Value> {
-		ReorganizingBuffer { depth, queue: VecDeque::with_capacity(depth + 1) }
+	pub fn new(depth: usize) -> ReorganizingBuffer<Value> {
+		ReorganizingBuffer {
+			depth,
+			queue: VecDeque::with_capacity(depth + 1),
+			confirmed_offset: None,
+		}
 	}
 
-	pub(crate) fn push(
+	// Restores a buffer from its snapshot, like pushing the items into a new buffer. Of a snapshot
+	// with `depth + 1` items the oldest is confirmed right away.
+	pub fn from_snapshot(
+		depth: usize,
+		items: Vec<(u64, Vec<Value>)>,
+	) -> Result<ReorganizingBuffer<Value>, ReorganizingBufferError> {
+		if items.len() > depth + 1 {
+			return Err(ReorganizingBufferError::SnapshotTooLarge(items.len()));
+		}
+		if let Some(pair) = items.windows(2).find(|pair| pair[1].0 <= pair[0].0) {
+			return Err(ReorganizingBufferError::UnorderedOffset(pair[1].0));
+		}
+
+		let mut buffer = ReorganizingBuffer::new(depth);
+		for item in items {
+			buffer.push(item)?;
+		}
+
+		Ok(buffer)
+	}
+
+	pub fn push(
 		&mut self,
 		(new_offset, new_value): (u64, Vec<Value>),
 	) -> Result<Option<(u64, Vec<Value>)>, ReorganizingBufferError> {
-		if let Some((last_offset, _)) = self.queue.back() {
+		let last_offset = self.queue.back().map(|(offset, _)| *offset).or(self.confirmed_offset);
+
+		if let Some(last_offset) = last_offset {
 			// Ensure new item does not exceed reorganization depth limit
-			let expected_offset = last_offset + 1;
-			if new_offset > expected_offset {
-				return Err(ReorganizingBufferError::MissingOffset(expected_offset));
+			if new_offset > last_offset && new_offset - last_offset > 1 {
+				return Err(ReorganizingBufferError::MissingOffset(last_offset + 1));
 			}
 
-			// Perform reorganization, if necessary
-			let reorg_depth = expected_offset - new_offset;
-			if reorg_depth > self.depth.try_into().unwrap() {
+			// Perform reorganization, if necessary, without overflowing at the end of the offset
+			// range
+			let reorg_depth = match new_offset > last_offset {
+				true => 0,
+				false => (last_offset - new_offset).saturating_add(1),
+			};
+			let reorgs_confirmed = self.confirmed_offset.is_some_and(|offset| new_offset <= offset);
+			if reorg_depth > self.depth.try_into().unwrap() || reorgs_confirmed {
 				return Err(ReorganizingBufferError::DepthExceeded(reorg_depth));
 			}
 			for _ in 0..reorg_depth {
@@ -43,17 +80,273 @@ impl<Value> ReorganizingBuffer<Value> {
 
 		// Return item that passed confirmation requirement
 		if self.queue.len() > self.depth {
-			Ok(self.queue.pop_front())
+			let confirmed = self.queue.pop_front();
+			self.confirmed_offset = confirmed.as_ref().map(|(offset, _)| *offset);
+			Ok(confirmed)
 		} else {
 			Ok(None)
 		}
 	}
+
+	// Number of offsets waiting for confirmation
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.queue.is_empty()
+	}
+
+	// Offset of the most recently pushed item still waiting for confirmation
+	pub fn last_block(&self) -> Option<u64> {
+		self.queue.back().map(|(offset, _)| *offset)
+	}
+
+	// Offset of the item confirmed next
+	pub fn first_queued_block(&self) -> Option<u64> {
+		self.queue.front().map(|(offset, _)| *offset)
+	}
+
+	// Offsets waiting for confirmation with their values, oldest first
+	pub fn snapshot(&self) -> Vec<(u64, Vec<Value>)>
+	where
+		Value: Clone,
+	{
+		self.queue.iter().cloned().collect()
+	}
+
+	// Like `snapshot`, without cloning
+	pub fn window(&self) -> impl Iterator<Item = &(u64, Vec<Value>)> {
+		self.queue.iter()
+	}
+
+	// Confirms the oldest items beyond the depth, oldest first. `push` confirms items as soon as
+	// they pass the depth, so only lowering `depth` leaves any behind. Unlike `drain`, items that
+	// can still be reorganized are kept.
+	pub fn take_confirmed(&mut self) -> Vec<(u64, Vec<Value>)> {
+		let count = self.queue.len().saturating_sub(self.depth);
+		let confirmed: Vec<_> = self.queue.drain(..count).collect();
+		if let Some((offset, _)) = confirmed.last() {
+			self.confirmed_offset = Some(*offset);
+		}
+		confirmed
+	}
+
+	pub fn drain(&mut self) -> Vec<(u64, Vec<Value>)> {
+		if let Some((offset, _)) = self.queue.back() {
+			self.confirmed_offset = Some(*offset);
+		}
+		self.queue.drain(..).collect()
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	mod snapshot {
+		use super::*;
+
+		#[test]
+		fn pending() {
+			let mut buffer = ReorganizingBuffer::<&str>::new(2);
+			assert_eq!(buffer.snapshot(), vec![]);
+
+			buffer.push((1, vec!["a"])).unwrap();
+			buffer.push((2, vec![])).unwrap();
+			buffer.push((3, vec!["b", "c"])).unwrap();
+
+			assert_eq!(buffer.snapshot(), vec![(2, vec![]), (3, vec!["b", "c"])]);
+			assert_eq!(buffer.len(), 2);
+		}
+	}
+
+	mod last_block {
+		use super::*;
+
+		#[test]
+		fn empty() {
+			let buffer = ReorganizingBuffer::<&str>::new(3);
+
+			assert_eq!(buffer.last_block(), None);
+			assert_eq!(buffer.first_queued_block(), None);
+		}
+
+		#[test]
+		fn single_item() {
+			let mut buffer = ReorganizingBuffer::new(3);
+			buffer.push((7, vec!["a"])).unwrap();
+
+			assert_eq!(buffer.last_block(), Some(7));
+			assert_eq!(buffer.first_queued_block(), Some(7));
+		}
+
+		#[test]
+		fn full() {
+			let mut buffer = ReorganizingBuffer::new(3);
+			for offset in 1..=5 {
+				buffer.push((offset, vec!["a"])).unwrap();
+			}
+
+			assert_eq!(buffer.last_block(), Some(5));
+			assert_eq!(buffer.first_queued_block(), Some(3));
+
+			// Reorganized items are replaced
+			buffer.push((4, vec!["b"])).unwrap();
+			assert_eq!(buffer.last_block(), Some(4));
+		}
+	}
+
+	mod window {
+		use super::*;
+
+		#[test]
+		fn all_pending() {
+			let mut buffer = ReorganizingBuffer::new(5);
+			for offset in 1..=5 {
+				assert_eq!(buffer.push((offset, vec![offset * 10])).unwrap(), None);
+			}
+
+			let window: Vec<_> = buffer.window().collect();
+
+			assert_eq!(
+				window,
+				vec![
+					&(1, vec![10]),
+					&(2, vec![20]),
+					&(3, vec![30]),
+					&(4, vec![40]),
+					&(5, vec![50])
+				]
+			);
+		}
+
+		#[test]
+		fn after_confirmation() {
+			let mut buffer = ReorganizingBuffer::new(2);
+			buffer.push((1, vec!["a"])).unwrap();
+			buffer.push((2, vec![])).unwrap();
+			buffer.push((3, vec!["b"])).unwrap();
+
+			assert_eq!(buffer.window().map(|(offset, _)| *offset).collect::<Vec<_>>(), vec![2, 3]);
+		}
+	}
+
+	mod from_snapshot {
+		use super::*;
+
+		#[test]
+		fn same_as_push() {
+			let items = vec![(1, vec!["a"]), (2, vec![]), (3, vec!["b", "c"])];
+			let mut pushed = ReorganizingBuffer::new(2);
+			for item in items.clone() {
+				pushed.push(item).unwrap();
+			}
+
+			let mut restored = ReorganizingBuffer::from_snapshot(2, items).unwrap();
+
+			assert_eq!(restored.queue, pushed.queue);
+			assert_eq!(restored.confirmed_offset, Some(1));
+			assert!(matches!(
+				restored.push((1, vec![])),
+				Err(ReorganizingBufferError::DepthExceeded(3))
+			));
+			assert_eq!(restored.push((4, vec!["d"])).unwrap(), Some((2, vec![])));
+		}
+
+		#[test]
+		fn empty() {
+			let buffer = ReorganizingBuffer::<&str>::from_snapshot(2, vec![]).unwrap();
+
+			assert!(buffer.is_empty());
+			assert_eq!(buffer.confirmed_offset, None);
+		}
+
+		#[test]
+		fn too_large() {
+			let items = vec![(1, vec!["a"]), (2, vec![]), (3, vec![]), (4, vec![])];
+
+			assert!(matches!(
+				ReorganizingBuffer::from_snapshot(2, items),
+				Err(ReorganizingBufferError::SnapshotTooLarge(4))
+			));
+		}
+
+		#[test]
+		fn unordered() {
+			let items = vec![(1, vec!["a"]), (3, vec![]), (3, vec![])];
+
+			assert!(matches!(
+				ReorganizingBuffer::from_snapshot(3, items),
+				Err(ReorganizingBufferError::UnorderedOffset(3))
+			));
+		}
+
+		#[test]
+		fn missing_offset() {
+			let items = vec![(1, vec!["a"]), (3, vec![])];
+
+			assert!(matches!(
+				ReorganizingBuffer::from_snapshot(3, items),
+				Err(ReorganizingBufferError::MissingOffset(2))
+			));
+		}
+	}
+
+	mod take_confirmed {
+		use super::*;
+
+		#[test]
+		fn within_depth() {
+			let mut buffer = ReorganizingBuffer::new(3);
+			buffer.push((1, vec!["a"])).unwrap();
+			buffer.push((2, vec!["b"])).unwrap();
+
+			assert_eq!(buffer.take_confirmed(), vec![]);
+			assert_eq!(buffer.len(), 2);
+		}
+
+		#[test]
+		fn lowered_depth() {
+			let mut buffer = ReorganizingBuffer::new(3);
+			for offset in 1..=3 {
+				buffer.push((offset, vec!["a"])).unwrap();
+			}
+			buffer.depth = 1;
+
+			assert_eq!(buffer.take_confirmed(), vec![(1, vec!["a"]), (2, vec!["a"])]);
+			assert_eq!(buffer.queue, vec![(3, vec!["a"])]);
+			assert_eq!(buffer.confirmed_offset, Some(2));
+			assert!(matches!(
+				buffer.push((2, vec![])),
+				Err(ReorganizingBufferError::DepthExceeded(2))
+			));
+		}
+	}
+
+	mod drain {
+		use super::*;
+
+		#[test]
+		fn empty() {
+			let mut buffer = ReorganizingBuffer::<&str>::new(3);
+			assert_eq!(buffer.drain(), vec![]);
+		}
+
+		#[test]
+		fn pending() {
+			let mut buffer = ReorganizingBuffer::<&str>::new(3);
+			assert_eq!(buffer.push((1, vec!["a"])).unwrap(), None);
+			assert_eq!(buffer.push((2, vec!["b", "c"])).unwrap(), None);
+
+			assert_eq!(buffer.drain(), vec![(1, vec!["a"]), (2, vec!["b", "c"])]);
+			assert!(buffer.queue.is_empty());
+
+			assert_eq!(buffer.push((3, vec!["d"])).unwrap(), None);
+			assert_eq!(buffer.queue, vec![(3, vec!["d"])]);
+		}
+	}
+
 	mod push {
 		use super::*;
 
@@ -167,6 +460,7 @@ mod tests {
 					let buffer_new = || ReorganizingBuffer {
 						depth: DEPTH,
 						queue: VecDeque::from([item_2(), item_3(), item_4()]),
+						confirmed_offset: None,
 					};
 
 					let mut buffer = buffer_new();
@@ -178,6 +472,14 @@ mod tests {
 					let item_1 = || (1, vec!["x"]);
 					assert!(buffer.push(item_1()).is_err());
 				}
+
+				#[test]
+				fn reorg_at_max_offset() {
+					let mut buffer = ReorganizingBuffer::<&str>::new(DEPTH);
+					assert_eq!(buffer.push((u64::MAX, vec!["a"])).unwrap(), None);
+					assert_eq!(buffer.push((u64::MAX, vec!["x"])).unwrap(), None);
+					assert_eq!(buffer.queue, vec![(u64::MAX, vec!["x"])]);
+				}
 			}
 
 			mod err {
@@ -212,12 +514,115 @@ mod tests {
 						let result = buffer.push(item_1());
 
 						match result {
-							Err(ReorganizingBufferError::DepthExceeded(4)) => assert!(true),
-							_ => assert!(false, "Unexpected result {:?}", result),
+							Err(ReorganizingBufferError::DepthExceeded(4)) => (),
+							_ => panic!("Unexpected result {:?}", result),
 						}
 						assert_eq!(buffer.queue, vec![item_2(), item_3(), item_4()]);
 					}
+
+					#[test]
+					fn behind_confirmed() {
+						let mut buffer = ReorganizingBuffer::<&str>::new(DEPTH);
+						for offset in 10..=13 {
+							buffer.push((offset, vec!["a"])).unwrap();
+						}
+						assert_eq!(buffer.push((11, vec!["x"])).unwrap(), None);
+
+						let result = buffer.push((9, vec!["y"]));
+
+						match result {
+							Err(ReorganizingBufferError::DepthExceeded(3)) => (),
+							_ => panic!("Unexpected result {:?}", result),
+						}
+						assert_eq!(buffer.queue, vec![(11, vec!["x"])]);
+					}
+
+					#[test]
+					fn full_offset_range() {
+						let mut buffer = ReorganizingBuffer::<&str>::new(DEPTH);
+						assert_eq!(buffer.push((u64::MAX, vec!["a"])).unwrap(), None);
+
+						let result = buffer.push((0, vec!["x"]));
+
+						match result {
+							Err(ReorganizingBufferError::DepthExceeded(u64::MAX)) => (),
+							_ => panic!("Unexpected result {:?}", result),
+						}
+						assert_eq!(buffer.queue, vec![(u64::MAX, vec!["a"])]);
+					}
+				}
+			}
+		}
+	}
+
+	mod properties {
+		use super::*;
+		use proptest::prelude::*;
+		use std::collections::HashSet;
+
+		#[derive(Debug, Clone)]
+		enum Operation {
+			Next,
+			Reorg(u64),
+			Gap(u64),
+		}
+
+		fn operation() -> impl Strategy<Value = Operation> {
+			prop_oneof![
+				6 => Just(Operation::Next),
+				3 => (1..8u64).prop_map(Operation::Reorg),
+				1 => (2..4u64).prop_map(Operation::Gap),
+			]
+		}
+
+		proptest! {
+			#![proptest_config(ProptestConfig::with_cases(1000))]
+
+			#[test]
+			fn invariants(
+				depth in 0..6usize,
+				start in 0..1_000_000u64,
+				operations in prop::collection::vec(operation(), 0..64),
+			) {
+				let mut buffer = ReorganizingBuffer::new(depth);
+				let mut last_offset = None::<u64>;
+				let mut emitted_offsets = Vec::new();
+				let mut emitted_values = HashSet::new();
+
+				for (value, operation) in operations.into_iter().enumerate() {
+					let offset = match (last_offset, operation) {
+						(None, _) => start,
+						(Some(last), Operation::Next) => last + 1,
+						(Some(last), Operation::Reorg(depth)) => (last + 1).saturating_sub(depth),
+						(Some(last), Operation::Gap(gap)) => last + gap,
+					};
+
+					let queue_before = buffer.queue.clone();
+					match buffer.push((offset, vec![value])) {
+						Ok(confirmed) => {
+							last_offset = Some(offset);
+							if let Some((confirmed_offset, values)) = confirmed {
+								emitted_offsets.push(confirmed_offset);
+								for value in values {
+									prop_assert!(emitted_values.insert(value));
+								}
+							}
+						},
+						// Rejected items must leave the buffer untouched
+						Err(_) => prop_assert_eq!(&buffer.queue, &queue_before),
+					}
+
+					prop_assert!(buffer.queue.len() <= depth + 1);
+				}
+
+				for (offset, values) in buffer.drain() {
+					emitted_offsets.push(offset);
+					for value in values {
+						prop_assert!(emitted_values.insert(value));
+					}
 				}
+
+				prop_assert!(emitted_offsets.windows(2).all(|pair| pair[0] < pair[1]));
 			}
 		}
 	}
@@ -0,0 +1,1294 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+use rust_decimal::Decimal;
+use std::{net::SocketAddr, path::PathBuf};
+use tracing::level_filters::LevelFilter;
+use web3::types::H160;
+
+use rust_uniswap_task::{
+	config::{read_endpoints, AppConfig, PoolConfig, MAX_CONFIRMATION_DEPTH},
+	event::SwapDirection,
+	network::Network,
+	output::OutputFormat,
+	parser::Protocol,
+	transport::{FinalityKind, TransportKind},
+	v2_parser::V2_FEE_TIER,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "uniswap-monitor", about = "Monitor Uniswap V3 pool swap events")]
+pub(crate) struct Cli {
+	/// TOML configuration file, overridden by any arguments given on the command line
+	#[arg(long, value_name = "PATH")]
+	pub(crate) config: Option<PathBuf>,
+
+	/// Maximal level of diagnostics written to stderr
+	#[arg(long, value_name = "LEVEL", default_value = "info")]
+	pub(crate) log_level: LevelFilter,
+
+	/// OTLP/gRPC endpoint receiving traces of the event pipeline, e.g. http://localhost:4317
+	#[arg(long, value_name = "URL")]
+	pub(crate) otlp_endpoint: Option<String>,
+
+	/// Network whose DAI/USDC pool is monitored by default, custom networks require --pool
+	/// [default: mainnet]
+	#[arg(long, value_enum)]
+	pub(crate) network: Option<Network>,
+
+	/// Address of a Uniswap V3 pool to monitor, may be repeated. Its fee tier and tokens are read
+	/// from the chain [default: DAI/USDC pool of the network]
+	#[arg(long, value_name = "HEX_ADDRESS")]
+	pub(crate) pool: Vec<H160>,
+
+	/// Number of blocks an event must be buried under before it is confirmed [default: 5]
+	#[arg(long, alias = "depth", value_name = "N", env = "CONFIRMATION_DEPTH")]
+	pub(crate) confirmation_depth: Option<usize>,
+
+	/// Blocks to follow: the latest ones confirmed after --confirmation-depth blocks, or the
+	/// ones the consensus layer marked safe or finalized [default: probabilistic]
+	#[arg(long, value_enum)]
+	pub(crate) finality: Option<FinalityKind>,
+
+	/// WebSocket endpoint of the Ethereum node
+	#[arg(long, value_name = "URL", env = "INFURA_WEBSOCKET_ENDPOINT")]
+	pub(crate) ws_url: Option<String>,
+
+	/// WebSocket endpoint used in round-robin order with the other --rpc endpoints instead of
+	/// --ws-url, may be repeated
+	#[arg(long, value_name = "URL")]
+	pub(crate) rpc: Vec<String>,
+
+	/// File listing WebSocket endpoints one per line, used along with --rpc
+	#[arg(long, value_name = "PATH")]
+	pub(crate) rpc_file: Option<PathBuf>,
+
+	/// Format of confirmed events written to stdout [default: text]
+	#[arg(long, value_enum)]
+	pub(crate) output: Option<OutputFormat>,
+
+	/// How new blocks are received from the Ethereum node [default: ws]
+	#[arg(long, value_enum)]
+	pub(crate) transport: Option<TransportKind>,
+
+	/// HTTP endpoint of the Ethereum node, used by the polling transport
+	#[arg(long, value_name = "URL", env = "INFURA_HTTP_ENDPOINT")]
+	pub(crate) http_url: Option<String>,
+
+	/// Seconds between polls of the latest block when using the HTTP transport [default: 12]
+	#[arg(long, value_name = "SECS")]
+	pub(crate) poll_interval: Option<u64>,
+
+	/// Replay swap events starting at this block before following new blocks
+	#[arg(long, value_name = "N")]
+	pub(crate) from_block: Option<u64>,
+
+	/// Stop after replaying up to this block instead of following new blocks
+	#[arg(long, value_name = "N", requires = "from_block")]
+	pub(crate) to_block: Option<u64>,
+
+	/// SQLite database storing confirmed events, monitoring resumes after its last stored block
+	#[arg(long, value_name = "PATH")]
+	pub(crate) db: Option<PathBuf>,
+
+	/// USDC amount from which a swap is reported as a whale trade [default: 100000.00]
+	#[arg(long, value_name = "USDC")]
+	pub(crate) whale_threshold: Option<Decimal>,
+
+	/// Process raw logs saved as newline-delimited JSON instead of connecting to a node
+	#[arg(long, value_name = "PATH", conflicts_with_all = ["from_block", "to_block"])]
+	pub(crate) replay: Option<PathBuf>,
+
+	/// Address of the HTTP server exposing Prometheus metrics at /metrics [default:
+	/// 127.0.0.1:9090]
+	#[arg(long, value_name = "ADDR")]
+	pub(crate) metrics_addr: Option<SocketAddr>,
+
+	/// Don't serve Prometheus metrics
+	#[arg(long, conflicts_with = "metrics_addr")]
+	pub(crate) no_metrics: bool,
+
+	/// Address of the gRPC server streaming confirmed swaps, disabled unless set
+	#[arg(long, value_name = "ADDR")]
+	pub(crate) grpc_addr: Option<SocketAddr>,
+
+	/// Address of the HTTP server exposing recent swaps and volume stats [default:
+	/// 127.0.0.1:8080]
+	#[arg(long, value_name = "ADDR")]
+	pub(crate) api_addr: Option<SocketAddr>,
+
+	/// Don't serve recent swaps and volume stats
+	#[arg(long, conflicts_with = "api_addr")]
+	pub(crate) no_api: bool,
+
+	/// Address of the WebSocket server streaming confirmed swaps, disabled unless set
+	#[arg(long, value_name = "ADDR")]
+	pub(crate) ws_server_addr: Option<SocketAddr>,
+
+	/// Address of the HTTP server answering liveness and readiness probes at /healthz and
+	/// /readyz, disabled unless set
+	#[arg(long, value_name = "ADDR")]
+	pub(crate) health_addr: Option<SocketAddr>,
+
+	/// JSON file storing the last confirmed block and pending blocks, processing resumes from it
+	/// on restart
+	#[arg(long, value_name = "PATH")]
+	pub(crate) state_file: Option<PathBuf>,
+
+	/// Also monitor pools created by the Uniswap V3 factory while running
+	#[arg(long)]
+	pub(crate) watch_factory: bool,
+
+	/// Only monitor created pools trading this token
+	#[arg(long, value_name = "HEX_ADDRESS", requires = "watch_factory")]
+	pub(crate) token_filter: Option<H160>,
+
+	/// JSON ABI of the monitored pools, must contain the Swap event [default: Uniswap V3 pool
+	/// ABI]
+	#[arg(long, value_name = "PATH")]
+	pub(crate) abi: Option<PathBuf>,
+
+	/// Fetch the ABI of the first pool from the network's block explorer and cache it, the
+	/// embedded ABI is used if that fails
+	#[arg(long, conflicts_with = "abi")]
+	pub(crate) fetch_abi: bool,
+
+	/// Directory caching the fetched ABIs [default: $XDG_CACHE_HOME/uniswap-monitor or
+	/// ~/.cache/uniswap-monitor]
+	#[arg(long, value_name = "PATH")]
+	pub(crate) abi_cache_dir: Option<PathBuf>,
+
+	/// Etherscan API key, required by --fetch-abi
+	#[arg(long, value_name = "KEY", env = "ETHERSCAN_API_KEY")]
+	pub(crate) etherscan_api_key: Option<String>,
+
+	/// Uniswap version of the monitored pools, V2 pairs require --pool [default: v3]
+	#[arg(long, value_enum)]
+	pub(crate) protocol: Option<Protocol>,
+
+	/// Also receive Transfer events of the monitored pools' tokens and report them along with the
+	/// swaps of the same transaction
+	#[arg(long, conflicts_with = "watch_factory")]
+	pub(crate) track_transfers: bool,
+
+	/// Discord webhook URL receiving confirmed whale trades
+	#[arg(long, value_name = "URL")]
+	pub(crate) discord_webhook: Option<String>,
+
+	/// Telegram bot token used to send confirmed whale trades
+	#[arg(long, value_name = "TOKEN", requires = "telegram_chat_id")]
+	pub(crate) telegram_bot_token: Option<String>,
+
+	/// Telegram chat receiving confirmed whale trades, negative for groups
+	#[arg(long, value_name = "ID", requires = "telegram_bot_token", allow_hyphen_values = true)]
+	pub(crate) telegram_chat_id: Option<String>,
+
+	/// Blocks without a swap after which a pool's price is reported as stale [default: 300]
+	#[arg(long, value_name = "N")]
+	pub(crate) stale_price_blocks: Option<u64>,
+
+	/// Look up the gas used by the transactions of confirmed swaps and write their cost after
+	/// the swaps, in the text and JSON outputs
+	#[arg(long)]
+	pub(crate) track_gas: bool,
+
+	/// Compress the confirmed events written to stdout with zstd, compressed replay files are
+	/// detected on their own
+	#[arg(long)]
+	pub(crate) compress: bool,
+
+	/// Total value locked in the monitored pools in USD, enables fee APR estimates of confirmed
+	/// blocks
+	#[arg(long, value_name = "USD")]
+	pub(crate) pool_tvl_usd: Option<Decimal>,
+
+	/// Maximal RPC requests per second for logs, blocks and pending transactions, e.g. 1.16 to
+	/// stay within 100,000 requests a day [default: unlimited]
+	#[arg(long, value_name = "N")]
+	pub(crate) rpc_rate_limit: Option<f64>,
+
+	/// Process blocks without writing, storing or publishing confirmed swaps, only a summary of
+	/// processed blocks, swaps, reorgs and parse errors is written every 10 blocks
+	#[arg(long)]
+	pub(crate) dry_run: bool,
+
+	/// Only emit swaps of at least this amount of the pool's token0
+	#[arg(long, alias = "min-dai-amount", value_name = "AMOUNT")]
+	pub(crate) min_token0_amount: Option<Decimal>,
+
+	/// Only emit swaps of at most this amount of the pool's token0
+	#[arg(long, alias = "max-dai-amount", value_name = "AMOUNT")]
+	pub(crate) max_token0_amount: Option<Decimal>,
+
+	/// Only emit swaps in this direction, token0_to_token1 or token1_to_token0
+	#[arg(long, value_name = "DIRECTION")]
+	pub(crate) direction_filter: Option<SwapDirection>,
+
+	/// Only emit swaps sent by this address, may be repeated
+	#[arg(long, value_name = "HEX_ADDRESS")]
+	pub(crate) sender_whitelist: Vec<H160>,
+
+	/// Only emit swaps received by this address, may be repeated
+	#[arg(long, value_name = "HEX_ADDRESS")]
+	pub(crate) receiver_whitelist: Vec<H160>,
+
+	/// TOML file mapping addresses to labels shown instead of them in text output and whale
+	/// alerts, e.g. "0xE592427A0AEce92De3Edee1F18E0157C05861564" = "Uniswap V3 Router"
+	#[arg(long, value_name = "PATH")]
+	pub(crate) address_book: Option<PathBuf>,
+
+	/// Warn when the last confirmed block is more than N blocks behind the latest block,
+	/// including the confirmation depth
+	#[arg(long, value_name = "N")]
+	pub(crate) max_lag: Option<u64>,
+
+	/// Also report swaps of the monitored pools' tokens sent to the Uniswap V3 SwapRouter while
+	/// they are pending in the mempool, and once their transactions are confirmed
+	#[arg(long)]
+	pub(crate) include_pending: bool,
+}
+
+impl Cli {
+	pub(crate) fn config(self) -> Result<AppConfig> {
+		let mut config = match &self.config {
+			Some(path) => AppConfig::from_toml_file(path)?,
+			None => AppConfig::default(),
+		};
+
+		if let Some(network) = self.network {
+			config.network = network;
+		}
+		if !self.pool.is_empty() {
+			config.pools = self
+				.pool
+				.iter()
+				.map(|pool| PoolConfig {
+					address: format!("{:?}", pool),
+					fee_tier: 0,
+					read_tokens: true,
+					..PoolConfig::default()
+				})
+				.collect();
+		}
+		if let Some(confirmation_depth) = self.confirmation_depth {
+			config.confirmation_depth = confirmation_depth;
+		}
+		if let Some(finality) = self.finality {
+			config.finality = finality;
+		}
+		if let Some(ws_url) = self.ws_url {
+			config.ws_url = ws_url;
+		}
+		let mut rpc_endpoints = self.rpc;
+		if let Some(rpc_file) = &self.rpc_file {
+			rpc_endpoints.extend(read_endpoints(rpc_file)?);
+		}
+		if !rpc_endpoints.is_empty() {
+			config.rpc_endpoints = rpc_endpoints;
+		}
+		if let Some(output) = self.output {
+			config.output_format = output;
+		}
+		if let Some(transport) = self.transport {
+			config.transport = transport;
+		}
+		if let Some(http_url) = self.http_url {
+			config.http_url = http_url;
+		}
+		if let Some(poll_interval) = self.poll_interval {
+			config.poll_interval = poll_interval;
+		}
+		if let Some(from_block) = self.from_block {
+			config.from_block = Some(from_block);
+		}
+		if let Some(to_block) = self.to_block {
+			config.to_block = Some(to_block);
+		}
+		if let Some(db) = self.db {
+			config.db = Some(db);
+		}
+		if let Some(whale_threshold) = self.whale_threshold {
+			config.whale_threshold = whale_threshold;
+		}
+		if let Some(replay) = self.replay {
+			config.replay = Some(replay);
+		}
+		if let Some(metrics_addr) = self.metrics_addr {
+			config.metrics_addr = Some(metrics_addr);
+		}
+		if self.no_metrics {
+			config.metrics_addr = None;
+		}
+		if let Some(grpc_addr) = self.grpc_addr {
+			config.grpc_addr = Some(grpc_addr);
+		}
+		if let Some(api_addr) = self.api_addr {
+			config.api_addr = Some(api_addr);
+		}
+		if self.no_api {
+			config.api_addr = None;
+		}
+		if let Some(ws_server_addr) = self.ws_server_addr {
+			config.ws_server_addr = Some(ws_server_addr);
+		}
+		if let Some(health_addr) = self.health_addr {
+			config.health_addr = Some(health_addr);
+		}
+		if let Some(state_file) = self.state_file {
+			config.state_file = Some(state_file);
+		}
+		if self.watch_factory {
+			config.watch_factory = true;
+		}
+		if let Some(token_filter) = self.token_filter {
+			config.token_filter = Some(token_filter);
+		}
+		if let Some(abi) = self.abi {
+			config.abi = Some(abi);
+		}
+		if self.fetch_abi {
+			config.fetch_abi = true;
+		}
+		if let Some(abi_cache_dir) = self.abi_cache_dir {
+			config.abi_cache_dir = Some(abi_cache_dir);
+		}
+		if let Some(etherscan_api_key) = self.etherscan_api_key {
+			config.etherscan_api_key = Some(etherscan_api_key);
+		}
+		if let Some(protocol) = self.protocol {
+			config.protocol = protocol;
+		}
+		if self.track_transfers {
+			config.track_transfers = true;
+		}
+		if let Some(discord_webhook) = self.discord_webhook {
+			config.discord_webhook = Some(discord_webhook);
+		}
+		if let Some(telegram_bot_token) = self.telegram_bot_token {
+			config.telegram_bot_token = Some(telegram_bot_token);
+		}
+		if let Some(telegram_chat_id) = self.telegram_chat_id {
+			config.telegram_chat_id = Some(telegram_chat_id);
+		}
+		if let Some(stale_price_blocks) = self.stale_price_blocks {
+			config.stale_price_blocks = stale_price_blocks;
+		}
+		if self.track_gas {
+			config.track_gas = true;
+		}
+		if self.compress {
+			config.compress = true;
+		}
+		if let Some(pool_tvl_usd) = self.pool_tvl_usd {
+			config.pool_tvl_usd = Some(pool_tvl_usd);
+		}
+		if let Some(rpc_rate_limit) = self.rpc_rate_limit {
+			config.rpc_rate_limit = Some(rpc_rate_limit);
+		}
+		if self.dry_run {
+			config.dry_run = true;
+		}
+		if let Some(min_token0_amount) = self.min_token0_amount {
+			config.swap_filter.min_token0_amount = Some(min_token0_amount);
+		}
+		if let Some(max_token0_amount) = self.max_token0_amount {
+			config.swap_filter.max_token0_amount = Some(max_token0_amount);
+		}
+		if let Some(direction) = self.direction_filter {
+			config.swap_filter.direction = Some(direction);
+		}
+		if !self.sender_whitelist.is_empty() {
+			config.swap_filter.sender_whitelist = Some(self.sender_whitelist);
+		}
+		if !self.receiver_whitelist.is_empty() {
+			config.swap_filter.receiver_whitelist = Some(self.receiver_whitelist);
+		}
+		if let Some(address_book) = self.address_book {
+			config.address_book = Some(address_book);
+		}
+		if let Some(max_lag) = self.max_lag {
+			config.max_lag = Some(max_lag);
+		}
+		if self.include_pending {
+			config.include_pending = true;
+		}
+
+		let network = config.network.config();
+		let example =
+			|url: Option<&str>| url.map(|url| format!(", e.g. {}", url)).unwrap_or_default();
+
+		match config.transport {
+			_ if config.replay.is_some() => (),
+			TransportKind::Ws if config.ws_url.is_empty() && config.rpc_endpoints.is_empty() =>
+				bail!(
+				"Missing WebSocket endpoint, set --ws-url, INFURA_WEBSOCKET_ENDPOINT or ws_url{}",
+				example(network.as_ref().map(|network| network.ws_url))
+			),
+			TransportKind::Http if config.http_url.is_empty() => bail!(
+				"Missing HTTP endpoint, set --http-url, INFURA_HTTP_ENDPOINT or http_url{}",
+				example(network.as_ref().map(|network| network.http_url))
+			),
+			_ => (),
+		}
+		if config.include_pending && config.transport == TransportKind::Http {
+			bail!("Pending transactions require --transport ws");
+		}
+		if config
+			.rpc_rate_limit
+			.is_some_and(|rate_limit| rate_limit.is_nan() || rate_limit <= 0.0)
+		{
+			bail!("RPC rate limit must be a positive number of requests per second");
+		}
+		if let (Some(min), Some(max)) =
+			(config.swap_filter.min_token0_amount, config.swap_filter.max_token0_amount)
+		{
+			if min > max {
+				bail!("Minimal token0 amount {} exceeds maximal token0 amount {}", min, max);
+			}
+		}
+		if config.confirmation_depth > MAX_CONFIRMATION_DEPTH {
+			bail!(
+				"Confirmation depth {} is out of range, expected a value between 0 and {}",
+				config.confirmation_depth,
+				MAX_CONFIRMATION_DEPTH
+			);
+		}
+		match (config.from_block, config.to_block) {
+			(None, Some(_)) => bail!("Replaying up to a block requires a starting block"),
+			(Some(from_block), Some(to_block)) if to_block < from_block =>
+				bail!("Replay range {}..={} is empty", from_block, to_block),
+			_ => (),
+		}
+		if config.fetch_abi && config.etherscan_api_key.is_none() {
+			bail!(
+				"Missing Etherscan API key, set --etherscan-api-key, ETHERSCAN_API_KEY or \
+				 etherscan_api_key"
+			);
+		}
+		// Created pools' tokens are read from the node, which replays don't connect to
+		if config.watch_factory && config.replay.is_some() {
+			bail!("Watching the factory requires a node, it can't be combined with --replay");
+		}
+		if config.protocol == Protocol::V2 {
+			if config.watch_factory {
+				bail!("Watching the factory requires --protocol v3");
+			}
+			if config.pools.is_empty() {
+				bail!("Missing V2 pair, set --pool or pools");
+			}
+			for pool in config.pools.iter_mut().filter(|pool| pool.fee_tier == 0) {
+				pool.fee_tier = V2_FEE_TIER;
+			}
+		}
+		if config.pools.is_empty() {
+			match network {
+				Some(network) => config.pools.push(network.pool),
+				None => bail!("Missing pool of the custom network, set --pool or pools"),
+			}
+		}
+
+		Ok(config)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rust_uniswap_task::{event::SwapFilter, transport::Finality};
+
+	mod config {
+		use super::*;
+
+		#[test]
+		fn defaults() {
+			let cli =
+				Cli::try_parse_from(["uniswap-monitor", "--ws-url", "wss://localhost"]).unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.pools, vec![PoolConfig::default()]);
+			assert_eq!(config.confirmation_depth, 5);
+			assert_eq!(config.ws_url, "wss://localhost");
+			assert_eq!(config.output_format, OutputFormat::Text);
+			assert_eq!(config.whale_threshold, Decimal::new(100_000, 0));
+			assert_eq!(config.stale_price_blocks, 300);
+			assert_eq!(config.metrics_addr, Some("127.0.0.1:9090".parse().unwrap()));
+			assert_eq!(config.api_addr, Some("127.0.0.1:8080".parse().unwrap()));
+		}
+
+		#[test]
+		fn no_metrics() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--no-metrics",
+			])
+			.unwrap();
+			assert_eq!(cli.config().unwrap().metrics_addr, None);
+
+			let result = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--no-metrics",
+				"--metrics-addr",
+				"0.0.0.0:9100",
+			]);
+			assert!(result.is_err());
+		}
+
+		#[test]
+		fn no_api() {
+			let cli =
+				Cli::try_parse_from(["uniswap-monitor", "--ws-url", "wss://localhost", "--no-api"])
+					.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.api_addr, None);
+			assert_eq!(config.metrics_addr, Some("127.0.0.1:9090".parse().unwrap()));
+		}
+
+		#[test]
+		fn log_level() {
+			let cli = Cli::try_parse_from(["uniswap-monitor", "--log-level", "debug"]).unwrap();
+			assert_eq!(cli.log_level, LevelFilter::DEBUG);
+
+			let cli = Cli::try_parse_from(["uniswap-monitor"]).unwrap();
+			assert_eq!(cli.log_level, LevelFilter::INFO);
+		}
+
+		#[test]
+		fn custom() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--pool",
+				"0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640",
+				"--depth",
+				"12",
+				"--ws-url",
+				"wss://localhost",
+				"--output",
+				"csv",
+				"--whale-threshold",
+				"250000.50",
+				"--metrics-addr",
+				"0.0.0.0:9100",
+				"--grpc-addr",
+				"127.0.0.1:50051",
+				"--api-addr",
+				"0.0.0.0:8081",
+				"--ws-server-addr",
+				"127.0.0.1:8546",
+				"--health-addr",
+				"0.0.0.0:8082",
+				"--state-file",
+				"state.json",
+				"--abi",
+				"pool_abi.json",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(
+				config.pools[0].contract_address().unwrap(),
+				"88e6a0c2ddd26feeb64f039a2c41296fcb3f5640".parse().unwrap()
+			);
+			assert!(config.pools[0].read_tokens);
+			assert_eq!(config.confirmation_depth, 12);
+			assert_eq!(config.output_format, OutputFormat::Csv);
+			assert_eq!(config.whale_threshold, Decimal::new(25_000_050, 2));
+			assert_eq!(config.metrics_addr, Some("0.0.0.0:9100".parse().unwrap()));
+			assert_eq!(config.grpc_addr, Some("127.0.0.1:50051".parse().unwrap()));
+			assert_eq!(config.api_addr, Some("0.0.0.0:8081".parse().unwrap()));
+			assert_eq!(config.ws_server_addr, Some("127.0.0.1:8546".parse().unwrap()));
+			assert_eq!(config.health_addr, Some("0.0.0.0:8082".parse().unwrap()));
+			assert_eq!(config.state_file, Some(PathBuf::from("state.json")));
+			assert_eq!(config.abi, Some(PathBuf::from("pool_abi.json")));
+		}
+
+		#[test]
+		fn confirmation_depth() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--confirmation-depth",
+				"0",
+			])
+			.unwrap();
+
+			assert_eq!(cli.config().unwrap().confirmation_depth, 0);
+		}
+
+		#[test]
+		fn confirmation_depth_out_of_range() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--confirmation-depth",
+				"101",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"Confirmation depth 101 is out of range, expected a value between 0 and 100"
+			);
+		}
+
+		#[test]
+		fn finality() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--finality",
+				"finalized",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.finality, FinalityKind::Finalized);
+			assert_eq!(config.finality(), Finality::Finalized);
+			assert_eq!(config.finality().confirmation_depth(), 0);
+		}
+
+		#[test]
+		fn overrides_file() {
+			let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.toml");
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--config",
+				path,
+				"--depth",
+				"20",
+				"--ws-url",
+				"wss://localhost",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.pools, vec![PoolConfig::default()]);
+			assert_eq!(config.confirmation_depth, 20);
+			assert_eq!(config.ws_url, "wss://localhost");
+		}
+
+		#[test]
+		fn multiple_pools() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--pool",
+				"0x5777d92f208679db4b9778590fa3cab3ac9e2168",
+				"--pool",
+				"0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640",
+			])
+			.unwrap();
+			let pools = cli.config().unwrap().pools;
+
+			assert_eq!(pools.len(), 2);
+			assert_eq!(
+				pools[0].contract_address().unwrap(),
+				PoolConfig::default().contract_address().unwrap()
+			);
+			assert_eq!(
+				pools[1].contract_address().unwrap(),
+				"88e6a0c2ddd26feeb64f039a2c41296fcb3f5640".parse().unwrap()
+			);
+		}
+
+		#[test]
+		fn named_network() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--network",
+				"arbitrum",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.network, Network::Arbitrum);
+			assert_eq!(config.pools, vec![Network::Arbitrum.config().unwrap().pool]);
+		}
+
+		#[test]
+		fn custom_network_without_pool() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--network",
+				"custom",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"Missing pool of the custom network, set --pool or pools"
+			);
+		}
+
+		#[test]
+		fn custom_network_without_endpoint() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--network",
+				"custom",
+				"--pool",
+				"0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"Missing WebSocket endpoint, set --ws-url, INFURA_WEBSOCKET_ENDPOINT or ws_url"
+			);
+		}
+
+		#[test]
+		fn watch_factory() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--watch-factory",
+				"--token-filter",
+				"0x6b175474e89094c44da98b954eedeac495271d0f",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert!(config.watch_factory);
+			assert_eq!(
+				config.token_filter,
+				Some("6b175474e89094c44da98b954eedeac495271d0f".parse().unwrap())
+			);
+		}
+
+		#[test]
+		fn fetch_abi() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--fetch-abi",
+				"--etherscan-api-key",
+				"KEY",
+				"--abi-cache-dir",
+				"abis",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert!(config.fetch_abi);
+			assert_eq!(config.etherscan_api_key, Some("KEY".to_string()));
+			assert_eq!(config.abi_cache_dir, Some(PathBuf::from("abis")));
+		}
+
+		#[test]
+		fn fetch_abi_without_api_key() {
+			let cli = Cli {
+				etherscan_api_key: None,
+				..Cli::try_parse_from([
+					"uniswap-monitor",
+					"--ws-url",
+					"wss://localhost",
+					"--fetch-abi",
+				])
+				.unwrap()
+			};
+			let err = cli.config().unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				"Missing Etherscan API key, set --etherscan-api-key, ETHERSCAN_API_KEY or \
+				 etherscan_api_key"
+			);
+		}
+
+		#[test]
+		fn fetch_abi_with_abi() {
+			let result =
+				Cli::try_parse_from(["uniswap-monitor", "--abi", "pool_abi.json", "--fetch-abi"]);
+
+			assert!(result.is_err());
+		}
+
+		#[test]
+		fn v2_protocol() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--protocol",
+				"v2",
+				"--pool",
+				"0xae461ca67b15dc8dc81ce7615e0320da1a9ab8d5",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.protocol, Protocol::V2);
+			assert_eq!(config.pools[0].address, "0xae461ca67b15dc8dc81ce7615e0320da1a9ab8d5");
+			assert_eq!(config.pools[0].fee_tier, V2_FEE_TIER);
+		}
+
+		#[test]
+		fn v2_protocol_without_pool() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--protocol",
+				"v2",
+			])
+			.unwrap();
+			let err = cli.config().unwrap_err();
+
+			assert_eq!(err.to_string(), "Missing V2 pair, set --pool or pools");
+		}
+
+		#[test]
+		fn v2_protocol_with_watch_factory() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--protocol",
+				"v2",
+				"--pool",
+				"0xae461ca67b15dc8dc81ce7615e0320da1a9ab8d5",
+				"--watch-factory",
+			])
+			.unwrap();
+			let err = cli.config().unwrap_err();
+
+			assert_eq!(err.to_string(), "Watching the factory requires --protocol v3");
+		}
+
+		#[test]
+		fn track_transfers() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--track-transfers",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert!(config.track_transfers);
+		}
+
+		#[test]
+		fn track_transfers_with_watch_factory() {
+			let result =
+				Cli::try_parse_from(["uniswap-monitor", "--track-transfers", "--watch-factory"]);
+
+			assert!(result.is_err());
+		}
+
+		#[test]
+		fn replay_with_watch_factory() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--replay",
+				"logs.jsonl",
+				"--watch-factory",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"Watching the factory requires a node, it can't be combined with --replay"
+			);
+		}
+
+		#[test]
+		fn discord_webhook() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--discord-webhook",
+				"https://discord.com/api/webhooks/1/token",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(
+				config.discord_webhook,
+				Some("https://discord.com/api/webhooks/1/token".to_string())
+			);
+		}
+
+		#[test]
+		fn telegram() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--telegram-bot-token",
+				"123:abc",
+				"--telegram-chat-id",
+				"-10042",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.telegram_bot_token, Some("123:abc".to_string()));
+			assert_eq!(config.telegram_chat_id, Some("-10042".to_string()));
+		}
+
+		#[test]
+		fn stale_price_blocks() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--stale-price-blocks",
+				"50",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.stale_price_blocks, 50);
+		}
+
+		#[test]
+		fn rpc_endpoints() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("endpoints.txt");
+			std::fs::write(&path, "wss://c\n").unwrap();
+
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--rpc",
+				"wss://a",
+				"--rpc",
+				"wss://b",
+				"--rpc-file",
+				path.to_str().unwrap(),
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(
+				config.rpc_endpoints,
+				vec!["wss://a".to_string(), "wss://b".to_string(), "wss://c".to_string()]
+			);
+		}
+
+		#[test]
+		fn track_gas() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--track-gas",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert!(config.track_gas);
+		}
+
+		#[test]
+		fn compress() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--compress",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert!(config.compress);
+		}
+
+		#[test]
+		fn pool_tvl_usd() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--pool-tvl-usd",
+				"50000000.50",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.pool_tvl_usd, Some(Decimal::new(5_000_000_050, 2)));
+		}
+
+		#[test]
+		fn rpc_rate_limit() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--rpc-rate-limit",
+				"1.16",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.rpc_rate_limit, Some(1.16));
+		}
+
+		#[test]
+		fn zero_rpc_rate_limit() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--rpc-rate-limit",
+				"0",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"RPC rate limit must be a positive number of requests per second"
+			);
+		}
+
+		#[test]
+		fn dry_run() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--dry-run",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert!(config.dry_run);
+		}
+
+		#[test]
+		fn swap_filter() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--min-token0-amount",
+				"1000",
+				"--max-token0-amount",
+				"50000.5",
+				"--direction-filter",
+				"token0_to_token1",
+				"--sender-whitelist",
+				"0x0101010101010101010101010101010101010101",
+				"--sender-whitelist",
+				"0x0202020202020202020202020202020202020202",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(
+				config.swap_filter,
+				SwapFilter {
+					min_token0_amount: Some(Decimal::from(1000)),
+					max_token0_amount: Some(Decimal::new(500_005, 1)),
+					direction: Some(SwapDirection::Token0ToToken1),
+					sender_whitelist: Some(vec![H160::repeat_byte(1), H160::repeat_byte(2)]),
+					receiver_whitelist: None,
+				}
+			);
+		}
+
+		#[test]
+		fn empty_token0_amount_range() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--min-dai-amount",
+				"100",
+				"--max-dai-amount",
+				"10",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"Minimal token0 amount 100 exceeds maximal token0 amount 10"
+			);
+		}
+
+		#[test]
+		fn address_book() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--address-book",
+				"addresses.toml",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.address_book, Some(PathBuf::from("addresses.toml")));
+		}
+
+		#[test]
+		fn max_lag() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--max-lag",
+				"20",
+			])
+			.unwrap();
+
+			assert_eq!(cli.config().unwrap().max_lag, Some(20));
+		}
+
+		#[test]
+		fn include_pending() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--include-pending",
+			])
+			.unwrap();
+
+			assert!(cli.config().unwrap().include_pending);
+		}
+
+		#[test]
+		fn include_pending_over_http() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--transport",
+				"http",
+				"--http-url",
+				"https://localhost",
+				"--include-pending",
+			])
+			.unwrap();
+
+			assert_eq!(
+				cli.config().unwrap_err().to_string(),
+				"Pending transactions require --transport ws"
+			);
+		}
+
+		#[test]
+		fn telegram_without_chat_id() {
+			let result = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--telegram-bot-token",
+				"123:abc",
+			]);
+
+			assert!(result.is_err());
+		}
+
+		#[test]
+		fn token_filter_without_watch_factory() {
+			let result = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--token-filter",
+				"0x6b175474e89094c44da98b954eedeac495271d0f",
+			]);
+
+			assert!(result.is_err());
+		}
+
+		#[test]
+		fn replay_without_endpoint() {
+			let cli = Cli::try_parse_from(["uniswap-monitor", "--replay", "logs.jsonl"]).unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.replay, Some(PathBuf::from("logs.jsonl")));
+		}
+
+		#[test]
+		fn replay_with_block_range() {
+			let result = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--replay",
+				"logs.jsonl",
+				"--from-block",
+				"100",
+			]);
+
+			assert!(result.is_err());
+		}
+
+		#[test]
+		fn http_transport() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--transport",
+				"http",
+				"--http-url",
+				"https://localhost",
+				"--poll-interval",
+				"3",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.transport, TransportKind::Http);
+			assert_eq!(config.http_url, "https://localhost");
+			assert_eq!(config.poll_interval, 3);
+		}
+
+		#[test]
+		fn block_range() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--from-block",
+				"100",
+				"--to-block",
+				"200",
+			])
+			.unwrap();
+			let config = cli.config().unwrap();
+
+			assert_eq!(config.from_block, Some(100));
+			assert_eq!(config.to_block, Some(200));
+		}
+
+		#[test]
+		fn empty_block_range() {
+			let cli = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--ws-url",
+				"wss://localhost",
+				"--from-block",
+				"200",
+				"--to-block",
+				"100",
+			])
+			.unwrap();
+
+			assert_eq!(cli.config().unwrap_err().to_string(), "Replay range 200..=100 is empty");
+		}
+
+		#[test]
+		fn invalid_pool() {
+			let result = Cli::try_parse_from([
+				"uniswap-monitor",
+				"--pool",
+				"0x1234",
+				"--ws-url",
+				"wss://localhost",
+			]);
+
+			assert!(result.is_err());
+		}
+	}
+}
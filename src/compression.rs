@@ -0,0 +1,140 @@
+use std::io::{self, BufReader, Chain, Cursor, Read, Write};
+
+// Precedes compressed data, so readers can tell it apart from plain text
+pub const MAGIC: &[u8; 4] = b"UVZ\x01";
+
+// Default zstd level, a good tradeoff for streaming output
+const LEVEL: i32 = 3;
+
+// Compresses everything written as a single zstd frame. Flushing ends the current block so the
+// data written so far can be read, the frame is finished when the writer is dropped.
+pub struct CompressedWriter<W: Write> {
+	encoder: zstd::stream::write::Encoder<'static, W>,
+}
+
+impl<W: Write> CompressedWriter<W> {
+	pub fn new(mut writer: W) -> io::Result<CompressedWriter<W>> {
+		writer.write_all(MAGIC)?;
+		Ok(CompressedWriter { encoder: zstd::stream::write::Encoder::new(writer, LEVEL)? })
+	}
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.encoder.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.encoder.flush()
+	}
+}
+
+impl<W: Write> Drop for CompressedWriter<W> {
+	fn drop(&mut self) {
+		// Nowhere to report the error, readers fail on the unfinished frame instead
+		let _ = self.encoder.do_finish();
+	}
+}
+
+// Reads data written by `CompressedWriter` as well as plain data, detected by the magic header
+pub enum CompressedReader<R: Read> {
+	Plain(Chain<Cursor<Vec<u8>>, R>),
+	Compressed(zstd::stream::read::Decoder<'static, BufReader<R>>),
+}
+
+impl<R: Read> CompressedReader<R> {
+	pub fn new(mut reader: R) -> io::Result<CompressedReader<R>> {
+		let mut header = Vec::with_capacity(MAGIC.len());
+		(&mut reader).take(MAGIC.len() as u64).read_to_end(&mut header)?;
+
+		Ok(match header == MAGIC {
+			true => CompressedReader::Compressed(zstd::stream::read::Decoder::new(reader)?),
+			// The bytes read so far are part of the plain data
+			false => CompressedReader::Plain(Cursor::new(header).chain(reader)),
+		})
+	}
+
+	pub fn is_compressed(&self) -> bool {
+		matches!(self, CompressedReader::Compressed(_))
+	}
+}
+
+impl<R: Read> Read for CompressedReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match self {
+			CompressedReader::Plain(reader) => reader.read(buf),
+			CompressedReader::Compressed(reader) => reader.read(buf),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn compress(data: &[u8]) -> Vec<u8> {
+		let mut output = Vec::new();
+		let mut writer = CompressedWriter::new(&mut output).unwrap();
+		writer.write_all(data).unwrap();
+		drop(writer);
+		output
+	}
+
+	fn read(data: &[u8]) -> (bool, Vec<u8>) {
+		let mut reader = CompressedReader::new(data).unwrap();
+		let mut output = Vec::new();
+		reader.read_to_end(&mut output).unwrap();
+		(reader.is_compressed(), output)
+	}
+
+	mod compressed_writer {
+		use super::*;
+
+		#[test]
+		fn magic_header() {
+			let data = "{\"event_type\":\"swap\"}\n".repeat(1000);
+
+			let compressed = compress(data.as_bytes());
+
+			assert!(compressed.starts_with(MAGIC));
+			assert!(compressed.len() < data.len() / 10);
+		}
+
+		#[test]
+		fn flush() {
+			let mut output = Vec::new();
+			let mut writer = CompressedWriter::new(&mut output).unwrap();
+			writer.write_all(b"first line\n").unwrap();
+			writer.flush().unwrap();
+			std::mem::forget(writer);
+
+			// The unfinished frame can't be read to the end, but the flushed data is there
+			let mut reader = CompressedReader::new(output.as_slice()).unwrap();
+			let mut line = [0; 11];
+			reader.read_exact(&mut line).unwrap();
+			assert_eq!(&line, b"first line\n");
+		}
+	}
+
+	mod compressed_reader {
+		use super::*;
+
+		#[test]
+		fn compressed() {
+			let data = b"line 1\nline 2\n";
+
+			assert_eq!(read(&compress(data)), (true, data.to_vec()));
+		}
+
+		#[test]
+		fn plain() {
+			assert_eq!(read(b"line 1\nline 2\n"), (false, b"line 1\nline 2\n".to_vec()));
+		}
+
+		#[test]
+		fn shorter_than_magic() {
+			assert_eq!(read(b"UV"), (false, b"UV".to_vec()));
+			assert_eq!(read(b""), (false, Vec::new()));
+		}
+	}
+}
@@ -0,0 +1,431 @@
+use anyhow::{Context, Result};
+use rust_decimal::Decimal;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{
+	net::SocketAddr,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+use web3::types::H160;
+
+use crate::{
+	api::DEFAULT_API_ADDR,
+	event::{SwapFilter, TokenInfo, TokenPair},
+	metrics::DEFAULT_METRICS_ADDR,
+	network::Network,
+	output::OutputFormat,
+	parser::Protocol,
+	transport::{Finality, FinalityKind, TransportKind, DEFAULT_POLL_INTERVAL},
+};
+
+const UNI_V3_DAI_USDC_POOL: &str = "5777d92f208679db4b9778590fa3cab3ac9e2168";
+
+pub(crate) const DEFAULT_CONFIRMATION_DEPTH: usize = 5;
+pub const MAX_CONFIRMATION_DEPTH: usize = 100;
+pub(crate) const DEFAULT_WHALE_THRESHOLD: Decimal = Decimal::from_parts(10_000_000, 0, 0, false, 2);
+// About an hour of mainnet blocks
+pub(crate) const DEFAULT_STALE_PRICE_BLOCKS: u64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AppConfig {
+	#[serde(default)]
+	pub network: Network,
+	#[serde(default)]
+	pub ws_url: String,
+	// WebSocket endpoints used in turn instead of `ws_url`
+	#[serde(default)]
+	pub rpc_endpoints: Vec<String>,
+	#[serde(default)]
+	pub pools: Vec<PoolConfig>,
+	#[serde(default = "default_confirmation_depth")]
+	pub confirmation_depth: usize,
+	#[serde(default)]
+	pub finality: FinalityKind,
+	#[serde(default)]
+	pub output_format: OutputFormat,
+	#[serde(default)]
+	pub transport: TransportKind,
+	#[serde(default)]
+	pub http_url: String,
+	#[serde(default = "default_poll_interval")]
+	pub poll_interval: u64,
+	#[serde(default)]
+	pub from_block: Option<u64>,
+	#[serde(default)]
+	pub to_block: Option<u64>,
+	#[serde(default)]
+	pub db: Option<PathBuf>,
+	#[serde(default = "default_whale_threshold")]
+	pub whale_threshold: Decimal,
+	#[serde(default)]
+	pub replay: Option<PathBuf>,
+	// Disabled by `metrics_addr = false`
+	#[serde(default = "default_metrics_addr", deserialize_with = "server_addr")]
+	pub metrics_addr: Option<SocketAddr>,
+	#[serde(default)]
+	pub grpc_addr: Option<SocketAddr>,
+	// Disabled by `api_addr = false`
+	#[serde(default = "default_api_addr", deserialize_with = "server_addr")]
+	pub api_addr: Option<SocketAddr>,
+	#[serde(default)]
+	pub ws_server_addr: Option<SocketAddr>,
+	#[serde(default)]
+	pub health_addr: Option<SocketAddr>,
+	#[serde(default)]
+	pub state_file: Option<PathBuf>,
+	#[serde(default)]
+	pub watch_factory: bool,
+	#[serde(default)]
+	pub token_filter: Option<H160>,
+	// ABI of pools without their own, read at startup instead of the embedded one
+	#[serde(default)]
+	pub abi: Option<PathBuf>,
+	#[serde(default)]
+	pub fetch_abi: bool,
+	// Where fetched ABIs are cached, see `etherscan::default_cache_dir`
+	#[serde(default)]
+	pub abi_cache_dir: Option<PathBuf>,
+	#[serde(default)]
+	pub etherscan_api_key: Option<String>,
+	#[serde(default)]
+	pub protocol: Protocol,
+	#[serde(default)]
+	pub track_transfers: bool,
+	#[serde(default)]
+	pub discord_webhook: Option<String>,
+	#[serde(default)]
+	pub telegram_bot_token: Option<String>,
+	#[serde(default)]
+	pub telegram_chat_id: Option<String>,
+	// Blocks without a swap after which a pool's price is reported as stale
+	#[serde(default = "default_stale_price_blocks")]
+	pub stale_price_blocks: u64,
+	#[serde(default)]
+	pub track_gas: bool,
+	#[serde(default)]
+	pub compress: bool,
+	// Total value locked in the monitored pools, fee APRs are only estimated when known
+	#[serde(default)]
+	pub pool_tvl_usd: Option<Decimal>,
+	// Requests per second sent for logs and blocks, unlimited when missing
+	#[serde(default)]
+	pub rpc_rate_limit: Option<f64>,
+	#[serde(default)]
+	pub dry_run: bool,
+	// Confirmed swaps not matching it are dropped before they are emitted
+	#[serde(default)]
+	pub swap_filter: SwapFilter,
+	// Labels shown in place of the addresses of text output and whale alerts
+	#[serde(default)]
+	pub address_book: Option<PathBuf>,
+	// Confirmed blocks may fall behind the latest block by this many blocks before it's reported
+	#[serde(default)]
+	pub max_lag: Option<u64>,
+	// Swaps sent to the SwapRouter are reported while pending, requires the WebSocket transport
+	#[serde(default)]
+	pub include_pending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PoolConfig {
+	pub address: String,
+	pub token0: TokenConfig,
+	pub token1: TokenConfig,
+	// Fee in hundredths of a basis point, read from the pool contract when 0
+	#[serde(default)]
+	pub fee_tier: u32,
+	// ABI of this pool, overrides the ABI configured for all pools
+	#[serde(default)]
+	pub abi: Option<PathBuf>,
+	// Set for pools given only by address, their token symbols and decimals are read from the
+	// token contracts
+	#[serde(skip)]
+	pub read_tokens: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenConfig {
+	pub symbol: String,
+	pub decimals: u32,
+	// Read from the pool contract when missing and transfers are tracked
+	#[serde(default)]
+	pub address: Option<H160>,
+}
+
+fn default_confirmation_depth() -> usize {
+	DEFAULT_CONFIRMATION_DEPTH
+}
+
+fn default_poll_interval() -> u64 {
+	DEFAULT_POLL_INTERVAL.as_secs()
+}
+
+fn default_whale_threshold() -> Decimal {
+	DEFAULT_WHALE_THRESHOLD
+}
+
+fn default_stale_price_blocks() -> u64 {
+	DEFAULT_STALE_PRICE_BLOCKS
+}
+
+fn default_metrics_addr() -> Option<SocketAddr> {
+	Some(DEFAULT_METRICS_ADDR.parse().unwrap())
+}
+
+fn default_api_addr() -> Option<SocketAddr> {
+	Some(DEFAULT_API_ADDR.parse().unwrap())
+}
+
+// Address of a server that is enabled by default, `false` disables it
+fn server_addr<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<SocketAddr>, D::Error> {
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum ServerAddr {
+		Addr(SocketAddr),
+		Enabled(bool),
+	}
+
+	match ServerAddr::deserialize(deserializer)? {
+		ServerAddr::Addr(addr) => Ok(Some(addr)),
+		ServerAddr::Enabled(false) => Ok(None),
+		ServerAddr::Enabled(true) => Err(D::Error::custom("expected an address or false")),
+	}
+}
+
+impl AppConfig {
+	pub fn from_toml_file(path: &Path) -> Result<AppConfig> {
+		let contents = std::fs::read_to_string(path)
+			.with_context(|| format!("Failed to read config file '{}'", path.display()))?;
+
+		toml::from_str(&contents)
+			.with_context(|| format!("Failed to parse config file '{}'", path.display()))
+	}
+
+	// The confirmation depth only applies to probabilistic finality
+	pub fn finality(&self) -> Finality {
+		self.finality.with_confirmations(self.confirmation_depth)
+	}
+
+	// The configured RPC endpoints, or only `ws_url` if there are none
+	pub fn ws_endpoints(&self) -> Vec<String> {
+		match self.rpc_endpoints.is_empty() {
+			true => vec![self.ws_url.clone()],
+			false => self.rpc_endpoints.clone(),
+		}
+	}
+}
+
+// One endpoint per line, blank lines and lines starting with `#` are skipped
+pub fn read_endpoints(path: &Path) -> Result<Vec<String>> {
+	let contents = std::fs::read_to_string(path)
+		.with_context(|| format!("Failed to read endpoints file '{}'", path.display()))?;
+
+	Ok(contents
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(str::to_string)
+		.collect())
+}
+
+impl Default for AppConfig {
+	fn default() -> Self {
+		AppConfig {
+			network: Network::default(),
+			ws_url: String::new(),
+			rpc_endpoints: Vec::new(),
+			pools: Vec::new(),
+			confirmation_depth: DEFAULT_CONFIRMATION_DEPTH,
+			finality: FinalityKind::default(),
+			output_format: OutputFormat::default(),
+			transport: TransportKind::default(),
+			http_url: String::new(),
+			poll_interval: default_poll_interval(),
+			from_block: None,
+			to_block: None,
+			db: None,
+			whale_threshold: DEFAULT_WHALE_THRESHOLD,
+			replay: None,
+			metrics_addr: default_metrics_addr(),
+			grpc_addr: None,
+			api_addr: default_api_addr(),
+			ws_server_addr: None,
+			health_addr: None,
+			state_file: None,
+			watch_factory: false,
+			token_filter: None,
+			abi: None,
+			fetch_abi: false,
+			abi_cache_dir: None,
+			etherscan_api_key: None,
+			protocol: Protocol::default(),
+			track_transfers: false,
+			discord_webhook: None,
+			telegram_bot_token: None,
+			telegram_chat_id: None,
+			stale_price_blocks: DEFAULT_STALE_PRICE_BLOCKS,
+			track_gas: false,
+			compress: false,
+			pool_tvl_usd: None,
+			rpc_rate_limit: None,
+			dry_run: false,
+			swap_filter: SwapFilter::default(),
+			address_book: None,
+			max_lag: None,
+			include_pending: false,
+		}
+	}
+}
+
+impl PoolConfig {
+	pub fn contract_address(&self) -> Result<H160> {
+		self.address
+			.parse()
+			.with_context(|| format!("Invalid pool address '{}'", self.address))
+	}
+
+	// Symbols and decimals of the pool's tokens, shared by all of its swaps
+	pub fn token_pair(&self) -> Arc<TokenPair> {
+		let info = |token: &TokenConfig| TokenInfo {
+			symbol: token.symbol.clone(),
+			decimals: token.decimals,
+		};
+		Arc::new(TokenPair { token0: info(&self.token0), token1: info(&self.token1) })
+	}
+}
+
+impl Default for PoolConfig {
+	fn default() -> Self {
+		PoolConfig {
+			address: UNI_V3_DAI_USDC_POOL.to_string(),
+			token0: TokenConfig { symbol: "DAI".to_string(), decimals: 18, address: None },
+			token1: TokenConfig { symbol: "USDC".to_string(), decimals: 6, address: None },
+			fee_tier: 100,
+			abi: None,
+			read_tokens: false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod from_toml_file {
+		use super::*;
+
+		#[test]
+		fn example() {
+			let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config.example.toml");
+			let config = AppConfig::from_toml_file(&path).unwrap();
+
+			assert_eq!(config.ws_url, "wss://mainnet.infura.io/ws/v3/CHANGE_ME");
+			assert_eq!(config.pools, vec![PoolConfig::default()]);
+			assert_eq!(config.confirmation_depth, 5);
+			assert_eq!(config.output_format, OutputFormat::Text);
+			assert_eq!(config.transport, TransportKind::Ws);
+			assert_eq!(config.poll_interval, 12);
+		}
+
+		#[test]
+		fn missing_file() {
+			let result = AppConfig::from_toml_file(Path::new("missing.toml"));
+
+			assert!(result.is_err());
+			assert_eq!(
+				result.unwrap_err().to_string(),
+				"Failed to read config file 'missing.toml'".to_string()
+			);
+		}
+	}
+
+	mod server_addr {
+		use super::*;
+
+		#[test]
+		fn default_and_disabled() {
+			let config: AppConfig = toml::from_str("").unwrap();
+			assert_eq!(config.metrics_addr, Some("127.0.0.1:9090".parse().unwrap()));
+
+			let config: AppConfig = toml::from_str("metrics_addr = false").unwrap();
+			assert_eq!(config.metrics_addr, None);
+
+			assert!(toml::from_str::<AppConfig>("metrics_addr = true").is_err());
+
+			let config: AppConfig = toml::from_str("api_addr = false").unwrap();
+			assert_eq!(config.api_addr, None);
+			assert_eq!(config.metrics_addr, Some("127.0.0.1:9090".parse().unwrap()));
+		}
+	}
+
+	mod ws_endpoints {
+		use super::*;
+
+		#[test]
+		fn ws_url() {
+			let config = AppConfig { ws_url: "wss://a".to_string(), ..AppConfig::default() };
+
+			assert_eq!(config.ws_endpoints(), vec!["wss://a".to_string()]);
+		}
+
+		#[test]
+		fn rpc_endpoints() {
+			let config = AppConfig {
+				ws_url: "wss://a".to_string(),
+				rpc_endpoints: vec!["wss://b".to_string(), "wss://c".to_string()],
+				..AppConfig::default()
+			};
+
+			assert_eq!(config.ws_endpoints(), vec!["wss://b".to_string(), "wss://c".to_string()]);
+		}
+	}
+
+	mod read_endpoints {
+		use super::*;
+
+		#[test]
+		fn skips_blank_lines_and_comments() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("endpoints.txt");
+			std::fs::write(&path, "# Primary\nwss://a\n\n  wss://b  \n").unwrap();
+
+			assert_eq!(
+				read_endpoints(&path).unwrap(),
+				vec!["wss://a".to_string(), "wss://b".to_string()]
+			);
+		}
+	}
+
+	mod contract_address {
+		use super::*;
+
+		#[test]
+		fn ok() {
+			let pool = PoolConfig::default();
+
+			assert_eq!(
+				pool.contract_address().unwrap(),
+				H160::from_slice(&[
+					0x57, 0x77, 0xd9, 0x2f, 0x20, 0x86, 0x79, 0xdb, 0x4b, 0x97, 0x78, 0x59, 0x0f,
+					0xa3, 0xca, 0xb3, 0xac, 0x9e, 0x21, 0x68,
+				])
+			);
+		}
+
+		#[test]
+		fn err() {
+			let pool = PoolConfig { address: "0x1234".to_string(), ..PoolConfig::default() };
+
+			assert!(pool.contract_address().is_err());
+		}
+	}
+
+	mod token_pair {
+		use super::*;
+
+		#[test]
+		fn default_pool() {
+			assert_eq!(PoolConfig::default().token_pair(), TokenPair::dai_usdc());
+		}
+	}
+}
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::{
+	analytics::WhaleDetector,
+	event::{SwapDirection, SwapEvent},
+	rate_limit::RateLimiter,
+};
+
+// Discord allows 5 webhook requests per second
+const REQUESTS_PER_SECOND: f64 = 5.0;
+
+const GREEN: u32 = 0x2ecc71;
+const RED: u32 = 0xe74c3c;
+
+// Posts whale trades among the confirmed events to a Discord webhook
+pub struct DiscordNotifier {
+	client: reqwest::Client,
+	webhook_url: String,
+	// Block explorer linked from the transaction hash, e.g. https://etherscan.io
+	explorer_url: Option<String>,
+	whale_detector: WhaleDetector,
+	rate_limiter: RateLimiter,
+}
+
+impl DiscordNotifier {
+	pub fn new(
+		webhook_url: String,
+		explorer_url: Option<String>,
+		whale_detector: WhaleDetector,
+	) -> DiscordNotifier {
+		DiscordNotifier {
+			client: reqwest::Client::new(),
+			webhook_url,
+			explorer_url,
+			whale_detector,
+			rate_limiter: RateLimiter::new(REQUESTS_PER_SECOND),
+		}
+	}
+
+	// Runs until the event channel is closed, failed posts are logged and skipped
+	pub async fn run(mut self, mut events: broadcast::Receiver<SwapEvent>) {
+		loop {
+			match events.recv().await {
+				Ok(event) if self.whale_detector.is_whale(&event) =>
+					if let Err(err) = self.post(&event).await {
+						warn!(error = %err, "Failed to post swap to Discord");
+					},
+				Ok(_) => (),
+				Err(RecvError::Lagged(skipped)) =>
+					warn!(skipped, "Discord notifier lagging, skipped events"),
+				Err(RecvError::Closed) => return,
+			}
+		}
+	}
+
+	async fn post(&mut self, event: &SwapEvent) -> Result<()> {
+		self.rate_limiter.acquire().await;
+
+		self.client
+			.post(&self.webhook_url)
+			.json(&self.message(event))
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.context("Failed to post Discord webhook")?;
+
+		Ok(())
+	}
+
+	pub fn message(&self, event: &SwapEvent) -> serde_json::Value {
+		let field =
+			|name: &str, value: String| json!({ "name": name, "value": value, "inline": true });
+		let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+		let transaction = event.transaction_hash.map(|hash| match &self.explorer_url {
+			Some(explorer_url) => format!("[{:?}]({}/tx/{:?})", hash, explorer_url, hash),
+			None => format!("{:?}", hash),
+		});
+
+		let direction = event.direction.with_pair(&event.amounts.pair).to_string();
+		let [(token0, token0_amount), (token1, token1_amount)] = event.amounts.to_token_amounts();
+
+		json!({
+			"embeds": [{
+				"title": format!("Whale swap {}", direction),
+				"color": match event.direction {
+					SwapDirection::Token0ToToken1 => GREEN,
+					SwapDirection::Token1ToToken0 => RED,
+				},
+				"fields": [
+					field("Pool", format!("{:?}", event.pool)),
+					field("Block", optional(event.block_number.map(|n| n.to_string()))),
+					field("Direction", direction),
+					field(&token0.symbol, token0_amount.to_string()),
+					field(&token1.symbol, token1_amount.to_string()),
+					field("Exchange rate", optional(event.exchange_rate().map(|r| r.to_string()))),
+					field("Transaction", optional(transaction)),
+				],
+			}],
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use axum::{extract::State, routing::post, Json, Router};
+	use rust_decimal::Decimal;
+	use std::{
+		net::SocketAddr,
+		sync::{Arc, Mutex},
+	};
+	use web3::types::{H160, H256};
+
+	fn notifier(webhook_url: String) -> DiscordNotifier {
+		DiscordNotifier::new(
+			webhook_url,
+			Some("https://etherscan.io".to_string()),
+			WhaleDetector { token1_threshold: Decimal::new(100_000, 0) },
+		)
+	}
+
+	fn swap(usdc: i64) -> SwapEvent {
+		SwapEventBuilder::default()
+			.block_number(123)
+			.transaction_hash(H256::from([5; 32]))
+			.pool(H160::from([4; 20]))
+			.direction(SwapDirection::Token1ToToken0)
+			.token0_amount(Decimal::new(usdc * 2, 0))
+			.token1_amount(Decimal::new(usdc, 0))
+			.build()
+	}
+
+	// Records the payloads posted to the webhook
+	async fn serve() -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+		let posted = Arc::new(Mutex::new(Vec::new()));
+		let app = Router::new()
+			.route(
+				"/webhook",
+				post(
+					|State(posted): State<Arc<Mutex<Vec<serde_json::Value>>>>,
+					 Json(payload): Json<serde_json::Value>| async move {
+						posted.lock().unwrap().push(payload);
+					},
+				),
+			)
+			.with_state(posted.clone());
+
+		let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+			.serve(app.into_make_service());
+		let addr = server.local_addr();
+		tokio::spawn(server);
+
+		(format!("http://{}/webhook", addr), posted)
+	}
+
+	mod message {
+		use super::*;
+
+		#[test]
+		fn embed() {
+			let message = notifier(String::new()).message(&swap(200_000));
+			let embed = &message["embeds"][0];
+
+			assert_eq!(embed["title"], "Whale swap USDC → DAI");
+			assert_eq!(embed["color"], RED);
+			assert_eq!(embed["fields"][0]["value"], format!("{:?}", H160::from([4; 20])));
+			assert_eq!(embed["fields"][1]["value"], "123");
+			assert_eq!(embed["fields"][2]["value"], "USDC → DAI");
+			assert_eq!(embed["fields"][3]["name"], "DAI");
+			assert_eq!(embed["fields"][3]["value"], "400000");
+			assert_eq!(embed["fields"][4]["value"], "200000");
+			assert_eq!(embed["fields"][5]["value"], "0.50");
+			assert_eq!(
+				embed["fields"][6]["value"],
+				format!(
+					"[{:?}](https://etherscan.io/tx/{:?})",
+					H256::from([5; 32]),
+					H256::from([5; 32])
+				)
+			);
+		}
+
+		#[test]
+		fn without_explorer() {
+			let notifier = DiscordNotifier { explorer_url: None, ..notifier(String::new()) };
+			let event = SwapEvent { direction: SwapDirection::Token0ToToken1, ..swap(200_000) };
+
+			let message = notifier.message(&event);
+
+			assert_eq!(message["embeds"][0]["color"], GREEN);
+			assert_eq!(
+				message["embeds"][0]["fields"][6]["value"],
+				format!("{:?}", H256::from([5; 32]))
+			);
+		}
+	}
+
+	mod run {
+		use super::*;
+
+		#[tokio::test]
+		async fn posts_whales() {
+			let (url, posted) = serve().await;
+			let (sender, receiver) = broadcast::channel(16);
+
+			sender.send(swap(50_000)).unwrap();
+			sender.send(swap(200_000)).unwrap();
+			drop(sender);
+			notifier(url).run(receiver).await;
+
+			let posted = posted.lock().unwrap();
+			assert_eq!(posted.len(), 1);
+			assert_eq!(posted[0]["embeds"][0]["fields"][4]["value"], "200000");
+		}
+
+		#[tokio::test]
+		async fn unreachable_webhook() {
+			let (sender, receiver) = broadcast::channel(16);
+
+			sender.send(swap(200_000)).unwrap();
+			drop(sender);
+
+			// Nothing listens there, the failure is only logged
+			notifier("http://127.0.0.1:1/webhook".to_string()).run(receiver).await;
+		}
+	}
+}
@@ -0,0 +1,419 @@
+use anyhow::{anyhow, Context};
+use rust_decimal::Decimal;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use web3::{
+	ethabi::{Address, Contract, Event, RawLog, Token},
+	types::{Bytes, CallRequest, Log, H256},
+};
+
+use crate::{
+	event::{SwapEvent, TokenInfo},
+	parser::{ParseError, SwapParser},
+	transport::ContractCaller,
+	util::u256_to_signed_decimal,
+};
+
+const ERC20_ABI: &[u8] = include_bytes!("contracts/erc20_abi.json");
+
+pub fn transfer_event_abi() -> anyhow::Result<Event> {
+	Ok(Contract::load(ERC20_ABI)?.event("Transfer")?.clone())
+}
+
+// Reads symbols and decimals from the token contracts, every token is only read once
+#[derive(Debug)]
+pub struct TokenInfoCache {
+	abi: Contract,
+	tokens: HashMap<Address, TokenInfo>,
+}
+
+impl TokenInfoCache {
+	pub fn new() -> anyhow::Result<TokenInfoCache> {
+		Ok(TokenInfoCache { abi: Contract::load(ERC20_ABI)?, tokens: HashMap::new() })
+	}
+
+	pub async fn get(
+		&mut self,
+		source: &mut impl ContractCaller,
+		token: Address,
+	) -> anyhow::Result<&TokenInfo> {
+		match self.tokens.entry(token) {
+			Entry::Occupied(entry) => Ok(entry.into_mut()),
+			Entry::Vacant(entry) => {
+				let info = Self::fetch(source, &self.abi, token)
+					.await
+					.with_context(|| format!("Failed to read token info of {:?}", token))?;
+				Ok(entry.insert(info))
+			},
+		}
+	}
+
+	async fn fetch(
+		source: &mut impl ContractCaller,
+		abi: &Contract,
+		token: Address,
+	) -> anyhow::Result<TokenInfo> {
+		let decimals = match Self::call(source, abi, token, "decimals").await?.as_slice() {
+			[Token::Uint(decimals)] if *decimals <= 255.into() => decimals.low_u32(),
+			_ => return Err(anyhow!("Unexpected output of token contract")),
+		};
+		// Some tokens like MKR return their symbol as bytes32, they are named by address instead
+		let symbol = match Self::call(source, abi, token, "symbol").await.as_deref() {
+			Ok([Token::String(symbol)]) => symbol.clone(),
+			_ => format!("{:?}", token),
+		};
+
+		Ok(TokenInfo { symbol, decimals })
+	}
+
+	async fn call(
+		source: &mut impl ContractCaller,
+		abi: &Contract,
+		token: Address,
+		name: &str,
+	) -> anyhow::Result<Vec<Token>> {
+		let function = abi.function(name)?;
+		let request = CallRequest {
+			to: Some(token),
+			data: Some(Bytes(function.encode_input(&[])?)),
+			..CallRequest::default()
+		};
+
+		let output = source.call_contract(request).await?;
+		Ok(function.decode_output(&output.0)?)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferEvent {
+	pub block_number: Option<u64>,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub token: Address,
+	pub from: Address,
+	pub to: Address,
+	pub amount: Decimal,
+}
+
+pub struct TransferParser;
+
+impl TransferParser {
+	// `decimals` of the token emitting the log
+	pub fn parse(log: Log, abi: &Event, decimals: u32) -> Result<TransferEvent, ParseError> {
+		let block_number = log.block_number.map(|n| n.as_u64());
+		let transaction_hash = log.transaction_hash;
+		let log_index = log.log_index.map(|i| i.as_u64());
+		let token = log.address;
+
+		let log = &abi.parse_log(RawLog { topics: log.topics, data: log.data.0 })?;
+		// Token supplies are far below 2^255, so the sign bit is never set
+		let value = SwapParser::get_uint(log, "value")?;
+
+		Ok(TransferEvent {
+			block_number,
+			transaction_hash,
+			log_index,
+			token,
+			from: SwapParser::get_address(log, "from")?,
+			to: SwapParser::get_address(log, "to")?,
+			amount: u256_to_signed_decimal(value, 256, decimals, SwapParser::DECIMAL_PRECISION)
+				.ok_or(ParseError::AmountOutOfRange(value))?,
+		})
+	}
+}
+
+// Swaps and token transfers of a single transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionContext {
+	pub transaction_hash: H256,
+	// Of the transaction's first event
+	pub block_number: Option<u64>,
+	pub swaps: Vec<SwapEvent>,
+	pub transfers: Vec<TransferEvent>,
+}
+
+impl TransactionContext {
+	fn new(transaction_hash: H256, block_number: Option<u64>) -> TransactionContext {
+		TransactionContext {
+			transaction_hash,
+			block_number,
+			swaps: Vec::new(),
+			transfers: Vec::new(),
+		}
+	}
+
+	// Ordered by transaction hash, events without one can't be correlated and are left out
+	pub fn group(swaps: &[SwapEvent], transfers: Vec<TransferEvent>) -> Vec<TransactionContext> {
+		let mut contexts = BTreeMap::new();
+		fn context(
+			contexts: &mut BTreeMap<H256, TransactionContext>,
+			transaction_hash: H256,
+			block_number: Option<u64>,
+		) -> &mut TransactionContext {
+			contexts
+				.entry(transaction_hash)
+				.or_insert_with(|| TransactionContext::new(transaction_hash, block_number))
+		}
+
+		for swap in swaps {
+			if let Some(transaction_hash) = swap.transaction_hash {
+				context(&mut contexts, transaction_hash, swap.block_number)
+					.swaps
+					.push(swap.clone());
+			}
+		}
+		for transfer in transfers {
+			if let Some(transaction_hash) = transfer.transaction_hash {
+				context(&mut contexts, transaction_hash, transfer.block_number)
+					.transfers
+					.push(transfer);
+			}
+		}
+
+		contexts.into_values().collect()
+	}
+}
+
+// Groups events arriving in log order. A transaction's context is complete once an event of
+// another transaction arrives, events without a transaction hash are left out.
+#[derive(Debug, Default)]
+pub struct TransactionGrouper {
+	current: Option<TransactionContext>,
+}
+
+impl TransactionGrouper {
+	pub fn new() -> TransactionGrouper {
+		TransactionGrouper::default()
+	}
+
+	// Returns the context of the previous transaction if the swap starts a new one
+	pub fn push_swap(&mut self, swap: SwapEvent) -> Option<TransactionContext> {
+		let completed = self.start(swap.transaction_hash?, swap.block_number);
+		if let Some(current) = &mut self.current {
+			current.swaps.push(swap);
+		}
+		completed
+	}
+
+	// Returns the context of the previous transaction if the transfer starts a new one
+	pub fn push_transfer(&mut self, transfer: TransferEvent) -> Option<TransactionContext> {
+		let completed = self.start(transfer.transaction_hash?, transfer.block_number);
+		if let Some(current) = &mut self.current {
+			current.transfers.push(transfer);
+		}
+		completed
+	}
+
+	// Context of the last transaction, once no more events follow
+	pub fn finish(&mut self) -> Option<TransactionContext> {
+		self.current.take()
+	}
+
+	fn start(
+		&mut self,
+		transaction_hash: H256,
+		block_number: Option<u64>,
+	) -> Option<TransactionContext> {
+		match &self.current {
+			Some(current) if current.transaction_hash == transaction_hash => None,
+			_ => self.current.replace(TransactionContext::new(transaction_hash, block_number)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		event::{SwapEventBuilder, TokenPair},
+		testing::{swap_event_abi, transfer_log, SwapLogBuilder},
+	};
+
+	fn transfer(transaction_hash: Option<H256>, amount: i64) -> TransferEvent {
+		TransferEvent {
+			block_number: Some(1),
+			transaction_hash,
+			log_index: None,
+			token: Address::from([3; 20]),
+			from: Address::from([1; 20]),
+			to: Address::from([2; 20]),
+			amount: Decimal::new(amount, 2),
+		}
+	}
+
+	mod token_info_cache {
+		use super::*;
+		use crate::testing::MockWeb3Provider;
+		use web3::ethabi;
+
+		#[tokio::test]
+		async fn symbol_and_decimals() {
+			let token = Address::from([1; 20]);
+			let mut source = MockWeb3Provider::new().with_token(token, "USDC", 6);
+
+			let info =
+				TokenInfoCache::new().unwrap().get(&mut source, token).await.unwrap().clone();
+
+			assert_eq!(info, TokenInfo { symbol: "USDC".to_string(), decimals: 6 });
+		}
+
+		#[tokio::test]
+		async fn bytes32_symbol() {
+			let token = Address::from([1; 20]);
+			let abi = Contract::load(ERC20_ABI).unwrap();
+			let mut symbol = b"MKR".to_vec();
+			symbol.resize(32, 0);
+			let mut source = MockWeb3Provider::new().with_token(token, "", 6).with_call(
+				token,
+				abi.function("symbol").unwrap().short_signature(),
+				ethabi::encode(&[Token::FixedBytes(symbol)]),
+			);
+
+			let info =
+				TokenInfoCache::new().unwrap().get(&mut source, token).await.unwrap().clone();
+
+			assert_eq!(info.symbol, format!("{:?}", token));
+			assert_eq!(info.decimals, 6);
+		}
+
+		#[tokio::test]
+		async fn not_a_token() {
+			let token = Address::from([1; 20]);
+
+			let err = TokenInfoCache::new()
+				.unwrap()
+				.get(&mut MockWeb3Provider::new(), token)
+				.await
+				.unwrap_err();
+
+			assert_eq!(err.to_string(), format!("Failed to read token info of {:?}", token));
+		}
+	}
+
+	mod parse {
+		use super::*;
+
+		#[test]
+		fn ok() {
+			let abi = transfer_event_abi().unwrap();
+			let log = Log {
+				block_number: Some(123.into()),
+				transaction_hash: Some(H256::from([5; 32])),
+				log_index: Some(7.into()),
+				..transfer_log(
+					&abi,
+					Address::from([3; 20]),
+					Address::from([1; 20]),
+					Address::from([2; 20]),
+					1_233_000_001,
+				)
+			};
+
+			assert_eq!(
+				TransferParser::parse(log, &abi, 6).unwrap(),
+				TransferEvent {
+					block_number: Some(123),
+					transaction_hash: Some(H256::from([5; 32])),
+					log_index: Some(7),
+					token: Address::from([3; 20]),
+					from: Address::from([1; 20]),
+					to: Address::from([2; 20]),
+					amount: Decimal::new(123300, 2),
+				}
+			);
+		}
+
+		#[test]
+		fn other_event() {
+			let abi = transfer_event_abi().unwrap();
+			let log = SwapLogBuilder::new(&swap_event_abi()).build();
+
+			assert!(matches!(
+				TransferParser::parse(log, &abi, 18),
+				Err(ParseError::LogParseFailure(_))
+			));
+		}
+	}
+
+	mod group {
+		use super::*;
+
+		#[test]
+		fn by_transaction() {
+			let abi = swap_event_abi();
+			let swap = |transaction_hash| {
+				SwapParser::parse_log_only(
+					SwapLogBuilder::new(&abi).transaction_hash(transaction_hash).build(),
+					&abi,
+					&TokenPair::dai_usdc(),
+				)
+				.unwrap()
+			};
+			let swaps = vec![swap(H256::from([2; 32])), swap(H256::from([1; 32]))];
+			let transfers = vec![
+				transfer(Some(H256::from([1; 32])), 100),
+				transfer(Some(H256::from([3; 32])), 200),
+				transfer(Some(H256::from([1; 32])), 300),
+				transfer(None, 400),
+			];
+
+			let contexts = TransactionContext::group(&swaps, transfers.clone());
+
+			assert_eq!(
+				contexts,
+				vec![
+					TransactionContext {
+						transaction_hash: H256::from([1; 32]),
+						block_number: None,
+						swaps: vec![swaps[1].clone()],
+						transfers: vec![transfers[0].clone(), transfers[2].clone()],
+					},
+					TransactionContext {
+						transaction_hash: H256::from([2; 32]),
+						block_number: None,
+						swaps: vec![swaps[0].clone()],
+						transfers: vec![],
+					},
+					TransactionContext {
+						transaction_hash: H256::from([3; 32]),
+						block_number: Some(1),
+						swaps: vec![],
+						transfers: vec![transfers[1].clone()],
+					},
+				]
+			);
+		}
+	}
+
+	mod transaction_grouper {
+		use super::*;
+
+		fn swap(transaction_hash: H256, block_number: u64) -> SwapEvent {
+			SwapEventBuilder::default()
+				.transaction_hash(transaction_hash)
+				.block_number(block_number)
+				.build()
+		}
+
+		#[test]
+		fn completes_on_next_transaction() {
+			let mut grouper = TransactionGrouper::new();
+
+			assert_eq!(grouper.push_swap(swap(H256::from([1; 32]), 10)), None);
+			assert_eq!(grouper.push_transfer(transfer(Some(H256::from([1; 32])), 100)), None);
+			assert_eq!(grouper.push_swap(swap(H256::from([1; 32]), 10)), None);
+			// Events without a transaction hash don't end the transaction
+			assert_eq!(grouper.push_transfer(transfer(None, 200)), None);
+
+			let completed = grouper.push_swap(swap(H256::from([2; 32]), 11)).unwrap();
+			assert_eq!(completed.transaction_hash, H256::from([1; 32]));
+			assert_eq!(completed.block_number, Some(10));
+			assert_eq!(completed.swaps.len(), 2);
+			assert_eq!(completed.transfers, vec![transfer(Some(H256::from([1; 32])), 100)]);
+
+			let last = grouper.finish().unwrap();
+			assert_eq!(last.transaction_hash, H256::from([2; 32]));
+			assert_eq!(last.block_number, Some(11));
+			assert_eq!(grouper.finish(), None);
+		}
+	}
+}
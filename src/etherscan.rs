@@ -0,0 +1,174 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use web3::types::H160;
+
+#[derive(Debug, Deserialize)]
+struct Response {
+	// "1" on success, the result holds the error message otherwise
+	status: String,
+	result: String,
+}
+
+// $XDG_CACHE_HOME/uniswap-monitor, or ~/.cache/uniswap-monitor without it
+pub fn default_cache_dir() -> Result<PathBuf> {
+	let cache_home = match std::env::var_os("XDG_CACHE_HOME") {
+		Some(cache_home) => PathBuf::from(cache_home),
+		None => std::env::var_os("HOME")
+			.map(|home| Path::new(&home).join(".cache"))
+			.context("Missing cache directory, set --abi-cache-dir")?,
+	};
+
+	Ok(cache_home.join("uniswap-monitor"))
+}
+
+// Cache file of the contract's ABI within `dir`
+pub fn cache_path(dir: &Path, address: H160) -> PathBuf {
+	dir.join(format!("abi_{:?}.json", address))
+}
+
+// Returns the cached ABI if there is one, otherwise fetches and caches it. The directory is
+// created if needed.
+pub async fn load_abi(api_url: &str, api_key: &str, address: H160, dir: &Path) -> Result<PathBuf> {
+	let path = cache_path(dir, address);
+	if path.exists() {
+		return Ok(path);
+	}
+
+	let abi = fetch_abi(api_url, api_key, address).await?;
+	std::fs::create_dir_all(dir)
+		.with_context(|| format!("Failed to create ABI cache directory '{}'", dir.display()))?;
+	std::fs::write(&path, abi)
+		.with_context(|| format!("Failed to write ABI cache file '{}'", path.display()))?;
+
+	Ok(path)
+}
+
+// ABI JSON of a contract with verified source code
+pub async fn fetch_abi(api_url: &str, api_key: &str, address: H160) -> Result<String> {
+	let response: Response = reqwest::Client::new()
+		.get(api_url)
+		.query(&[
+			("module", "contract"),
+			("action", "getabi"),
+			("address", &format!("{:?}", address)),
+			("apikey", api_key),
+		])
+		.send()
+		.await
+		.and_then(|response| response.error_for_status())
+		.with_context(|| format!("Failed to fetch ABI of {:?} from Etherscan", address))?
+		.json()
+		.await
+		.context("Unexpected response from Etherscan")?;
+
+	if response.status != "1" {
+		bail!("Etherscan has no ABI of {:?}: {}", address, response.result);
+	}
+
+	Ok(response.result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{extract::Query, routing::get, Json, Router};
+	use std::{collections::HashMap, net::SocketAddr};
+
+	const ABI: &str = r#"[{"type":"event","name":"Swap","inputs":[],"anonymous":false}]"#;
+
+	// Answers like Etherscan, knowing the ABI of a single contract
+	async fn serve(address: H160) -> String {
+		let app = Router::new().route(
+			"/api",
+			get(move |Query(query): Query<HashMap<String, String>>| async move {
+				let known = query.get("module").map(String::as_str) == Some("contract") &&
+					query.get("action").map(String::as_str) == Some("getabi") &&
+					query.get("apikey").map(String::as_str) == Some("KEY") &&
+					query.get("address") == Some(&format!("{:?}", address));
+
+				Json(match known {
+					true => serde_json::json!({ "status": "1", "message": "OK", "result": ABI }),
+					false => serde_json::json!({
+						"status": "0",
+						"message": "NOTOK",
+						"result": "Contract source code not verified",
+					}),
+				})
+			}),
+		);
+
+		let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+			.serve(app.into_make_service());
+		let addr = server.local_addr();
+		tokio::spawn(server);
+
+		format!("http://{}/api", addr)
+	}
+
+	mod fetch_abi {
+		use super::*;
+
+		#[tokio::test]
+		async fn verified() {
+			let url = serve(H160::from([1; 20])).await;
+
+			assert_eq!(fetch_abi(&url, "KEY", H160::from([1; 20])).await.unwrap(), ABI);
+		}
+
+		#[tokio::test]
+		async fn unverified() {
+			let url = serve(H160::from([1; 20])).await;
+
+			let err = fetch_abi(&url, "KEY", H160::from([2; 20])).await.unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				format!(
+					"Etherscan has no ABI of {:?}: Contract source code not verified",
+					H160::from([2; 20])
+				)
+			);
+		}
+	}
+
+	mod load_abi {
+		use super::*;
+
+		#[tokio::test]
+		async fn caches() {
+			let dir = tempfile::tempdir().unwrap();
+			let url = serve(H160::from([1; 20])).await;
+
+			let path = load_abi(&url, "KEY", H160::from([1; 20]), dir.path()).await.unwrap();
+
+			assert_eq!(path, cache_path(dir.path(), H160::from([1; 20])));
+			assert_eq!(std::fs::read_to_string(&path).unwrap(), ABI);
+		}
+
+		#[tokio::test]
+		async fn creates_dir() {
+			let dir = tempfile::tempdir().unwrap();
+			let cache_dir = dir.path().join("cache").join("uniswap-monitor");
+			let url = serve(H160::from([1; 20])).await;
+
+			let path = load_abi(&url, "KEY", H160::from([1; 20]), &cache_dir).await.unwrap();
+
+			assert_eq!(path, cache_path(&cache_dir, H160::from([1; 20])));
+			assert!(path.exists());
+		}
+
+		#[tokio::test]
+		async fn cached() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = cache_path(dir.path(), H160::from([1; 20]));
+			std::fs::write(&path, ABI).unwrap();
+
+			// Nothing listens there, so any request would fail
+			let loaded =
+				load_abi("http://127.0.0.1:1/api", "KEY", H160::from([1; 20]), dir.path()).await;
+
+			assert_eq!(loaded.unwrap(), path);
+		}
+	}
+}
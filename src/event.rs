@@ -1,44 +1,1087 @@
 use rust_decimal::Decimal;
-use web3::ethabi::Address;
+use std::{
+	cmp::Ordering,
+	fmt,
+	hash::{Hash, Hasher},
+	str::FromStr,
+	sync::{Arc, OnceLock},
+};
+use web3::{
+	ethabi::Address,
+	types::{H256, U256},
+};
 
-#[derive(Debug, PartialEq)]
+use crate::{
+	address_book::AddressBook, network::Network, pool_state::PoolState, price,
+	util::checksum_address,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct SwapEvent {
+	pub block_number: Option<u64>,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub timestamp: Option<u64>,
+	pub pool: Address,
 	pub sender: Address,
 	pub receiver: Address,
 	pub direction: SwapDirection,
 	pub amounts: SwapAmounts,
+	// Pool state after the swap
+	pub sqrt_price_x96: U256,
+	pub liquidity: u128,
+	pub tick: i32,
 }
 
-impl ToString for SwapEvent {
-	fn to_string(&self) -> String {
-		match self.direction {
-			SwapDirection::DaiToUsdc => format!(
-				"Swap {} {} DAI -> {} USDC {}",
-				self.sender, self.amounts.dai, self.amounts.usdc, self.receiver
-			),
-			SwapDirection::UsdcToDai => format!(
-				"Swap {} {} USDC -> {} DAI {}",
-				self.sender, self.amounts.usdc, self.amounts.dai, self.receiver
-			),
+impl SwapEvent {
+	pub const CSV_HEADER: &'static str =
+		"block_number,transaction_hash,log_index,sender,receiver,direction,token0_amount,\
+		 token1_amount,exchange_rate";
+
+	pub fn exchange_rate(&self) -> Option<Decimal> {
+		self.amounts.exchange_rate()
+	}
+
+	// Fee tier in hundredths of a basis point, as configured for Uniswap V3 pools
+	pub fn fee_amount(&self, fee_tier: u32) -> SwapAmounts {
+		let fee = |amount: Decimal| amount * Decimal::from(fee_tier) / Decimal::from(1_000_000);
+		SwapAmounts {
+			token0_amount: fee(self.amounts.token0_amount),
+			token1_amount: fee(self.amounts.token1_amount),
+			pair: self.amounts.pair.clone(),
 		}
-		.to_string()
+	}
+
+	// Change of the pool price caused by the swap in basis points, `None` for pools without a
+	// price before the swap or changes beyond the decimal range
+	pub fn price_impact_bps(&self, pre_state: &PoolState) -> Option<Decimal> {
+		price::price_impact(pre_state.sqrt_price_x96, self.sqrt_price_x96)?
+			.checked_mul(Decimal::ONE_HUNDRED)
+	}
+
+	pub fn matches_filter(&self, filter: &SwapFilter) -> bool {
+		filter.matches(self)
+	}
+
+	// Displayed like the swap itself, with known addresses replaced by their labels
+	pub fn labeled<'a>(&'a self, address_book: &'a AddressBook) -> LabeledSwapEvent<'a> {
+		LabeledSwapEvent { event: self, address_book }
+	}
+
+	// Transaction on the network's block explorer, custom networks have none
+	pub fn to_etherscan_url(&self, network: &Network) -> Option<String> {
+		let transaction_hash = self.transaction_hash?;
+		Some(format!("{}/tx/{:?}", network.config()?.explorer_url, transaction_hash))
+	}
+
+	pub fn to_csv_row(&self) -> String {
+		fn field<T: ToString>(value: Option<T>) -> String {
+			value.map(|v| v.to_string()).unwrap_or_default()
+		}
+
+		format!(
+			"{},{},{},{:?},{:?},{},{},{},{}",
+			field(self.block_number),
+			field(self.transaction_hash.map(|hash| format!("{:?}", hash))),
+			field(self.log_index),
+			self.sender,
+			self.receiver,
+			self.direction.as_str(),
+			self.amounts.token0_amount,
+			self.amounts.token1_amount,
+			field(self.exchange_rate()),
+		)
 	}
 }
 
-#[derive(Debug, PartialEq)]
+// Canonical chain order, by block number and then log index, unknown positions sort last.
+// Remaining fields only break ties, so the order stays consistent with equality.
+impl Ord for SwapEvent {
+	fn cmp(&self, other: &Self) -> Ordering {
+		fn known_first<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+			match (a, b) {
+				(Some(a), Some(b)) => a.cmp(&b),
+				(Some(_), None) => Ordering::Less,
+				(None, Some(_)) => Ordering::Greater,
+				(None, None) => Ordering::Equal,
+			}
+		}
+
+		known_first(self.block_number, other.block_number)
+			.then_with(|| known_first(self.log_index, other.log_index))
+			.then_with(|| {
+				let key = |event: &SwapEvent| {
+					(
+						event.transaction_hash,
+						event.timestamp,
+						event.pool,
+						event.sender,
+						event.receiver,
+						event.sqrt_price_x96,
+						event.liquidity,
+						event.tick,
+					)
+				};
+				key(self).cmp(&key(other))
+			})
+			.then_with(|| self.direction.cmp(&other.direction))
+			.then_with(|| self.amounts.cmp(&other.amounts))
+	}
+}
+
+impl PartialOrd for SwapEvent {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Display for SwapEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.labeled(&AddressBook::default()).fmt(f)
+	}
+}
+
+pub struct LabeledSwapEvent<'a> {
+	event: &'a SwapEvent,
+	address_book: &'a AddressBook,
+}
+
+impl fmt::Display for LabeledSwapEvent<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let event = self.event;
+		let SwapAmounts { token0_amount, token1_amount, pair } = &event.amounts;
+		let name = |address: &Address| match self.address_book.label(address) {
+			Some(label) => label.to_string(),
+			None => checksum_address(address),
+		};
+		let (sender, receiver) = (name(&event.sender), name(&event.receiver));
+
+		let (amount_in, amount_out) = match event.direction {
+			SwapDirection::Token0ToToken1 => (token0_amount, token1_amount),
+			SwapDirection::Token1ToToken0 => (token1_amount, token0_amount),
+		};
+		let (token_in, token_out) = event.direction.tokens(pair);
+
+		write!(
+			f,
+			"Swap {} {} {} -> {} {} {}",
+			sender, amount_in, token_in.symbol, amount_out, token_out.symbol, receiver
+		)
+	}
+}
+
+pub struct SwapEventBuilder {
+	event: SwapEvent,
+}
+
+impl Default for SwapEventBuilder {
+	fn default() -> Self {
+		SwapEventBuilder {
+			event: SwapEvent {
+				block_number: None,
+				transaction_hash: None,
+				log_index: None,
+				timestamp: None,
+				pool: Address::zero(),
+				sender: Address::zero(),
+				receiver: Address::zero(),
+				direction: SwapDirection::Token0ToToken1,
+				amounts: SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::ZERO),
+				sqrt_price_x96: U256::zero(),
+				liquidity: 0,
+				tick: 0,
+			},
+		}
+	}
+}
+
+impl SwapEventBuilder {
+	pub fn block_number(mut self, block_number: u64) -> Self {
+		self.event.block_number = Some(block_number);
+		self
+	}
+
+	pub fn transaction_hash(mut self, transaction_hash: H256) -> Self {
+		self.event.transaction_hash = Some(transaction_hash);
+		self
+	}
+
+	pub fn log_index(mut self, log_index: u64) -> Self {
+		self.event.log_index = Some(log_index);
+		self
+	}
+
+	pub fn timestamp(mut self, timestamp: u64) -> Self {
+		self.event.timestamp = Some(timestamp);
+		self
+	}
+
+	pub fn pool(mut self, pool: Address) -> Self {
+		self.event.pool = pool;
+		self
+	}
+
+	pub fn sender(mut self, sender: Address) -> Self {
+		self.event.sender = sender;
+		self
+	}
+
+	pub fn receiver(mut self, receiver: Address) -> Self {
+		self.event.receiver = receiver;
+		self
+	}
+
+	pub fn direction(mut self, direction: SwapDirection) -> Self {
+		self.event.direction = direction;
+		self
+	}
+
+	pub fn token0_amount(mut self, token0_amount: Decimal) -> Self {
+		self.event.amounts.token0_amount = token0_amount;
+		self
+	}
+
+	pub fn token1_amount(mut self, token1_amount: Decimal) -> Self {
+		self.event.amounts.token1_amount = token1_amount;
+		self
+	}
+
+	pub fn pair(mut self, pair: Arc<TokenPair>) -> Self {
+		self.event.amounts.pair = pair;
+		self
+	}
+
+	pub fn sqrt_price_x96(mut self, sqrt_price_x96: U256) -> Self {
+		self.event.sqrt_price_x96 = sqrt_price_x96;
+		self
+	}
+
+	pub fn liquidity(mut self, liquidity: u128) -> Self {
+		self.event.liquidity = liquidity;
+		self
+	}
+
+	pub fn tick(mut self, tick: i32) -> Self {
+		self.event.tick = tick;
+		self
+	}
+
+	pub fn build(self) -> SwapEvent {
+		self.event
+	}
+}
+
+#[derive(
+	Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum SwapDirection {
-	DaiToUsdc,
-	UsdcToDai,
+	// Names of the DAI/USDC pool, where DAI is token0, are still accepted
+	#[serde(alias = "dai_to_usdc")]
+	Token0ToToken1,
+	#[serde(alias = "usdc_to_dai")]
+	Token1ToToken0,
 }
 
-#[derive(Debug, PartialEq)]
+impl SwapDirection {
+	// Same as the serialized value, also used in the database. Tokens are named by their position
+	// in the pool, `with_pair` names them by symbol.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			SwapDirection::Token0ToToken1 => "token0_to_token1",
+			SwapDirection::Token1ToToken0 => "token1_to_token0",
+		}
+	}
+
+	// Token sold to the pool and token bought from it
+	pub fn tokens<'a>(&self, pair: &'a TokenPair) -> (&'a TokenInfo, &'a TokenInfo) {
+		match self {
+			SwapDirection::Token0ToToken1 => (&pair.token0, &pair.token1),
+			SwapDirection::Token1ToToken0 => (&pair.token1, &pair.token0),
+		}
+	}
+
+	// Displayed with the symbols of the pool's tokens, e.g. "DAI → USDC"
+	pub fn with_pair<'a>(&'a self, pair: &'a TokenPair) -> PairDirection<'a> {
+		PairDirection { direction: self, pair }
+	}
+}
+
+pub struct PairDirection<'a> {
+	direction: &'a SwapDirection,
+	pair: &'a TokenPair,
+}
+
+impl PairDirection<'_> {
+	// Lowercase and in snake case like the serialized value, e.g. "dai_to_usdc", used as metrics
+	// label
+	pub fn name(&self) -> String {
+		let (token_in, token_out) = self.direction.tokens(self.pair);
+		format!("{}_to_{}", token_in.symbol.to_lowercase(), token_out.symbol.to_lowercase())
+	}
+}
+
+impl fmt::Display for PairDirection<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (token_in, token_out) = self.direction.tokens(self.pair);
+		write!(f, "{} → {}", token_in.symbol, token_out.symbol)
+	}
+}
+
+// Case-insensitive, in snake case like the serialized value or in Pascal case like the variant
+impl FromStr for SwapDirection {
+	type Err = SwapDirectionParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.replace('_', "").to_lowercase().as_str() {
+			"token0totoken1" | "daitousdc" => Ok(SwapDirection::Token0ToToken1),
+			"token1totoken0" | "usdctodai" => Ok(SwapDirection::Token1ToToken0),
+			_ => Err(SwapDirectionParseError { value: s.to_string() }),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapDirectionParseError {
+	pub value: String,
+}
+
+impl fmt::Display for SwapDirectionParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid swap direction '{}', expected token0_to_token1 or token1_to_token0",
+			self.value
+		)
+	}
+}
+
+impl std::error::Error for SwapDirectionParseError {}
+
+// Conditions a swap has to meet to be emitted, missing conditions match every swap
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct SwapFilter {
+	// Inclusive bounds of the token0 amount
+	#[serde(default, alias = "min_dai_amount")]
+	pub min_token0_amount: Option<Decimal>,
+	#[serde(default, alias = "max_dai_amount")]
+	pub max_token0_amount: Option<Decimal>,
+	#[serde(default)]
+	pub direction: Option<SwapDirection>,
+	#[serde(default)]
+	pub sender_whitelist: Option<Vec<Address>>,
+	#[serde(default)]
+	pub receiver_whitelist: Option<Vec<Address>>,
+}
+
+impl SwapFilter {
+	pub fn matches(&self, event: &SwapEvent) -> bool {
+		let token0_amount = event.amounts.token0_amount;
+
+		self.min_token0_amount.map_or(true, |min| token0_amount >= min) &&
+			self.max_token0_amount.map_or(true, |max| token0_amount <= max) &&
+			self.direction.as_ref().map_or(true, |direction| event.direction == *direction) &&
+			self.sender_whitelist
+				.as_ref()
+				.map_or(true, |senders| senders.contains(&event.sender)) &&
+			self.receiver_whitelist
+				.as_ref()
+				.map_or(true, |receivers| receivers.contains(&event.receiver))
+	}
+}
+
+#[derive(
+	Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct TokenInfo {
+	pub symbol: String,
+	pub decimals: u32,
+}
+
+// Tokens of a pool, ordered by address like in the pool contract
+#[derive(
+	Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct TokenPair {
+	pub token0: TokenInfo,
+	pub token1: TokenInfo,
+}
+
+impl TokenPair {
+	// Shared by all swaps of the default DAI/USDC pool
+	pub fn dai_usdc() -> Arc<TokenPair> {
+		static DAI_USDC: OnceLock<Arc<TokenPair>> = OnceLock::new();
+
+		DAI_USDC
+			.get_or_init(|| {
+				Arc::new(TokenPair {
+					token0: TokenInfo { symbol: "DAI".to_string(), decimals: 18 },
+					token1: TokenInfo { symbol: "USDC".to_string(), decimals: 6 },
+				})
+			})
+			.clone()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub struct SwapAmounts {
-	pub dai: Decimal,
-	pub usdc: Decimal,
+	pub token0_amount: Decimal,
+	pub token1_amount: Decimal,
+	pub pair: Arc<TokenPair>,
+}
+
+// Equal decimals may differ in scale (e.g. 1.0 and 1.00), so hash the normalized form
+impl Hash for SwapAmounts {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.token0_amount.normalize().to_string().hash(state);
+		self.token1_amount.normalize().to_string().hash(state);
+		self.pair.hash(state);
+	}
 }
 
 impl SwapAmounts {
+	pub fn dai_usdc(dai: Decimal, usdc: Decimal) -> SwapAmounts {
+		SwapAmounts { token0_amount: dai, token1_amount: usdc, pair: TokenPair::dai_usdc() }
+	}
+
 	pub(crate) fn abs(&self) -> SwapAmounts {
-		SwapAmounts { dai: self.dai.abs(), usdc: self.usdc.abs() }
+		SwapAmounts {
+			token0_amount: self.token0_amount.abs(),
+			token1_amount: self.token1_amount.abs(),
+			pair: self.pair.clone(),
+		}
+	}
+
+	// Absolute amounts of a swap are positive, amounts truncated to zero make a degenerate swap
+	pub fn is_valid(&self) -> bool {
+		self.token0_amount > Decimal::ZERO && self.token1_amount > Decimal::ZERO
+	}
+
+	// Both amounts with `target_scale` decimal places, rounding the dropped ones
+	pub fn scaled(&self, target_scale: u32) -> SwapAmounts {
+		let scaled = |mut amount: Decimal| {
+			amount.rescale(target_scale);
+			amount
+		};
+
+		SwapAmounts {
+			token0_amount: scaled(self.token0_amount),
+			token1_amount: scaled(self.token1_amount),
+			pair: self.pair.clone(),
+		}
+	}
+
+	pub fn percentage_change(from: &SwapAmounts, to: &SwapAmounts) -> Option<Decimal> {
+		let old_price = from.exchange_rate()?;
+		let new_price = to.exchange_rate()?;
+
+		new_price
+			.checked_sub(old_price)?
+			.checked_div(old_price)?
+			.checked_mul(Decimal::ONE_HUNDRED)
+	}
+
+	pub fn exchange_rate(&self) -> Option<Decimal> {
+		self.token1_amount.checked_div(self.token0_amount)
+	}
+
+	// Each token with its amount, token0 first, for code that handles any pair
+	pub fn to_token_amounts(&self) -> [(&TokenInfo, Decimal); 2] {
+		[(&self.pair.token0, self.token0_amount), (&self.pair.token1, self.token1_amount)]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod hash {
+		use super::*;
+		use std::collections::hash_map::DefaultHasher;
+
+		fn hash_of(amounts: &SwapAmounts) -> u64 {
+			let mut hasher = DefaultHasher::new();
+			amounts.hash(&mut hasher);
+			hasher.finish()
+		}
+
+		#[test]
+		fn equal_amounts() {
+			let a = SwapAmounts::dai_usdc(Decimal::new(12345, 2), Decimal::new(6789, 2));
+			let b = SwapAmounts::dai_usdc(Decimal::new(123450, 3), Decimal::new(67890, 3));
+
+			assert_eq!(a, b);
+			assert_eq!(hash_of(&a), hash_of(&b));
+		}
+
+		#[test]
+		fn distinct_amounts() {
+			let a = SwapAmounts::dai_usdc(Decimal::new(12345, 2), Decimal::new(6789, 2));
+			let b = SwapAmounts::dai_usdc(Decimal::new(6789, 2), Decimal::new(12345, 2));
+
+			assert_ne!(hash_of(&a), hash_of(&b));
+		}
+	}
+
+	mod swap_event_builder {
+		use super::*;
+
+		#[test]
+		fn defaults() {
+			let event = SwapEventBuilder::default().build();
+
+			assert_eq!(
+				event,
+				SwapEvent {
+					block_number: None,
+					transaction_hash: None,
+					log_index: None,
+					timestamp: None,
+					pool: Address::zero(),
+					sender: Address::zero(),
+					receiver: Address::zero(),
+					direction: SwapDirection::Token0ToToken1,
+					amounts: SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::ZERO),
+					sqrt_price_x96: U256::zero(),
+					liquidity: 0,
+					tick: 0,
+				}
+			);
+		}
+
+		#[test]
+		fn all_fields() {
+			let event = SwapEventBuilder::default()
+				.block_number(12345)
+				.transaction_hash(H256([1; 32]))
+				.log_index(3)
+				.timestamp(1_700_000_000)
+				.pool(Address::from([4; 20]))
+				.sender(Address::from([2; 20]))
+				.receiver(Address::from([3; 20]))
+				.direction(SwapDirection::Token1ToToken0)
+				.token0_amount(Decimal::new(100, 2))
+				.token1_amount(Decimal::new(101, 2))
+				.sqrt_price_x96(U256::from(2).pow(96.into()))
+				.liquidity(1_000_000)
+				.tick(-5)
+				.build();
+
+			assert_eq!(
+				event,
+				SwapEvent {
+					block_number: Some(12345),
+					transaction_hash: Some(H256([1; 32])),
+					log_index: Some(3),
+					timestamp: Some(1_700_000_000),
+					pool: Address::from([4; 20]),
+					sender: Address::from([2; 20]),
+					receiver: Address::from([3; 20]),
+					direction: SwapDirection::Token1ToToken0,
+					amounts: SwapAmounts::dai_usdc(Decimal::new(100, 2), Decimal::new(101, 2)),
+					sqrt_price_x96: U256::from(2).pow(96.into()),
+					liquidity: 1_000_000,
+					tick: -5,
+				}
+			);
+		}
+	}
+
+	mod ord {
+		use super::*;
+
+		fn event(block_number: Option<u64>, log_index: Option<u64>) -> SwapEvent {
+			SwapEvent { block_number, log_index, ..SwapEventBuilder::default().build() }
+		}
+
+		#[test]
+		fn different_blocks() {
+			let mut events = vec![event(Some(12), Some(0)), event(Some(10), Some(5))];
+			events.sort();
+
+			assert_eq!(events, vec![event(Some(10), Some(5)), event(Some(12), Some(0))]);
+		}
+
+		#[test]
+		fn same_block() {
+			let mut events =
+				vec![event(Some(10), None), event(Some(10), Some(7)), event(Some(10), Some(2))];
+			events.sort();
+
+			assert_eq!(
+				events,
+				vec![event(Some(10), Some(2)), event(Some(10), Some(7)), event(Some(10), None)]
+			);
+		}
+
+		#[test]
+		fn consistent_with_eq() {
+			let a = event(Some(10), Some(2));
+			let b = SwapEvent { sender: Address::from([1; 20]), ..a.clone() };
+
+			assert_eq!(a.cmp(&a.clone()), Ordering::Equal);
+			assert_ne!(a.cmp(&b), Ordering::Equal);
+		}
+	}
+
+	mod fee_amount {
+		use super::*;
+
+		#[test]
+		fn fee_tiers() {
+			let event = SwapEventBuilder::default()
+				.token0_amount(Decimal::new(1_000_000, 2))
+				.token1_amount(Decimal::new(999_900, 2))
+				.build();
+
+			assert_eq!(
+				event.fee_amount(100),
+				SwapAmounts::dai_usdc(Decimal::ONE, Decimal::new(9999, 4))
+			);
+			assert_eq!(
+				event.fee_amount(3000),
+				SwapAmounts::dai_usdc(Decimal::from(30), Decimal::new(29997, 3))
+			);
+			assert_eq!(event.fee_amount(0), SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::ZERO));
+		}
+	}
+
+	mod display {
+		use super::*;
+
+		#[test]
+		fn token_symbols() {
+			let pair = TokenPair {
+				token0: TokenInfo { symbol: "WETH".to_string(), decimals: 18 },
+				token1: TokenInfo { symbol: "USDT".to_string(), decimals: 6 },
+			};
+			let event = SwapEventBuilder::default()
+				.pair(Arc::new(pair))
+				.direction(SwapDirection::Token1ToToken0)
+				.token0_amount(Decimal::new(1, 0))
+				.token1_amount(Decimal::new(300000, 2))
+				.build();
+
+			assert_eq!(
+				event.to_string(),
+				format!("Swap 0x{0} 3000.00 USDT -> 1 WETH 0x{0}", "0".repeat(40))
+			);
+		}
+
+		#[test]
+		fn labeled() {
+			use std::collections::HashMap;
+
+			let event = SwapEventBuilder::default()
+				.sender(Address::repeat_byte(1))
+				.receiver(Address::repeat_byte(2))
+				.token0_amount(Decimal::new(100, 0))
+				.token1_amount(Decimal::new(9990, 2))
+				.build();
+			let address_book = AddressBook::new(HashMap::from([(
+				Address::repeat_byte(1),
+				"Uniswap V3 Router".to_string(),
+			)]));
+
+			assert_eq!(
+				event.labeled(&address_book).to_string(),
+				format!("Swap Uniswap V3 Router 100 DAI -> 99.90 USDC 0x{}", "02".repeat(20))
+			);
+		}
+	}
+
+	mod swap_direction {
+		use super::*;
+
+		#[test]
+		fn with_pair() {
+			let pair = TokenPair::dai_usdc();
+
+			assert_eq!(SwapDirection::Token0ToToken1.with_pair(&pair).to_string(), "DAI → USDC");
+			assert_eq!(SwapDirection::Token1ToToken0.with_pair(&pair).to_string(), "USDC → DAI");
+			assert_eq!(SwapDirection::Token0ToToken1.with_pair(&pair).name(), "dai_to_usdc");
+			assert_eq!(SwapDirection::Token1ToToken0.with_pair(&pair).name(), "usdc_to_dai");
+		}
+
+		#[test]
+		fn with_other_pair() {
+			let pair = TokenPair {
+				token0: TokenInfo { symbol: "USDC".to_string(), decimals: 6 },
+				token1: TokenInfo { symbol: "WETH".to_string(), decimals: 18 },
+			};
+
+			assert_eq!(SwapDirection::Token1ToToken0.with_pair(&pair).to_string(), "WETH → USDC");
+			assert_eq!(SwapDirection::Token1ToToken0.with_pair(&pair).name(), "weth_to_usdc");
+		}
+
+		#[test]
+		fn as_str_matches_serde() {
+			for direction in [SwapDirection::Token0ToToken1, SwapDirection::Token1ToToken0] {
+				assert_eq!(
+					serde_json::to_value(&direction).unwrap(),
+					serde_json::Value::from(direction.as_str())
+				);
+			}
+		}
+
+		#[test]
+		fn from_str() {
+			for direction in [SwapDirection::Token0ToToken1, SwapDirection::Token1ToToken0] {
+				assert_eq!(direction.as_str().parse::<SwapDirection>().unwrap(), direction);
+			}
+
+			for name in ["Token0ToToken1", "TOKEN0_TO_TOKEN1", "DaiToUsdc", "dai_to_usdc"] {
+				assert_eq!(name.parse::<SwapDirection>().unwrap(), SwapDirection::Token0ToToken1);
+			}
+			assert_eq!(
+				"UsdcToDai".parse::<SwapDirection>().unwrap(),
+				SwapDirection::Token1ToToken0
+			);
+		}
+
+		#[test]
+		fn tokens() {
+			let pair = TokenPair::dai_usdc();
+			let symbols = |direction: SwapDirection| {
+				let (token_in, token_out) = direction.tokens(&pair);
+				(token_in.symbol.as_str(), token_out.symbol.as_str())
+			};
+
+			assert_eq!(symbols(SwapDirection::Token0ToToken1), ("DAI", "USDC"));
+			assert_eq!(symbols(SwapDirection::Token1ToToken0), ("USDC", "DAI"));
+		}
+
+		#[test]
+		fn from_str_unknown() {
+			let err = "dai_to_weth".parse::<SwapDirection>().unwrap_err();
+
+			assert_eq!(err, SwapDirectionParseError { value: "dai_to_weth".to_string() });
+			assert_eq!(
+				err.to_string(),
+				"Invalid swap direction 'dai_to_weth', expected token0_to_token1 or \
+				 token1_to_token0"
+			);
+		}
+	}
+
+	mod swap_filter {
+		use super::*;
+
+		fn swap(direction: SwapDirection, amount: i64, sender: u8, receiver: u8) -> SwapEvent {
+			SwapEventBuilder::default()
+				.direction(direction)
+				.token0_amount(Decimal::from(amount))
+				.token1_amount(Decimal::from(amount))
+				.sender(Address::repeat_byte(sender))
+				.receiver(Address::repeat_byte(receiver))
+				.build()
+		}
+
+		#[test]
+		fn empty() {
+			assert!(
+				swap(SwapDirection::Token0ToToken1, 1, 1, 2).matches_filter(&SwapFilter::default())
+			);
+		}
+
+		#[test]
+		fn min_token0_amount() {
+			let filter =
+				SwapFilter { min_token0_amount: Some(Decimal::from(100)), ..SwapFilter::default() };
+
+			assert!(swap(SwapDirection::Token0ToToken1, 100, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 99, 1, 2).matches_filter(&filter));
+		}
+
+		#[test]
+		fn max_token0_amount() {
+			let filter =
+				SwapFilter { max_token0_amount: Some(Decimal::from(100)), ..SwapFilter::default() };
+
+			assert!(swap(SwapDirection::Token0ToToken1, 100, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 101, 1, 2).matches_filter(&filter));
+		}
+
+		#[test]
+		fn direction() {
+			let filter = SwapFilter {
+				direction: Some(SwapDirection::Token1ToToken0),
+				..SwapFilter::default()
+			};
+
+			assert!(swap(SwapDirection::Token1ToToken0, 1, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 1, 1, 2).matches_filter(&filter));
+		}
+
+		#[test]
+		fn sender_whitelist() {
+			let filter = SwapFilter {
+				sender_whitelist: Some(vec![Address::repeat_byte(1), Address::repeat_byte(3)]),
+				..SwapFilter::default()
+			};
+
+			assert!(swap(SwapDirection::Token0ToToken1, 1, 3, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 1, 2, 1).matches_filter(&filter));
+		}
+
+		#[test]
+		fn receiver_whitelist() {
+			let filter = SwapFilter {
+				receiver_whitelist: Some(vec![Address::repeat_byte(2)]),
+				..SwapFilter::default()
+			};
+
+			assert!(swap(SwapDirection::Token0ToToken1, 1, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 1, 2, 1).matches_filter(&filter));
+			// An empty whitelist matches no swap
+			let filter = SwapFilter { receiver_whitelist: Some(vec![]), ..SwapFilter::default() };
+			assert!(!swap(SwapDirection::Token0ToToken1, 1, 1, 2).matches_filter(&filter));
+		}
+
+		#[test]
+		fn combined() {
+			let filter = SwapFilter {
+				min_token0_amount: Some(Decimal::from(10)),
+				max_token0_amount: Some(Decimal::from(100)),
+				direction: Some(SwapDirection::Token0ToToken1),
+				sender_whitelist: Some(vec![Address::repeat_byte(1)]),
+				receiver_whitelist: Some(vec![Address::repeat_byte(2)]),
+			};
+
+			assert!(swap(SwapDirection::Token0ToToken1, 50, 1, 2).matches_filter(&filter));
+			// Each condition on its own rejects the swap
+			assert!(!swap(SwapDirection::Token0ToToken1, 5, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 500, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token1ToToken0, 50, 1, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 50, 9, 2).matches_filter(&filter));
+			assert!(!swap(SwapDirection::Token0ToToken1, 50, 1, 9).matches_filter(&filter));
+		}
+
+		#[test]
+		fn deserialize() {
+			let filter: SwapFilter = toml::from_str(
+				r#"
+				min_token0_amount = "1000.5"
+				direction = "token1_to_token0"
+				sender_whitelist = ["0x0101010101010101010101010101010101010101"]
+				"#,
+			)
+			.unwrap();
+
+			assert_eq!(
+				filter,
+				SwapFilter {
+					min_token0_amount: Some(Decimal::new(10_005, 1)),
+					direction: Some(SwapDirection::Token1ToToken0),
+					sender_whitelist: Some(vec![Address::repeat_byte(1)]),
+					..SwapFilter::default()
+				}
+			);
+		}
+
+		// Filters written for the DAI/USDC pool keep working
+		#[test]
+		fn deserialize_dai_usdc_names() {
+			let filter: SwapFilter = toml::from_str(
+				r#"
+				max_dai_amount = "10"
+				direction = "usdc_to_dai"
+				"#,
+			)
+			.unwrap();
+
+			assert_eq!(filter.max_token0_amount, Some(Decimal::from(10)));
+			assert_eq!(filter.direction, Some(SwapDirection::Token1ToToken0));
+		}
+	}
+
+	mod price_impact_bps {
+		use super::*;
+
+		#[test]
+		fn basis_points() {
+			let event =
+				SwapEventBuilder::default().sqrt_price_x96(U256::from(1_000_100) << 96).build();
+			let pre_state =
+				PoolState { sqrt_price_x96: U256::from(1_000_000) << 96, ..PoolState::default() };
+
+			// (1.0001^2 - 1) * 10_000
+			assert_eq!(event.price_impact_bps(&pre_state), Some(Decimal::new(20001, 4)));
+		}
+
+		#[test]
+		fn uninitialized_pool() {
+			let event = SwapEventBuilder::default().sqrt_price_x96(U256::one() << 96).build();
+
+			assert_eq!(event.price_impact_bps(&PoolState::default()), None);
+		}
+	}
+
+	mod to_etherscan_url {
+		use super::*;
+
+		#[test]
+		fn named_networks() {
+			let event = SwapEventBuilder::default().transaction_hash(H256([0xab; 32])).build();
+			let hash = "ab".repeat(32);
+
+			assert_eq!(
+				event.to_etherscan_url(&Network::Mainnet),
+				Some(format!("https://etherscan.io/tx/0x{}", hash))
+			);
+			assert_eq!(
+				event.to_etherscan_url(&Network::Arbitrum),
+				Some(format!("https://arbiscan.io/tx/0x{}", hash))
+			);
+			assert_eq!(event.to_etherscan_url(&Network::Custom), None);
+		}
+
+		#[test]
+		fn without_transaction_hash() {
+			assert_eq!(
+				SwapEventBuilder::default().build().to_etherscan_url(&Network::Mainnet),
+				None
+			);
+		}
+	}
+
+	mod to_csv_row {
+		use super::*;
+
+		#[test]
+		fn full() {
+			let event = SwapEventBuilder::default()
+				.block_number(15_000_000)
+				.transaction_hash(H256([0xab; 32]))
+				.log_index(7)
+				.sender(Address::from([0x11; 20]))
+				.receiver(Address::from([0x22; 20]))
+				.direction(SwapDirection::Token0ToToken1)
+				.token0_amount(Decimal::new(20000, 2))
+				.token1_amount(Decimal::new(19990, 2))
+				.build();
+
+			assert_eq!(
+				event.to_csv_row(),
+				format!(
+					"15000000,0x{},7,0x{},0x{},token0_to_token1,200.00,199.90,0.9995",
+					"ab".repeat(32),
+					"11".repeat(20),
+					"22".repeat(20),
+				)
+			);
+		}
+
+		#[test]
+		fn missing_context() {
+			let event = SwapEventBuilder::default()
+				.sender(Address::from([0x11; 20]))
+				.receiver(Address::from([0x22; 20]))
+				.direction(SwapDirection::Token1ToToken0)
+				.token1_amount(Decimal::new(100, 2))
+				.build();
+
+			assert_eq!(
+				event.to_csv_row(),
+				format!(",,,0x{},0x{},token1_to_token0,0,1.00,", "11".repeat(20), "22".repeat(20))
+			);
+		}
+	}
+
+	mod is_valid {
+		use super::*;
+
+		#[test]
+		fn positive() {
+			assert!(SwapAmounts::dai_usdc(Decimal::new(1, 2), Decimal::new(1, 2)).is_valid());
+		}
+
+		#[test]
+		fn zero() {
+			assert!(!SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::new(1, 2)).is_valid());
+			assert!(!SwapAmounts::dai_usdc(Decimal::new(1, 2), Decimal::new(0, 2)).is_valid());
+		}
+
+		#[test]
+		fn negative() {
+			assert!(!SwapAmounts::dai_usdc(Decimal::new(-1, 2), Decimal::new(1, 2)).is_valid());
+			assert!(!SwapAmounts::dai_usdc(Decimal::new(1, 2), Decimal::new(-1, 2)).is_valid());
+		}
+	}
+
+	mod scaled {
+		use super::*;
+
+		#[test]
+		fn to_zero() {
+			let amounts = SwapAmounts::dai_usdc(Decimal::new(123450, 2), Decimal::new(-123349, 2));
+
+			let scaled = amounts.scaled(0);
+
+			assert_eq!(scaled.token0_amount.to_string(), "1235");
+			assert_eq!(scaled.token1_amount.to_string(), "-1233");
+		}
+
+		#[test]
+		fn to_six() {
+			let amounts = SwapAmounts::dai_usdc(Decimal::new(123456, 2), Decimal::new(5, 0));
+
+			let scaled = amounts.scaled(6);
+
+			assert_eq!(scaled.token0_amount.to_string(), "1234.560000");
+			assert_eq!(scaled.token1_amount.to_string(), "5.000000");
+			assert_eq!(scaled, amounts);
+		}
+	}
+
+	mod to_token_amounts {
+		use super::*;
+
+		#[test]
+		fn token_order() {
+			let amounts = SwapAmounts::dai_usdc(Decimal::new(100, 0), Decimal::new(-99, 0));
+
+			let [(token0, amount0), (token1, amount1)] = amounts.to_token_amounts();
+
+			assert_eq!((token0.symbol.as_str(), amount0), ("DAI", Decimal::new(100, 0)));
+			assert_eq!((token1.symbol.as_str(), amount1), ("USDC", Decimal::new(-99, 0)));
+		}
+	}
+
+	mod percentage_change {
+		use super::*;
+
+		#[test]
+		fn positive_drift() {
+			let from = SwapAmounts::dai_usdc(Decimal::new(10000, 2), Decimal::new(10000, 2));
+			let to = SwapAmounts::dai_usdc(Decimal::new(10000, 2), Decimal::new(10150, 2));
+
+			assert_eq!(SwapAmounts::percentage_change(&from, &to), Some(Decimal::new(150, 2)));
+		}
+
+		#[test]
+		fn negative_drift() {
+			let from = SwapAmounts::dai_usdc(Decimal::new(20000, 2), Decimal::new(20000, 2));
+			let to = SwapAmounts::dai_usdc(Decimal::new(20000, 2), Decimal::new(19900, 2));
+
+			assert_eq!(SwapAmounts::percentage_change(&from, &to), Some(Decimal::new(-50, 2)));
+		}
+
+		#[test]
+		fn zero_dai() {
+			let zero = SwapAmounts::dai_usdc(Decimal::ZERO, Decimal::new(10000, 2));
+			let other = SwapAmounts::dai_usdc(Decimal::new(10000, 2), Decimal::new(10000, 2));
+
+			assert_eq!(SwapAmounts::percentage_change(&zero, &other), None);
+			assert_eq!(SwapAmounts::percentage_change(&other, &zero), None);
+		}
+
+		#[test]
+		fn unchanged() {
+			let from = SwapAmounts::dai_usdc(Decimal::new(1585187, 2), Decimal::new(1585037, 2));
+			let to = SwapAmounts::dai_usdc(Decimal::new(3170374, 2), Decimal::new(3170074, 2));
+
+			assert_eq!(SwapAmounts::percentage_change(&from, &to), Some(Decimal::ZERO));
+		}
 	}
 }
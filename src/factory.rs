@@ -0,0 +1,138 @@
+use anyhow::Result;
+use web3::{
+	ethabi::{Address, Event, RawLog},
+	types::Log,
+};
+
+use crate::parser::SwapParser;
+
+// Uniswap V3 factory, deployed at the same address on mainnet, Arbitrum and Polygon
+pub const UNI_V3_FACTORY: &str = "1f98431c8ad98523631ae4a59f267346ea31f984";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolCreated {
+	pub block_number: Option<u64>,
+	pub token0: Address,
+	pub token1: Address,
+	// Fee tier in hundredths of a basis point
+	pub fee: u32,
+	pub tick_spacing: i32,
+	pub pool: Address,
+}
+
+impl PoolCreated {
+	pub fn involves(&self, token: Address) -> bool {
+		self.token0 == token || self.token1 == token
+	}
+}
+
+pub struct FactoryParser;
+
+impl FactoryParser {
+	pub fn parse(log: Log, abi: &Event) -> Result<PoolCreated> {
+		let block_number = log.block_number.map(|n| n.as_u64());
+
+		let log = abi.parse_log(RawLog { topics: log.topics, data: log.data.0 })?;
+
+		Ok(PoolCreated {
+			block_number,
+			token0: SwapParser::get_address(&log, "token0")?,
+			token1: SwapParser::get_address(&log, "token1")?,
+			// Both are 24 bit values, the low 32 bits of their two's complement keep the sign
+			fee: SwapParser::get_uint(&log, "fee")?.low_u32(),
+			tick_spacing: SwapParser::get_int(&log, "tickSpacing")?.low_u32() as i32,
+			pool: SwapParser::get_address(&log, "pool")?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::{pool_created_abi, pool_created_log};
+
+	mod parse {
+		use super::*;
+
+		mod ok {
+			use super::*;
+
+			#[test]
+			fn pool_created() {
+				let abi = pool_created_abi();
+				let log = Log {
+					block_number: Some(12_369_739.into()),
+					..pool_created_log(
+						&abi,
+						Address::from([1; 20]),
+						Address::from([2; 20]),
+						500,
+						10,
+						Address::from([3; 20]),
+					)
+				};
+
+				assert_eq!(
+					FactoryParser::parse(log, &abi).unwrap(),
+					PoolCreated {
+						block_number: Some(12_369_739),
+						token0: Address::from([1; 20]),
+						token1: Address::from([2; 20]),
+						fee: 500,
+						tick_spacing: 10,
+						pool: Address::from([3; 20]),
+					}
+				);
+			}
+
+			#[test]
+			fn negative_tick_spacing() {
+				let abi = pool_created_abi();
+				let log = pool_created_log(
+					&abi,
+					Address::from([1; 20]),
+					Address::from([2; 20]),
+					100,
+					-1,
+					Address::from([3; 20]),
+				);
+
+				assert_eq!(FactoryParser::parse(log, &abi).unwrap().tick_spacing, -1);
+			}
+		}
+
+		mod err {
+			use super::*;
+			use crate::testing::{swap_event_abi, SwapLogBuilder};
+
+			#[test]
+			fn swap_log() {
+				let log = SwapLogBuilder::new(&swap_event_abi()).build();
+
+				assert!(FactoryParser::parse(log, &pool_created_abi()).is_err());
+			}
+		}
+	}
+
+	mod involves {
+		use super::*;
+
+		#[test]
+		fn either_token() {
+			let abi = pool_created_abi();
+			let log = pool_created_log(
+				&abi,
+				Address::from([1; 20]),
+				Address::from([2; 20]),
+				500,
+				10,
+				Address::from([3; 20]),
+			);
+			let created = FactoryParser::parse(log, &abi).unwrap();
+
+			assert!(created.involves(Address::from([1; 20])));
+			assert!(created.involves(Address::from([2; 20])));
+			assert!(!created.involves(Address::from([3; 20])));
+		}
+	}
+}
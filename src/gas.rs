@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use tokio::sync::{
+	broadcast::{self, error::RecvError},
+	mpsc,
+};
+use tracing::warn;
+use web3::{
+	types::{H256, U256},
+	Transport, Web3,
+};
+
+use crate::{event::SwapEvent, retry::RetryPolicy};
+
+pub const RECEIPT_CACHE_CAPACITY: usize = 1_000;
+
+// Gas paid by the transaction of a swap, in gas units and wei per gas unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct SwapCost {
+	pub transaction_hash: H256,
+	pub gas_used: u64,
+	pub effective_gas_price: u64,
+}
+
+// Looks up the receipts of swap transactions. Swaps of the same transaction, e.g. routed through
+// several pools, share a receipt that is only fetched once.
+#[derive(Debug)]
+pub struct GasTracker<T: Transport> {
+	web3: Web3<T>,
+	costs: LruCache<H256, SwapCost>,
+}
+
+impl<T: Transport> GasTracker<T> {
+	pub fn new(web3: Web3<T>) -> GasTracker<T> {
+		GasTracker {
+			web3,
+			costs: LruCache::new(
+				NonZeroUsize::new(RECEIPT_CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN),
+			),
+		}
+	}
+
+	pub async fn cost(&mut self, transaction_hash: H256) -> Result<SwapCost> {
+		if let Some(cost) = self.costs.get(&transaction_hash) {
+			return Ok(*cost);
+		}
+
+		let cost = self
+			.fetch(transaction_hash)
+			.await
+			.with_context(|| format!("Failed to read receipt of {:?}", transaction_hash))?;
+		self.costs.put(transaction_hash, cost);
+		Ok(cost)
+	}
+
+	async fn fetch(&self, transaction_hash: H256) -> Result<SwapCost> {
+		let receipt = RetryPolicy::default()
+			.execute(|| async { Ok(self.web3.eth().transaction_receipt(transaction_hash).await?) })
+			.await?
+			.context("Transaction not found")?;
+		let to_u64 = |value: Option<U256>, name: &str| {
+			value
+				.with_context(|| format!("Receipt without {}", name))
+				.and_then(|value| u64::try_from(value).ok().context(format!("{} overflow", name)))
+		};
+
+		Ok(SwapCost {
+			transaction_hash,
+			gas_used: to_u64(receipt.gas_used, "gasUsed")?,
+			effective_gas_price: to_u64(receipt.effective_gas_price, "effectiveGasPrice")?,
+		})
+	}
+}
+
+impl<T> GasTracker<T>
+where
+	T: Transport + Send,
+	T::Out: Send,
+{
+	// Sends the cost of every transaction once, when its first swap is confirmed. Runs until
+	// either channel is closed, swaps without a transaction hash are skipped.
+	pub async fn run(
+		mut self,
+		mut events: broadcast::Receiver<SwapEvent>,
+		costs: mpsc::Sender<SwapCost>,
+	) {
+		loop {
+			match events.recv().await {
+				Ok(SwapEvent { transaction_hash: Some(hash), .. })
+					if !self.costs.contains(&hash) =>
+					match self.cost(hash).await {
+						Ok(cost) =>
+							if costs.send(cost).await.is_err() {
+								return;
+							},
+						Err(err) => warn!(error = %err, "Failed to track swap gas"),
+					},
+				Ok(_) => (),
+				Err(RecvError::Lagged(skipped)) =>
+					warn!(skipped, "Gas tracker lagging, skipped events"),
+				Err(RecvError::Closed) => return,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::MockTransport;
+	use web3::types::TransactionReceipt;
+
+	fn receipt(transaction_hash: H256) -> TransactionReceipt {
+		TransactionReceipt {
+			transaction_hash,
+			gas_used: Some(U256::from(150_000)),
+			effective_gas_price: Some(U256::from(30_000_000_000u64)),
+			..Default::default()
+		}
+	}
+
+	mod run {
+		use super::*;
+		use crate::event::SwapEventBuilder;
+
+		#[tokio::test]
+		async fn sends_cost_per_transaction() {
+			let (first, second) = (H256::from([1; 32]), H256::from([2; 32]));
+			let transport = MockTransport::default()
+				.with_receipt(receipt(first))
+				.with_receipt(receipt(second));
+			let (events, receiver) = broadcast::channel(10);
+			let (sender, mut costs) = mpsc::channel(10);
+
+			for hash in [first, first, second] {
+				events.send(SwapEventBuilder::default().transaction_hash(hash).build()).unwrap();
+			}
+			events.send(SwapEventBuilder::default().build()).unwrap();
+			drop(events);
+			GasTracker::new(Web3::new(transport)).run(receiver, sender).await;
+
+			assert_eq!(costs.recv().await.unwrap().transaction_hash, first);
+			assert_eq!(costs.recv().await.unwrap().transaction_hash, second);
+			assert_eq!(costs.recv().await, None);
+		}
+	}
+
+	mod cost {
+		use super::*;
+
+		#[tokio::test]
+		async fn cached() {
+			let hash = H256::from([1; 32]);
+			let transport = MockTransport::default().with_receipt(receipt(hash));
+			let mut tracker = GasTracker::new(Web3::new(transport.clone()));
+
+			let cost = tracker.cost(hash).await.unwrap();
+			tracker.cost(hash).await.unwrap();
+
+			assert_eq!(
+				cost,
+				SwapCost {
+					transaction_hash: hash,
+					gas_used: 150_000,
+					effective_gas_price: 30_000_000_000
+				}
+			);
+			assert_eq!(transport.receipt_count(), 1);
+		}
+
+		#[tokio::test]
+		async fn unknown_transaction() {
+			let hash = H256::from([1; 32]);
+			let transport = MockTransport::default();
+			let mut tracker = GasTracker::new(Web3::new(transport.clone()));
+
+			let err = tracker.cost(hash).await.unwrap_err();
+
+			assert_eq!(
+				format!("{:#}", err),
+				format!("Failed to read receipt of {:?}: Transaction not found", hash)
+			);
+			// Failures aren't cached
+			tracker.cost(hash).await.unwrap_err();
+			assert_eq!(transport.receipt_count(), 2);
+		}
+
+		#[tokio::test]
+		async fn without_gas_price() {
+			let hash = H256::from([1; 32]);
+			let transport = MockTransport::default()
+				.with_receipt(TransactionReceipt { effective_gas_price: None, ..receipt(hash) });
+
+			let err = GasTracker::new(Web3::new(transport)).cost(hash).await.unwrap_err();
+
+			assert_eq!(err.root_cause().to_string(), "Receipt without effectiveGasPrice");
+		}
+	}
+}
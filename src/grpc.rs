@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use std::{future::Future, net::SocketAddr, pin::Pin};
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_stream::{
+	wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, TcpListenerStream},
+	Stream, StreamExt,
+};
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::{
+	event::{SwapDirection, SwapEvent},
+	server,
+};
+
+pub mod proto {
+	tonic::include_proto!("pool_monitor");
+}
+
+use proto::{
+	pool_monitor_server::{PoolMonitor, PoolMonitorServer},
+	SubscribeRequest, SwapEventProto,
+};
+
+impl From<&SwapEvent> for SwapEventProto {
+	fn from(event: &SwapEvent) -> Self {
+		let direction = match event.direction {
+			SwapDirection::Token0ToToken1 => proto::SwapDirection::Token0ToToken1,
+			SwapDirection::Token1ToToken0 => proto::SwapDirection::Token1ToToken0,
+		};
+
+		SwapEventProto {
+			block_number: event.block_number,
+			transaction_hash: event.transaction_hash.map(|hash| format!("{:?}", hash)),
+			log_index: event.log_index,
+			timestamp: event.timestamp,
+			pool: format!("{:?}", event.pool),
+			sender: format!("{:?}", event.sender),
+			receiver: format!("{:?}", event.receiver),
+			direction: direction.into(),
+			token0_amount: event.amounts.token0_amount.to_string(),
+			token1_amount: event.amounts.token1_amount.to_string(),
+		}
+	}
+}
+
+pub struct PoolMonitorService {
+	events: broadcast::Sender<SwapEvent>,
+}
+
+impl PoolMonitorService {
+	pub fn new(events: broadcast::Sender<SwapEvent>) -> PoolMonitorService {
+		PoolMonitorService { events }
+	}
+}
+
+type SwapStream = Pin<Box<dyn Stream<Item = Result<SwapEventProto, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl PoolMonitor for PoolMonitorService {
+	type SubscribeSwapsStream = SwapStream;
+
+	async fn subscribe_swaps(
+		&self,
+		request: Request<SubscribeRequest>,
+	) -> Result<Response<SwapStream>, Status> {
+		info!(remote_addr = ?request.remote_addr(), "gRPC subscriber connected");
+
+		// Subscribers that fall behind skip the missed events instead of being disconnected
+		let stream =
+			BroadcastStream::new(self.events.subscribe()).filter_map(|event| match event {
+				Ok(event) => Some(Ok(SwapEventProto::from(&event))),
+				Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+					warn!(skipped, "gRPC subscriber lagging, skipped events");
+					None
+				},
+			});
+
+		Ok(Response::new(Box::pin(stream)))
+	}
+}
+
+pub async fn serve(
+	addr: SocketAddr,
+	events: broadcast::Sender<SwapEvent>,
+) -> Result<impl Future<Output = Result<(), tonic::transport::Error>>> {
+	let listener = server::bind(addr)
+		.and_then(|listener| Ok(TcpListener::from_std(listener)?))
+		.with_context(|| format!("Failed to serve gRPC on {}", addr))?;
+
+	Ok(Server::builder()
+		.add_service(PoolMonitorServer::new(PoolMonitorService::new(events)))
+		.serve_with_incoming(TcpListenerStream::new(listener)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use rust_decimal::Decimal;
+	use web3::types::{Address, H256};
+
+	fn event() -> SwapEvent {
+		SwapEventBuilder::default()
+			.block_number(123)
+			.transaction_hash(H256::from([1; 32]))
+			.log_index(7)
+			.pool(Address::from([4; 20]))
+			.sender(Address::from([2; 20]))
+			.receiver(Address::from([3; 20]))
+			.direction(SwapDirection::Token1ToToken0)
+			.token0_amount(Decimal::new(10050, 2))
+			.token1_amount(Decimal::new(10100, 2))
+			.build()
+	}
+
+	mod swap_event_proto {
+		use super::*;
+
+		#[test]
+		fn from_event() {
+			let proto = SwapEventProto::from(&event());
+
+			assert_eq!(
+				proto,
+				SwapEventProto {
+					block_number: Some(123),
+					transaction_hash: Some(format!("0x{}", "01".repeat(32))),
+					log_index: Some(7),
+					timestamp: None,
+					pool: format!("0x{}", "04".repeat(20)),
+					sender: format!("0x{}", "02".repeat(20)),
+					receiver: format!("0x{}", "03".repeat(20)),
+					direction: proto::SwapDirection::Token1ToToken0.into(),
+					token0_amount: "100.50".to_string(),
+					token1_amount: "101.00".to_string(),
+				}
+			);
+		}
+	}
+
+	mod subscribe_swaps {
+		use super::*;
+
+		#[tokio::test]
+		async fn receives_broadcast_events() {
+			let (events, _) = broadcast::channel(16);
+			let service = PoolMonitorService::new(events.clone());
+
+			let mut stream = service
+				.subscribe_swaps(Request::new(SubscribeRequest {}))
+				.await
+				.unwrap()
+				.into_inner();
+			events.send(event()).unwrap();
+
+			let received = stream.next().await.unwrap().unwrap();
+			assert_eq!(received, SwapEventProto::from(&event()));
+		}
+	}
+}
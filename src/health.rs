@@ -0,0 +1,155 @@
+use anyhow::Result;
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	routing::get,
+	Router,
+};
+use std::{
+	future::Future,
+	net::SocketAddr,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use crate::server;
+
+// Age of the last block head received from the node after which the monitor is no longer ready.
+// Heads keep arriving while blocks wait for confirmations or finality, so it doesn't depend on
+// either.
+pub const MAX_BLOCK_AGE: Duration = Duration::from_secs(60);
+
+// Shared by the block source, the pipeline and the health check server
+#[derive(Debug)]
+pub struct HealthState {
+	max_block_age: Duration,
+	// Starts at creation, so the first head has `max_block_age` to arrive
+	last_head_time: Mutex<Instant>,
+	is_connected: AtomicBool,
+}
+
+impl HealthState {
+	pub fn new(max_block_age: Duration) -> HealthState {
+		HealthState {
+			max_block_age,
+			last_head_time: Mutex::new(Instant::now()),
+			is_connected: AtomicBool::new(false),
+		}
+	}
+
+	// Called by the block sources for every head received from the node
+	pub fn record_head(&self) {
+		*self.last_head_time.lock().unwrap() = Instant::now();
+	}
+
+	pub fn set_connected(&self, is_connected: bool) {
+		self.is_connected.store(is_connected, Ordering::Relaxed);
+	}
+
+	pub fn is_connected(&self) -> bool {
+		self.is_connected.load(Ordering::Relaxed)
+	}
+
+	pub fn last_head_age(&self) -> Duration {
+		self.last_head_time.lock().unwrap().elapsed()
+	}
+}
+
+// `/healthz` succeeds while the process is running, `/readyz` only while it's connected to the
+// node and receiving blocks
+pub fn router(state: Arc<HealthState>) -> Router {
+	Router::new()
+		.route("/healthz", get(healthz))
+		.route("/readyz", get(readyz))
+		.with_state(state)
+}
+
+pub fn serve(
+	addr: SocketAddr,
+	state: Arc<HealthState>,
+) -> Result<impl Future<Output = hyper::Result<()>>> {
+	Ok(axum::Server::from_tcp(server::bind(addr)?)?.serve(router(state).into_make_service()))
+}
+
+async fn healthz() -> &'static str {
+	"ok"
+}
+
+async fn readyz(State(state): State<Arc<HealthState>>) -> Response {
+	if !state.is_connected() {
+		return (StatusCode::SERVICE_UNAVAILABLE, "not connected").into_response();
+	}
+
+	let age = state.last_head_age();
+	match age <= state.max_block_age {
+		true => "ready".into_response(),
+		false =>
+			(StatusCode::SERVICE_UNAVAILABLE, format!("no block received for {}s", age.as_secs()))
+				.into_response(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{body::Body, http::Request};
+	use tower::ServiceExt;
+
+	async fn get(state: Arc<HealthState>, uri: &str) -> (StatusCode, String) {
+		let request = Request::get(uri).body(Body::empty()).unwrap();
+		let response = router(state).oneshot(request).await.unwrap();
+
+		let status = response.status();
+		let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+		(status, String::from_utf8(body.to_vec()).unwrap())
+	}
+
+	mod router {
+		use super::*;
+
+		#[tokio::test]
+		async fn healthz() {
+			let state = Arc::new(HealthState::new(MAX_BLOCK_AGE));
+
+			assert_eq!(get(state, "/healthz").await, (StatusCode::OK, "ok".to_string()));
+		}
+
+		#[tokio::test]
+		async fn readyz_connected() {
+			let state = Arc::new(HealthState::new(MAX_BLOCK_AGE));
+			state.set_connected(true);
+			state.record_head();
+
+			assert_eq!(get(state, "/readyz").await, (StatusCode::OK, "ready".to_string()));
+		}
+
+		#[tokio::test]
+		async fn readyz_disconnected() {
+			let state = Arc::new(HealthState::new(MAX_BLOCK_AGE));
+
+			assert_eq!(
+				get(state, "/readyz").await,
+				(StatusCode::SERVICE_UNAVAILABLE, "not connected".to_string())
+			);
+		}
+
+		#[tokio::test]
+		async fn readyz_stale_block() {
+			let state = Arc::new(HealthState::new(Duration::from_millis(50)));
+			state.set_connected(true);
+
+			tokio::time::sleep(Duration::from_millis(100)).await;
+
+			assert_eq!(
+				get(state.clone(), "/readyz").await,
+				(StatusCode::SERVICE_UNAVAILABLE, "no block received for 0s".to_string())
+			);
+			state.record_head();
+			assert_eq!(get(state, "/readyz").await.0, StatusCode::OK);
+		}
+	}
+}
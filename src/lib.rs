@@ -0,0 +1,41 @@
+pub mod abi;
+pub mod address_book;
+pub mod analytics;
+pub mod api;
+pub mod buffer;
+pub mod compression;
+pub mod config;
+pub mod discord;
+pub mod erc20;
+pub mod etherscan;
+pub mod event;
+pub mod factory;
+pub mod gas;
+pub mod grpc;
+pub mod health;
+pub mod log_file;
+pub mod mempool;
+pub mod metrics;
+pub mod network;
+pub mod output;
+pub mod parser;
+pub mod pool_info;
+pub mod pool_state;
+pub mod price;
+pub mod queue;
+pub mod rate_limit;
+pub mod retry;
+pub mod router;
+pub mod server;
+pub mod state;
+pub mod store;
+pub mod subscription;
+pub mod telegram;
+pub mod telemetry;
+// Mocks and builders for tests, benches and fuzz targets
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transport;
+pub mod util;
+pub mod v2_parser;
+pub mod ws_server;
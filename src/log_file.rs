@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use std::{
+	collections::BTreeMap,
+	fs::File,
+	io::{BufRead, BufReader},
+	path::Path,
+};
+use web3::types::Log;
+
+use crate::compression::CompressedReader;
+
+// Reads raw logs saved as newline-delimited JSON, grouped by block number. The file may be
+// compressed by `CompressedWriter`.
+pub fn read(path: &Path) -> Result<BTreeMap<u64, Vec<Log>>> {
+	let file = File::open(path)
+		.and_then(CompressedReader::new)
+		.with_context(|| format!("Failed to read replay file '{}'", path.display()))?;
+
+	parse(BufReader::new(file))
+		.with_context(|| format!("Failed to parse replay file '{}'", path.display()))
+}
+
+pub fn parse(reader: impl BufRead) -> Result<BTreeMap<u64, Vec<Log>>> {
+	let mut blocks = BTreeMap::<u64, Vec<Log>>::new();
+
+	for (index, line) in reader.lines().enumerate() {
+		let line = line?;
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let log: Log = serde_json::from_str(&line)
+			.with_context(|| format!("Invalid log on line {}", index + 1))?;
+		let block_number = log
+			.block_number
+			.with_context(|| format!("Log without block number on line {}", index + 1))?;
+
+		blocks.entry(block_number.as_u64()).or_default().push(log);
+	}
+
+	Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::{swap_event_abi, SwapLogBuilder};
+
+	fn line(block_number: u64, log_index: u64) -> String {
+		let log = SwapLogBuilder::new(&swap_event_abi())
+			.block_number(block_number)
+			.log_index(log_index)
+			.build();
+		serde_json::to_string(&log).unwrap()
+	}
+
+	mod read {
+		use super::*;
+		use crate::compression::CompressedWriter;
+		use std::io::Write;
+
+		#[test]
+		fn compressed() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("logs.ndjson.zst");
+			let mut writer = CompressedWriter::new(File::create(&path).unwrap()).unwrap();
+			writeln!(writer, "{}\n{}", line(10, 0), line(11, 1)).unwrap();
+			drop(writer);
+
+			let blocks = read(&path).unwrap();
+
+			assert_eq!(blocks.keys().copied().collect::<Vec<_>>(), vec![10, 11]);
+		}
+	}
+
+	mod parse {
+		use super::*;
+
+		mod ok {
+			use super::*;
+
+			#[test]
+			fn groups_by_block() {
+				let contents = [line(12, 0), line(10, 1), String::new(), line(12, 2)].join("\n");
+
+				let blocks = parse(contents.as_bytes()).unwrap();
+
+				assert_eq!(blocks.keys().copied().collect::<Vec<_>>(), vec![10, 12]);
+				assert_eq!(blocks[&10].len(), 1);
+				assert_eq!(
+					blocks[&12].iter().map(|log| log.log_index).collect::<Vec<_>>(),
+					vec![Some(0.into()), Some(2.into())]
+				);
+			}
+
+			#[test]
+			fn empty() {
+				assert!(parse("".as_bytes()).unwrap().is_empty());
+			}
+		}
+
+		mod err {
+			use super::*;
+
+			#[test]
+			fn invalid_json() {
+				let contents = [line(10, 0), "{".to_string()].join("\n");
+
+				let err = parse(contents.as_bytes()).unwrap_err();
+
+				assert_eq!(err.to_string(), "Invalid log on line 2");
+			}
+
+			#[test]
+			fn missing_block_number() {
+				let log = SwapLogBuilder::new(&swap_event_abi()).build();
+				let contents = serde_json::to_string(&log).unwrap();
+
+				let err = parse(contents.as_bytes()).unwrap_err();
+
+				assert_eq!(err.to_string(), "Log without block number on line 1");
+			}
+		}
+	}
+}
@@ -1,88 +1,2080 @@
-pub mod buffer;
-pub mod event;
-pub mod parser;
+mod cli;
 
-use futures::StreamExt;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use rust_decimal::Decimal;
+use std::{
+	collections::{hash_map::Entry, BTreeMap, HashMap},
+	future::Future,
+	io::Write,
+	mem::discriminant,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, field, info, info_span, warn};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use web3::{
+	ethabi::Event,
+	transports::Http,
+	types::{BlockNumber, Filter, FilterBuilder, Log, H160},
+	Transport, Web3,
+};
 
-const UNI_V3_DAI_USDC_POOL: &str = "5777d92f208679db4b9778590fa3cab3ac9e2168";
+use rust_uniswap_task::{
+	abi::ContractAbi,
+	address_book::AddressBook,
+	analytics::{
+		self, BlockLagMonitor, BlockLagWarning, FeeVolumeTracker, PoolHealthMonitor,
+		PoolHealthStatus, VolumeSnapshot, WhaleDetector, BLOCKS_PER_DAY,
+	},
+	api::{self, RecentSwaps, RECENT_SWAPS_CAPACITY},
+	buffer::ReorganizingBufferError,
+	compression::CompressedWriter,
+	config::{AppConfig, PoolConfig, TokenConfig},
+	discord::DiscordNotifier,
+	erc20::{
+		transfer_event_abi, TokenInfoCache, TransactionContext, TransferEvent, TransferParser,
+	},
+	etherscan,
+	event::{SwapEvent, SwapFilter, TokenInfo, TokenPair},
+	factory::{FactoryParser, UNI_V3_FACTORY},
+	gas::{GasTracker, SwapCost},
+	grpc,
+	health::{self, HealthState, MAX_BLOCK_AGE},
+	log_file,
+	mempool::{PendingBlockMonitor, SwapRouterDecoder, PENDING_SWAPS_CAPACITY, SWAP_ROUTER},
+	metrics::{self, Metrics},
+	output::EventOutput,
+	parser::{EventParser, Protocol, SeenSet},
+	pool_info::PoolInfoCache,
+	rate_limit::SharedRateLimiter,
+	state::{MonitorState, PoolSnapshot},
+	store::SqliteEventStore,
+	subscription::PoolSubscription,
+	telegram::{TelegramNotifier, TELEGRAM_API_URL},
+	telemetry,
+	transport::{
+		BlockSource, FinalizedSource, PollingSource, RpcConnectionPool, TransportKind,
+		WebSocketSource,
+	},
+	ws_server,
+};
+
+// Number of blocks covered by a single historical `eth_getLogs` request
+const HISTORY_CHUNK_SIZE: u64 = 1000;
+
+// Confirmed events buffered for each subscriber of the event servers
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+// Processed blocks between two summaries of a dry run
+const DRY_RUN_SUMMARY_INTERVAL: u64 = 10;
 
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() -> Result<()> {
 	dotenv::dotenv().ok();
 
-	let ws_url =
-		&std::env::var("INFURA_WEBSOCKET_ENDPOINT").expect("INFURA_WEBSOCKET_ENDPOINT env var");
+	let cli = cli::Cli::parse();
+
+	let otlp_layer = cli.otlp_endpoint.as_deref().map(telemetry::otlp_layer).transpose()?;
+	tracing_subscriber::registry()
+		.with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+		.with(otlp_layer)
+		.with(cli.log_level)
+		.init();
+
+	let result = monitor(cli).await;
+	telemetry::shutdown();
+	result
+}
+
+async fn monitor(cli: cli::Cli) -> Result<()> {
+	let mut config = cli.config()?;
+
+	let finality = config.finality();
+	info!(?finality, confirmation_depth = finality.confirmation_depth(), "Configured finality");
+
+	let store = config.db.as_deref().map(SqliteEventStore::open).transpose()?;
+	resume(&mut config, store.as_ref())?;
+
+	let mut sinks = Sinks {
+		writer: match config.compress {
+			true => Box::new(CompressedWriter::new(std::io::stdout())?),
+			false => Box::new(std::io::stdout()),
+		},
+		store,
+		metrics: Arc::new(Metrics::new()?),
+		events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+		recent: Arc::new(RecentSwaps::new(RECENT_SWAPS_CAPACITY)),
+		costs: None,
+	};
+
+	if config.fetch_abi {
+		fetch_abi(&mut config).await;
+	}
+
+	if let Some(path) = &config.replay {
+		// Replays are offline, the tokens of pools given by address are unknown
+		if config.pools.iter().any(|pool| pool.read_tokens) {
+			bail!("Replays need the tokens of every pool, set pools in the config file");
+		}
+		let blocks = log_file::read(path)?;
+		return replay_file(blocks, &config, sinks);
+	}
+
+	let rpc = Arc::new(RpcConnectionPool::new(config.ws_endpoints())?);
+	if config.transport == TransportKind::Ws && rpc.endpoints().len() > 1 {
+		info!(endpoint_count = rpc.endpoints().len(), "Rotating through RPC endpoints");
+	}
+
+	if let Some(addr) = config.metrics_addr {
+		let server = metrics::serve(addr, sinks.metrics.clone())
+			.with_context(|| format!("Failed to serve metrics on {}", addr))?;
+		info!(%addr, "Serving metrics");
+		spawn_server("Metrics", server);
+	}
+
+	if let Some(addr) = config.api_addr {
+		let server = api::serve(addr, sinks.recent.clone())
+			.with_context(|| format!("Failed to serve API on {}", addr))?;
+		info!(%addr, "Serving API");
+		spawn_server("API", server);
+	}
+
+	if let Some(addr) = config.grpc_addr {
+		let server = grpc::serve(addr, sinks.events.clone()).await?;
+		info!(%addr, "Serving gRPC");
+		spawn_server("gRPC", server);
+	}
+
+	if let Some(addr) = config.ws_server_addr {
+		let server = ws_server::serve(addr, sinks.events.clone(), config.network).await?;
+		info!(%addr, "Serving WebSocket");
+		spawn_server("WebSocket", server);
+	}
+
+	// Readiness follows the block heads received from the node
+	let health = Arc::new(HealthState::new(MAX_BLOCK_AGE));
+	if let Some(addr) = config.health_addr {
+		let server = health::serve(addr, health.clone())
+			.with_context(|| format!("Failed to serve health checks on {}", addr))?;
+		info!(%addr, "Serving health checks");
+		spawn_server("Health check", server);
+	}
+
+	if let Some(webhook_url) = &config.discord_webhook {
+		let explorer_url = config.network.config().map(|network| network.explorer_url.to_string());
+		let notifier = DiscordNotifier::new(
+			webhook_url.clone(),
+			explorer_url,
+			WhaleDetector { token1_threshold: config.whale_threshold },
+		);
+		tokio::spawn(notifier.run(sinks.events.subscribe()));
+		info!("Posting whale trades to Discord");
+	}
+
+	if let (Some(bot_token), Some(chat_id)) = (&config.telegram_bot_token, &config.telegram_chat_id)
+	{
+		let notifier = TelegramNotifier::new(
+			TELEGRAM_API_URL,
+			bot_token,
+			chat_id.clone(),
+			WhaleDetector { token1_threshold: config.whale_threshold },
+		);
+		tokio::spawn(notifier.run(sinks.events.subscribe()));
+		info!("Sending whale trades to Telegram");
+	}
+
+	if config.track_gas {
+		let events = sinks.events.subscribe();
+		let (costs, receiver) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+		sinks.costs = Some(receiver);
+		match config.transport {
+			TransportKind::Ws => {
+				let web3 = rpc.next_web3().await?;
+				tokio::spawn(GasTracker::new(web3).run(events, costs));
+			},
+			TransportKind::Http => {
+				let web3 = Web3::new(Http::new(&config.http_url)?);
+				tokio::spawn(GasTracker::new(web3).run(events, costs));
+			},
+		}
+		info!("Tracking gas of confirmed swaps");
+	}
+
+	resolve_pool_info(&mut config, &rpc).await?;
+
+	// Shared by the block source and the pending swap monitor
+	let rate_limiter = config.rpc_rate_limit.map(SharedRateLimiter::new);
+
+	if config.include_pending {
+		let tokens = config
+			.pools
+			.iter()
+			.flat_map(|pool| [pool.token0.address, pool.token1.address])
+			.flatten()
+			.collect();
+		let mut monitor = PendingBlockMonitor::new(PENDING_SWAPS_CAPACITY, tokens);
+		if let Some(rate_limiter) = &rate_limiter {
+			monitor = monitor.with_rate_limit(rate_limiter.clone());
+		}
+		let decoder = SwapRouterDecoder::new(SWAP_ROUTER.parse()?)?;
+		let web3 = rpc.next_web3().await?;
+		let events = sinks.events.subscribe();
+		tokio::spawn(async move {
+			if let Err(err) = monitor.run(decoder, web3, events).await {
+				error!(%err, "Stopped tracking pending swaps");
+			}
+		});
+		info!("Tracking pending swaps");
+	}
+
+	match config.transport {
+		TransportKind::Ws => {
+			let mut source = WebSocketSource::connect(rpc).await.with_health(health);
+			if let Some(rate_limiter) = rate_limiter {
+				source = source.with_rate_limit(rate_limiter);
+			}
+			run_at_finality(source, &config, sinks).await
+		},
+		TransportKind::Http => {
+			let interval = std::time::Duration::from_secs(config.poll_interval);
+			let mut source = PollingSource::new(&config.http_url, interval)?.with_health(health);
+			if let Some(rate_limiter) = rate_limiter {
+				source = source.with_rate_limit(rate_limiter);
+			}
+			run_at_finality(source, &config, sinks).await
+		},
+	}
+}
+
+// Switches to the first pool's ABI from the network's block explorer, keeps the embedded ABI if
+// that fails
+async fn fetch_abi(config: &mut AppConfig) {
+	let api_key = config.etherscan_api_key.clone().unwrap_or_default();
+	let loaded = async {
+		let network = config.network.config().context("Custom networks have no block explorer")?;
+		let dir = match &config.abi_cache_dir {
+			Some(dir) => dir.clone(),
+			None => etherscan::default_cache_dir()?,
+		};
+		let pool = config.pools[0].contract_address()?;
+		etherscan::load_abi(network.explorer_api_url, &api_key, pool, &dir).await
+	}
+	.await;
+
+	match loaded {
+		Ok(path) => {
+			info!(path = %path.display(), "Loaded pool ABI from the block explorer");
+			config.abi = Some(path);
+		},
+		Err(err) =>
+			warn!(error = %err, "Failed to load pool ABI from the block explorer, using embedded ABI"),
+	}
+}
+
+// Reads fee tiers and token addresses missing from the configuration from the pool contracts
+async fn resolve_pool_info(config: &mut AppConfig, rpc: &RpcConnectionPool) -> Result<()> {
+	// Transfers and pending swaps are matched by token address
+	let missing_tokens = (config.track_transfers || config.include_pending) &&
+		config
+			.pools
+			.iter()
+			.any(|pool| pool.token0.address.is_none() || pool.token1.address.is_none());
+	let read_tokens = config.pools.iter().any(|pool| pool.read_tokens);
+	if config.pools.iter().all(|pool| pool.fee_tier != 0) && !missing_tokens && !read_tokens {
+		return Ok(());
+	}
+
+	match config.transport {
+		TransportKind::Ws => {
+			let web3 = rpc.next_web3().await?;
+			read_pool_info(web3, config).await
+		},
+		TransportKind::Http => {
+			let web3 = Web3::new(Http::new(&config.http_url)?);
+			read_pool_info(web3, config).await
+		},
+	}
+}
+
+async fn read_pool_info<T: Transport>(web3: Web3<T>, config: &mut AppConfig) -> Result<()> {
+	read_fee_tiers(web3.clone(), &mut config.pools).await?;
+	let read_tokens = config.pools.iter().any(|pool| pool.read_tokens);
+	if config.track_transfers || config.include_pending || read_tokens {
+		read_token_addresses(web3.clone(), &mut config.pools).await?;
+	}
+	read_token_info(web3, &mut config.pools).await
+}
+
+async fn read_fee_tiers<T: Transport>(web3: Web3<T>, pools: &mut [PoolConfig]) -> Result<()> {
+	let mut pool_infos = PoolInfoCache::new(web3)?;
+
+	for pool in pools.iter_mut().filter(|pool| pool.fee_tier == 0) {
+		let info = pool_infos.get(pool.contract_address()?).await?;
+		info!(pool = %pool.address, fee_tier = info.fee_tier, "Read pool fee tier");
+		pool.fee_tier = info.fee_tier;
+	}
+
+	Ok(())
+}
+
+async fn read_token_addresses<T: Transport>(web3: Web3<T>, pools: &mut [PoolConfig]) -> Result<()> {
+	let pool_infos = PoolInfoCache::new(web3)?;
+
+	for pool in pools
+		.iter_mut()
+		.filter(|pool| pool.token0.address.is_none() || pool.token1.address.is_none())
+	{
+		let (token0, token1) = pool_infos.tokens(pool.contract_address()?).await?;
+		info!(pool = %pool.address, ?token0, ?token1, "Read pool tokens");
+		pool.token0.address = Some(token0);
+		pool.token1.address = Some(token1);
+	}
+
+	Ok(())
+}
+
+// Reads symbols and decimals of the tokens of pools given only by address, after their addresses
+async fn read_token_info<T: Transport>(mut web3: Web3<T>, pools: &mut [PoolConfig]) -> Result<()> {
+	let mut tokens = TokenInfoCache::new()?;
+
+	for pool in pools.iter_mut().filter(|pool| pool.read_tokens) {
+		for token in [&mut pool.token0, &mut pool.token1] {
+			let address = token.address.context("Missing token address")?;
+			let TokenInfo { symbol, decimals } = tokens.get(&mut web3, address).await?;
+			token.symbol.clone_from(symbol);
+			token.decimals = *decimals;
+		}
+		info!(
+			pool = %pool.address,
+			token0 = %pool.token0.symbol,
+			token1 = %pool.token1.symbol,
+			"Read pool token info"
+		);
+		pool.read_tokens = false;
+	}
+
+	Ok(())
+}
+
+// Continues after the last confirmed block of the state file or the store, unless set explicitly
+fn resume(config: &mut AppConfig, store: Option<&SqliteEventStore>) -> Result<()> {
+	if config.from_block.is_some() {
+		return Ok(());
+	}
+
+	if let Some(state) = config.state_file.as_deref().map(MonitorState::load).transpose()?.flatten()
+	{
+		info!(from_block = state.last_confirmed_block + 1, "Resuming after last confirmed block");
+		config.from_block = Some(state.last_confirmed_block + 1);
+	} else if let Some(last_block) =
+		store.map(SqliteEventStore::last_block_number).transpose()?.flatten()
+	{
+		info!(from_block = last_block + 1, "Resuming after last stored block");
+		config.from_block = Some(last_block + 1);
+	}
+
+	Ok(())
+}
+
+fn spawn_server<E: std::fmt::Display>(
+	name: &'static str,
+	server: impl Future<Output = Result<(), E>> + Send + 'static,
+) {
+	tokio::spawn(async move {
+		if let Err(err) = server.await {
+			error!(%err, "{} server failed", name);
+		}
+	});
+}
+
+// Safe and finalized blocks are looked up whenever the source receives a new block
+async fn run_at_finality(
+	mut source: impl BlockSource,
+	config: &AppConfig,
+	sinks: Sinks,
+) -> Result<()> {
+	match config.finality().block_tag() {
+		Some(tag) => run(&mut FinalizedSource::new(source, tag), config, sinks).await,
+		None => run(&mut source, config, sinks).await,
+	}
+}
+
+async fn run(source: &mut impl BlockSource, config: &AppConfig, sinks: Sinks) -> Result<()> {
+	run_until(source, config, sinks, shutdown_signal()).await
+}
+
+// Blocks still waiting for confirmation when shutting down could yet be reorganized, so they are
+// saved to the state file to be confirmed after restarting. Without a state file they would be
+// lost, so they are flushed like when the source runs out of blocks.
+async fn run_until(
+	source: &mut impl BlockSource,
+	config: &AppConfig,
+	sinks: Sinks,
+	shutdown: impl Future<Output = std::io::Result<()>>,
+) -> Result<()> {
+	let mut pipeline = Pipeline::new(config, sinks)?;
+
+	let completed = tokio::select! {
+		result = follow(source, &mut pipeline, config) => result?,
+		result = shutdown => {
+			result?;
+			info!("Shutdown requested");
+			false
+		},
+	};
+
+	match &config.state_file {
+		Some(path) if !completed => {
+			pipeline.checkpoint(path)?;
+			pipeline.finish()?;
+		},
+		_ => {
+			let flushed = pipeline.drain()?;
+			info!(event_count = flushed, "Flushed pending events");
+		},
+	}
+
+	Ok(())
+}
+
+// Feeds saved logs through the pipeline, filling blocks without logs in between
+fn replay_file(
+	mut blocks: BTreeMap<u64, Vec<Log>>,
+	config: &AppConfig,
+	sinks: Sinks,
+) -> Result<()> {
+	let mut pipeline = Pipeline::new(config, sinks)?;
+
+	let (Some(&first_block), Some(&last_block)) = (blocks.keys().next(), blocks.keys().last())
+	else {
+		info!("Replay file contains no logs");
+		return Ok(());
+	};
+
+	for block_number in first_block..=last_block {
+		let logs = blocks.remove(&block_number).unwrap_or_default();
+		if !pipeline.process(block_number, None, logs)? {
+			return Ok(());
+		}
+	}
 
-	let web3 = web3::Web3::new(web3::transports::ws::WebSocket::new(ws_url).await?);
+	let flushed = pipeline.drain()?;
+	info!(event_count = flushed, "Flushed pending events");
 
-	let contract_address = hex::decode(UNI_V3_DAI_USDC_POOL).unwrap();
-	let contract_address = web3::types::H160::from_slice(&contract_address[..]);
+	Ok(())
+}
 
-	let contract = web3::contract::Contract::from_json(
-		web3.eth(),
-		contract_address,
-		include_bytes!("contracts/uniswap_pool_abi.json"),
-	)?;
+fn pool_created_abi() -> Result<Event> {
+	let contract =
+		web3::ethabi::Contract::load(&include_bytes!("contracts/uniswap_factory_abi.json")[..])?;
+	Ok(contract.event("PoolCreated")?.clone())
+}
 
-	let swap_event_abi = contract.abi().events_by_name("Swap")?.first().unwrap();
+// Returns `false` if the pipeline stopped early because it could not accept a block
+async fn follow(
+	source: &mut impl BlockSource,
+	pipeline: &mut Pipeline,
+	config: &AppConfig,
+) -> Result<bool> {
+	// Last block replayed from history, live blocks up to it are skipped
+	let mut history_end = None;
 
-	let swap_event_signature = swap_event_abi.signature();
+	if let Some(from_block) = config.from_block {
+		let to_block = match config.to_block {
+			Some(to_block) => to_block,
+			None => source.latest_block().await?,
+		};
+		pipeline.chain_head = Some(to_block);
 
-	let mut block_stream = web3.eth_subscribe().subscribe_new_heads().await?;
+		if !replay(source, pipeline, from_block, to_block).await? {
+			return Ok(false);
+		}
+		if config.to_block.is_some() {
+			return Ok(true);
+		}
 
-	let mut buffer = buffer::ReorganizingBuffer::new(5);
+		history_end = Some(to_block);
+	}
+
+	while let Some(block) = source.next_block().await {
+		pipeline.chain_head = Some(block.number);
+		if let Some(end) = history_end {
+			if block.number <= end {
+				continue;
+			}
+			if !replay(source, pipeline, end + 1, block.number - 1).await? {
+				return Ok(false);
+			}
+			history_end = None;
+		}
 
+		let logs =
+			fetch_logs(source, pipeline, FilterBuilder::default().block_hash(block.hash)).await?;
+
+		if !pipeline.process(block.number, Some(block.timestamp), logs)? {
+			return Ok(false);
+		}
+	}
+
+	Ok(true)
+}
+
+// Logs are requested again after pools were created in them, so swaps of the new pools in the
+// same blocks are included
+async fn fetch_logs(
+	source: &mut impl BlockSource,
+	pipeline: &mut Pipeline,
+	builder: FilterBuilder,
+) -> Result<Vec<Log>> {
 	loop {
-		let block = match block_stream.next().await {
-			Some(Ok(block)) => block,
-			_ => continue,
+		let logs = source.logs(pipeline.filter(builder.clone())).await?;
+		if !pipeline.add_created_pools(source, &logs).await? {
+			return Ok(logs);
+		}
+	}
+}
+
+async fn shutdown_signal() -> std::io::Result<()> {
+	#[cfg(unix)]
+	{
+		let mut terminate =
+			tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+
+		tokio::select! {
+			result = tokio::signal::ctrl_c() => result,
+			_ = terminate.recv() => Ok(()),
+		}
+	}
+
+	#[cfg(not(unix))]
+	tokio::signal::ctrl_c().await
+}
+
+// Feeds all blocks of the inclusive range through the pipeline, returns `false` if it stopped early
+async fn replay(
+	source: &mut impl BlockSource,
+	pipeline: &mut Pipeline,
+	from_block: u64,
+	to_block: u64,
+) -> Result<bool> {
+	for chunk_start in (from_block..=to_block).step_by(HISTORY_CHUNK_SIZE as usize) {
+		let chunk_end = to_block.min(chunk_start + HISTORY_CHUNK_SIZE - 1);
+
+		let logs = fetch_logs(
+			source,
+			pipeline,
+			FilterBuilder::default()
+				.from_block(BlockNumber::Number(chunk_start.into()))
+				.to_block(BlockNumber::Number(chunk_end.into())),
+		)
+		.await?;
+
+		let mut logs_by_block = BTreeMap::<u64, Vec<Log>>::new();
+		for log in logs {
+			let block_number = log.block_number.context("Historical log without block number")?;
+			logs_by_block.entry(block_number.as_u64()).or_default().push(log);
+		}
+
+		for block_number in chunk_start..=chunk_end {
+			let logs = logs_by_block.remove(&block_number).unwrap_or_default();
+			// Headers are only fetched for blocks with logs, their timestamps aren't needed
+			// otherwise
+			let timestamp = match logs.is_empty() {
+				true => None,
+				false => Some(source.block_head(block_number).await?.timestamp),
+			};
+			if !pipeline.process(block_number, timestamp, logs)? {
+				return Ok(false);
+			}
+		}
+	}
+
+	Ok(true)
+}
+
+struct Pipeline {
+	parser: &'static dyn EventParser,
+	confirmation_depth: usize,
+	output: Box<dyn EventOutput>,
+	store: Option<SqliteEventStore>,
+	// Every pool receives every block, so all subscriptions confirm blocks in lockstep
+	subscriptions: HashMap<H160, PoolSubscription>,
+	last_block: Option<u64>,
+	last_emitted_block: Option<u64>,
+	// Latest block of the source, ahead of the processed blocks while catching up
+	chain_head: Option<u64>,
+	state_file: Option<PathBuf>,
+	factory: Option<FactoryWatch>,
+	transfers: Option<TransferWatch>,
+	seen: SeenSet,
+	whale_detector: WhaleDetector,
+	// With the last reported status of each pool
+	health: HashMap<H160, (PoolHealthMonitor, PoolHealthStatus)>,
+	stale_price_blocks: u64,
+	fee_volume: FeeVolumeTracker,
+	pool_tvl_usd: Option<Decimal>,
+	metrics: Arc<Metrics>,
+	events: broadcast::Sender<SwapEvent>,
+	recent: Arc<RecentSwaps>,
+	costs: Option<mpsc::Receiver<SwapCost>>,
+	swap_filter: SwapFilter,
+	address_book: AddressBook,
+	// Counts reported instead of the confirmed swaps, parse errors are counted instead of failing
+	dry_run: Option<DryRunSummary>,
+	block_lag: Option<BlockLagMonitor>,
+}
+
+struct DryRunSummary {
+	writer: Box<dyn Write + Send>,
+	block_count: u64,
+	swap_count: u64,
+	reorg_count: u64,
+	parse_error_count: u64,
+}
+
+impl DryRunSummary {
+	fn new(writer: Box<dyn Write + Send>) -> DryRunSummary {
+		DryRunSummary {
+			writer,
+			block_count: 0,
+			swap_count: 0,
+			reorg_count: 0,
+			parse_error_count: 0,
+		}
+	}
+
+	fn write(&mut self) -> Result<()> {
+		writeln!(
+			self.writer,
+			"Processed {} blocks, {} swaps, {} reorgs, {} parse errors",
+			self.block_count, self.swap_count, self.reorg_count, self.parse_error_count
+		)?;
+		Ok(self.writer.flush()?)
+	}
+}
+
+// Pools created by the factory are added to the monitored pools, unless they lack the filtered
+// token
+struct FactoryWatch {
+	pool_created_abi: Event,
+	address: H160,
+	token_filter: Option<H160>,
+	tokens: TokenInfoCache,
+}
+
+impl FactoryWatch {
+	fn matches(&self, log: &Log) -> bool {
+		log.address == self.address &&
+			log.topics.first() == Some(&self.pool_created_abi.signature())
+	}
+
+	async fn token(&mut self, source: &mut impl BlockSource, address: H160) -> Result<TokenConfig> {
+		let TokenInfo { symbol, decimals } = self.tokens.get(source, address).await?;
+		Ok(TokenConfig { symbol: symbol.clone(), decimals: *decimals, address: Some(address) })
+	}
+}
+
+// Transfers of the monitored pools' tokens, correlated with swaps of the same transaction
+struct TransferWatch {
+	transfer_abi: Event,
+	// Decimals by token address
+	tokens: HashMap<H160, u32>,
+}
+
+impl TransferWatch {
+	fn matches(&self, log: &Log) -> bool {
+		self.tokens.contains_key(&log.address) &&
+			log.topics.first() == Some(&self.transfer_abi.signature())
+	}
+
+	fn parse(&self, log: Log) -> Result<TransferEvent> {
+		let decimals = self.tokens[&log.address];
+		Ok(TransferParser::parse(log, &self.transfer_abi, decimals)?)
+	}
+}
+
+// Everything confirmed events are delivered to
+struct Sinks {
+	writer: Box<dyn Write + Send>,
+	store: Option<SqliteEventStore>,
+	metrics: Arc<Metrics>,
+	events: broadcast::Sender<SwapEvent>,
+	recent: Arc<RecentSwaps>,
+	// Gas of confirmed swaps read by the gas tracker, written with the next confirmed block
+	costs: Option<mpsc::Receiver<SwapCost>>,
+}
+
+impl Pipeline {
+	fn new(
+		config: &AppConfig,
+		Sinks { writer, store, metrics, events, recent, costs }: Sinks,
+	) -> Result<Pipeline> {
+		let address_book = match &config.address_book {
+			Some(path) => {
+				let address_book = AddressBook::load_from_toml(path)?;
+				info!(label_count = address_book.len(), "Loaded address book");
+				address_book
+			},
+			None => AddressBook::default(),
+		};
+		let (output, dry_run) = match config.dry_run {
+			true => (
+				config.output_format.backend(
+					std::io::sink(),
+					address_book.clone(),
+					config.network,
+				)?,
+				Some(DryRunSummary::new(writer)),
+			),
+			false =>
+				(config.output_format.backend(writer, address_book.clone(), config.network)?, None),
+		};
+
+		let parser = config.protocol.parser();
+		let confirmation_depth = config.finality().confirmation_depth();
+		let mut subscriptions = HashMap::new();
+		for pool in &config.pools {
+			let abi = match pool.abi.as_ref().or(config.abi.as_ref()) {
+				Some(path) => ContractAbi::from_file(path)?,
+				None => ContractAbi::embedded(config.protocol)?,
+			};
+			let subscription =
+				PoolSubscription::new(pool.clone(), abi, confirmation_depth, parser)?;
+			subscriptions.insert(subscription.address(), subscription);
+		}
+
+		let factory = match config.watch_factory {
+			true => Some(FactoryWatch {
+				pool_created_abi: pool_created_abi()?,
+				address: UNI_V3_FACTORY.parse()?,
+				token_filter: config.token_filter,
+				tokens: TokenInfoCache::new()?,
+			}),
+			false => None,
+		};
+
+		let transfers = match config.track_transfers {
+			true => Some(TransferWatch {
+				transfer_abi: transfer_event_abi()?,
+				tokens: config
+					.pools
+					.iter()
+					.flat_map(|pool| [&pool.token0, &pool.token1])
+					.map(|token| {
+						let address = token.address.with_context(|| {
+							format!("Missing address of token {}", token.symbol)
+						})?;
+						Ok((address, token.decimals))
+					})
+					.collect::<Result<_>>()?,
+			}),
+			false => None,
+		};
+
+		let state = config.state_file.as_deref().map(MonitorState::load).transpose()?.flatten();
+		let last_emitted_block = state.as_ref().map(|state| state.last_confirmed_block);
+		for PoolSnapshot { pool, pair, blocks } in
+			state.map(|state| state.buffer_snapshot).unwrap_or_default()
+		{
+			let subscription = match subscriptions.entry(pool) {
+				Entry::Occupied(entry) => entry.into_mut(),
+				// Pools discovered through the factory before the restart keep being monitored,
+				// with their fee tier unknown
+				Entry::Vacant(entry) if config.watch_factory => {
+					let token = |token: TokenInfo| TokenConfig {
+						symbol: token.symbol,
+						decimals: token.decimals,
+						address: None,
+					};
+					let default = PoolConfig::default();
+					let (token0, token1) = match pair {
+						Some(TokenPair { token0, token1 }) => (token(token0), token(token1)),
+						None => (default.token0, default.token1),
+					};
+					entry.insert(PoolSubscription::new(
+						PoolConfig {
+							address: format!("{:?}", pool),
+							token0,
+							token1,
+							fee_tier: 0,
+							abi: None,
+							read_tokens: false,
+						},
+						ContractAbi::embedded(Protocol::V3)?,
+						confirmation_depth,
+						parser,
+					)?)
+				},
+				// Pools no longer monitored are dropped
+				Entry::Vacant(_) => continue,
+			};
+			subscription
+				.restore(blocks)
+				.map_err(|err| anyhow!("Invalid snapshot of pool {:?}: {:?}", pool, err))?;
+		}
+
+		Ok(Pipeline {
+			parser,
+			confirmation_depth,
+			output,
+			store: store.filter(|_| !config.dry_run),
+			subscriptions,
+			last_block: None,
+			last_emitted_block,
+			chain_head: None,
+			state_file: config.state_file.clone().filter(|_| !config.dry_run),
+			factory,
+			transfers,
+			seen: SeenSet::default(),
+			whale_detector: WhaleDetector { token1_threshold: config.whale_threshold },
+			health: HashMap::new(),
+			stale_price_blocks: config.stale_price_blocks,
+			fee_volume: FeeVolumeTracker::new(BLOCKS_PER_DAY),
+			pool_tvl_usd: config.pool_tvl_usd,
+			metrics,
+			events,
+			recent,
+			costs,
+			swap_filter: config.swap_filter.clone(),
+			address_book,
+			dry_run,
+			block_lag: config.max_lag.map(BlockLagMonitor::new),
+		})
+	}
+
+	fn filter(&self, builder: FilterBuilder) -> Filter {
+		let mut topics: Vec<_> =
+			self.subscriptions.values().map(PoolSubscription::swap_topic).collect();
+		topics.sort();
+		topics.dedup();
+		let mut addresses: Vec<_> = self.subscriptions.keys().copied().collect();
+		if let Some(transfers) = &self.transfers {
+			topics.push(transfers.transfer_abi.signature());
+			addresses.extend(transfers.tokens.keys());
+		}
+		// Pools created within the requested blocks are added by `add_created_pools`, after which
+		// the logs are requested again
+		if let Some(factory) = &self.factory {
+			topics.push(factory.pool_created_abi.signature());
+			addresses.push(factory.address);
+		}
+
+		builder.address(addresses).topics(Some(topics), None, None, None).build()
+	}
+
+	// Monitors the pools created in the logs, returns whether any was added. Pools whose tokens
+	// can't be read are skipped.
+	async fn add_created_pools(
+		&mut self,
+		source: &mut impl BlockSource,
+		logs: &[Log],
+	) -> Result<bool> {
+		let Some(factory) = &mut self.factory else {
+			return Ok(false);
+		};
+
+		let created_pools = logs
+			.iter()
+			.filter(|log| factory.matches(log))
+			.map(|log| FactoryParser::parse(log.clone(), &factory.pool_created_abi))
+			.collect::<Result<Vec<_>>>()?;
+
+		let mut added = false;
+		for created in created_pools {
+			if factory.token_filter.is_some_and(|token| !created.involves(token)) ||
+				self.subscriptions.contains_key(&created.pool)
+			{
+				continue;
+			}
+
+			let (token0, token1) = match (
+				factory.token(source, created.token0).await,
+				factory.token(source, created.token1).await,
+			) {
+				(Ok(token0), Ok(token1)) => (token0, token1),
+				(Err(err), _) | (_, Err(err)) => {
+					warn!(pool = ?created.pool, error = %err, "Skipping created pool");
+					continue;
+				},
+			};
+
+			info!(
+				pool = ?created.pool,
+				token0 = %token0.symbol,
+				token1 = %token1.symbol,
+				fee = created.fee,
+				"Monitoring created pool"
+			);
+			let pool = PoolConfig {
+				address: format!("{:?}", created.pool),
+				token0,
+				token1,
+				fee_tier: created.fee,
+				abi: None,
+				read_tokens: false,
+			};
+			self.subscriptions.insert(
+				created.pool,
+				PoolSubscription::new(
+					pool,
+					ContractAbi::embedded(Protocol::V3)?,
+					self.confirmation_depth,
+					self.parser,
+				)?,
+			);
+			added = true;
+		}
+
+		Ok(added)
+	}
+
+	// Whether the log is a swap of one of the monitored pools
+	fn matches(&self, log: &Log) -> bool {
+		self.subscriptions
+			.get(&log.address)
+			.is_some_and(|subscription| subscription.matches(log))
+	}
+
+	// Returns `false` once the pipeline can no longer accept blocks
+	fn process(
+		&mut self,
+		block_number: u64,
+		timestamp: Option<u64>,
+		mut logs: Vec<Log>,
+	) -> Result<bool> {
+		let span = info_span!(
+			"block",
+			block_number,
+			event_count = field::Empty,
+			reorg_depth = field::Empty
+		);
+		let _entered = span.enter();
+
+		let transfers = match &self.transfers {
+			Some(watch) => logs
+				.iter()
+				.filter(|log| watch.matches(log))
+				.map(|log| watch.parse(log.clone()))
+				.collect::<Result<Vec<_>>>()?,
+			None => Vec::new(),
 		};
+		logs.retain(|log| self.matches(log));
+
+		let mut events = Vec::with_capacity(logs.len());
+		for log in logs {
+			match (self.subscriptions[&log.address].parse(log, timestamp), &mut self.dry_run) {
+				(Ok(event), _) => events.push(event),
+				(Err(_), Some(summary)) => summary.parse_error_count += 1,
+				(Err(err), None) => return Err(err),
+			}
+		}
+		span.record("event_count", events.len());
+
+		// Dry runs only report their summaries
+		if self.dry_run.is_none() {
+			info!(block_number, event_count = events.len(), "Received block");
+		}
+
+		// Whale trades are reported as soon as they are seen, before confirmation
+		for event in events.iter().filter(|event| self.whale_detector.is_whale(event)) {
+			warn!(
+				block_number,
+				token1_amount = %event.amounts.token1_amount,
+				"WHALE ALERT: {}",
+				event.labeled(&self.address_book)
+			);
+		}
+
+		// Token flows are reported as soon as they are seen, like whale trades
+		if self.transfers.is_some() && self.dry_run.is_none() {
+			for context in TransactionContext::group(&events, transfers)
+				.into_iter()
+				.filter(|context| !context.swaps.is_empty())
+			{
+				info!(
+					transaction_hash = ?context.transaction_hash,
+					swap_count = context.swaps.len(),
+					transfer_count = context.transfers.len(),
+					"Token flow"
+				);
+				for transfer in &context.transfers {
+					debug!(
+						token = ?transfer.token,
+						from = ?transfer.from,
+						to = ?transfer.to,
+						amount = %transfer.amount,
+						"Transfer"
+					);
+				}
+			}
+		}
+
+		if let Some(last_block) = self.last_block.filter(|last| block_number <= *last) {
+			let depth = last_block - block_number + 1;
+			span.record("reorg_depth", depth);
+			warn!(block_number, depth, "Chain reorganization");
+			self.metrics.record_reorg();
+			if let Some(summary) = &mut self.dry_run {
+				summary.reorg_count += 1;
+			}
+		}
+
+		self.check_health(block_number, &events);
+
+		if let Some(summary) = &mut self.dry_run {
+			summary.block_count += 1;
+			summary.swap_count += events.len() as u64;
+		}
+
+		let mut events_by_pool = HashMap::<H160, Vec<SwapEvent>>::new();
+		for event in events {
+			events_by_pool.entry(event.pool).or_default().push(event);
+		}
+
+		let mut confirmed = BTreeMap::<u64, Vec<SwapEvent>>::new();
+		for (pool, subscription) in &mut self.subscriptions {
+			let events = events_by_pool.remove(pool).unwrap_or_default();
+
+			match subscription.push(block_number, events) {
+				Ok(Some((block_number, events))) =>
+					confirmed.entry(block_number).or_default().extend(events),
+				Ok(None) => (),
+				Err(ReorganizingBufferError::DepthExceeded(depth)) => {
+					error!(
+						max_depth = self.confirmation_depth,
+						depth, "Maximal reorganization depth exceeded, terminating"
+					);
+					return Ok(false);
+				},
+				Err(ReorganizingBufferError::MissingOffset(expected_block_number)) => {
+					warn!(expected_block_number, "Skipped block number, terminating");
+					return Ok(false);
+				},
+				// Only restoring a snapshot fails otherwise
+				Err(err) =>
+					return Err(anyhow!("Failed to buffer block {}: {:?}", block_number, err)),
+			}
+		}
+
+		self.emit_merged(confirmed)?;
+
+		self.last_block = Some(block_number);
+
+		let occupancy = self
+			.subscriptions
+			.values()
+			.map(PoolSubscription::pending_blocks)
+			.max()
+			.unwrap_or(0);
+		self.metrics.set_buffer_occupancy(occupancy);
+		if let Some(last_emitted_block) = self.last_emitted_block {
+			let chain_head = self.chain_head.map_or(block_number, |head| head.max(block_number));
+			self.metrics.set_block_lag(chain_head.saturating_sub(last_emitted_block));
+
+			if let Some(BlockLagWarning { confirmed, latest, lag }) = self
+				.block_lag
+				.as_mut()
+				.and_then(|monitor| monitor.update(last_emitted_block, chain_head))
+			{
+				warn!(confirmed, latest, lag, "Confirmed blocks falling behind");
+			}
+		}
+
+		if let Some(summary) = self
+			.dry_run
+			.as_mut()
+			.filter(|summary| summary.block_count % DRY_RUN_SUMMARY_INTERVAL == 0)
+		{
+			summary.write()?;
+		}
+
+		Ok(true)
+	}
+
+	// Pools are reported once when they turn unhealthy, not again for every following block
+	fn check_health(&mut self, block_number: u64, events: &[SwapEvent]) {
+		for pool in self.subscriptions.keys() {
+			let (monitor, last_status) = self.health.entry(*pool).or_insert_with(|| {
+				(PoolHealthMonitor::new(self.stale_price_blocks), PoolHealthStatus::Healthy)
+			});
+			for event in events.iter().filter(|event| event.pool == *pool) {
+				monitor.record(event);
+			}
+
+			let status = monitor.check(block_number);
+			if status != PoolHealthStatus::Healthy &&
+				discriminant(&status) != discriminant(last_status)
+			{
+				warn!(pool = ?pool, ?status, "Unhealthy pool");
+			}
+			*last_status = status;
+		}
+	}
+
+	// Emits all blocks still waiting for confirmation, returns the number of emitted events
+	fn drain(&mut self) -> Result<usize> {
+		let mut pending = BTreeMap::<u64, Vec<SwapEvent>>::new();
+		for subscription in self.subscriptions.values_mut() {
+			for (block_number, events) in subscription.drain() {
+				pending.entry(block_number).or_default().extend(events);
+			}
+		}
+
+		let count = pending.values().map(Vec::len).sum();
+		self.emit_merged(pending)?;
+		self.finish()?;
+
+		Ok(count)
+	}
+
+	// Saves the blocks still waiting for confirmation to the state file, so they are confirmed
+	// after restarting instead of being dropped
+	fn checkpoint(&self, path: &Path) -> Result<()> {
+		let event_count: usize =
+			self.subscriptions.values().map(PoolSubscription::pending_events).sum();
+
+		// Before the first confirmed block, processing resumes with the first pending one
+		let last_confirmed_block = self.last_emitted_block.or_else(|| {
+			let first_pending = self
+				.subscriptions
+				.values()
+				.filter_map(PoolSubscription::first_pending_block)
+				.min();
+			first_pending.map(|block_number| block_number.saturating_sub(1))
+		});
+		if let Some(last_confirmed_block) = last_confirmed_block {
+			self.save_state(path, last_confirmed_block)?;
+			info!(event_count, path = %path.display(), "Saved unconfirmed events");
+		}
+
+		Ok(())
+	}
+
+	fn save_state(&self, path: &Path, last_confirmed_block: u64) -> Result<()> {
+		let mut buffer_snapshot: Vec<_> = self
+			.subscriptions
+			.iter()
+			.map(|(pool, subscription)| PoolSnapshot {
+				pool: *pool,
+				pair: Some((**subscription.pair()).clone()),
+				blocks: subscription.snapshot(),
+			})
+			.collect();
+		buffer_snapshot.sort_by_key(|snapshot| snapshot.pool);
+
+		MonitorState { last_confirmed_block, buffer_snapshot }.save(path)
+	}
+
+	// Completes the periodic summaries with the blocks processed since the last one
+	fn finish(&mut self) -> Result<()> {
+		if let Some(summary) = self
+			.dry_run
+			.as_mut()
+			.filter(|summary| summary.block_count % DRY_RUN_SUMMARY_INTERVAL != 0)
+		{
+			summary.write()?;
+		}
+
+		Ok(())
+	}
+
+	// Emits blocks of all pools in block order, events within a block in log order
+	fn emit_merged(&mut self, blocks: BTreeMap<u64, Vec<SwapEvent>>) -> Result<()> {
+		for (block_number, mut events) in blocks {
+			// Blocks still waiting for confirmation are replaced as a whole when delivered again,
+			// so logs can only be recognized as duplicates once confirmed
+			let count = events.len();
+			events.retain(|event| self.seen.insert(event.transaction_hash, event.log_index));
+			if events.len() < count {
+				self.metrics.record_duplicates(count - events.len());
+			}
+			events.retain(|event| event.matches_filter(&self.swap_filter));
 
-		let block_number = match block.number {
-			Some(number) => number,
-			_ => continue,
+			events.sort();
+			self.emit(block_number, &events)?;
+		}
+		Ok(())
+	}
+
+	fn emit(&mut self, block_number: u64, events: &[SwapEvent]) -> Result<()> {
+		let fee_tiers: Vec<_> = events
+			.iter()
+			.filter_map(|event| {
+				// Pools with an unknown fee tier are left out of the fee totals
+				let fee_tier = self.subscriptions.get(&event.pool)?.pool_config.fee_tier;
+				(fee_tier != 0).then_some((event, fee_tier))
+			})
+			.collect();
+		let token1_fees: Decimal = fee_tiers
+			.iter()
+			.map(|(event, fee_tier)| event.fee_amount(*fee_tier).token1_amount)
+			.sum();
+		self.fee_volume.record(
+			block_number,
+			fee_tiers
+				.iter()
+				.map(|(event, fee_tier)| (*fee_tier, event.amounts.token1_amount)),
+		);
+
+		let snapshot = VolumeSnapshot {
+			block_number,
+			fee_apr: self.pool_tvl_usd.and_then(|tvl| self.fee_volume.fee_apr(tvl)),
+			..analytics::aggregate(events)
 		};
+		if self.dry_run.is_none() {
+			info!(
+				block_number = snapshot.block_number,
+				swap_count = snapshot.swap_count,
+				token0_volume = %snapshot.total_token0_volume,
+				token1_volume = %snapshot.total_token1_volume,
+				net_token0_flow = %snapshot.net_token0_flow,
+				dominant_direction = ?snapshot.dominant_direction,
+				volume_ratio = ?snapshot.volume_ratio.ratio(),
+				token1_fees = %token1_fees,
+				fee_apr = ?snapshot.fee_apr,
+				"Confirmed block volume"
+			);
+		}
+
+		if let Some(store) = &mut self.store {
+			store.insert_events(events)?;
+		}
+
+		self.metrics.record_swaps(events);
+		// Dry runs don't reach the notifiers and event servers
+		for event in events.iter().filter(|_| self.dry_run.is_none()) {
+			// Sending only fails while nobody is subscribed
+			let _ = self.events.send(event.clone());
+		}
+		self.recent.record(snapshot, events);
+		self.last_emitted_block = Some(block_number);
+
+		self.output.write_block(block_number, events)?;
+		if let Some(costs) = &mut self.costs {
+			while let Ok(cost) = costs.try_recv() {
+				self.output.write_cost(&cost)?;
+			}
+		}
+
+		// Saved last, so a crash before it only repeats the block after restarting
+		if let Some(path) = &self.state_file {
+			self.save_state(path, block_number)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rust_uniswap_task::{
+		config::{PoolConfig, TokenConfig},
+		event::SwapDirection,
+		output::OutputFormat,
+		testing::{
+			block_header, pool_created_log, swap_event_abi, transfer_log, v2_swap_event_abi,
+			v2_swap_log, MockWeb3Provider, SharedBuffer, SwapLogBuilder,
+		},
+		transport::FinalityKind,
+	};
+	use web3::types::{Bytes, H256};
+
+	fn sinks(output: &SharedBuffer) -> Sinks {
+		Sinks {
+			writer: Box::new(output.clone()),
+			store: None,
+			metrics: Arc::new(Metrics::new().unwrap()),
+			events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+			recent: Arc::new(RecentSwaps::new(RECENT_SWAPS_CAPACITY)),
+			costs: None,
+		}
+	}
+
+	mod run {
+		use super::*;
+
+		fn config() -> AppConfig {
+			AppConfig {
+				pools: vec![PoolConfig::default()],
+				confirmation_depth: 2,
+				output_format: OutputFormat::Json,
+				..AppConfig::default()
+			}
+		}
+
+		fn swap_log(log_index: u64, amount0: i128, amount1: i128) -> Log {
+			SwapLogBuilder::new(&swap_event_abi())
+				.log_index(log_index)
+				.amount0(amount0)
+				.amount1(amount1)
+				.build()
+		}
+
+		async fn run_json(mut provider: MockWeb3Provider) -> Vec<serde_json::Value> {
+			let output = SharedBuffer::default();
+			run(&mut provider, &config(), sinks(&output)).await.unwrap();
+
+			output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str(line).unwrap())
+				.collect()
+		}
+
+		#[tokio::test]
+		async fn emits_swaps_in_block_order() {
+			let provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 1_700_000_000),
+					vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 1_700_000_012), vec![])
+				.with_block(
+					block_header(102, H256::from_low_u64_be(102), 1_700_000_024),
+					vec![swap_log(3, -2_005_000_000_000_000_000, 2_001_000)],
+				)
+				.with_block(block_header(103, H256::from_low_u64_be(103), 1_700_000_036), vec![]);
+
+			let events = run_json(provider).await;
+
+			assert_eq!(events.len(), 2);
+			assert_eq!(events[0]["block_number"], 100);
+			assert_eq!(events[0]["direction"], "token0_to_token1");
+			assert_eq!(events[0]["token0_amount"], "1.00");
+			assert_eq!(events[0]["token1_amount"], "0.99");
+			assert_eq!(events[1]["block_number"], 102);
+			assert_eq!(events[1]["log_index"], 3);
+			assert_eq!(events[1]["direction"], "token1_to_token0");
+			assert_eq!(events[1]["token0_amount"], "2.00");
+			assert_eq!(events[1]["token1_amount"], "2.00");
+		}
+
+		#[tokio::test]
+		async fn writes_swap_costs() {
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+			let output = SharedBuffer::default();
+			let (costs, receiver) = mpsc::channel(1);
+			costs
+				.send(SwapCost {
+					transaction_hash: H256::from_low_u64_be(1),
+					gas_used: 150_000,
+					effective_gas_price: 30_000_000_000,
+				})
+				.await
+				.unwrap();
+
+			let sinks = Sinks { costs: Some(receiver), ..sinks(&output) };
+			run(&mut provider, &config(), sinks).await.unwrap();
+
+			let records: Vec<serde_json::Value> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str(line).unwrap())
+				.collect();
+			assert_eq!(records.len(), 2);
+			assert_eq!(records[0]["event_type"], "swap");
+			assert_eq!(records[1]["event_type"], "swap_cost");
+			assert_eq!(records[1]["gas_used"], 150_000);
+		}
+
+		#[tokio::test]
+		async fn filters_swaps() {
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![
+						swap_log(0, 1_000_000_000_000_000_000, -999_000),
+						swap_log(1, -2_005_000_000_000_000_000, 2_001_000),
+					],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+			let config = AppConfig {
+				swap_filter: SwapFilter {
+					direction: Some(SwapDirection::Token1ToToken0),
+					..SwapFilter::default()
+				},
+				..config()
+			};
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			let event: serde_json::Value = serde_json::from_str(&output.contents()).unwrap();
+			assert_eq!(event["log_index"], 1);
+			assert_eq!(event["direction"], "token1_to_token0");
+		}
+
+		#[tokio::test]
+		async fn estimates_fee_apr() {
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![swap_log(0, 1_000_000_000_000_000_000, -1_000_000)],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![])
+				.with_block(
+					block_header(102, H256::from_low_u64_be(102), 0),
+					vec![swap_log(0, -3_000_000_000_000_000_000, 3_000_000)],
+				)
+				.with_block(block_header(103, H256::from_low_u64_be(103), 0), vec![]);
+			let config = AppConfig { pool_tvl_usd: Some(Decimal::from(36_500)), ..config() };
+			let sinks = sinks(&SharedBuffer::default());
+			let recent = sinks.recent.clone();
+
+			run(&mut provider, &config, sinks).await.unwrap();
+
+			// $4 over 4 blocks at 0.01% is $0.72 of fees a day
+			let snapshot = recent.stats().latest_block.unwrap();
+			assert_eq!(snapshot.block_number, 103);
+			assert_eq!(snapshot.fee_apr, Some(Decimal::new(72, 2)));
+		}
+
+		#[tokio::test]
+		async fn timestamps_historical_swaps() {
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 1_700_000_000),
+					vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 1_700_000_012), vec![]);
+			let config = AppConfig { from_block: Some(100), to_block: Some(101), ..config() };
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			let event: serde_json::Value = serde_json::from_str(&output.contents()).unwrap();
+			assert_eq!(event["block_number"], 100);
+			assert_eq!(event["timestamp"], 1_700_000_000);
+		}
+
+		#[tokio::test]
+		async fn dry_run() {
+			let mut provider = MockWeb3Provider::new();
+			for block_number in 100..112 {
+				let logs = match block_number {
+					100 => vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+					// Truncated data
+					101 => vec![Log { data: Bytes(vec![0; 32]), ..swap_log(0, 1, -1) }],
+					_ => vec![],
+				};
+				provider = provider.with_block(
+					block_header(block_number, H256::from_low_u64_be(block_number), 0),
+					logs,
+				);
+			}
+			provider =
+				provider.with_block(block_header(111, H256::from_low_u64_be(1111), 0), vec![]);
+			let config = AppConfig { dry_run: true, ..config() };
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			assert_eq!(
+				output.contents(),
+				"Processed 10 blocks, 1 swaps, 0 reorgs, 1 parse errors\n\
+				 Processed 13 blocks, 1 swaps, 1 reorgs, 1 parse errors\n"
+			);
+		}
+
+		#[tokio::test]
+		async fn finalized_blocks() {
+			let mut provider = MockWeb3Provider::new().with_finality_lag(2);
+			for block_number in 100..105 {
+				let logs = match block_number {
+					101 => vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+					// Not finalized by the last block
+					103 => vec![swap_log(0, -3_000_000_000_000_000_000, 3_002_000)],
+					_ => vec![],
+				};
+				provider = provider.with_block(
+					block_header(block_number, H256::from_low_u64_be(block_number), 0),
+					logs,
+				);
+			}
+			let config = AppConfig { finality: FinalityKind::Finalized, ..config() };
+			let output = SharedBuffer::default();
+
+			run_at_finality(provider, &config, sinks(&output)).await.unwrap();
+
+			let events: Vec<serde_json::Value> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str(line).unwrap())
+				.collect();
+			assert_eq!(events.len(), 1);
+			assert_eq!(events[0]["block_number"], 101);
+		}
+
+		#[tokio::test]
+		async fn drops_reorganized_swaps() {
+			let provider = MockWeb3Provider::new()
+				.with_block(block_header(100, H256::from_low_u64_be(100), 1_700_000_000), vec![])
+				.with_block(
+					block_header(101, H256::from_low_u64_be(101), 1_700_000_012),
+					vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+				)
+				.with_block(
+					block_header(101, H256::from_low_u64_be(1101), 1_700_000_013),
+					vec![swap_log(1, -3_000_000_000_000_000_000, 3_002_000)],
+				)
+				.with_block(block_header(102, H256::from_low_u64_be(102), 1_700_000_024), vec![]);
+
+			let events = run_json(provider).await;
+
+			assert_eq!(events.len(), 1);
+			assert_eq!(events[0]["block_number"], 101);
+			assert_eq!(events[0]["log_index"], 1);
+			assert_eq!(events[0]["direction"], "token1_to_token0");
+		}
+
+		#[tokio::test]
+		async fn skips_duplicate_logs() {
+			let log = Log {
+				transaction_hash: Some(H256::from_low_u64_be(1)),
+				..swap_log(0, 1_000_000_000_000_000_000, -999_000)
+			};
+			let provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![log.clone(), log],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+
+			let events = run_json(provider).await;
+
+			assert_eq!(events.len(), 1);
+			assert_eq!(events[0]["block_number"], 100);
+		}
+
+		#[tokio::test]
+		async fn parses_swaps_with_pool_abi() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("pool_abi.json");
+			let custom_abi =
+				String::from_utf8(include_bytes!("contracts/uniswap_pool_abi.json").to_vec())
+					.unwrap()
+					.replace("\"int256\"", "\"int128\"");
+			std::fs::write(&path, custom_abi).unwrap();
+
+			let custom_pool = H160::from([7; 20]);
+			let custom_abi = ContractAbi::from_file(&path).unwrap();
+			let custom_swap = |pool, log_index| {
+				SwapLogBuilder::new(&custom_abi.swap_event)
+					.address(pool)
+					.log_index(log_index)
+					.build()
+			};
+
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![
+						swap_log(0, 1_000_000_000_000_000_000, -999_000),
+						custom_swap(custom_pool, 1),
+						// Swaps are only matched against the ABI of their own pool
+						custom_swap(PoolConfig::default().contract_address().unwrap(), 2),
+					],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+			let config = AppConfig {
+				pools: vec![
+					PoolConfig::default(),
+					PoolConfig {
+						address: format!("{:?}", custom_pool),
+						abi: Some(path),
+						..PoolConfig::default()
+					},
+				],
+				..config()
+			};
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			let log_indices: Vec<_> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+				.map(|event| event["log_index"].as_u64().unwrap())
+				.collect();
+			assert_eq!(log_indices, vec![0, 1]);
+		}
+
+		#[tokio::test]
+		async fn parses_v2_swaps() {
+			let pair = H160::from([7; 20]);
+			let abi = v2_swap_event_abi();
+			let v2_swap = |log_index: u64| Log {
+				log_index: Some(log_index.into()),
+				..v2_swap_log(
+					&abi,
+					pair,
+					H160::from([1; 20]),
+					H160::from([2; 20]),
+					[2_000_000_000_000_000_000, 0, 0, 1_999_000],
+				)
+			};
+
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![v2_swap(0), Log { address: pair, ..swap_log(1, 1, -1) }],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+			let config = AppConfig {
+				pools: vec![PoolConfig { address: format!("{:?}", pair), ..PoolConfig::default() }],
+				protocol: Protocol::V2,
+				..config()
+			};
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			let events: Vec<_> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+				.collect();
+			assert_eq!(events.len(), 1);
+			assert_eq!(events[0]["log_index"], 0);
+			assert_eq!(events[0]["direction"], "token0_to_token1");
+		}
+
+		#[tokio::test]
+		async fn tracks_transfers() {
+			let (dai, usdc) = (H160::from([1; 20]), H160::from([2; 20]));
+			let abi = transfer_event_abi().unwrap();
+			let transaction_hash = H256::from([5; 32]);
+			let transfer = |token, log_index: u64| Log {
+				transaction_hash: Some(transaction_hash),
+				log_index: Some(log_index.into()),
+				..transfer_log(&abi, token, H160::from([8; 20]), H160::from([9; 20]), 1_000_000)
+			};
+
+			let mut provider = MockWeb3Provider::new()
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![
+						transfer(dai, 0),
+						Log {
+							transaction_hash: Some(transaction_hash),
+							..swap_log(1, 1_000_000_000_000_000_000, -999_000)
+						},
+						transfer(usdc, 2),
+						// Transfers of other tokens are dropped
+						transfer(H160::from([3; 20]), 3),
+					],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+			let pool = PoolConfig::default();
+			let config = AppConfig {
+				pools: vec![PoolConfig {
+					token0: TokenConfig { address: Some(dai), ..pool.token0.clone() },
+					token1: TokenConfig { address: Some(usdc), ..pool.token1.clone() },
+					..pool
+				}],
+				track_transfers: true,
+				..config()
+			};
+			let output = SharedBuffer::default();
+
+			let pipeline = Pipeline::new(&config, sinks(&output)).unwrap();
+			let filter = serde_json::to_value(pipeline.filter(FilterBuilder::default())).unwrap();
+			assert_eq!(filter["address"].as_array().unwrap().len(), 3);
+			assert_eq!(filter["topics"][0][1], serde_json::to_value(abi.signature()).unwrap());
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			assert_eq!(output.contents().lines().count(), 1);
+		}
+
+		#[tokio::test]
+		async fn transfers_without_token_address() {
+			let config = AppConfig { track_transfers: true, ..config() };
+
+			let err = Pipeline::new(&config, sinks(&SharedBuffer::default())).err().unwrap();
+
+			assert_eq!(err.to_string(), "Missing address of token DAI");
+		}
+
+		#[tokio::test]
+		async fn monitors_created_pools() {
+			let abi = pool_created_abi().unwrap();
+			let dai = H160::from([1; 20]);
+			let created_pool =
+				|token0, pool| pool_created_log(&abi, token0, H160::from([2; 20]), 100, 1, pool);
+			let pool_swap = |pool, log_index| Log {
+				address: pool,
+				..swap_log(log_index, 1_000_000_000_000_000_000, -2_000_000_000_000_000_000)
+			};
+
+			let mut provider = MockWeb3Provider::new()
+				.with_token(dai, "DAI", 18)
+				.with_token(H160::from([2; 20]), "WETH", 18)
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![
+						created_pool(dai, H160::from([10; 20])),
+						pool_swap(H160::from([10; 20]), 1),
+						created_pool(H160::from([3; 20]), H160::from([11; 20])),
+					],
+				)
+				.with_block(
+					block_header(101, H256::from_low_u64_be(101), 0),
+					vec![pool_swap(H160::from([10; 20]), 0), pool_swap(H160::from([11; 20]), 1)],
+				)
+				.with_block(block_header(102, H256::from_low_u64_be(102), 0), vec![]);
+			let config = AppConfig { watch_factory: true, token_filter: Some(dai), ..config() };
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			let events: Vec<serde_json::Value> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str(line).unwrap())
+				.collect();
+			assert_eq!(events.len(), 2);
+			assert_eq!(events[0]["block_number"], 100);
+			assert_eq!(events[0]["pool"], format!("{:?}", H160::from([10; 20])));
+			assert_eq!(events[0]["token1"], "WETH");
+			assert_eq!(events[0]["token1_amount"], "2.00");
+			assert_eq!(events[1]["block_number"], 101);
+			assert_eq!(events[1]["pool"], format!("{:?}", H160::from([10; 20])));
+		}
+
+		#[tokio::test]
+		async fn shutdown_flushes_unconfirmed_blocks() {
+			let mut provider = (100..=103)
+				.fold(MockWeb3Provider::new(), |provider, number| {
+					provider.with_block(
+						block_header(number, H256::from_low_u64_be(number), 0),
+						vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+					)
+				})
+				.endless();
+			let output = SharedBuffer::default();
+
+			// Requested once the provider waits for the next block
+			let shutdown = async {
+				tokio::task::yield_now().await;
+				Ok(())
+			};
+			run_until(&mut provider, &config(), sinks(&output), shutdown).await.unwrap();
+
+			let block_numbers: Vec<_> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+				.map(|event| event["block_number"].as_u64().unwrap())
+				.collect();
+			assert_eq!(block_numbers, vec![100, 101, 102, 103]);
+		}
 
-		let logs = web3
-			.eth()
-			.logs(
-				web3::types::FilterBuilder::default()
-					.block_hash(block.hash.unwrap())
-					.address(vec![contract_address])
-					.topics(Some(vec![swap_event_signature]), None, None, None)
-					.build(),
+		#[tokio::test]
+		async fn skips_created_pools_of_unknown_tokens() {
+			let abi = pool_created_abi().unwrap();
+			let pool = H160::from([10; 20]);
+			let mut provider = MockWeb3Provider::new()
+				.with_token(H160::from([1; 20]), "DAI", 18)
+				.with_block(
+					block_header(100, H256::from_low_u64_be(100), 0),
+					vec![
+						pool_created_log(
+							&abi,
+							H160::from([1; 20]),
+							H160::from([2; 20]),
+							100,
+							1,
+							pool,
+						),
+						Log { address: pool, ..swap_log(1, 1_000_000_000_000_000_000, -999_000) },
+					],
+				)
+				.with_block(block_header(101, H256::from_low_u64_be(101), 0), vec![]);
+			let config = AppConfig { watch_factory: true, ..config() };
+			let output = SharedBuffer::default();
+
+			run(&mut provider, &config, sinks(&output)).await.unwrap();
+
+			assert_eq!(output.contents(), "");
+		}
+
+		#[tokio::test]
+		async fn lags_behind_chain_head() {
+			let mut provider = (100..=110).fold(MockWeb3Provider::new(), |provider, number| {
+				provider.with_block(block_header(number, H256::from_low_u64_be(number), 0), vec![])
+			});
+			let config = AppConfig { from_block: Some(100), max_lag: Some(5), ..config() };
+			let sinks = sinks(&SharedBuffer::default());
+			let metrics = sinks.metrics.clone();
+			let mut pipeline = Pipeline::new(&config, sinks).unwrap();
+
+			// Replaying history from block 100 while the chain is at block 110
+			pipeline.chain_head = Some(provider.latest_block().await.unwrap());
+			for block_number in 100..=103 {
+				assert!(pipeline.process(block_number, None, vec![]).unwrap());
+			}
+
+			assert!(metrics.encode().unwrap().contains("uniswap_block_lag 9\n"));
+		}
+
+		#[tokio::test]
+		async fn resumes_after_crash() {
+			let dir = tempfile::tempdir().unwrap();
+			let config = AppConfig { state_file: Some(dir.path().join("state.json")), ..config() };
+			let with_block = |provider: MockWeb3Provider, number: u64| {
+				let logs = match number % 2 {
+					0 => vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+					_ => vec![],
+				};
+				provider.with_block(block_header(number, H256::from_low_u64_be(number), 0), logs)
+			};
+			let block_numbers = |output: &SharedBuffer| -> Vec<u64> {
+				output
+					.contents()
+					.lines()
+					.map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+					.map(|event| event["block_number"].as_u64().unwrap())
+					.collect()
+			};
+
+			// Process blocks up to 103, then stop without flushing pending blocks
+			let output = SharedBuffer::default();
+			let mut pipeline = Pipeline::new(&config, sinks(&output)).unwrap();
+			let mut crashing = (100..=103).fold(MockWeb3Provider::new(), with_block);
+			follow(&mut crashing, &mut pipeline, &config).await.unwrap();
+			drop(pipeline);
+
+			assert_eq!(block_numbers(&output), vec![100]);
+
+			let mut config = config;
+			resume(&mut config, None).unwrap();
+			assert_eq!(config.from_block, Some(102));
+
+			let output = SharedBuffer::default();
+			let mut restarted = (100..=105).fold(MockWeb3Provider::new(), with_block);
+			run(&mut restarted, &config, sinks(&output)).await.unwrap();
+
+			assert_eq!(block_numbers(&output), vec![102, 104]);
+		}
+
+		#[tokio::test]
+		async fn resumes_after_shutdown() {
+			let dir = tempfile::tempdir().unwrap();
+			let config = AppConfig { state_file: Some(dir.path().join("state.json")), ..config() };
+			let with_block = |provider: MockWeb3Provider, number: u64| {
+				provider.with_block(
+					block_header(number, H256::from_low_u64_be(number), 0),
+					vec![swap_log(0, 1_000_000_000_000_000_000, -999_000)],
+				)
+			};
+			let block_numbers = |output: &SharedBuffer| -> Vec<u64> {
+				output
+					.contents()
+					.lines()
+					.map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+					.map(|event| event["block_number"].as_u64().unwrap())
+					.collect()
+			};
+
+			// Shut down before any block was confirmed
+			let output = SharedBuffer::default();
+			let mut stopping = (100..=101).fold(MockWeb3Provider::new(), with_block).endless();
+			let shutdown = async {
+				tokio::task::yield_now().await;
+				Ok(())
+			};
+			run_until(&mut stopping, &config, sinks(&output), shutdown).await.unwrap();
+
+			assert_eq!(block_numbers(&output), Vec::<u64>::new());
+			let state = MonitorState::load(config.state_file.as_ref().unwrap()).unwrap().unwrap();
+			assert_eq!(state.last_confirmed_block, 99);
+			assert_eq!(state.buffer_snapshot[0].blocks.len(), 2);
+
+			let mut config = config;
+			resume(&mut config, None).unwrap();
+			assert_eq!(config.from_block, Some(100));
+
+			let output = SharedBuffer::default();
+			let mut restarted = (100..=103).fold(MockWeb3Provider::new(), with_block);
+			run(&mut restarted, &config, sinks(&output)).await.unwrap();
+
+			assert_eq!(block_numbers(&output), vec![100, 101, 102, 103]);
+		}
+	}
+
+	mod read_fee_tiers {
+		use super::*;
+		use rust_uniswap_task::testing::MockTransport;
+		use web3::ethabi::{self, Token};
+
+		#[tokio::test]
+		async fn fills_unknown_fee_tiers() {
+			let abi = web3::ethabi::Contract::load(
+				&include_bytes!("contracts/uniswap_pool_abi.json")[..],
 			)
-			.await?;
-
-		let events = logs
-			.into_iter()
-			.map(|log| parser::SwapParser::parse(log, swap_event_abi))
-			.collect::<Result<Vec<_>, _>>()?;
-
-		println!("BLOCK {} - {} Swap Events", block_number, events.len());
-
-		match buffer.push((block_number.as_u64(), events)) {
-			Ok(Some((block_number, events))) =>
-				if !events.is_empty() {
-					println!("---");
-					println!("CONFIRMED EVENTS FROM BLOCK {}:", block_number);
-					for event in events {
-						println!("- {}", event.to_string());
-					}
-					println!("---");
+			.unwrap();
+			let selector = |name| abi.function(name).unwrap().short_signature();
+			let slot0 = ethabi::encode(&[
+				Token::Uint(1.into()),
+				Token::Int(0.into()),
+				Token::Uint(0.into()),
+				Token::Uint(0.into()),
+				Token::Uint(0.into()),
+				Token::Uint(0.into()),
+				Token::Bool(true),
+			]);
+			let transport = MockTransport::default()
+				.with_call(
+					H160::from([4; 20]),
+					selector("fee"),
+					ethabi::encode(&[Token::Uint(500.into())]),
+				)
+				.with_call(H160::from([4; 20]), selector("slot0"), slot0);
+
+			let mut pools = vec![
+				PoolConfig::default(),
+				PoolConfig {
+					address: format!("{:?}", H160::from([4; 20])),
+					fee_tier: 0,
+					..PoolConfig::default()
 				},
-			Ok(None) => (),
-			Err(buffer::ReorganizingBufferError::DepthExceeded(depth)) => {
-				println!(
-					"WARNING: Maximal reorganization depth {} exceeded ({}). Terminating.",
-					buffer.depth, depth,
+			];
+			read_fee_tiers(Web3::new(transport.clone()), &mut pools).await.unwrap();
+
+			assert_eq!(pools[0].fee_tier, 100);
+			assert_eq!(pools[1].fee_tier, 500);
+			assert_eq!(transport.call_count(), 2);
+		}
+	}
+
+	mod read_token_addresses {
+		use super::*;
+		use rust_uniswap_task::testing::MockTransport;
+		use web3::ethabi::{self, Token};
+
+		#[tokio::test]
+		async fn fills_unknown_addresses() {
+			let abi = web3::ethabi::Contract::load(
+				&include_bytes!("contracts/uniswap_pool_abi.json")[..],
+			)
+			.unwrap();
+			let selector = |name| abi.function(name).unwrap().short_signature();
+			let pool = PoolConfig::default().contract_address().unwrap();
+			let transport = MockTransport::default()
+				.with_call(
+					pool,
+					selector("token0"),
+					ethabi::encode(&[Token::Address([1; 20].into())]),
+				)
+				.with_call(
+					pool,
+					selector("token1"),
+					ethabi::encode(&[Token::Address([2; 20].into())]),
 				);
-				return Ok(());
-			},
-			Err(buffer::ReorganizingBufferError::MissingOffset(expected_block_number)) => {
-				println!("WARNING: Skipped block number {}. Terminating.", expected_block_number,);
-				return Ok(());
-			},
+
+			let mut pools = vec![PoolConfig::default()];
+			read_token_addresses(Web3::new(transport), &mut pools).await.unwrap();
+
+			assert_eq!(pools[0].token0.address, Some(H160::from([1; 20])));
+			assert_eq!(pools[0].token1.address, Some(H160::from([2; 20])));
+		}
+	}
+
+	mod read_token_info {
+		use super::*;
+		use rust_uniswap_task::testing::MockTransport;
+		use web3::ethabi::{self, Token};
+
+		#[tokio::test]
+		async fn pools_given_by_address() {
+			let abi = web3::ethabi::Contract::load(&include_bytes!("contracts/erc20_abi.json")[..])
+				.unwrap();
+			let selector = |name| abi.function(name).unwrap().short_signature();
+			let (usdc, weth) = (H160::from([1; 20]), H160::from([2; 20]));
+			let transport = MockTransport::default()
+				.with_call(
+					usdc,
+					selector("symbol"),
+					ethabi::encode(&[Token::String("USDC".into())]),
+				)
+				.with_call(usdc, selector("decimals"), ethabi::encode(&[Token::Uint(6.into())]))
+				.with_call(
+					weth,
+					selector("symbol"),
+					ethabi::encode(&[Token::String("WETH".into())]),
+				)
+				.with_call(weth, selector("decimals"), ethabi::encode(&[Token::Uint(18.into())]));
+			let pool = PoolConfig::default();
+			let given = PoolConfig {
+				token0: TokenConfig { address: Some(usdc), ..pool.token0.clone() },
+				token1: TokenConfig { address: Some(weth), ..pool.token1.clone() },
+				read_tokens: true,
+				..pool.clone()
+			};
+
+			let mut pools = vec![pool.clone(), given];
+			read_token_info(Web3::new(transport.clone()), &mut pools).await.unwrap();
+
+			assert_eq!(pools[0], pool);
+			assert_eq!(
+				pools[1].token0,
+				TokenConfig { symbol: "USDC".to_string(), decimals: 6, address: Some(usdc) }
+			);
+			assert_eq!(
+				pools[1].token1,
+				TokenConfig { symbol: "WETH".to_string(), decimals: 18, address: Some(weth) }
+			);
+			assert!(!pools[1].read_tokens);
+			assert_eq!(transport.call_count(), 4);
+		}
+	}
+
+	mod replay_file {
+		use super::*;
+
+		#[test]
+		fn fills_gaps_and_skips_foreign_logs() {
+			let abi = swap_event_abi();
+			let swap_log = |block_number, log_index| {
+				SwapLogBuilder::new(&abi)
+					.block_number(block_number)
+					.log_index(log_index)
+					.build()
+			};
+			let foreign_log = SwapLogBuilder::new(&abi).address(H160::from([9; 20])).build();
+
+			let blocks = BTreeMap::from([
+				(10, vec![swap_log(10, 0), foreign_log]),
+				(13, vec![swap_log(13, 4)]),
+			]);
+			let config = AppConfig {
+				pools: vec![PoolConfig::default()],
+				output_format: OutputFormat::Json,
+				..AppConfig::default()
+			};
+			let output = SharedBuffer::default();
+
+			replay_file(blocks, &config, sinks(&output)).unwrap();
+
+			let events: Vec<serde_json::Value> = output
+				.contents()
+				.lines()
+				.map(|line| serde_json::from_str(line).unwrap())
+				.collect();
+			assert_eq!(events.len(), 2);
+			assert_eq!(events[0]["block_number"], 10);
+			assert_eq!(events[1]["block_number"], 13);
+			assert_eq!(events[1]["log_index"], 4);
 		}
 	}
 }
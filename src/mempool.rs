@@ -0,0 +1,447 @@
+use anyhow::{anyhow, bail, Context, Result};
+use futures::StreamExt;
+use lru::LruCache;
+use std::{collections::HashSet, num::NonZeroUsize, pin::pin};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, info, warn};
+use web3::{
+	ethabi::{Contract, Token},
+	transports::WebSocket,
+	types::{Address, Transaction, TransactionId, H256, U256},
+	Web3,
+};
+
+use crate::{event::SwapEvent, rate_limit::SharedRateLimiter};
+
+// Uniswap V3 SwapRouter, deployed at the same address on mainnet and the major L2s
+pub const SWAP_ROUTER: &str = "e592427a0aece92de3edee1f18e0157c05861564";
+
+// Pending swaps kept for correlation, the oldest ones are dropped first
+pub const PENDING_SWAPS_CAPACITY: usize = 10_000;
+
+// Pending transactions fetched at the same time, further hashes wait in the subscription
+const MAX_CONCURRENT_FETCHES: usize = 16;
+
+const SWAP_ROUTER_ABI: &[u8] = include_bytes!("contracts/uniswap_swap_router_abi.json");
+
+// Length of a token address followed by a fee tier in a multi-hop path
+const PATH_HOP_LENGTH: usize = 23;
+
+// In the tokens' smallest units. Only one of the amounts is exact, the other one is the
+// slippage limit, i.e. the minimal output of exact input swaps or the maximal input of exact
+// output swaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimatedAmounts {
+	pub amount_in: U256,
+	pub amount_out: U256,
+}
+
+// Swap sent to the SwapRouter and not included in a block yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSwapEvent {
+	pub tx_hash: H256,
+	pub token_in: Address,
+	pub token_out: Address,
+	pub estimated_amounts: EstimatedAmounts,
+	// Unix timestamp after which the router rejects the swap
+	pub deadline: u64,
+}
+
+// Decodes the calldata of transactions calling the SwapRouter's swap functions directly, swaps
+// batched through `multicall` are not decoded
+#[derive(Debug, Clone)]
+pub struct SwapRouterDecoder {
+	address: Address,
+	abi: Contract,
+}
+
+impl SwapRouterDecoder {
+	pub fn new(address: Address) -> Result<SwapRouterDecoder> {
+		Ok(SwapRouterDecoder { address, abi: Contract::load(SWAP_ROUTER_ABI)? })
+	}
+
+	// Returns `None` for transactions to other contracts or calling other functions
+	pub fn decode(&self, transaction: &Transaction) -> Result<Option<PendingSwapEvent>> {
+		if transaction.to != Some(self.address) || transaction.input.0.len() < 4 {
+			return Ok(None);
+		}
+		let (selector, data) = transaction.input.0.split_at(4);
+		let Some(function) =
+			self.abi.functions().find(|function| function.short_signature() == selector)
+		else {
+			return Ok(None);
+		};
+
+		let params = match function.decode_input(data)?.pop() {
+			Some(Token::Tuple(params)) => params,
+			_ => bail!("Missing parameters of {}", function.name),
+		};
+		let param = |index: usize| params.get(index).cloned();
+		let invalid = || anyhow!("Invalid parameters of {}", function.name);
+		let address = |index| param(index).and_then(Token::into_address).ok_or_else(invalid);
+		let uint = |index| param(index).and_then(Token::into_uint).ok_or_else(invalid);
+		let path = |index| param(index).and_then(Token::into_bytes).ok_or_else(invalid);
+
+		let (token_in, token_out, deadline, amount_in, amount_out) = match function.name.as_str() {
+			"exactInputSingle" => (address(0)?, address(1)?, uint(4)?, uint(5)?, uint(6)?),
+			"exactOutputSingle" => (address(0)?, address(1)?, uint(4)?, uint(6)?, uint(5)?),
+			"exactInput" => {
+				let (token_in, token_out) = path_tokens(&path(0)?)?;
+				(token_in, token_out, uint(2)?, uint(3)?, uint(4)?)
+			},
+			// Exact output paths are encoded in reverse, starting with the output token
+			"exactOutput" => {
+				let (token_out, token_in) = path_tokens(&path(0)?)?;
+				(token_in, token_out, uint(2)?, uint(4)?, uint(3)?)
+			},
+			_ => bail!("Unexpected SwapRouter function {}", function.name),
+		};
+
+		Ok(Some(PendingSwapEvent {
+			tx_hash: transaction.hash,
+			token_in,
+			token_out,
+			estimated_amounts: EstimatedAmounts { amount_in, amount_out },
+			// Deadlines beyond the range of timestamps never pass
+			deadline: u64::try_from(deadline).unwrap_or(u64::MAX),
+		}))
+	}
+}
+
+// First and last token of a path of tokens separated by fee tiers
+fn path_tokens(path: &[u8]) -> Result<(Address, Address)> {
+	if path.len() < Address::len_bytes() + PATH_HOP_LENGTH ||
+		(path.len() - Address::len_bytes()) % PATH_HOP_LENGTH != 0
+	{
+		bail!("Invalid swap path of {} bytes", path.len());
+	}
+
+	Ok((
+		Address::from_slice(&path[..Address::len_bytes()]),
+		Address::from_slice(&path[path.len() - Address::len_bytes()..]),
+	))
+}
+
+// Pending swaps by transaction hash, correlated with the swaps of the same transactions once
+// those are confirmed
+#[derive(Debug)]
+pub struct PendingBlockMonitor {
+	pending: LruCache<H256, PendingSwapEvent>,
+	// Tokens of the monitored pools, swaps of other tokens are skipped
+	tokens: HashSet<Address>,
+	rate_limiter: Option<SharedRateLimiter>,
+}
+
+impl PendingBlockMonitor {
+	pub fn new(capacity: usize, tokens: HashSet<Address>) -> PendingBlockMonitor {
+		PendingBlockMonitor {
+			pending: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+			tokens,
+			rate_limiter: None,
+		}
+	}
+
+	// Limits the requests for pending transactions, the subscription is not limited
+	pub fn with_rate_limit(mut self, rate_limiter: SharedRateLimiter) -> PendingBlockMonitor {
+		self.rate_limiter = Some(rate_limiter);
+		self
+	}
+
+	// Multi-hop swaps only count if their input or output token is monitored
+	pub fn is_monitored(&self, event: &PendingSwapEvent) -> bool {
+		self.tokens.contains(&event.token_in) || self.tokens.contains(&event.token_out)
+	}
+
+	pub fn record(&mut self, event: PendingSwapEvent) {
+		self.pending.put(event.tx_hash, event);
+	}
+
+	// Swaps routed through several pools are only correlated with their first confirmed swap
+	pub fn correlate(&mut self, swap: &SwapEvent) -> Option<PendingSwapEvent> {
+		self.pending.pop(&swap.transaction_hash?)
+	}
+
+	pub fn len(&self) -> usize {
+		self.pending.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+
+	// Runs until the event channel is closed or the subscription to pending transactions ends.
+	// Transactions that can't be fetched or decoded are skipped.
+	pub async fn run(
+		mut self,
+		decoder: SwapRouterDecoder,
+		web3: Web3<WebSocket>,
+		mut events: broadcast::Receiver<SwapEvent>,
+	) -> Result<()> {
+		let transactions = web3
+			.eth_subscribe()
+			.subscribe_new_pending_transactions()
+			.await
+			.context("Failed to subscribe to pending transactions")?;
+
+		let (web3, decoder, rate_limiter) = (&web3, &decoder, self.rate_limiter.clone());
+		let rate_limiter = &rate_limiter;
+		let mut swaps = pin!(transactions
+			.map(|hash| async move {
+				let hash = hash?;
+				if let Some(rate_limiter) = rate_limiter {
+					rate_limiter.acquire().await;
+				}
+				Ok::<_, web3::Error>((hash, pending_swap(web3, decoder, hash).await))
+			})
+			.buffer_unordered(MAX_CONCURRENT_FETCHES));
+
+		loop {
+			tokio::select! {
+				swap = swaps.next() => match swap {
+					Some(Ok((hash, swap))) => match swap {
+						Ok(Some(event)) if self.is_monitored(&event) => {
+							info!(
+								transaction_hash = ?event.tx_hash,
+								token_in = ?event.token_in,
+								token_out = ?event.token_out,
+								amount_in = %event.estimated_amounts.amount_in,
+								amount_out = %event.estimated_amounts.amount_out,
+								deadline = event.deadline,
+								"Pending swap"
+							);
+							self.record(event);
+						},
+						// Swaps of other tokens and other transactions
+						Ok(_) => (),
+						Err(err) =>
+							debug!(error = %err, transaction_hash = ?hash, "Skipped pending transaction"),
+					},
+					Some(Err(err)) => warn!(error = %err, "Failed to receive pending transaction"),
+					None => bail!("Pending transaction subscription closed"),
+				},
+				event = events.recv() => match event {
+					Ok(swap) =>
+						if let Some(pending) = self.correlate(&swap) {
+							info!(
+								transaction_hash = ?pending.tx_hash,
+								block_number = swap.block_number,
+								log_index = swap.log_index,
+								amount_in = %pending.estimated_amounts.amount_in,
+								amount_out = %pending.estimated_amounts.amount_out,
+								"Pending swap confirmed"
+							);
+						},
+					Err(RecvError::Lagged(skipped)) =>
+						warn!(skipped, "Pending swap monitor lagging, skipped events"),
+					Err(RecvError::Closed) => return Ok(()),
+				},
+			}
+		}
+	}
+}
+
+// Transactions dropped from the mempool before they're fetched are skipped
+async fn pending_swap(
+	web3: &Web3<WebSocket>,
+	decoder: &SwapRouterDecoder,
+	hash: H256,
+) -> Result<Option<PendingSwapEvent>> {
+	match web3.eth().transaction(TransactionId::Hash(hash)).await? {
+		Some(transaction) => decoder.decode(&transaction),
+		None => Ok(None),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use web3::types::Bytes;
+
+	const DAI: Address = Address::repeat_byte(1);
+	const USDC: Address = Address::repeat_byte(2);
+	const WETH: Address = Address::repeat_byte(3);
+
+	fn decoder() -> SwapRouterDecoder {
+		SwapRouterDecoder::new(SWAP_ROUTER.parse().unwrap()).unwrap()
+	}
+
+	fn transaction(function: &str, params: Vec<Token>) -> Transaction {
+		let abi = Contract::load(SWAP_ROUTER_ABI).unwrap();
+		let input = abi.function(function).unwrap().encode_input(&[Token::Tuple(params)]).unwrap();
+
+		Transaction {
+			hash: H256::from_low_u64_be(1),
+			to: Some(SWAP_ROUTER.parse().unwrap()),
+			input: Bytes(input),
+			..Default::default()
+		}
+	}
+
+	fn single_params(amount: u64, limit: u64) -> Vec<Token> {
+		vec![
+			Token::Address(DAI),
+			Token::Address(USDC),
+			Token::Uint(100.into()),
+			Token::Address(Address::repeat_byte(9)),
+			Token::Uint(1_700_000_000.into()),
+			Token::Uint(amount.into()),
+			Token::Uint(limit.into()),
+			Token::Uint(U256::zero()),
+		]
+	}
+
+	// DAI → USDC → WETH in route order
+	fn path(tokens: [Address; 3]) -> Token {
+		let mut path = tokens[0].as_bytes().to_vec();
+		for token in &tokens[1..] {
+			path.extend([0, 0, 100]);
+			path.extend(token.as_bytes());
+		}
+		Token::Bytes(path)
+	}
+
+	fn multi_hop_params(path: Token, amount: u64, limit: u64) -> Vec<Token> {
+		vec![
+			path,
+			Token::Address(Address::repeat_byte(9)),
+			Token::Uint(1_700_000_000.into()),
+			Token::Uint(amount.into()),
+			Token::Uint(limit.into()),
+		]
+	}
+
+	fn event(
+		token_in: Address,
+		token_out: Address,
+		amount_in: u64,
+		amount_out: u64,
+	) -> PendingSwapEvent {
+		PendingSwapEvent {
+			tx_hash: H256::from_low_u64_be(1),
+			token_in,
+			token_out,
+			estimated_amounts: EstimatedAmounts {
+				amount_in: amount_in.into(),
+				amount_out: amount_out.into(),
+			},
+			deadline: 1_700_000_000,
+		}
+	}
+
+	mod decode {
+		use super::*;
+
+		#[test]
+		fn exact_input_single() {
+			let transaction = transaction("exactInputSingle", single_params(1_000, 990));
+
+			assert_eq!(decoder().decode(&transaction).unwrap(), Some(event(DAI, USDC, 1_000, 990)));
+		}
+
+		#[test]
+		fn exact_output_single() {
+			let transaction = transaction("exactOutputSingle", single_params(990, 1_000));
+
+			assert_eq!(decoder().decode(&transaction).unwrap(), Some(event(DAI, USDC, 1_000, 990)));
+		}
+
+		#[test]
+		fn exact_input() {
+			let transaction =
+				transaction("exactInput", multi_hop_params(path([DAI, USDC, WETH]), 1_000, 5));
+
+			assert_eq!(decoder().decode(&transaction).unwrap(), Some(event(DAI, WETH, 1_000, 5)));
+		}
+
+		#[test]
+		fn exact_output() {
+			let transaction =
+				transaction("exactOutput", multi_hop_params(path([WETH, USDC, DAI]), 5, 1_000));
+
+			assert_eq!(decoder().decode(&transaction).unwrap(), Some(event(DAI, WETH, 1_000, 5)));
+		}
+
+		#[test]
+		fn other_contract() {
+			let transaction = Transaction {
+				to: Some(Address::repeat_byte(9)),
+				..transaction("exactInputSingle", single_params(1_000, 990))
+			};
+
+			assert_eq!(decoder().decode(&transaction).unwrap(), None);
+		}
+
+		#[test]
+		fn other_function() {
+			// Selector of `multicall`
+			let transaction = Transaction {
+				input: Bytes(vec![0xac, 0x96, 0x50, 0xd8, 0, 0]),
+				..transaction("exactInputSingle", single_params(1_000, 990))
+			};
+
+			assert_eq!(decoder().decode(&transaction).unwrap(), None);
+		}
+
+		#[test]
+		fn invalid_path() {
+			let transaction = transaction(
+				"exactInput",
+				multi_hop_params(Token::Bytes(DAI.as_bytes().to_vec()), 1_000, 5),
+			);
+
+			assert_eq!(
+				decoder().decode(&transaction).unwrap_err().to_string(),
+				"Invalid swap path of 20 bytes"
+			);
+		}
+	}
+
+	mod is_monitored {
+		use super::*;
+
+		#[test]
+		fn input_or_output_token() {
+			let monitor = PendingBlockMonitor::new(PENDING_SWAPS_CAPACITY, HashSet::from([USDC]));
+
+			assert!(monitor.is_monitored(&event(DAI, USDC, 1_000, 990)));
+			assert!(monitor.is_monitored(&event(USDC, WETH, 1_000, 5)));
+			assert!(!monitor.is_monitored(&event(DAI, WETH, 1_000, 5)));
+		}
+	}
+
+	mod correlate {
+		use super::*;
+
+		#[test]
+		fn by_transaction_hash() {
+			let mut monitor = PendingBlockMonitor::new(PENDING_SWAPS_CAPACITY, HashSet::new());
+			monitor.record(event(DAI, USDC, 1_000, 990));
+			let swap =
+				SwapEventBuilder::default().transaction_hash(H256::from_low_u64_be(1)).build();
+			let other =
+				SwapEventBuilder::default().transaction_hash(H256::from_low_u64_be(2)).build();
+
+			assert_eq!(monitor.correlate(&other), None);
+			assert_eq!(monitor.correlate(&swap), Some(event(DAI, USDC, 1_000, 990)));
+			// Only correlated once
+			assert_eq!(monitor.correlate(&swap), None);
+			assert!(monitor.is_empty());
+		}
+
+		#[test]
+		fn evicts_oldest() {
+			let mut monitor = PendingBlockMonitor::new(1, HashSet::new());
+			monitor.record(event(DAI, USDC, 1_000, 990));
+			monitor.record(PendingSwapEvent {
+				tx_hash: H256::from_low_u64_be(2),
+				..event(USDC, DAI, 990, 1_000)
+			});
+
+			let swap =
+				SwapEventBuilder::default().transaction_hash(H256::from_low_u64_be(1)).build();
+			assert_eq!(monitor.correlate(&swap), None);
+			assert_eq!(monitor.len(), 1);
+		}
+	}
+}
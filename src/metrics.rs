@@ -0,0 +1,219 @@
+use anyhow::Result;
+use hyper::{
+	header::CONTENT_TYPE,
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use prometheus::{
+	Counter, Encoder, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use rust_decimal::prelude::ToPrimitive;
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
+
+use crate::{event::SwapEvent, server};
+
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9090";
+
+// Metrics live in their own registry, so every process starts counting from zero
+pub struct Metrics {
+	registry: Registry,
+	swaps_total: IntCounterVec,
+	swap_volume_token0_total: Counter,
+	swap_volume_token1_total: Counter,
+	reorgs_total: IntCounter,
+	duplicates_skipped_total: IntCounter,
+	block_lag: IntGauge,
+	buffer_occupancy: IntGauge,
+}
+
+impl Metrics {
+	pub fn new() -> Result<Metrics> {
+		let metrics = Metrics {
+			registry: Registry::new(),
+			swaps_total: IntCounterVec::new(
+				Opts::new("uniswap_swaps_total", "Confirmed swaps by direction"),
+				&["direction"],
+			)?,
+			swap_volume_token0_total: Counter::new(
+				"uniswap_swap_volume_token0_total",
+				"Token0 volume of confirmed swaps",
+			)?,
+			swap_volume_token1_total: Counter::new(
+				"uniswap_swap_volume_token1_total",
+				"Token1 volume of confirmed swaps",
+			)?,
+			reorgs_total: IntCounter::new(
+				"uniswap_reorgs_total",
+				"Observed chain reorganizations",
+			)?,
+			duplicates_skipped_total: IntCounter::new(
+				"uniswap_duplicates_skipped_total",
+				"Swaps skipped because their log was delivered more than once",
+			)?,
+			block_lag: IntGauge::new(
+				"uniswap_block_lag",
+				"Blocks between the latest received and the latest emitted block",
+			)?,
+			buffer_occupancy: IntGauge::new(
+				"uniswap_buffer_occupancy",
+				"Blocks waiting for confirmation",
+			)?,
+		};
+
+		metrics.registry.register(Box::new(metrics.swaps_total.clone()))?;
+		metrics.registry.register(Box::new(metrics.swap_volume_token0_total.clone()))?;
+		metrics.registry.register(Box::new(metrics.swap_volume_token1_total.clone()))?;
+		metrics.registry.register(Box::new(metrics.reorgs_total.clone()))?;
+		metrics.registry.register(Box::new(metrics.duplicates_skipped_total.clone()))?;
+		metrics.registry.register(Box::new(metrics.block_lag.clone()))?;
+		metrics.registry.register(Box::new(metrics.buffer_occupancy.clone()))?;
+
+		Ok(metrics)
+	}
+
+	pub fn record_swaps(&self, events: &[SwapEvent]) {
+		for event in events {
+			let direction = event.direction.with_pair(&event.amounts.pair).name();
+			self.swaps_total.with_label_values(&[&direction]).inc();
+			self.swap_volume_token0_total
+				.inc_by(event.amounts.token0_amount.to_f64().unwrap_or_default());
+			self.swap_volume_token1_total
+				.inc_by(event.amounts.token1_amount.to_f64().unwrap_or_default());
+		}
+	}
+
+	pub fn record_reorg(&self) {
+		self.reorgs_total.inc();
+	}
+
+	pub fn record_duplicates(&self, count: usize) {
+		self.duplicates_skipped_total.inc_by(count.try_into().unwrap_or(u64::MAX));
+	}
+
+	pub fn set_block_lag(&self, lag: u64) {
+		self.block_lag.set(lag.try_into().unwrap_or(i64::MAX));
+	}
+
+	pub fn set_buffer_occupancy(&self, occupancy: usize) {
+		self.buffer_occupancy.set(occupancy.try_into().unwrap_or(i64::MAX));
+	}
+
+	pub fn encode(&self) -> Result<String> {
+		let mut buffer = Vec::new();
+		TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+		Ok(String::from_utf8(buffer)?)
+	}
+}
+
+pub fn serve(
+	addr: SocketAddr,
+	metrics: Arc<Metrics>,
+) -> Result<impl Future<Output = hyper::Result<()>>> {
+	let make_service = make_service_fn(move |_| {
+		let metrics = metrics.clone();
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request| {
+				let metrics = metrics.clone();
+				async move { Ok::<_, Infallible>(respond(&request, &metrics)) }
+			}))
+		}
+	});
+
+	Ok(Server::from_tcp(server::bind(addr)?)?.serve(make_service))
+}
+
+fn respond(request: &Request<Body>, metrics: &Metrics) -> Response<Body> {
+	let status = |status: StatusCode| {
+		let mut response = Response::new(Body::empty());
+		*response.status_mut() = status;
+		response
+	};
+
+	if (request.method(), request.uri().path()) != (&Method::GET, "/metrics") {
+		return status(StatusCode::NOT_FOUND);
+	}
+
+	match metrics.encode() {
+		Ok(body) => {
+			let mut response = Response::new(Body::from(body));
+			response
+				.headers_mut()
+				.insert(CONTENT_TYPE, TextEncoder::new().format_type().parse().unwrap());
+			response
+		},
+		Err(_) => status(StatusCode::INTERNAL_SERVER_ERROR),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::{SwapDirection, SwapEventBuilder};
+	use rust_decimal::Decimal;
+
+	mod encode {
+		use super::*;
+
+		#[test]
+		fn starts_at_zero() {
+			let encoded = Metrics::new().unwrap().encode().unwrap();
+
+			assert!(encoded.contains("uniswap_swap_volume_token0_total 0\n"));
+			assert!(encoded.contains("uniswap_reorgs_total 0\n"));
+			assert!(encoded.contains("uniswap_block_lag 0\n"));
+		}
+
+		#[test]
+		fn records_swaps() {
+			let metrics = Metrics::new().unwrap();
+			metrics.record_swaps(&[
+				SwapEventBuilder::default()
+					.token0_amount(Decimal::new(150, 1))
+					.token1_amount(Decimal::from(14))
+					.build(),
+				SwapEventBuilder::default()
+					.direction(SwapDirection::Token1ToToken0)
+					.token0_amount(Decimal::from(5))
+					.token1_amount(Decimal::from(6))
+					.build(),
+			]);
+			metrics.record_reorg();
+			metrics.record_duplicates(2);
+			metrics.set_block_lag(5);
+			metrics.set_buffer_occupancy(4);
+
+			let encoded = metrics.encode().unwrap();
+
+			assert!(encoded.contains("uniswap_swaps_total{direction=\"dai_to_usdc\"} 1\n"));
+			assert!(encoded.contains("uniswap_swaps_total{direction=\"usdc_to_dai\"} 1\n"));
+			assert!(encoded.contains("uniswap_swap_volume_token0_total 20\n"));
+			assert!(encoded.contains("uniswap_swap_volume_token1_total 20\n"));
+			assert!(encoded.contains("uniswap_reorgs_total 1\n"));
+			assert!(encoded.contains("uniswap_duplicates_skipped_total 2\n"));
+			assert!(encoded.contains("uniswap_block_lag 5\n"));
+			assert!(encoded.contains("uniswap_buffer_occupancy 4\n"));
+		}
+	}
+
+	mod respond {
+		use super::*;
+
+		#[test]
+		fn metrics() {
+			let request = Request::get("/metrics").body(Body::empty()).unwrap();
+
+			let response = respond(&request, &Metrics::new().unwrap());
+
+			assert_eq!(response.status(), StatusCode::OK);
+		}
+
+		#[test]
+		fn unknown_path() {
+			let request = Request::get("/").body(Body::empty()).unwrap();
+
+			let response = respond(&request, &Metrics::new().unwrap());
+
+			assert_eq!(response.status(), StatusCode::NOT_FOUND);
+		}
+	}
+}
@@ -0,0 +1,109 @@
+use serde::Deserialize;
+
+use crate::config::{PoolConfig, TokenConfig};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+	#[default]
+	Mainnet,
+	Arbitrum,
+	Polygon,
+	// Any other network, endpoint and pools have to be given explicitly
+	Custom,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+	// EIP-155 chain ID
+	pub chain_id: u64,
+	// Suggested endpoints, the API key placeholder has to be replaced
+	pub ws_url: &'static str,
+	pub http_url: &'static str,
+	// Block explorer linking transactions at /tx/<hash>
+	pub explorer_url: &'static str,
+	// Etherscan compatible API of the block explorer, serving verified contract ABIs
+	pub explorer_api_url: &'static str,
+	// Uniswap V3 DAI/USDC pool with the lowest fee tier
+	pub pool: PoolConfig,
+}
+
+impl Network {
+	// Returns `None` for custom networks
+	pub fn config(self) -> Option<NetworkConfig> {
+		match self {
+			Network::Mainnet => Some(NetworkConfig {
+				chain_id: 1,
+				ws_url: "wss://mainnet.infura.io/ws/v3/<API_KEY>",
+				http_url: "https://mainnet.infura.io/v3/<API_KEY>",
+				explorer_url: "https://etherscan.io",
+				explorer_api_url: "https://api.etherscan.io/api",
+				pool: PoolConfig::default(),
+			}),
+			Network::Arbitrum => Some(NetworkConfig {
+				chain_id: 42161,
+				ws_url: "wss://arbitrum-mainnet.infura.io/ws/v3/<API_KEY>",
+				http_url: "https://arbitrum-mainnet.infura.io/v3/<API_KEY>",
+				explorer_url: "https://arbiscan.io",
+				explorer_api_url: "https://api.arbiscan.io/api",
+				pool: PoolConfig {
+					address: "f0428617433652c9dc6d1093a42adfbf30d29f74".to_string(),
+					token0: token("DAI", 18),
+					token1: token("USDC", 6),
+					fee_tier: 100,
+					abi: None,
+					read_tokens: false,
+				},
+			}),
+			// Bridged USDC sorts before DAI here, so the pool's tokens are swapped
+			Network::Polygon => Some(NetworkConfig {
+				chain_id: 137,
+				ws_url: "wss://polygon-mainnet.infura.io/ws/v3/<API_KEY>",
+				http_url: "https://polygon-mainnet.infura.io/v3/<API_KEY>",
+				explorer_url: "https://polygonscan.com",
+				explorer_api_url: "https://api.polygonscan.com/api",
+				pool: PoolConfig {
+					address: "5645dcb64c059aa11212707fbf4e7f984440a8cf".to_string(),
+					token0: token("USDC", 6),
+					token1: token("DAI", 18),
+					fee_tier: 100,
+					abi: None,
+					read_tokens: false,
+				},
+			}),
+			Network::Custom => None,
+		}
+	}
+}
+
+fn token(symbol: &str, decimals: u32) -> TokenConfig {
+	TokenConfig { symbol: symbol.to_string(), decimals, address: None }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod config {
+		use super::*;
+
+		#[test]
+		fn named_networks() {
+			for (network, chain_id) in
+				[(Network::Mainnet, 1), (Network::Arbitrum, 42161), (Network::Polygon, 137)]
+			{
+				let config = network.config().unwrap();
+
+				assert_eq!(config.chain_id, chain_id);
+				assert!(config.pool.contract_address().is_ok());
+				assert!(config.ws_url.starts_with("wss://"));
+				assert!(config.http_url.starts_with("https://"));
+			}
+		}
+
+		#[test]
+		fn custom() {
+			assert_eq!(Network::Custom.config(), None);
+		}
+	}
+}
@@ -0,0 +1,542 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::io::{Result, Write};
+use web3::types::{H160, H256};
+
+use crate::{
+	address_book::AddressBook,
+	event::{SwapDirection, SwapEvent},
+	gas::SwapCost,
+	network::Network,
+	parser::SwapParser,
+	subscription::BlockContext,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+	#[default]
+	Text,
+	Json,
+	Csv,
+}
+
+impl OutputFormat {
+	// Backend writing confirmed events in this format, CSV starts with its header. Only the text
+	// format shows address labels, the others keep addresses machine-readable. JSON links the
+	// transactions on the network's block explorer.
+	pub fn backend<W: Write + Send + 'static>(
+		self,
+		writer: W,
+		address_book: AddressBook,
+		network: Network,
+	) -> anyhow::Result<Box<dyn EventOutput>> {
+		Ok(match self {
+			OutputFormat::Text => Box::new(TextOutput::new(writer).with_address_book(address_book)),
+			OutputFormat::Json => Box::new(JsonOutput::new(writer).with_network(network)),
+			OutputFormat::Csv => {
+				let mut output = CsvOutput::new(writer);
+				output.write_header()?;
+				Box::new(output)
+			},
+		})
+	}
+}
+
+// Destination of confirmed events
+pub trait EventOutput: Send {
+	fn write_event(&mut self, event: &SwapEvent) -> anyhow::Result<()>;
+
+	fn flush(&mut self) -> anyhow::Result<()>;
+
+	// Called for every confirmed block, including blocks without events
+	fn write_block(&mut self, _block_number: u64, events: &[SwapEvent]) -> anyhow::Result<()> {
+		for event in events {
+			self.write_event(event)?;
+		}
+		self.flush()
+	}
+
+	fn write_context(&mut self, context: &BlockContext) -> anyhow::Result<()> {
+		let events: Vec<_> = context.swaps().cloned().collect();
+		self.write_block(context.block_number, &events)
+	}
+
+	// Called with the gas of tracked transactions once it's known, after their swaps. CSV rows
+	// only have the swap columns, so costs are left out by default.
+	fn write_cost(&mut self, _cost: &SwapCost) -> anyhow::Result<()> {
+		Ok(())
+	}
+}
+
+pub struct TextOutput<W: Write> {
+	writer: W,
+	address_book: AddressBook,
+}
+
+impl<W: Write> TextOutput<W> {
+	pub fn new(writer: W) -> Self {
+		TextOutput { writer, address_book: AddressBook::default() }
+	}
+
+	// Senders and receivers with a label are shown by it instead of their address
+	pub fn with_address_book(mut self, address_book: AddressBook) -> Self {
+		self.address_book = address_book;
+		self
+	}
+}
+
+impl<W: Write + Send> EventOutput for TextOutput<W> {
+	fn write_event(&mut self, event: &SwapEvent) -> anyhow::Result<()> {
+		writeln!(self.writer, "- {}", event.labeled(&self.address_book))?;
+		Ok(())
+	}
+
+	fn flush(&mut self) -> anyhow::Result<()> {
+		self.writer.flush()?;
+		Ok(())
+	}
+
+	// Events are listed below their block, blocks without events are left out
+	fn write_block(&mut self, block_number: u64, events: &[SwapEvent]) -> anyhow::Result<()> {
+		if events.is_empty() {
+			return Ok(());
+		}
+
+		writeln!(self.writer, "---")?;
+		writeln!(self.writer, "CONFIRMED EVENTS FROM BLOCK {}:", block_number)?;
+		for event in events {
+			self.write_event(event)?;
+		}
+		writeln!(self.writer, "---")?;
+		self.flush()
+	}
+
+	fn write_cost(&mut self, cost: &SwapCost) -> anyhow::Result<()> {
+		writeln!(
+			self.writer,
+			"GAS OF TRANSACTION {:?}: {} at {} wei",
+			cost.transaction_hash, cost.gas_used, cost.effective_gas_price
+		)?;
+		self.flush()
+	}
+}
+
+pub struct CsvWriter<W: Write> {
+	writer: W,
+}
+
+impl<W: Write> CsvWriter<W> {
+	pub fn new(writer: W) -> Self {
+		CsvWriter { writer }
+	}
+
+	pub fn write_header(&mut self) -> Result<()> {
+		writeln!(self.writer, "{}", SwapEvent::CSV_HEADER)
+	}
+
+	pub fn write_event(&mut self, event: &SwapEvent) -> Result<()> {
+		writeln!(self.writer, "{}", event.to_csv_row())
+	}
+}
+
+pub struct JsonOutput<W: Write> {
+	writer: W,
+	// Transactions are only linked once the network is known
+	network: Option<Network>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct JsonSwapEvent<'a> {
+	event_type: &'static str,
+	block_number: Option<u64>,
+	transaction_hash: Option<H256>,
+	log_index: Option<u64>,
+	timestamp: Option<u64>,
+	pool: H160,
+	sender: H160,
+	receiver: H160,
+	direction: &'a SwapDirection,
+	// Symbols of the pool's tokens
+	token0: &'a str,
+	token1: &'a str,
+	token0_amount: String,
+	token1_amount: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	etherscan_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonSwapCost<'a> {
+	event_type: &'static str,
+	#[serde(flatten)]
+	cost: &'a SwapCost,
+}
+
+impl<'a> JsonSwapEvent<'a> {
+	// Links the transaction on the network's block explorer
+	pub(crate) fn with_network(event: &'a SwapEvent, network: &Network) -> Self {
+		JsonSwapEvent {
+			etherscan_url: event.to_etherscan_url(network),
+			..JsonSwapEvent::from(event)
+		}
+	}
+}
+
+impl<'a> From<&'a SwapEvent> for JsonSwapEvent<'a> {
+	fn from(event: &'a SwapEvent) -> Self {
+		JsonSwapEvent {
+			event_type: "swap",
+			block_number: event.block_number,
+			transaction_hash: event.transaction_hash,
+			log_index: event.log_index,
+			timestamp: event.timestamp,
+			pool: event.pool,
+			sender: event.sender,
+			receiver: event.receiver,
+			direction: &event.direction,
+			token0: &event.amounts.pair.token0.symbol,
+			token1: &event.amounts.pair.token1.symbol,
+			token0_amount: event.amounts.token0_amount.to_string(),
+			token1_amount: event.amounts.token1_amount.to_string(),
+			etherscan_url: None,
+		}
+	}
+}
+
+impl<W: Write> JsonOutput<W> {
+	pub fn new(writer: W) -> Self {
+		JsonOutput { writer, network: None }
+	}
+
+	pub fn with_network(mut self, network: Network) -> Self {
+		self.network = Some(network);
+		self
+	}
+}
+
+impl<W: Write + Send> EventOutput for JsonOutput<W> {
+	fn write_event(&mut self, event: &SwapEvent) -> anyhow::Result<()> {
+		let event = match &self.network {
+			Some(network) => JsonSwapEvent::with_network(event, network),
+			None => JsonSwapEvent::from(event),
+		};
+		serde_json::to_writer(&mut self.writer, &event)?;
+		writeln!(self.writer)?;
+		Ok(())
+	}
+
+	fn flush(&mut self) -> anyhow::Result<()> {
+		self.writer.flush()?;
+		Ok(())
+	}
+
+	fn write_cost(&mut self, cost: &SwapCost) -> anyhow::Result<()> {
+		serde_json::to_writer(&mut self.writer, &JsonSwapCost { event_type: "swap_cost", cost })?;
+		writeln!(self.writer)?;
+		self.flush()
+	}
+}
+
+pub struct CsvOutput<W: Write> {
+	writer: csv::Writer<W>,
+}
+
+#[derive(Serialize)]
+struct CsvSwapEvent<'a> {
+	block_number: Option<u64>,
+	tx_hash: Option<H256>,
+	log_index: Option<u64>,
+	sender: H160,
+	receiver: H160,
+	direction: &'a SwapDirection,
+	token0_amount: String,
+	token1_amount: String,
+	exchange_rate: Option<Decimal>,
+	timestamp: Option<u64>,
+}
+
+impl<W: Write> CsvOutput<W> {
+	pub const HEADER: [&'static str; 10] = [
+		"block_number",
+		"tx_hash",
+		"log_index",
+		"sender",
+		"receiver",
+		"direction",
+		"token0_amount",
+		"token1_amount",
+		"exchange_rate",
+		"timestamp",
+	];
+
+	pub fn new(writer: W) -> Self {
+		CsvOutput { writer: csv::WriterBuilder::new().has_headers(false).from_writer(writer) }
+	}
+
+	pub fn write_header(&mut self) -> csv::Result<()> {
+		self.writer.write_record(Self::HEADER)
+	}
+
+	fn fixed_point(mut amount: Decimal) -> String {
+		amount.rescale(SwapParser::DECIMAL_PRECISION);
+		amount.to_string()
+	}
+}
+
+// Flushed after every block, so rows are not held back by the CSV writer's buffer
+impl<W: Write + Send> EventOutput for CsvOutput<W> {
+	fn write_event(&mut self, event: &SwapEvent) -> anyhow::Result<()> {
+		self.writer.serialize(CsvSwapEvent {
+			block_number: event.block_number,
+			tx_hash: event.transaction_hash,
+			log_index: event.log_index,
+			sender: event.sender,
+			receiver: event.receiver,
+			direction: &event.direction,
+			token0_amount: Self::fixed_point(event.amounts.token0_amount),
+			token1_amount: Self::fixed_point(event.amounts.token1_amount),
+			exchange_rate: event.exchange_rate(),
+			timestamp: event.timestamp,
+		})?;
+		Ok(())
+	}
+
+	fn flush(&mut self) -> anyhow::Result<()> {
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		event::{SwapDirection, SwapEventBuilder},
+		testing::SharedBuffer,
+	};
+	use rust_decimal::Decimal;
+	use web3::types::H256;
+
+	mod backend {
+		use super::*;
+
+		#[test]
+		fn csv_header() {
+			let output = SharedBuffer::default();
+			let mut backend = OutputFormat::Csv
+				.backend(output.clone(), AddressBook::default(), Network::Mainnet)
+				.unwrap();
+			backend.write_block(1, &[SwapEventBuilder::default().build()]).unwrap();
+
+			let contents = output.contents();
+			let lines = contents.lines().collect::<Vec<_>>();
+			assert_eq!(lines.len(), 2);
+			assert!(lines[0].starts_with("block_number,tx_hash"));
+		}
+	}
+
+	mod text_output {
+		use super::*;
+
+		#[test]
+		fn skips_empty_blocks() {
+			let mut output = Vec::new();
+			let mut writer = TextOutput::new(&mut output);
+			writer.write_block(1, &[]).unwrap();
+			writer.write_block(2, &[SwapEventBuilder::default().build()]).unwrap();
+
+			let output = String::from_utf8(output).unwrap();
+			let lines = output.lines().collect::<Vec<_>>();
+			assert_eq!(lines.len(), 4);
+			assert_eq!(lines[1], "CONFIRMED EVENTS FROM BLOCK 2:");
+		}
+
+		#[test]
+		fn address_labels() {
+			let sender = H160::repeat_byte(1);
+			let address_book =
+				AddressBook::new([(sender, "Coinbase 7".to_string())].into_iter().collect());
+			let mut output = Vec::new();
+			let mut writer = TextOutput::new(&mut output).with_address_book(address_book);
+
+			writer.write_event(&SwapEventBuilder::default().sender(sender).build()).unwrap();
+
+			let output = String::from_utf8(output).unwrap();
+			assert!(output.starts_with("- Swap Coinbase 7 0 DAI -> 0 USDC "));
+		}
+	}
+
+	mod csv_writer {
+		use super::*;
+
+		#[test]
+		fn header_and_events() {
+			let event = |block_number, log_index, direction| {
+				SwapEventBuilder::default()
+					.block_number(block_number)
+					.transaction_hash(H256::zero())
+					.log_index(log_index)
+					.direction(direction)
+					.token0_amount(Decimal::new(400, 2))
+					.token1_amount(Decimal::new(500, 2))
+					.build()
+			};
+
+			let mut output = Vec::new();
+			let mut writer = CsvWriter::new(&mut output);
+			writer.write_header().unwrap();
+			writer.write_event(&event(1, 0, SwapDirection::Token0ToToken1)).unwrap();
+			writer.write_event(&event(2, 3, SwapDirection::Token1ToToken0)).unwrap();
+
+			let zero_hash = format!("0x{}", "0".repeat(64));
+			let zero_address = format!("0x{}", "0".repeat(40));
+			let expected = [
+				SwapEvent::CSV_HEADER.to_string(),
+				format!("1,{0},0,{1},{1},token0_to_token1,4.00,5.00,1.25", zero_hash, zero_address),
+				format!("2,{0},3,{1},{1},token1_to_token0,4.00,5.00,1.25", zero_hash, zero_address),
+			];
+
+			assert_eq!(String::from_utf8(output).unwrap(), expected.join("\n") + "\n");
+		}
+	}
+
+	mod csv_output {
+		use super::*;
+
+		#[test]
+		fn header_and_events() {
+			let mut output = Vec::new();
+			let mut writer = CsvOutput::new(&mut output);
+			writer.write_header().unwrap();
+			writer
+				.write_event(
+					&SwapEventBuilder::default()
+						.block_number(15_000_000)
+						.transaction_hash(H256([0xab; 32]))
+						.log_index(7)
+						.timestamp(1_700_000_000)
+						.direction(SwapDirection::Token1ToToken0)
+						.token0_amount(Decimal::new(4, 0))
+						.token1_amount(Decimal::new(5, 0))
+						.build(),
+				)
+				.unwrap();
+			writer
+				.write_event(&SwapEventBuilder::default().token1_amount(Decimal::new(1, 0)).build())
+				.unwrap();
+			writer.flush().unwrap();
+			drop(writer);
+
+			let zero_address = format!("0x{}", "0".repeat(40));
+			let expected = [
+				"block_number,tx_hash,log_index,sender,receiver,direction,token0_amount,\
+				 token1_amount,exchange_rate,timestamp"
+					.to_string(),
+				format!(
+					"15000000,0x{},7,{1},{1},token1_to_token0,4.00,5.00,1.25,1700000000",
+					"ab".repeat(32),
+					zero_address
+				),
+				format!(",,,{0},{0},token0_to_token1,0.00,1.00,,", zero_address),
+			];
+
+			assert_eq!(String::from_utf8(output).unwrap(), expected.join("\n") + "\n");
+		}
+	}
+
+	mod json_output {
+		use super::*;
+
+		#[test]
+		fn one_object_per_line() {
+			let mut output = Vec::new();
+			let mut writer = JsonOutput::new(&mut output);
+			writer
+				.write_event(
+					&SwapEventBuilder::default()
+						.block_number(15_000_000)
+						.transaction_hash(H256([0xab; 32]))
+						.log_index(7)
+						.direction(SwapDirection::Token1ToToken0)
+						.token0_amount(Decimal::new(1585187, 2))
+						.token1_amount(Decimal::new(1585037, 2))
+						.build(),
+				)
+				.unwrap();
+			writer.write_event(&SwapEventBuilder::default().build()).unwrap();
+
+			let output = String::from_utf8(output).unwrap();
+			let lines = output.lines().collect::<Vec<_>>();
+			assert_eq!(lines.len(), 2);
+
+			let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+			assert_eq!(
+				first,
+				serde_json::json!({
+					"event_type": "swap",
+					"block_number": 15_000_000,
+					"transaction_hash": format!("0x{}", "ab".repeat(32)),
+					"log_index": 7,
+					"timestamp": null,
+					"pool": format!("0x{}", "0".repeat(40)),
+					"sender": format!("0x{}", "0".repeat(40)),
+					"receiver": format!("0x{}", "0".repeat(40)),
+					"direction": "token1_to_token0",
+					"token0": "DAI",
+					"token1": "USDC",
+					"token0_amount": "15851.87",
+					"token1_amount": "15850.37",
+				})
+			);
+
+			let second: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+			assert_eq!(second["block_number"], serde_json::Value::Null);
+			assert_eq!(second["direction"], "token0_to_token1");
+		}
+
+		#[test]
+		fn etherscan_url() {
+			let mut output = Vec::new();
+			let mut writer = JsonOutput::new(&mut output).with_network(Network::Arbitrum);
+			writer
+				.write_event(
+					&SwapEventBuilder::default().transaction_hash(H256([0xab; 32])).build(),
+				)
+				.unwrap();
+			writer.write_event(&SwapEventBuilder::default().build()).unwrap();
+
+			let output = String::from_utf8(output).unwrap();
+			let lines = output.lines().collect::<Vec<_>>();
+			let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+			let second: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+			assert_eq!(
+				first["etherscan_url"],
+				format!("https://arbiscan.io/tx/0x{}", "ab".repeat(32))
+			);
+			assert_eq!(second.get("etherscan_url"), None);
+		}
+
+		#[test]
+		fn swap_cost() {
+			let mut output = Vec::new();
+			let cost = SwapCost {
+				transaction_hash: H256([0xab; 32]),
+				gas_used: 150_000,
+				effective_gas_price: 30_000_000_000,
+			};
+			JsonOutput::new(&mut output).write_cost(&cost).unwrap();
+
+			let record: serde_json::Value = serde_json::from_slice(&output).unwrap();
+			assert_eq!(
+				record,
+				serde_json::json!({
+					"event_type": "swap_cost",
+					"transaction_hash": format!("0x{}", "ab".repeat(32)),
+					"gas_used": 150_000,
+					"effective_gas_price": 30_000_000_000u64,
+				})
+			);
+		}
+	}
+}
@@ -1,122 +1,705 @@
-use anyhow::{anyhow, Context, Result};
-use rust_decimal::{prelude::ToPrimitive, Decimal};
+use anyhow::Context;
+use lru::LruCache;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, num::NonZeroUsize, sync::Arc};
+use tracing::info_span;
 use web3::{
-	ethabi::{Address, Int, LogParam, Token},
-	types::U256,
+	ethabi::{Address, Int, LogParam, Token, Uint},
+	types::{H256, U256},
+	Transport,
 };
 
-use crate::event::{SwapAmounts, SwapDirection, SwapEvent};
+use crate::{
+	event::{SwapAmounts, SwapDirection, SwapEvent, TokenPair},
+	price::{is_valid_tick, MAX_TICK, MIN_TICK},
+	util::u256_to_signed_decimal,
+	v2_parser::V2SwapParser,
+};
+
+pub struct SwapParser;
+
+// Turns the swap logs of one pool protocol into swap events, with the amounts of the pool's pair
+pub trait EventParser {
+	fn parse(
+		&self,
+		log: web3::types::Log,
+		abi: &web3::ethabi::Event,
+		pair: &Arc<TokenPair>,
+	) -> Result<SwapEvent>;
+}
+
+impl EventParser for SwapParser {
+	fn parse(
+		&self,
+		log: web3::types::Log,
+		abi: &web3::ethabi::Event,
+		pair: &Arc<TokenPair>,
+	) -> Result<SwapEvent> {
+		Self::parse_log_only(log, abi, pair)
+	}
+}
+
+// Uniswap version of the monitored pools
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+	V2,
+	#[default]
+	V3,
+}
+
+impl Protocol {
+	pub fn parser(self) -> &'static dyn EventParser {
+		match self {
+			Protocol::V2 => &V2SwapParser,
+			Protocol::V3 => &SwapParser,
+		}
+	}
+}
+
+type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum ParseError {
+	MissingParam { name: &'static str },
+	UnexpectedType { name: &'static str, expected: &'static str, actual: &'static str },
+	ParamIndexOutOfBounds { index: usize, len: usize },
+	InvalidSign { message: String },
+	InvalidAmounts { token0_amount: Decimal, token1_amount: Decimal },
+	InvalidTick(i32),
+	// Raw amount too large for a `Decimal` with the token's decimals
+	AmountOutOfRange(U256),
+	LogParseFailure(web3::ethabi::Error),
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ParseError::MissingParam { name } => write!(f, "Missing log param '{}'", name),
+			ParseError::UnexpectedType { name, expected, actual } => write!(
+				f,
+				"Expected log param '{}' of type '{}' but got '{}'",
+				name, expected, actual
+			),
+			ParseError::ParamIndexOutOfBounds { index, len } =>
+				write!(f, "Log param index {} out of bounds (log has {} params)", index, len),
+			ParseError::InvalidSign { message } => write!(f, "{}", message),
+			ParseError::InvalidAmounts { token0_amount, token1_amount } => write!(
+				f,
+				"Swap amounts must be positive, but got {} and {}",
+				token0_amount, token1_amount
+			),
+			ParseError::InvalidTick(tick) =>
+				write!(f, "Tick {} is out of range [{}, {}]", tick, MIN_TICK, MAX_TICK),
+			ParseError::AmountOutOfRange(amount) =>
+				write!(f, "Amount {:#x} exceeds the decimal range", amount),
+			ParseError::LogParseFailure(err) => write!(f, "Failed to decode log: {}", err),
+		}
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<web3::ethabi::Error> for ParseError {
+	fn from(err: web3::ethabi::Error) -> Self {
+		ParseError::LogParseFailure(err)
+	}
+}
+
+// Parse error along with the block and transaction of the log that caused it
+#[derive(Debug)]
+pub struct ParseContext {
+	pub block_number: Option<u64>,
+	pub tx_hash: Option<H256>,
+	pub error: ParseError,
+}
+
+impl ParseContext {
+	// Wraps an error of parsing the log, taken before the log is consumed by the parser
+	pub fn of(log: &web3::types::Log) -> impl FnOnce(ParseError) -> ParseContext {
+		let block_number = log.block_number.map(|n| n.as_u64());
+		let tx_hash = log.transaction_hash;
+		move |error| ParseContext { block_number, tx_hash, error }
+	}
+}
+
+// The underlying error is the source, e.g. shown after a colon by `{:#}` of `anyhow::Error`
+impl fmt::Display for ParseContext {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Failed to parse swap")?;
+		if let Some(block_number) = self.block_number {
+			write!(f, " in block {}", block_number)?;
+		}
+		// Shortened like 0x1234…abcd
+		if let Some(tx_hash) = self.tx_hash {
+			write!(f, ", tx {}", tx_hash)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for ParseContext {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.error)
+	}
+}
 
-pub(crate) struct SwapParser;
+pub const SEEN_SET_CAPACITY: usize = 10_000;
 
-macro_rules! type_err {
-	($actual:literal, $expected:literal, $name:expr) => {
-		Err(anyhow!("Expected log param '{}' of type '{}' but got '{}'", $name, $expected, $actual))
+// Most recently seen logs, identified by transaction hash and log index
+#[derive(Debug)]
+pub struct SeenSet(LruCache<(H256, u64), ()>);
+
+impl SeenSet {
+	pub fn new(capacity: usize) -> SeenSet {
+		SeenSet(LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)))
+	}
+
+	// Returns `false` if the log has been seen before. Logs without transaction hash or log
+	// index can't be told apart, so they are always new.
+	pub fn insert(&mut self, transaction_hash: Option<H256>, log_index: Option<u64>) -> bool {
+		match (transaction_hash, log_index) {
+			(Some(transaction_hash), Some(log_index)) =>
+				self.0.put((transaction_hash, log_index), ()).is_none(),
+			_ => true,
+		}
+	}
+}
+
+impl Default for SeenSet {
+	fn default() -> Self {
+		SeenSet::new(SEEN_SET_CAPACITY)
+	}
+}
+
+macro_rules! type_err_token {
+	($token:expr, $expected:literal, $name:expr) => {
+		Err(ParseError::UnexpectedType {
+			name: $name,
+			expected: $expected,
+			actual: token_type_name($token),
+		})
 	};
 }
 
+// Name of the token's variant, as used in type errors
+fn token_type_name(token: &Token) -> &'static str {
+	match token {
+		Token::Address(_) => "Address",
+		Token::FixedBytes(_) => "FixedBytes",
+		Token::Bytes(_) => "Bytes",
+		Token::Int(_) => "Int",
+		Token::Uint(_) => "Uint",
+		Token::Bool(_) => "Bool",
+		Token::String(_) => "String",
+		Token::FixedArray(_) => "FixedArray",
+		Token::Array(_) => "Array",
+		Token::Tuple(_) => "Tuple",
+	}
+}
+
 impl SwapParser {
-	const DECIMAL_PRECISION: u32 = 2;
-	const DAI_BASE: u32 = 18;
-	const USDC_BASE: u32 = 6;
+	pub(crate) const DECIMAL_PRECISION: u32 = 2;
+
+	// Takes everything but the block timestamp from the log, amounts are scaled by the decimals of
+	// the pool's pair
+	pub fn parse_log_only(
+		log: web3::types::Log,
+		abi: &web3::ethabi::Event,
+		pair: &Arc<TokenPair>,
+	) -> Result<SwapEvent> {
+		let block_number = log.block_number.map(|n| n.as_u64());
+		let transaction_hash = log.transaction_hash;
+		let log_index = log.log_index.map(|i| i.as_u64());
+		let pool = log.address;
 
-	pub(crate) fn parse(log: web3::types::Log, abi: &web3::ethabi::Event) -> Result<SwapEvent> {
 		let log = web3::ethabi::RawLog { topics: log.topics, data: log.data.0 };
 		let log = &abi.parse_log(log)?;
 
 		let sender = Self::get_address(log, "sender")?;
 		let receiver = Self::get_address(log, "recipient")?;
-		let dai = Self::get_int(log, "amount0")?;
-		let usdc = Self::get_int(log, "amount1")?;
+		let amount0 = Self::get_int(log, "amount0")?;
+		let amount1 = Self::get_int(log, "amount1")?;
+		let sqrt_price_x96 = Self::get_uint(log, "sqrtPriceX96")?;
+		let liquidity = Self::get_uint(log, "liquidity")?.low_u128();
+		// int24, the low 32 bits of its two's complement keep the sign
+		let tick = Self::get_int(log, "tick")?.low_u32() as i32;
+		// int24 covers more than the valid ticks, others come from corrupt logs
+		if !is_valid_tick(tick) {
+			return Err(ParseError::InvalidTick(tick));
+		}
 
 		let amounts = SwapAmounts {
-			dai: Self::to_decimal(dai, Self::DAI_BASE),
-			usdc: Self::to_decimal(usdc, Self::USDC_BASE),
+			token0_amount: Self::to_decimal(amount0, pair.token0.decimals)?,
+			token1_amount: Self::to_decimal(amount1, pair.token1.decimals)?,
+			pair: pair.clone(),
 		};
 
+		// Zero amounts have a positive sign, so they are checked before the direction
+		Self::validate(&amounts.abs())?;
+
 		let event = SwapEvent {
+			block_number,
+			transaction_hash,
+			log_index,
+			timestamp: None,
+			pool,
 			sender,
 			receiver,
 			direction: Self::get_direction(&amounts)?,
 			amounts: amounts.abs(),
+			sqrt_price_x96,
+			liquidity,
+			tick,
 		};
 
 		Ok(event)
 	}
 
-	fn get_direction(amounts: &SwapAmounts) -> Result<SwapDirection> {
-		let dai_pos = amounts.dai.is_sign_positive();
-		let usdc_pos = amounts.usdc.is_sign_positive();
+	// The log doesn't carry the timestamp of its block, it has to be looked up separately
+	pub fn parse_with_context(
+		log: web3::types::Log,
+		abi: &web3::ethabi::Event,
+		pair: &Arc<TokenPair>,
+		block_timestamp: u64,
+	) -> Result<SwapEvent, ParseContext> {
+		let context = ParseContext::of(&log);
+		let event = Self::parse_log_only(log, abi, pair).map_err(context)?;
+		Ok(SwapEvent { timestamp: Some(block_timestamp), ..event })
+	}
+
+	// Fails on the first log that can't be parsed
+	pub fn parse_batch(
+		logs: Vec<web3::types::Log>,
+		abi: &web3::ethabi::Event,
+		pair: &Arc<TokenPair>,
+	) -> Result<Vec<SwapEvent>> {
+		logs.into_iter()
+			.map(|log| {
+				let _entered = info_span!("parse_log", log_index = ?log.log_index).entered();
+				Self::parse_log_only(log, abi, pair)
+			})
+			.collect()
+	}
+
+	// Swaps of the given pools in a transaction, in log order, each parsed with its pool's tokens.
+	// Meant for debugging single transactions, the monitor itself reads logs by block.
+	pub async fn parse_transaction(
+		tx_hash: H256,
+		provider: &web3::Web3<impl Transport>,
+		abi: &web3::ethabi::Event,
+		pairs: &HashMap<Address, Arc<TokenPair>>,
+	) -> anyhow::Result<Vec<SwapEvent>> {
+		let receipt = provider
+			.eth()
+			.transaction_receipt(tx_hash)
+			.await
+			.with_context(|| format!("Failed to read receipt of {:?}", tx_hash))?
+			.with_context(|| format!("Transaction {:?} not found", tx_hash))?;
+
+		let swap_topic = abi.signature();
+		let events = receipt
+			.logs
+			.into_iter()
+			.filter(|log| log.topics.first() == Some(&swap_topic))
+			.filter_map(|log| Some((pairs.get(&log.address)?, log)))
+			.map(|(pair, log)| {
+				let context = ParseContext::of(&log);
+				Self::parse_log_only(log, abi, pair).map_err(context)
+			})
+			.collect::<Result<_, _>>()?;
+		Ok(events)
+	}
+
+	pub(crate) fn get_direction(amounts: &SwapAmounts) -> Result<SwapDirection> {
+		let token0_pos = amounts.token0_amount.is_sign_positive();
+		let token1_pos = amounts.token1_amount.is_sign_positive();
 
-		match (dai_pos, usdc_pos) {
-			(true, false) => Ok(SwapDirection::DaiToUsdc),
-			(false, true) => Ok(SwapDirection::UsdcToDai),
-			(true, true) =>
-				Err(anyhow!("Swap amounts must have distinct signs, but both are positive")),
-			(false, false) =>
-				Err(anyhow!("Swap amounts must have distinct signs, but both are negative")),
+		match (token0_pos, token1_pos) {
+			(true, false) => Ok(SwapDirection::Token0ToToken1),
+			(false, true) => Ok(SwapDirection::Token1ToToken0),
+			(true, true) => Err(ParseError::InvalidSign {
+				message: "Swap amounts must have distinct signs, but both are positive".to_string(),
+			}),
+			(false, false) => Err(ParseError::InvalidSign {
+				message: "Swap amounts must have distinct signs, but both are negative".to_string(),
+			}),
 		}
 	}
 
-	fn get_address<'a>(log: &'a web3::ethabi::Log, name: &'static str) -> Result<Address> {
-		match Self::get_param(log, name)?.value {
-			Token::Address(address) => Ok(address),
-			Token::FixedBytes(_) => type_err!("FixedBytes", "Address", name),
-			Token::Bytes(_) => type_err!("Bytes", "Address", name),
-			Token::Int(_) => type_err!("Int", "Address", name),
-			Token::Uint(_) => type_err!("Uint", "Address", name),
-			Token::Bool(_) => type_err!("Bool", "Address", name),
-			Token::String(_) => type_err!("String", "Address", name),
-			Token::FixedArray(_) => type_err!("FixedArray", "Address", name),
-			Token::Array(_) => type_err!("Array", "Address", name),
-			Token::Tuple(_) => type_err!("Tuple", "Address", name),
+	// Sanity check of the absolute amounts of a swap
+	pub(crate) fn validate(amounts: &SwapAmounts) -> Result<()> {
+		match amounts.is_valid() {
+			true => Ok(()),
+			false => Err(ParseError::InvalidAmounts {
+				token0_amount: amounts.token0_amount,
+				token1_amount: amounts.token1_amount,
+			}),
 		}
 	}
 
-	fn get_int<'a>(log: &'a web3::ethabi::Log, name: &'static str) -> Result<Int> {
-		match Self::get_param(log, name)?.value {
-			Token::Int(int) => Ok(int),
-			Token::Address(_) => type_err!("Address", "Int", name),
-			Token::FixedBytes(_) => type_err!("FixedBytes", "Int", name),
-			Token::Bytes(_) => type_err!("Bytes", "Int", name),
-			Token::Uint(_) => type_err!("Uint", "Int", name),
-			Token::Bool(_) => type_err!("Bool", "Int", name),
-			Token::String(_) => type_err!("String", "Int", name),
-			Token::FixedArray(_) => type_err!("FixedArray", "Int", name),
-			Token::Array(_) => type_err!("Array", "Int", name),
-			Token::Tuple(_) => type_err!("Tuple", "Int", name),
+	pub(crate) fn get_address(log: &web3::ethabi::Log, name: &'static str) -> Result<Address> {
+		match &Self::get_param(log, name)?.value {
+			Token::Address(address) => Ok(*address),
+			token => type_err_token!(token, "Address", name),
 		}
 	}
 
-	fn get_param<'a>(log: &'a web3::ethabi::Log, name: &'static str) -> Result<&'a LogParam> {
-		log.params
-			.iter()
-			.find(|p| p.name == name)
-			.with_context(|| format!("Missing log param '{}'", name))
+	pub(crate) fn get_int(log: &web3::ethabi::Log, name: &'static str) -> Result<Int> {
+		match &Self::get_param(log, name)?.value {
+			Token::Int(int) => Ok(*int),
+			token => type_err_token!(token, "Int", name),
+		}
 	}
 
-	fn to_decimal(n: U256, base: u32) -> Decimal {
-		let dp = Self::DECIMAL_PRECISION;
+	pub(crate) fn get_uint(log: &web3::ethabi::Log, name: &'static str) -> Result<Uint> {
+		match &Self::get_param(log, name)?.value {
+			Token::Uint(uint) => Ok(*uint),
+			token => type_err_token!(token, "Uint", name),
+		}
+	}
 
-		let base = base - dp;
-		let base = U256::from(10).pow(U256::from(base));
+	// Public for parsers of other events, none of the events parsed here has string params
+	pub fn get_string(log: &web3::ethabi::Log, name: &'static str) -> Result<String> {
+		match &Self::get_param(log, name)?.value {
+			Token::String(string) => Ok(string.clone()),
+			token => type_err_token!(token, "String", name),
+		}
+	}
 
-		let is_negative = n > U256::from(u128::MAX);
+	// Public for parsers of router events, which encode multi-hop paths as arrays
+	pub fn get_array(log: &web3::ethabi::Log, name: &'static str) -> Result<Vec<Token>> {
+		match &Self::get_param(log, name)?.value {
+			Token::Array(tokens) | Token::FixedArray(tokens) => Ok(tokens.clone()),
+			token => type_err_token!(token, "Array", name),
+		}
+	}
 
-		let n = if is_negative { U256::MAX - n } else { n };
+	fn get_param<'a>(log: &'a web3::ethabi::Log, name: &'static str) -> Result<&'a LogParam> {
+		log.params
+			.iter()
+			.find(|p| p.name == name)
+			.ok_or(ParseError::MissingParam { name })
+	}
 
-		let n: U256 = n / base;
-		let n = n.as_u128().to_i128().unwrap();
-		let n = if is_negative { n * -1 } else { n };
+	// Public for parsers of ABIs without param names, the parsed events are looked up by name
+	pub fn get_param_by_index(log: &web3::ethabi::Log, index: usize) -> Result<&LogParam> {
+		log.params
+			.get(index)
+			.ok_or(ParseError::ParamIndexOutOfBounds { index, len: log.params.len() })
+	}
 
-		let n = Decimal::from_i128_with_scale(n, dp);
-		n
+	// Swap amounts are int256
+	fn to_decimal(n: U256, base: u32) -> Result<Decimal> {
+		u256_to_signed_decimal(n, 256, base, Self::DECIMAL_PRECISION)
+			.ok_or(ParseError::AmountOutOfRange(n))
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::{
+		event::TokenInfo,
+		testing::{swap_event_abi, SwapLogBuilder},
+	};
+
+	mod parse_log_only {
+		use super::*;
+
+		mod ok {
+			use super::*;
+
+			#[test]
+			fn token0_to_token1() {
+				let abi = swap_event_abi();
+				let log = SwapLogBuilder::new(&abi)
+					.address(Address::from([4; 20]))
+					.block_number(123)
+					.transaction_hash(H256::from([5; 32]))
+					.log_index(7)
+					.sender(Address::from([1; 20]))
+					.recipient(Address::from([2; 20]))
+					.amount0(1_234_560_000_000_000_000_000)
+					.amount1(-1_233_000_001)
+					.sqrt_price_x96(U256::from_dec_str("79224201403219477170569").unwrap())
+					.liquidity(5_000_000_000_000_000)
+					.tick(-276_325)
+					.build();
+
+				let event = SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()).unwrap();
+
+				assert_eq!(
+					event,
+					SwapEvent {
+						block_number: Some(123),
+						transaction_hash: Some(H256::from([5; 32])),
+						log_index: Some(7),
+						timestamp: None,
+						pool: Address::from([4; 20]),
+						sender: Address::from([1; 20]),
+						receiver: Address::from([2; 20]),
+						direction: SwapDirection::Token0ToToken1,
+						amounts: SwapAmounts::dai_usdc(
+							Decimal::new(123456, 2),
+							Decimal::new(123300, 2)
+						),
+						sqrt_price_x96: U256::from_dec_str("79224201403219477170569").unwrap(),
+						liquidity: 5_000_000_000_000_000,
+						tick: -276_325,
+					}
+				);
+			}
+
+			#[test]
+			fn token1_to_token0() {
+				let abi = swap_event_abi();
+				let log = SwapLogBuilder::new(&abi)
+					.amount0(-500_000_000_000_000_000_000)
+					.amount1(500_100_000)
+					.build();
+
+				let event = SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()).unwrap();
+
+				assert_eq!(event.direction, SwapDirection::Token1ToToken0);
+				assert_eq!(event.amounts.token1_amount, Decimal::new(50010, 2));
+			}
+
+			#[test]
+			fn pool_decimals() {
+				let abi = swap_event_abi();
+				let pair = Arc::new(TokenPair {
+					token0: TokenInfo { symbol: "USDC".to_string(), decimals: 6 },
+					token1: TokenInfo { symbol: "WETH".to_string(), decimals: 18 },
+				});
+				let log = SwapLogBuilder::new(&abi)
+					.amount0(3_000_000_000)
+					.amount1(-1_000_000_000_000_000_000)
+					.build();
+
+				let event = SwapParser::parse_log_only(log, &abi, &pair).unwrap();
+
+				assert_eq!(event.direction, SwapDirection::Token0ToToken1);
+				assert_eq!(
+					event.amounts,
+					SwapAmounts {
+						token0_amount: Decimal::new(300000, 2),
+						token1_amount: Decimal::new(100, 2),
+						pair,
+					}
+				);
+			}
+
+			#[test]
+			fn batch() {
+				let abi = swap_event_abi();
+				let logs = (0..3).map(|i| SwapLogBuilder::new(&abi).log_index(i).build()).collect();
+
+				let events = SwapParser::parse_batch(logs, &abi, &TokenPair::dai_usdc()).unwrap();
+
+				assert_eq!(
+					events.iter().map(|event| event.log_index).collect::<Vec<_>>(),
+					vec![Some(0), Some(1), Some(2)]
+				);
+			}
+		}
+
+		mod err {
+			use super::*;
+
+			#[test]
+			fn same_signs() {
+				let abi = swap_event_abi();
+				let log = SwapLogBuilder::new(&abi)
+					.amount0(1_000_000_000_000_000_000)
+					.amount1(1_000_000)
+					.build();
+
+				assert!(matches!(
+					SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()),
+					Err(ParseError::InvalidSign { .. })
+				));
+			}
+
+			// Amounts below the display precision are truncated to zero
+			#[test]
+			fn zero_amount() {
+				let abi = swap_event_abi();
+				let log = SwapLogBuilder::new(&abi)
+					.amount0(1_000_000_000_000_000_000)
+					.amount1(-1_000)
+					.build();
+
+				let err =
+					SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()).unwrap_err();
+
+				assert_eq!(err.to_string(), "Swap amounts must be positive, but got 1.00 and 0.00");
+			}
+
+			#[test]
+			fn tick_out_of_range() {
+				let abi = swap_event_abi();
+				let log = SwapLogBuilder::new(&abi)
+					.amount0(1_000_000_000_000_000_000)
+					.amount1(-999_000)
+					.tick(-887_273)
+					.build();
+
+				let err =
+					SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()).unwrap_err();
+
+				assert!(matches!(err, ParseError::InvalidTick(-887_273)));
+				assert_eq!(err.to_string(), "Tick -887273 is out of range [-887272, 887272]");
+			}
+
+			#[test]
+			fn truncated_data() {
+				let abi = swap_event_abi();
+				let mut log = SwapLogBuilder::new(&abi).build();
+				log.data.0.truncate(64);
+
+				assert!(matches!(
+					SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()),
+					Err(ParseError::LogParseFailure(_))
+				));
+			}
+		}
+	}
+
+	mod parse_with_context {
+		use super::*;
+
+		#[test]
+		fn timestamp() {
+			let abi = swap_event_abi();
+			let log = SwapLogBuilder::new(&abi).block_number(123).log_index(7).build();
+
+			let event = SwapParser::parse_with_context(
+				log.clone(),
+				&abi,
+				&TokenPair::dai_usdc(),
+				1_700_000_000,
+			)
+			.unwrap();
+
+			assert_eq!(
+				event,
+				SwapEvent {
+					timestamp: Some(1_700_000_000),
+					..SwapParser::parse_log_only(log, &abi, &TokenPair::dai_usdc()).unwrap()
+				}
+			);
+		}
+
+		#[test]
+		fn invalid_log() {
+			let abi = swap_event_abi();
+			let log = SwapLogBuilder::new(&abi)
+				.block_number(18_234_567)
+				.transaction_hash(H256::from_low_u64_be(0xabcd))
+				.amount0(1)
+				.amount1(1)
+				.build();
+
+			let err =
+				SwapParser::parse_with_context(log, &abi, &TokenPair::dai_usdc(), 1_700_000_000)
+					.unwrap_err();
+
+			assert_eq!(err.block_number, Some(18_234_567));
+			assert!(matches!(err.error, ParseError::InvalidAmounts { .. }));
+			assert_eq!(
+				format!("{:#}", anyhow::Error::from(err)),
+				"Failed to parse swap in block 18234567, tx 0x0000…abcd: Swap amounts must be \
+				 positive, but got 0.00 and 0.00"
+			);
+		}
+	}
+
+	mod parse_transaction {
+		use super::*;
+		use crate::{
+			erc20::transfer_event_abi,
+			testing::{transfer_log, MockTransport},
+		};
+		use web3::{types::TransactionReceipt, Web3};
+
+		#[tokio::test]
+		async fn swap_logs() {
+			let abi = swap_event_abi();
+			let tx_hash = H256::from([7; 32]);
+			let usdc_weth = Arc::new(TokenPair {
+				token0: TokenInfo { symbol: "USDC".to_string(), decimals: 6 },
+				token1: TokenInfo { symbol: "WETH".to_string(), decimals: 18 },
+			});
+			let pairs = HashMap::from([
+				(Address::repeat_byte(4), TokenPair::dai_usdc()),
+				(Address::repeat_byte(5), usdc_weth.clone()),
+			]);
+			let swap = |log_index, pool| {
+				SwapLogBuilder::new(&abi)
+					.address(pool)
+					.transaction_hash(tx_hash)
+					.log_index(log_index)
+					.amount0(1_000_000_000_000_000_000)
+					.amount1(-999_000)
+					.build()
+			};
+			let transfer = transfer_log(
+				&transfer_event_abi().unwrap(),
+				Address::repeat_byte(1),
+				Address::repeat_byte(2),
+				Address::repeat_byte(3),
+				1_000,
+			);
+			let transport = MockTransport::default().with_receipt(TransactionReceipt {
+				transaction_hash: tx_hash,
+				logs: vec![
+					transfer,
+					swap(1, Address::repeat_byte(4)),
+					// Swaps of other pools are skipped
+					swap(2, Address::repeat_byte(6)),
+					SwapLogBuilder::new(&abi)
+						.address(Address::repeat_byte(5))
+						.transaction_hash(tx_hash)
+						.log_index(3)
+						.amount0(1_000_000_000)
+						.amount1(-500_000_000_000_000_000)
+						.build(),
+				],
+				..Default::default()
+			});
+
+			let events =
+				SwapParser::parse_transaction(tx_hash, &Web3::new(transport), &abi, &pairs)
+					.await
+					.unwrap();
+
+			assert_eq!(events.len(), 2);
+			assert_eq!(events[0].log_index, Some(1));
+			assert_eq!(events[0].amounts.pair, TokenPair::dai_usdc());
+			assert_eq!(events[1].log_index, Some(3));
+			assert_eq!(events[1].transaction_hash, Some(tx_hash));
+			assert_eq!(events[1].amounts.pair, usdc_weth);
+			assert_eq!(events[1].amounts.token0_amount, Decimal::from(1_000));
+		}
+
+		#[tokio::test]
+		async fn unknown_transaction() {
+			let tx_hash = H256::from([7; 32]);
+
+			let err = SwapParser::parse_transaction(
+				tx_hash,
+				&Web3::new(MockTransport::default()),
+				&swap_event_abi(),
+				&HashMap::new(),
+			)
+			.await
+			.unwrap_err();
+
+			assert_eq!(err.to_string(), format!("Transaction {:?} not found", tx_hash));
+		}
+	}
 
 	mod get_direction {
 		use super::*;
@@ -125,25 +708,25 @@ mod tests {
 			use super::*;
 
 			#[test]
-			fn dai_to_usdc() {
+			fn token0_to_token1() {
 				let dai = Decimal::new(12345, 2);
 				let usdc = Decimal::new(-678, 2);
-				let amounts = SwapAmounts { dai, usdc };
+				let amounts = SwapAmounts::dai_usdc(dai, usdc);
 				let result = SwapParser::get_direction(&amounts);
 
 				assert!(result.is_ok());
-				assert_eq!(result.unwrap(), SwapDirection::DaiToUsdc);
+				assert_eq!(result.unwrap(), SwapDirection::Token0ToToken1);
 			}
 
 			#[test]
-			fn usdc_to_dai() {
+			fn token1_to_token0() {
 				let dai = Decimal::new(-1234, 2);
 				let usdc = Decimal::new(6678, 2);
-				let amounts = SwapAmounts { dai, usdc };
+				let amounts = SwapAmounts::dai_usdc(dai, usdc);
 				let result = SwapParser::get_direction(&amounts);
 
 				assert!(result.is_ok());
-				assert_eq!(result.unwrap(), SwapDirection::UsdcToDai);
+				assert_eq!(result.unwrap(), SwapDirection::Token1ToToken0);
 			}
 		}
 
@@ -154,7 +737,7 @@ mod tests {
 			fn both_positive() {
 				let dai = Decimal::new(12345, 2);
 				let usdc = Decimal::new(6789, 2);
-				let amounts = SwapAmounts { dai, usdc };
+				let amounts = SwapAmounts::dai_usdc(dai, usdc);
 				let result = SwapParser::get_direction(&amounts);
 
 				assert!(result.is_err());
@@ -168,7 +751,7 @@ mod tests {
 			fn both_negative() {
 				let dai = Decimal::new(-1234, 2);
 				let usdc = Decimal::new(-567, 2);
-				let amounts = SwapAmounts { dai, usdc };
+				let amounts = SwapAmounts::dai_usdc(dai, usdc);
 				let result = SwapParser::get_direction(&amounts);
 
 				assert!(result.is_err());
@@ -180,6 +763,48 @@ mod tests {
 		}
 	}
 
+	mod seen_set {
+		use super::*;
+
+		#[test]
+		fn evicts_least_recent() {
+			let mut seen = SeenSet::new(2);
+			let hash = Some(H256::from([5; 32]));
+
+			assert!(seen.insert(hash, Some(1)));
+			assert!(seen.insert(hash, Some(2)));
+			assert!(!seen.insert(hash, Some(1)));
+			assert!(seen.insert(hash, Some(3)));
+
+			assert!(seen.insert(hash, Some(2)));
+			assert!(!seen.insert(hash, Some(3)));
+		}
+	}
+
+	mod token_type_name {
+		use super::*;
+
+		#[test]
+		fn variants() {
+			let names = [
+				(Token::Address([0; 20].into()), "Address"),
+				(Token::FixedBytes(vec![]), "FixedBytes"),
+				(Token::Bytes(vec![]), "Bytes"),
+				(Token::Int(0.into()), "Int"),
+				(Token::Uint(0.into()), "Uint"),
+				(Token::Bool(true), "Bool"),
+				(Token::String(String::new()), "String"),
+				(Token::FixedArray(vec![]), "FixedArray"),
+				(Token::Array(vec![]), "Array"),
+				(Token::Tuple(vec![]), "Tuple"),
+			];
+
+			for (token, name) in names {
+				assert_eq!(token_type_name(&token), name);
+			}
+		}
+	}
+
 	mod get_address {
 		use super::*;
 
@@ -271,7 +896,14 @@ mod tests {
 				};
 				let result = SwapParser::get_int(&log, "foo");
 
-				assert!(result.is_err());
+				assert!(matches!(
+					result,
+					Err(ParseError::UnexpectedType {
+						name: "foo",
+						expected: "Int",
+						actual: "Address"
+					})
+				));
 				assert_eq!(
 					result.unwrap_err().to_string(),
 					"Expected log param 'foo' of type 'Int' but got 'Address'".to_string()
@@ -280,6 +912,136 @@ mod tests {
 		}
 	}
 
+	mod get_string {
+		use super::*;
+
+		#[test]
+		fn ok() {
+			let log = web3::ethabi::Log {
+				params: vec![LogParam {
+					name: "foo".to_string(),
+					value: Token::String("DAI".to_string()),
+				}],
+			};
+			let result = SwapParser::get_string(&log, "foo");
+
+			assert!(result.is_ok());
+			assert_eq!(result.unwrap(), "DAI".to_string());
+		}
+
+		mod err {
+			use super::*;
+
+			#[test]
+			fn missing() {
+				let log = web3::ethabi::Log {
+					params: vec![LogParam {
+						name: "bar".to_string(),
+						value: Token::String("DAI".to_string()),
+					}],
+				};
+				let result = SwapParser::get_string(&log, "foo");
+
+				assert!(result.is_err());
+				assert_eq!(result.unwrap_err().to_string(), "Missing log param 'foo'".to_string());
+			}
+
+			#[test]
+			fn unexpected_type() {
+				let log = web3::ethabi::Log {
+					params: vec![LogParam { name: "foo".to_string(), value: Token::Bool(true) }],
+				};
+				let result = SwapParser::get_string(&log, "foo");
+
+				assert!(result.is_err());
+				assert_eq!(
+					result.unwrap_err().to_string(),
+					"Expected log param 'foo' of type 'String' but got 'Bool'".to_string()
+				);
+			}
+		}
+	}
+
+	mod get_array {
+		use super::*;
+
+		#[test]
+		fn ok() {
+			let tokens = vec![Token::Address([1; 20].into()), Token::Address([2; 20].into())];
+
+			for value in [Token::Array(tokens.clone()), Token::FixedArray(tokens.clone())] {
+				let log =
+					web3::ethabi::Log { params: vec![LogParam { name: "foo".to_string(), value }] };
+
+				assert_eq!(SwapParser::get_array(&log, "foo").unwrap(), tokens);
+			}
+		}
+
+		mod err {
+			use super::*;
+
+			#[test]
+			fn missing() {
+				let log = web3::ethabi::Log {
+					params: vec![LogParam { name: "bar".to_string(), value: Token::Array(vec![]) }],
+				};
+				let result = SwapParser::get_array(&log, "foo");
+
+				assert!(result.is_err());
+				assert_eq!(result.unwrap_err().to_string(), "Missing log param 'foo'".to_string());
+			}
+
+			#[test]
+			fn unexpected_type() {
+				let log = web3::ethabi::Log {
+					params: vec![LogParam { name: "foo".to_string(), value: Token::Tuple(vec![]) }],
+				};
+				let result = SwapParser::get_array(&log, "foo");
+
+				assert!(result.is_err());
+				assert_eq!(
+					result.unwrap_err().to_string(),
+					"Expected log param 'foo' of type 'Array' but got 'Tuple'".to_string()
+				);
+			}
+		}
+	}
+
+	mod get_param_by_index {
+		use super::*;
+
+		fn log() -> web3::ethabi::Log {
+			web3::ethabi::Log {
+				params: vec![
+					LogParam { name: String::new(), value: Token::Bool(true) },
+					LogParam { name: String::new(), value: Token::Uint(5.into()) },
+				],
+			}
+		}
+
+		#[test]
+		fn ok() {
+			let log = log();
+
+			assert_eq!(
+				SwapParser::get_param_by_index(&log, 1).unwrap().value,
+				Token::Uint(5.into())
+			);
+		}
+
+		#[test]
+		fn out_of_bounds() {
+			let log = log();
+			let result = SwapParser::get_param_by_index(&log, 5);
+
+			assert!(result.is_err());
+			assert_eq!(
+				result.unwrap_err().to_string(),
+				"Log param index 5 out of bounds (log has 2 params)".to_string()
+			);
+		}
+	}
+
 	mod to_decimal {
 		use super::*;
 
@@ -288,7 +1050,7 @@ mod tests {
 			let dai_int = U256::from_dec_str("15851874999999999770624").unwrap();
 			let dai_dec = Decimal::new(1585187, SwapParser::DECIMAL_PRECISION);
 
-			assert_eq!(dai_dec, SwapParser::to_decimal(dai_int, SwapParser::DAI_BASE));
+			assert_eq!(dai_dec, SwapParser::to_decimal(dai_int, 18).unwrap());
 		}
 
 		#[test]
@@ -299,7 +1061,49 @@ mod tests {
 			.unwrap();
 			let usdc_dec = Decimal::new(-1585037, SwapParser::DECIMAL_PRECISION);
 
-			assert_eq!(usdc_dec, SwapParser::to_decimal(usdc_int, SwapParser::USDC_BASE));
+			assert_eq!(usdc_dec, SwapParser::to_decimal(usdc_int, 6).unwrap());
+		}
+
+		#[test]
+		fn negative_exact() {
+			let usdc_int = U256::MAX - 9_999;
+			let usdc_dec = Decimal::new(-1, SwapParser::DECIMAL_PRECISION);
+
+			assert_eq!(usdc_dec, SwapParser::to_decimal(usdc_int, 6).unwrap());
+		}
+
+		#[test]
+		fn int128_bounds() {
+			for n in [i128::MIN, i128::MAX] {
+				let int = crate::testing::int256(n);
+				let dec = Decimal::from_i128_with_scale(n / 10i128.pow(16), 2);
+
+				assert_eq!(dec, SwapParser::to_decimal(int, 18).unwrap());
+			}
+		}
+
+		// With 6 decimals the int128 bounds exceed the 96 bit mantissa of a `Decimal`
+		#[test]
+		fn int128_bounds_of_6_decimals() {
+			for n in [i128::MIN, i128::MAX] {
+				let int = crate::testing::int256(n);
+
+				assert_eq!(
+					SwapParser::to_decimal(int, 6).unwrap_err().to_string(),
+					format!("Amount {:#x} exceeds the decimal range", int)
+				);
+			}
+		}
+
+		// Swap amounts are int256, so a log can carry magnitudes beyond int128
+		#[test]
+		fn beyond_int128() {
+			for int in [U256::one() << 200, U256::MAX - (U256::one() << 200) + 1] {
+				assert_eq!(
+					SwapParser::to_decimal(int, 18).unwrap_err().to_string(),
+					format!("Amount {:#x} exceeds the decimal range", int)
+				);
+			}
 		}
 	}
 }
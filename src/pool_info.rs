@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::{hash_map::Entry, HashMap};
+use web3::{
+	ethabi::{Contract, Token},
+	types::{Address, Bytes, CallRequest, U256},
+	Transport, Web3,
+};
+
+use crate::retry::RetryPolicy;
+
+const POOL_ABI: &[u8] = include_bytes!("contracts/uniswap_pool_abi.json");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolInfo {
+	// Fee in hundredths of a basis point, e.g. 100 for 0.01%
+	pub fee_tier: u32,
+	// Price and tick as of the time the pool was first looked up
+	pub sqrt_price_x96: U256,
+	pub tick: i32,
+}
+
+// Reads pool parameters from the pool contracts, every pool is only read once
+#[derive(Debug)]
+pub struct PoolInfoCache<T: Transport> {
+	web3: Web3<T>,
+	abi: Contract,
+	pools: HashMap<Address, PoolInfo>,
+}
+
+impl<T: Transport> PoolInfoCache<T> {
+	pub fn new(web3: Web3<T>) -> Result<PoolInfoCache<T>> {
+		Ok(PoolInfoCache { web3, abi: Contract::load(POOL_ABI)?, pools: HashMap::new() })
+	}
+
+	pub async fn get(&mut self, pool: Address) -> Result<&PoolInfo> {
+		let PoolInfoCache { web3, abi, pools } = self;
+
+		match pools.entry(pool) {
+			Entry::Occupied(entry) => Ok(entry.into_mut()),
+			Entry::Vacant(entry) => {
+				let info = Self::fetch(web3, abi, pool)
+					.await
+					.with_context(|| format!("Failed to read pool info of {:?}", pool))?;
+				Ok(entry.insert(info))
+			},
+		}
+	}
+
+	// Tokens are read on every call, unlike the cached pool info. Uniswap V2 pairs have the same
+	// token getters.
+	pub async fn tokens(&self, pool: Address) -> Result<(Address, Address)> {
+		let tokens = async {
+			let token0 = Self::call(&self.web3, &self.abi, pool, "token0").await?;
+			let token1 = Self::call(&self.web3, &self.abi, pool, "token1").await?;
+
+			match (token0.as_slice(), token1.as_slice()) {
+				([Token::Address(token0)], [Token::Address(token1)]) => Ok((*token0, *token1)),
+				_ => Err(anyhow!("Unexpected output of pool contract")),
+			}
+		};
+
+		tokens.await.with_context(|| format!("Failed to read tokens of {:?}", pool))
+	}
+
+	async fn fetch(web3: &Web3<T>, abi: &Contract, pool: Address) -> Result<PoolInfo> {
+		let fee = Self::call(web3, abi, pool, "fee").await?;
+		let slot0 = Self::call(web3, abi, pool, "slot0").await?;
+
+		// Both are 24 bit values, the low 32 bits of their two's complement keep the sign
+		match (fee.as_slice(), slot0.as_slice()) {
+			([Token::Uint(fee)], [Token::Uint(sqrt_price_x96), Token::Int(tick), ..]) =>
+				Ok(PoolInfo {
+					fee_tier: fee.low_u32(),
+					sqrt_price_x96: *sqrt_price_x96,
+					tick: tick.low_u32() as i32,
+				}),
+			_ => Err(anyhow!("Unexpected output of pool contract")),
+		}
+	}
+
+	async fn call(web3: &Web3<T>, abi: &Contract, pool: Address, name: &str) -> Result<Vec<Token>> {
+		let function = abi.function(name)?;
+		let request = CallRequest {
+			to: Some(pool),
+			data: Some(Bytes(function.encode_input(&[])?)),
+			..CallRequest::default()
+		};
+
+		let output = RetryPolicy::default()
+			.execute(|| async { Ok(web3.eth().call(request.clone(), None).await?) })
+			.await?;
+		Ok(function.decode_output(&output.0)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::MockTransport;
+	use web3::ethabi;
+
+	fn transport(pool: Address) -> MockTransport {
+		let abi = Contract::load(POOL_ABI).unwrap();
+		let selector = |name| abi.function(name).unwrap().short_signature();
+
+		MockTransport::default()
+			.with_call(pool, selector("fee"), ethabi::encode(&[Token::Uint(100.into())]))
+			.with_call(
+				pool,
+				selector("token0"),
+				ethabi::encode(&[Token::Address(Address::from([1; 20]))]),
+			)
+			.with_call(
+				pool,
+				selector("token1"),
+				ethabi::encode(&[Token::Address(Address::from([2; 20]))]),
+			)
+			.with_call(
+				pool,
+				selector("slot0"),
+				ethabi::encode(&[
+					Token::Uint(U256::from_dec_str("79228162514264337593543").unwrap()),
+					Token::Int(U256::MAX - 276_323),
+					Token::Uint(1.into()),
+					Token::Uint(1.into()),
+					Token::Uint(1.into()),
+					Token::Uint(0.into()),
+					Token::Bool(true),
+				]),
+			)
+	}
+
+	mod get {
+		use super::*;
+
+		#[tokio::test]
+		async fn reads_once() {
+			let pool = Address::from([4; 20]);
+			let transport = transport(pool);
+			let mut cache = PoolInfoCache::new(Web3::new(transport.clone())).unwrap();
+
+			let expected = PoolInfo {
+				fee_tier: 100,
+				sqrt_price_x96: U256::from_dec_str("79228162514264337593543").unwrap(),
+				tick: -276_324,
+			};
+			assert_eq!(cache.get(pool).await.unwrap(), &expected);
+			assert_eq!(cache.get(pool).await.unwrap(), &expected);
+			assert_eq!(transport.call_count(), 2);
+		}
+
+		#[tokio::test]
+		async fn unknown_pool() {
+			let mut cache =
+				PoolInfoCache::new(Web3::new(transport(Address::from([4; 20])))).unwrap();
+
+			let err = cache.get(Address::from([5; 20])).await.unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				format!("Failed to read pool info of {:?}", Address::from([5; 20]))
+			);
+		}
+	}
+	mod tokens {
+		use super::*;
+
+		#[tokio::test]
+		async fn ok() {
+			let pool = Address::from([4; 20]);
+			let cache = PoolInfoCache::new(Web3::new(transport(pool))).unwrap();
+
+			assert_eq!(
+				cache.tokens(pool).await.unwrap(),
+				(Address::from([1; 20]), Address::from([2; 20]))
+			);
+		}
+
+		#[tokio::test]
+		async fn unknown_pool() {
+			let cache = PoolInfoCache::new(Web3::new(transport(Address::from([4; 20])))).unwrap();
+
+			let err = cache.tokens(Address::from([5; 20])).await.unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				format!("Failed to read tokens of {:?}", Address::from([5; 20]))
+			);
+		}
+	}
+}
@@ -0,0 +1,124 @@
+use web3::types::U256;
+
+use crate::event::SwapEvent;
+
+// Live pool state, kept up to date by applying the pool's events in chain order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolState {
+	pub sqrt_price_x96: U256,
+	pub tick: i32,
+	// Liquidity of the positions in range of the current tick
+	pub liquidity: u128,
+	// Fees earned per unit of liquidity as Q128.128, swap logs don't report these so they
+	// stay as last set, e.g. from the pool contract
+	pub fee_growth_global_0: U256,
+	pub fee_growth_global_1: U256,
+}
+
+impl PoolState {
+	pub fn apply_initialize(&mut self, sqrt_price_x96: U256, tick: i32) {
+		self.sqrt_price_x96 = sqrt_price_x96;
+		self.tick = tick;
+	}
+
+	// Swaps report the price, tick and in-range liquidity after the swap
+	pub fn apply_swap(&mut self, event: &SwapEvent) {
+		self.sqrt_price_x96 = event.sqrt_price_x96;
+		self.tick = event.tick;
+		self.liquidity = event.liquidity;
+	}
+
+	pub fn apply_mint(&mut self, tick_lower: i32, tick_upper: i32, amount: u128) {
+		if self.in_range(tick_lower, tick_upper) {
+			self.liquidity = self.liquidity.saturating_add(amount);
+		}
+	}
+
+	pub fn apply_burn(&mut self, tick_lower: i32, tick_upper: i32, amount: u128) {
+		if self.in_range(tick_lower, tick_upper) {
+			self.liquidity = self.liquidity.saturating_sub(amount);
+		}
+	}
+
+	// Same bounds as the pool contract, the upper tick is exclusive
+	fn in_range(&self, tick_lower: i32, tick_upper: i32) -> bool {
+		(tick_lower..tick_upper).contains(&self.tick)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+
+	fn initialized() -> PoolState {
+		let mut state = PoolState::default();
+		state.apply_initialize(U256::from(2).pow(96.into()), 0);
+		state
+	}
+
+	mod apply_swap {
+		use super::*;
+
+		#[test]
+		fn takes_state_after_swap() {
+			let mut state = initialized();
+			let event = SwapEventBuilder::default()
+				.sqrt_price_x96(U256::from(3).pow(48.into()))
+				.liquidity(1_000)
+				.tick(-12)
+				.build();
+
+			state.apply_swap(&event);
+
+			assert_eq!(
+				state,
+				PoolState {
+					sqrt_price_x96: U256::from(3).pow(48.into()),
+					tick: -12,
+					liquidity: 1_000,
+					..PoolState::default()
+				}
+			);
+		}
+	}
+
+	mod apply_mint {
+		use super::*;
+
+		#[test]
+		fn in_range() {
+			let mut state = initialized();
+
+			state.apply_mint(-10, 10, 500);
+			state.apply_mint(0, 10, 200);
+
+			assert_eq!(state.liquidity, 700);
+		}
+
+		#[test]
+		fn out_of_range() {
+			let mut state = initialized();
+
+			state.apply_mint(10, 20, 500);
+			state.apply_mint(-10, 0, 200);
+
+			assert_eq!(state.liquidity, 0);
+		}
+	}
+
+	mod apply_burn {
+		use super::*;
+
+		#[test]
+		fn in_range() {
+			let mut state = initialized();
+			state.apply_mint(-10, 10, 500);
+
+			state.apply_burn(-10, 10, 200);
+			state.apply_burn(10, 20, 100);
+
+			assert_eq!(state.liquidity, 300);
+		}
+	}
+}
@@ -0,0 +1,269 @@
+use ethereum_types::U512;
+use rust_decimal::{Decimal, MathematicalOps};
+use web3::types::U256;
+
+const TICK_BASE: Decimal = Decimal::from_parts(10001, 0, 0, false, 4);
+
+// Bounds of V3 ticks, their prices 1.0001^tick cover the range of sqrtPriceX96
+pub const MIN_TICK: i32 = -887_272;
+pub const MAX_TICK: i32 = 887_272;
+
+// Inclusive range of ticks, lower tick first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickRange(pub i32, pub i32);
+
+impl TickRange {
+	pub const FULL: TickRange = TickRange(MIN_TICK, MAX_TICK);
+
+	pub fn contains(&self, tick: i32) -> bool {
+		(self.0..=self.1).contains(&tick)
+	}
+}
+
+pub fn is_valid_tick(tick: i32) -> bool {
+	TickRange::FULL.contains(tick)
+}
+
+// Returns `None` if 1.0001^|tick| or the resulting price does not fit into a `Decimal`, as for
+// ticks near the bounds
+pub fn tick_to_price(tick: i32, token0_decimals: u32, token1_decimals: u32) -> Option<Decimal> {
+	let (mut numerator, mut denominator) = decimal_offset(token0_decimals, token1_decimals)?;
+
+	let ratio = TICK_BASE.checked_powu(tick.unsigned_abs().into())?;
+	if tick >= 0 {
+		numerator = numerator.checked_mul(ratio)?;
+	} else {
+		denominator = denominator.checked_mul(ratio)?;
+	}
+
+	numerator.checked_div(denominator)
+}
+
+// Returns `None` if the resulting price does not fit into a `Decimal`, as for sqrt prices near
+// the maximum
+pub fn sqrt_price_x96_to_price(
+	sqrt_price: U256,
+	token0_decimals: u32,
+	token1_decimals: u32,
+) -> Option<Decimal> {
+	let mut numerator = sqrt_price.full_mul(sqrt_price);
+	let mut denominator = U512::one() << 192;
+
+	let offset = |decimals: u32| U512::from(10).checked_pow(decimals.into());
+	if token0_decimals >= token1_decimals {
+		numerator = numerator.checked_mul(offset(token0_decimals - token1_decimals)?)?;
+	} else {
+		denominator = denominator.checked_mul(offset(token1_decimals - token0_decimals)?)?;
+	}
+
+	ratio_to_decimal(numerator, denominator)
+}
+
+// Percentage change of the price between the sqrt prices, token decimals cancel out. Returns
+// `None` if the pre-swap price is zero or the change does not fit into a `Decimal`.
+pub fn price_impact(pre_sqrt_price: U256, post_sqrt_price: U256) -> Option<Decimal> {
+	let pre_price = pre_sqrt_price.full_mul(pre_sqrt_price);
+	let post_price = post_sqrt_price.full_mul(post_sqrt_price);
+	if pre_price.is_zero() {
+		return None;
+	}
+
+	let hundred = U512::from(100);
+	match post_price >= pre_price {
+		true => ratio_to_decimal((post_price - pre_price).checked_mul(hundred)?, pre_price),
+		false => ratio_to_decimal((pre_price - post_price).checked_mul(hundred)?, pre_price)
+			.map(|impact| -impact),
+	}
+}
+
+// Uses the largest scale whose mantissa still fits into a `Decimal`
+fn ratio_to_decimal(numerator: U512, denominator: U512) -> Option<Decimal> {
+	let max_mantissa = U512::from(Decimal::MAX.mantissa() as u128);
+	for scale in (0..=Decimal::MAX_SCALE).rev() {
+		let Some(scaled) = numerator.checked_mul(U512::exp10(scale as usize)) else {
+			continue;
+		};
+		let mantissa = scaled / denominator;
+		if mantissa <= max_mantissa {
+			return Some(Decimal::from_i128_with_scale(mantissa.low_u128() as i128, scale));
+		}
+	}
+
+	None
+}
+
+fn decimal_offset(token0_decimals: u32, token1_decimals: u32) -> Option<(Decimal, Decimal)> {
+	let ten = Decimal::TEN;
+	if token0_decimals >= token1_decimals {
+		Some((ten.checked_powu((token0_decimals - token1_decimals).into())?, Decimal::ONE))
+	} else {
+		Some((Decimal::ONE, ten.checked_powu((token1_decimals - token0_decimals).into())?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DAI_DECIMALS: u32 = 18;
+	const USDC_DECIMALS: u32 = 6;
+
+	fn assert_approx_eq(actual: Decimal, expected: Decimal) {
+		let tolerance = Decimal::new(1, 12);
+		assert!(
+			(actual - expected).abs() < tolerance,
+			"Expected {} to be approximately {}",
+			actual,
+			expected
+		);
+	}
+
+	mod is_valid_tick {
+		use super::*;
+
+		#[test]
+		fn bounds() {
+			assert!(is_valid_tick(MIN_TICK));
+			assert!(is_valid_tick(0));
+			assert!(is_valid_tick(MAX_TICK));
+			assert!(!is_valid_tick(MIN_TICK - 1));
+			assert!(!is_valid_tick(MAX_TICK + 1));
+		}
+
+		#[test]
+		fn tick_range() {
+			let range = TickRange(-10, 10);
+
+			assert!(range.contains(-10));
+			assert!(range.contains(10));
+			assert!(!range.contains(11));
+			assert!(!TickRange(10, -10).contains(0));
+		}
+	}
+
+	mod tick_to_price {
+		use super::*;
+
+		#[test]
+		fn zero_tick() {
+			assert_eq!(tick_to_price(0, 6, 6), Some(Decimal::ONE));
+		}
+
+		#[test]
+		fn positive_tick() {
+			assert_approx_eq(tick_to_price(10, 6, 6).unwrap(), Decimal::new(1001000450120021, 15));
+		}
+
+		#[test]
+		fn dai_usdc() {
+			let price = tick_to_price(-276324, DAI_DECIMALS, USDC_DECIMALS).unwrap();
+			assert_approx_eq(price, Decimal::new(10000026438309507, 16));
+
+			let price = tick_to_price(-276330, DAI_DECIMALS, USDC_DECIMALS).unwrap();
+			assert_approx_eq(price, Decimal::new(9994028521892198, 16));
+		}
+
+		#[test]
+		fn inverted_decimals() {
+			let price = tick_to_price(276324, USDC_DECIMALS, DAI_DECIMALS).unwrap();
+			assert_approx_eq(price, Decimal::new(9999973561760392, 16));
+		}
+
+		// 1.0001^887272 is about 3.4e38, beyond the range of a `Decimal`
+		#[test]
+		fn tick_bounds() {
+			assert_eq!(tick_to_price(MIN_TICK, DAI_DECIMALS, USDC_DECIMALS), None);
+			assert_eq!(tick_to_price(MAX_TICK, DAI_DECIMALS, USDC_DECIMALS), None);
+		}
+
+		#[test]
+		fn decimals_beyond_range() {
+			assert_eq!(tick_to_price(0, 255, 0), None);
+		}
+	}
+
+	mod sqrt_price_x96_to_price {
+		use super::*;
+
+		#[test]
+		fn unit_price() {
+			assert_eq!(sqrt_price_x96_to_price(U256::one() << 96, 6, 6), Some(Decimal::ONE));
+		}
+
+		#[test]
+		fn dai_usdc() {
+			let sqrt_price = U256::from_dec_str("79232123823359799118286").unwrap();
+			let price = sqrt_price_x96_to_price(sqrt_price, DAI_DECIMALS, USDC_DECIMALS).unwrap();
+			assert_approx_eq(price, Decimal::new(10001, 4));
+		}
+
+		#[test]
+		fn matches_tick() {
+			// sqrt(1.0001^-276324) * 2^96
+			let sqrt_price = U256::from_dec_str("79228267247129223624113").unwrap();
+			let price = sqrt_price_x96_to_price(sqrt_price, DAI_DECIMALS, USDC_DECIMALS).unwrap();
+			assert_approx_eq(price, tick_to_price(-276324, DAI_DECIMALS, USDC_DECIMALS).unwrap());
+		}
+
+		// Sqrt prices of the tick bounds, the maximum's price of about 3.4e38 exceeds the range
+		#[test]
+		fn sqrt_ratio_bounds() {
+			let min_sqrt_ratio = U256::from(4_295_128_739u64);
+			let max_sqrt_ratio =
+				U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap();
+
+			// 2.9e-39 is truncated to the 28 decimal places of a `Decimal`
+			assert_eq!(sqrt_price_x96_to_price(min_sqrt_ratio, 6, 6), Some(Decimal::ZERO));
+			assert_eq!(
+				sqrt_price_x96_to_price(min_sqrt_ratio, DAI_DECIMALS, USDC_DECIMALS),
+				Some(Decimal::new(29, 28))
+			);
+			assert_eq!(sqrt_price_x96_to_price(max_sqrt_ratio, 6, 6), None);
+		}
+
+		#[test]
+		fn decimals_beyond_range() {
+			assert_eq!(sqrt_price_x96_to_price(U256::one() << 96, 255, 0), None);
+		}
+	}
+	mod price_impact {
+		use super::*;
+
+		#[test]
+		fn unchanged() {
+			let sqrt_price = U256::from_dec_str("79232123823359799118286").unwrap();
+			assert_eq!(price_impact(sqrt_price, sqrt_price), Some(Decimal::ZERO));
+		}
+
+		#[test]
+		fn doubled_sqrt_price() {
+			let sqrt_price = U256::one() << 96;
+			assert_eq!(price_impact(sqrt_price, sqrt_price * 2), Some(Decimal::new(300, 0)));
+			assert_eq!(price_impact(sqrt_price * 2, sqrt_price), Some(Decimal::new(-75, 0)));
+		}
+
+		#[test]
+		fn matches_prices() {
+			let pre = U256::from_dec_str("79228267247129223624113").unwrap();
+			let post = U256::from_dec_str("79232123823359799118286").unwrap();
+
+			let pre_price = sqrt_price_x96_to_price(pre, DAI_DECIMALS, USDC_DECIMALS).unwrap();
+			let post_price = sqrt_price_x96_to_price(post, DAI_DECIMALS, USDC_DECIMALS).unwrap();
+			assert_approx_eq(
+				price_impact(pre, post).unwrap(),
+				(post_price - pre_price) / pre_price * Decimal::ONE_HUNDRED,
+			);
+		}
+
+		#[test]
+		fn zero_pre_price() {
+			assert_eq!(price_impact(U256::zero(), U256::one()), None);
+		}
+
+		// A pre-swap price of 1 / 2^192 and a maximal post-swap price overflow the `Decimal`
+		#[test]
+		fn beyond_range() {
+			assert_eq!(price_impact(U256::one(), U256::MAX), None);
+		}
+	}
+}
@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+// Keeps the most recent items whose combined size stays within `capacity_bytes`, sizes are given
+// by the caller, e.g. the length of an item's serialized form
+#[derive(Debug, Clone)]
+pub struct MemoryBoundedEventQueue<T: Clone> {
+	capacity_bytes: usize,
+	current_bytes: usize,
+	queue: VecDeque<(T, usize)>,
+}
+
+impl<T: Clone> MemoryBoundedEventQueue<T> {
+	pub fn new(capacity_bytes: usize) -> MemoryBoundedEventQueue<T> {
+		MemoryBoundedEventQueue { capacity_bytes, current_bytes: 0, queue: VecDeque::new() }
+	}
+
+	// Evicts the oldest items until the new one fits, items larger than the capacity are dropped
+	// without evicting anything
+	pub fn push(&mut self, item: T, size: usize) {
+		if size > self.capacity_bytes {
+			return;
+		}
+
+		while self.current_bytes + size > self.capacity_bytes {
+			let Some((_, evicted)) = self.queue.pop_front() else { break };
+			self.current_bytes -= evicted;
+		}
+		self.queue.push_back((item, size));
+		self.current_bytes += size;
+	}
+
+	pub fn len(&self) -> usize {
+		self.queue.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.queue.is_empty()
+	}
+
+	pub fn byte_usage(&self) -> usize {
+		self.current_bytes
+	}
+
+	// Oldest first
+	pub fn iter(&self) -> impl Iterator<Item = &T> {
+		self.queue.iter().map(|(item, _)| item)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod push {
+		use super::*;
+
+		#[test]
+		fn within_capacity() {
+			let mut queue = MemoryBoundedEventQueue::new(10);
+
+			queue.push('a', 4);
+			queue.push('b', 6);
+
+			assert_eq!(queue.len(), 2);
+			assert_eq!(queue.byte_usage(), 10);
+			assert_eq!(queue.iter().collect::<String>(), "ab");
+		}
+
+		#[test]
+		fn evicts_oldest() {
+			let mut queue = MemoryBoundedEventQueue::new(10);
+			queue.push('a', 4);
+			queue.push('b', 4);
+
+			queue.push('c', 5);
+
+			assert_eq!(queue.iter().collect::<String>(), "bc");
+			assert_eq!(queue.byte_usage(), 9);
+
+			queue.push('d', 10);
+
+			assert_eq!(queue.iter().collect::<String>(), "d");
+			assert_eq!(queue.byte_usage(), 10);
+		}
+
+		#[test]
+		fn larger_than_capacity() {
+			let mut queue = MemoryBoundedEventQueue::new(10);
+			queue.push('a', 4);
+
+			queue.push('b', 11);
+
+			assert_eq!(queue.iter().collect::<String>(), "a");
+			assert_eq!(queue.byte_usage(), 4);
+		}
+
+		#[test]
+		fn zero_capacity() {
+			let mut queue = MemoryBoundedEventQueue::new(0);
+
+			queue.push('a', 1);
+
+			assert!(queue.is_empty());
+		}
+	}
+}
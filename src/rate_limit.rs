@@ -0,0 +1,112 @@
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
+
+// Spaces out requests to stay within a number of requests per second, allowing bursts of up to
+// a second's worth of requests after idle periods. Used for RPC nodes and webhooks alike.
+#[derive(Debug)]
+pub struct RateLimiter {
+	requests_per_second: f64,
+	last_request: Instant,
+	// Requests that can be sent right away
+	budget: f64,
+}
+
+impl RateLimiter {
+	pub fn new(requests_per_second: f64) -> RateLimiter {
+		RateLimiter {
+			requests_per_second,
+			last_request: Instant::now(),
+			budget: requests_per_second.max(1.0),
+		}
+	}
+
+	pub async fn acquire(&mut self) {
+		let now = Instant::now();
+		let refill = now.duration_since(self.last_request).as_secs_f64() * self.requests_per_second;
+		self.budget = (self.budget + refill).min(self.requests_per_second.max(1.0));
+		self.last_request = now;
+
+		if self.budget < 1.0 {
+			let wait = Duration::from_secs_f64((1.0 - self.budget) / self.requests_per_second);
+			tokio::time::sleep(wait).await;
+			self.last_request = now + wait;
+			self.budget = 1.0;
+		}
+		self.budget -= 1.0;
+	}
+}
+
+// Rate limiter shared by tasks sending requests to the same service, e.g. the block source and
+// the pending swap monitor. The lock is held while waiting, so concurrent requests take turns.
+#[derive(Debug, Clone)]
+pub struct SharedRateLimiter(Arc<Mutex<RateLimiter>>);
+
+impl SharedRateLimiter {
+	pub fn new(requests_per_second: f64) -> SharedRateLimiter {
+		SharedRateLimiter(Arc::new(Mutex::new(RateLimiter::new(requests_per_second))))
+	}
+
+	pub async fn acquire(&self) {
+		self.0.lock().await.acquire().await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod rate_limiter {
+		use super::*;
+
+		#[tokio::test]
+		async fn waits_for_budget() {
+			let mut rate_limiter = RateLimiter::new(10.0);
+			let start = Instant::now();
+
+			// A second's worth of requests is sent right away
+			for _ in 0..10 {
+				rate_limiter.acquire().await;
+			}
+			assert!(start.elapsed() < Duration::from_millis(100));
+
+			rate_limiter.acquire().await;
+			rate_limiter.acquire().await;
+
+			assert!(start.elapsed() >= Duration::from_millis(200));
+		}
+
+		#[tokio::test]
+		async fn partial_budget() {
+			let mut rate_limiter = RateLimiter::new(5.0);
+			rate_limiter.budget = 0.5;
+			let start = Instant::now();
+
+			rate_limiter.acquire().await;
+
+			// Half a request at 5 per second
+			assert!(start.elapsed() >= Duration::from_millis(100));
+			assert!(rate_limiter.budget < 0.1);
+		}
+	}
+
+	mod shared_rate_limiter {
+		use super::*;
+
+		#[tokio::test]
+		async fn shared_budget() {
+			let rate_limiter = SharedRateLimiter::new(10.0);
+			let other = rate_limiter.clone();
+			let start = Instant::now();
+
+			let acquire = |rate_limiter: SharedRateLimiter| async move {
+				for _ in 0..6 {
+					rate_limiter.acquire().await;
+				}
+			};
+			tokio::join!(acquire(rate_limiter), acquire(other));
+
+			// Two requests beyond a second's worth
+			assert!(start.elapsed() >= Duration::from_millis(200));
+		}
+	}
+}
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use std::{future::Future, io, time::Duration};
+use tracing::warn;
+use web3::error::TransportError;
+
+// Retries failed requests with exponential backoff, as long as their errors are transient
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+	// Including the first attempt
+	max_attempts: u32,
+	initial_delay: Duration,
+	multiplier: f64,
+	max_delay: Duration,
+}
+
+impl RetryPolicy {
+	pub fn new(
+		max_attempts: u32,
+		initial_delay: Duration,
+		multiplier: f64,
+		max_delay: Duration,
+	) -> RetryPolicy {
+		RetryPolicy { max_attempts, initial_delay, multiplier, max_delay }
+	}
+
+	// Returns the error of the last attempt once all attempts failed, or the first error that
+	// isn't retryable
+	pub async fn execute<F, Fut, T>(&self, mut f: F) -> Result<T>
+	where
+		F: FnMut() -> Fut,
+		Fut: Future<Output = Result<T>>,
+	{
+		let mut delay = self.initial_delay;
+		let mut attempt = 1;
+
+		loop {
+			match f().await {
+				Ok(value) => return Ok(value),
+				Err(err) if attempt < self.max_attempts && is_retryable(&err) => {
+					warn!(error = %err, attempt, retry_in = ?delay, "Request failed");
+					tokio::time::sleep(delay).await;
+					delay = delay.mul_f64(self.multiplier).min(self.max_delay);
+					attempt += 1;
+				},
+				Err(err) => return Err(err),
+			}
+		}
+	}
+}
+
+impl Default for RetryPolicy {
+	fn default() -> RetryPolicy {
+		RetryPolicy::new(5, Duration::from_secs(1), 2.0, Duration::from_secs(60))
+	}
+}
+
+// Connection failures and timeouts anywhere in the error chain. Errors returned by the node,
+// e.g. reverted calls, fail the same way when retried.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+	err.chain().any(|cause| {
+		if let Some(err) = cause.downcast_ref::<web3::Error>() {
+			return match err {
+				web3::Error::Unreachable | web3::Error::Io(_) => true,
+				// HTTP status codes, only rate limiting and server errors are transient
+				web3::Error::Transport(TransportError::Code(code)) => *code == 429 || *code >= 500,
+				web3::Error::Transport(TransportError::Message(_)) => true,
+				_ => false,
+			};
+		}
+
+		match cause.downcast_ref::<io::Error>() {
+			Some(err) => matches!(
+				err.kind(),
+				io::ErrorKind::ConnectionRefused |
+					io::ErrorKind::ConnectionReset |
+					io::ErrorKind::ConnectionAborted |
+					io::ErrorKind::NotConnected |
+					io::ErrorKind::BrokenPipe |
+					io::ErrorKind::TimedOut |
+					io::ErrorKind::UnexpectedEof
+			),
+			None => cause.is::<tokio::time::error::Elapsed>(),
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::{anyhow, Context};
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	fn policy() -> RetryPolicy {
+		RetryPolicy::new(3, Duration::from_millis(10), 2.0, Duration::from_millis(15))
+	}
+
+	// Fails with the given error until the attempt succeeds, counting the attempts
+	async fn attempt(
+		attempts: &AtomicU32,
+		succeeds_at: u32,
+		error: fn() -> anyhow::Error,
+	) -> Result<u32> {
+		let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+		match attempt >= succeeds_at {
+			true => Ok(attempt),
+			false => Err(error()),
+		}
+	}
+
+	mod execute {
+		use super::*;
+		use tokio::time::Instant;
+
+		#[tokio::test]
+		async fn retries_transient_errors() {
+			let attempts = AtomicU32::new(0);
+			let start = Instant::now();
+
+			let result = policy()
+				.execute(|| attempt(&attempts, 3, || web3::Error::Unreachable.into()))
+				.await;
+
+			assert_eq!(result.unwrap(), 3);
+			// 10ms, then 20ms capped at 15ms
+			assert!(start.elapsed() >= Duration::from_millis(25));
+		}
+
+		#[tokio::test]
+		async fn gives_up() {
+			let attempts = AtomicU32::new(0);
+
+			let err = policy()
+				.execute(|| attempt(&attempts, 4, || web3::Error::Unreachable.into()))
+				.await
+				.unwrap_err();
+
+			assert_eq!(err.to_string(), "Server is unreachable");
+			assert_eq!(attempts.load(Ordering::SeqCst), 3);
+		}
+
+		#[tokio::test]
+		async fn non_retryable_error() {
+			let attempts = AtomicU32::new(0);
+
+			let err = policy()
+				.execute(|| attempt(&attempts, 3, || anyhow!("Execution reverted")))
+				.await
+				.unwrap_err();
+
+			assert_eq!(err.to_string(), "Execution reverted");
+			assert_eq!(attempts.load(Ordering::SeqCst), 1);
+		}
+	}
+
+	mod is_retryable {
+		use super::*;
+
+		#[test]
+		fn connection_errors() {
+			let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+
+			assert!(is_retryable(&web3::Error::Io(refused).into()));
+			assert!(is_retryable(
+				&Err::<(), _>(web3::Error::Unreachable)
+					.context("Failed to fetch logs")
+					.unwrap_err()
+			));
+			assert!(is_retryable(
+				&web3::Error::Transport(TransportError::Message("connection closed".into())).into()
+			));
+		}
+
+		#[tokio::test]
+		async fn timeouts() {
+			let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+				.await
+				.unwrap_err();
+
+			assert!(is_retryable(&elapsed.into()));
+			assert!(is_retryable(&io::Error::from(io::ErrorKind::TimedOut).into()));
+		}
+
+		#[test]
+		fn status_codes() {
+			let status = |code| web3::Error::Transport(TransportError::Code(code)).into();
+
+			assert!(is_retryable(&status(429)));
+			assert!(is_retryable(&status(503)));
+			assert!(!is_retryable(&status(401)));
+		}
+
+		#[test]
+		fn other_errors() {
+			assert!(!is_retryable(&web3::Error::Decoder("invalid hex".into()).into()));
+			assert!(!is_retryable(&io::Error::from(io::ErrorKind::NotFound).into()));
+			assert!(!is_retryable(&anyhow!("Block 0x1 not found")));
+		}
+	}
+}
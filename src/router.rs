@@ -0,0 +1,195 @@
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use web3::types::Address;
+
+use crate::{
+	erc20::TransactionGrouper,
+	event::{SwapDirection, SwapEvent},
+};
+
+// Swap routed by the SwapRouter through two pools, the intermediate token passing from one hop
+// to the next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiHopSwap {
+	// In route order
+	pub hops: Vec<SwapEvent>,
+	// Token and amount paid into the first hop
+	pub net_input: (Address, Decimal),
+	// Token and amount received from the last hop
+	pub net_output: (Address, Decimal),
+}
+
+// Token and amount flowing into and out of the pool of a swap
+type Leg = ((Address, Decimal), (Address, Decimal));
+
+// The router emits no events, so multi-hop swaps are reconstructed from the pool swaps of a
+// transaction. Expects swaps in log order.
+#[derive(Debug)]
+pub struct MultiHopReconstructor {
+	// Token addresses of each pool, token0 first
+	tokens: HashMap<Address, (Address, Address)>,
+	grouper: TransactionGrouper,
+}
+
+impl MultiHopReconstructor {
+	pub fn new(tokens: HashMap<Address, (Address, Address)>) -> MultiHopReconstructor {
+		MultiHopReconstructor { tokens, grouper: TransactionGrouper::new() }
+	}
+
+	// Returns the multi-hop swap of the previous transaction if the swap starts a new one
+	pub fn push(&mut self, swap: SwapEvent) -> Option<MultiHopSwap> {
+		let completed = self.grouper.push_swap(swap)?;
+		self.reconstruct(completed.swaps)
+	}
+
+	// Multi-hop swap of the last transaction, once no more swaps follow
+	pub fn finish(&mut self) -> Option<MultiHopSwap> {
+		let completed = self.grouper.finish()?;
+		self.reconstruct(completed.swaps)
+	}
+
+	// Only transactions with exactly two swaps are considered, in either order
+	pub fn reconstruct(&self, swaps: Vec<SwapEvent>) -> Option<MultiHopSwap> {
+		let [first, second]: [SwapEvent; 2] = swaps.try_into().ok()?;
+		let (first_leg, second_leg) = (self.leg(&first)?, self.leg(&second)?);
+
+		let ((net_input, _), (_, net_output), hops) = if first_leg.1 == second_leg.0 {
+			(first_leg, second_leg, vec![first, second])
+		} else if second_leg.1 == first_leg.0 {
+			(second_leg, first_leg, vec![second, first])
+		} else {
+			return None;
+		};
+
+		Some(MultiHopSwap { hops, net_input, net_output })
+	}
+
+	fn leg(&self, swap: &SwapEvent) -> Option<Leg> {
+		let (token0, token1) = self.tokens.get(&swap.pool)?;
+		let amount0 = (*token0, swap.amounts.token0_amount);
+		let amount1 = (*token1, swap.amounts.token1_amount);
+
+		Some(match swap.direction {
+			SwapDirection::Token0ToToken1 => (amount0, amount1),
+			SwapDirection::Token1ToToken0 => (amount1, amount0),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use web3::types::H256;
+
+	const POOL_A: Address = Address::repeat_byte(0xa);
+	const POOL_B: Address = Address::repeat_byte(0xb);
+	const DAI: Address = Address::repeat_byte(1);
+	const USDC: Address = Address::repeat_byte(2);
+	const USDT: Address = Address::repeat_byte(3);
+
+	// DAI/USDC and USDT/USDC pools
+	fn reconstructor() -> MultiHopReconstructor {
+		MultiHopReconstructor::new(HashMap::from([(POOL_A, (DAI, USDC)), (POOL_B, (USDT, USDC))]))
+	}
+
+	fn swap(
+		transaction_hash: u64,
+		pool: Address,
+		direction: SwapDirection,
+		token0_amount: i64,
+		token1_amount: i64,
+	) -> SwapEvent {
+		SwapEventBuilder::default()
+			.transaction_hash(H256::from_low_u64_be(transaction_hash))
+			.pool(pool)
+			.direction(direction)
+			.token0_amount(Decimal::new(token0_amount, 2))
+			.token1_amount(Decimal::new(token1_amount, 2))
+			.build()
+	}
+
+	mod reconstruct {
+		use super::*;
+
+		#[test]
+		fn dai_to_usdt() {
+			// DAI → USDC in pool A, then USDC → USDT in pool B
+			let swaps = vec![
+				swap(1, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900),
+				swap(1, POOL_B, SwapDirection::Token1ToToken0, 99_800, 99_900),
+			];
+
+			let multi_hop = reconstructor().reconstruct(swaps.clone()).unwrap();
+
+			assert_eq!(multi_hop.hops, swaps);
+			assert_eq!(multi_hop.net_input, (DAI, Decimal::new(100_000, 2)));
+			assert_eq!(multi_hop.net_output, (USDT, Decimal::new(99_800, 2)));
+		}
+
+		#[test]
+		fn route_order() {
+			let swaps = vec![
+				swap(1, POOL_B, SwapDirection::Token1ToToken0, 99_800, 99_900),
+				swap(1, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900),
+			];
+
+			let multi_hop = reconstructor().reconstruct(swaps.clone()).unwrap();
+
+			assert_eq!(multi_hop.hops, vec![swaps[1].clone(), swaps[0].clone()]);
+			assert_eq!(multi_hop.net_input.0, DAI);
+			assert_eq!(multi_hop.net_output.0, USDT);
+		}
+
+		#[test]
+		fn unmatched_amounts() {
+			let swaps = vec![
+				swap(1, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900),
+				swap(1, POOL_B, SwapDirection::Token1ToToken0, 99_800, 50_000),
+			];
+
+			assert_eq!(reconstructor().reconstruct(swaps), None);
+		}
+
+		#[test]
+		fn unknown_pool() {
+			let swaps = vec![
+				swap(1, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900),
+				swap(1, Address::repeat_byte(0xc), SwapDirection::Token1ToToken0, 99_800, 99_900),
+			];
+
+			assert_eq!(reconstructor().reconstruct(swaps), None);
+		}
+
+		#[test]
+		fn single_swap() {
+			let swaps = vec![swap(1, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900)];
+
+			assert_eq!(reconstructor().reconstruct(swaps), None);
+		}
+	}
+
+	mod push {
+		use super::*;
+
+		#[test]
+		fn groups_by_transaction() {
+			let mut reconstructor = reconstructor();
+
+			assert_eq!(
+				reconstructor.push(swap(1, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900)),
+				None
+			);
+			assert_eq!(
+				reconstructor.push(swap(1, POOL_B, SwapDirection::Token1ToToken0, 99_800, 99_900)),
+				None
+			);
+
+			let completed =
+				reconstructor.push(swap(2, POOL_A, SwapDirection::Token0ToToken1, 100_000, 99_900));
+			assert_eq!(completed.unwrap().hops.len(), 2);
+			// The last transaction has a single swap
+			assert_eq!(reconstructor.finish(), None);
+		}
+	}
+}
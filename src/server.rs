@@ -0,0 +1,27 @@
+use anyhow::Result;
+use std::net::{SocketAddr, TcpListener};
+
+// Every server binds with this before returning the future serving requests, so a taken address is
+// reported at startup instead of by the spawned server
+pub fn bind(addr: SocketAddr) -> Result<TcpListener> {
+	let listener = TcpListener::bind(addr)?;
+	// Required by tokio and hyper to take over the listener
+	listener.set_nonblocking(true)?;
+	Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod bind {
+		use super::*;
+
+		#[test]
+		fn taken_address() {
+			let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+
+			assert!(bind(listener.local_addr().unwrap()).is_err());
+		}
+	}
+}
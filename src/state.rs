@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, io::ErrorKind, path::Path};
+use web3::types::Address;
+
+use crate::event::{SwapEvent, TokenPair};
+
+// Progress persisted after every confirmed block, so a restarted monitor continues without gaps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonitorState {
+	pub last_confirmed_block: u64,
+	pub buffer_snapshot: Vec<PoolSnapshot>,
+}
+
+// Blocks of a single pool still waiting for confirmation, oldest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+	pub pool: Address,
+	// Tokens of the pool, missing in states saved before they were recorded
+	#[serde(default)]
+	pub pair: Option<TokenPair>,
+	pub blocks: Vec<(u64, Vec<SwapEvent>)>,
+}
+
+impl MonitorState {
+	// Returns `None` if no state has been saved yet
+	pub fn load(path: &Path) -> Result<Option<MonitorState>> {
+		let contents = match fs::read_to_string(path) {
+			Ok(contents) => contents,
+			Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+			Err(err) =>
+				return Err(err)
+					.with_context(|| format!("Failed to read state file '{}'", path.display())),
+		};
+
+		serde_json::from_str(&contents)
+			.map(Some)
+			.with_context(|| format!("Failed to parse state file '{}'", path.display()))
+	}
+
+	// Writes to a temporary file first, a crash mid-write leaves the previous state intact
+	pub fn save(&self, path: &Path) -> Result<()> {
+		let mut temp_path = path.as_os_str().to_owned();
+		temp_path.push(".tmp");
+
+		let contents = serde_json::to_vec(self)?;
+		fs::write(&temp_path, contents)
+			.and_then(|()| fs::rename(&temp_path, path))
+			.with_context(|| format!("Failed to write state file '{}'", path.display()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use rust_decimal::Decimal;
+
+	fn state() -> MonitorState {
+		let event = SwapEventBuilder::default()
+			.block_number(102)
+			.log_index(3)
+			.pool(Address::from([4; 20]))
+			.token0_amount(Decimal::new(10050, 2))
+			.token1_amount(Decimal::new(10100, 2))
+			.build();
+
+		MonitorState {
+			last_confirmed_block: 100,
+			buffer_snapshot: vec![PoolSnapshot {
+				pool: Address::from([4; 20]),
+				pair: Some((*TokenPair::dai_usdc()).clone()),
+				blocks: vec![(101, vec![]), (102, vec![event])],
+			}],
+		}
+	}
+
+	mod load {
+		use super::*;
+
+		#[test]
+		fn missing_file() {
+			let dir = tempfile::tempdir().unwrap();
+
+			assert_eq!(MonitorState::load(&dir.path().join("state.json")).unwrap(), None);
+		}
+
+		#[test]
+		fn invalid_file() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("state.json");
+			fs::write(&path, "{").unwrap();
+
+			let err = MonitorState::load(&path).unwrap_err();
+			assert!(err.to_string().starts_with("Failed to parse state file"));
+		}
+	}
+
+	mod save {
+		use super::*;
+
+		#[test]
+		fn round_trip() {
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("state.json");
+
+			state().save(&path).unwrap();
+			MonitorState { last_confirmed_block: 101, ..state() }.save(&path).unwrap();
+
+			assert_eq!(
+				MonitorState::load(&path).unwrap(),
+				Some(MonitorState { last_confirmed_block: 101, ..state() })
+			);
+			assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+		}
+	}
+}
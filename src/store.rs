@@ -0,0 +1,536 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, params_from_iter, Connection};
+use std::{
+	path::Path,
+	sync::{Arc, Mutex},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use web3::types::U256;
+
+use crate::event::{SwapAmounts, SwapEvent, SwapFilter, TokenPair};
+
+// Persists confirmed swaps, shared by the components reading them back
+pub trait EventStore: Send + Sync {
+	fn store(&self, event: &SwapEvent) -> Result<()>;
+
+	// Matching swaps in block and log order
+	fn query(&self, filter: &SwapFilter) -> Result<Vec<SwapEvent>>;
+
+	fn count(&self) -> Result<u64>;
+}
+
+// Keeps swaps for the lifetime of the process, e.g. for tests or short-lived runs
+#[derive(Debug, Default)]
+pub struct InMemoryEventStore {
+	events: Mutex<Vec<SwapEvent>>,
+}
+
+impl InMemoryEventStore {
+	pub fn new() -> InMemoryEventStore {
+		InMemoryEventStore::default()
+	}
+}
+
+impl EventStore for InMemoryEventStore {
+	fn store(&self, event: &SwapEvent) -> Result<()> {
+		self.events.lock().unwrap().push(event.clone());
+		Ok(())
+	}
+
+	fn query(&self, filter: &SwapFilter) -> Result<Vec<SwapEvent>> {
+		let mut events: Vec<_> = self
+			.events
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|event| filter.matches(event))
+			.cloned()
+			.collect();
+		events.sort();
+		Ok(events)
+	}
+
+	fn count(&self) -> Result<u64> {
+		Ok(self.events.lock().unwrap().len() as u64)
+	}
+}
+
+// Each entry upgrades the schema by one version, tracked in `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+	"CREATE TABLE IF NOT EXISTS swaps (
+		id INTEGER PRIMARY KEY,
+		block_number INTEGER,
+		tx_hash TEXT,
+		log_index INTEGER,
+		sender TEXT,
+		receiver TEXT,
+		direction TEXT,
+		dai_amount TEXT,
+		usdc_amount TEXT,
+		exchange_rate TEXT,
+		created_at INTEGER
+	)",
+	"ALTER TABLE swaps ADD COLUMN pool TEXT",
+	// Amounts are of the pool's own tokens, rows stored before are of the DAI/USDC pool
+	"ALTER TABLE swaps RENAME COLUMN dai_amount TO token0_amount;
+	ALTER TABLE swaps RENAME COLUMN usdc_amount TO token1_amount;
+	ALTER TABLE swaps ADD COLUMN pair TEXT;
+	UPDATE swaps SET direction = 'token0_to_token1' WHERE direction = 'dai_to_usdc';
+	UPDATE swaps SET direction = 'token1_to_token0' WHERE direction = 'usdc_to_dai';",
+];
+
+pub struct SqliteEventStore {
+	connection: Mutex<Connection>,
+}
+
+impl SqliteEventStore {
+	pub fn open(path: &Path) -> Result<SqliteEventStore> {
+		let connection = Connection::open(path)
+			.with_context(|| format!("Failed to open database '{}'", path.display()))?;
+
+		SqliteEventStore::from_connection(connection)
+	}
+
+	fn from_connection(mut connection: Connection) -> Result<SqliteEventStore> {
+		Self::migrate(&mut connection)?;
+		Ok(SqliteEventStore { connection: Mutex::new(connection) })
+	}
+
+	fn migrate(connection: &mut Connection) -> Result<()> {
+		let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+		for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+			let transaction = connection.transaction()?;
+			transaction
+				.execute_batch(migration)
+				.with_context(|| format!("Failed to apply migration {}", index + 1))?;
+			transaction.pragma_update(None, "user_version", index + 1)?;
+			transaction.commit()?;
+		}
+
+		Ok(())
+	}
+
+	pub fn insert_events(&mut self, events: &[SwapEvent]) -> Result<()> {
+		insert(self.connection.get_mut().unwrap(), events)
+	}
+
+	pub fn last_block_number(&self) -> Result<Option<u64>> {
+		let block_number = self.connection.lock().unwrap().query_row(
+			"SELECT MAX(block_number) FROM swaps",
+			[],
+			|row| row.get(0),
+		)?;
+
+		Ok(block_number)
+	}
+}
+
+impl EventStore for SqliteEventStore {
+	fn store(&self, event: &SwapEvent) -> Result<()> {
+		insert(&mut self.connection.lock().unwrap(), std::slice::from_ref(event))
+	}
+
+	// Swaps are read back without pool state, which isn't stored
+	fn query(&self, filter: &SwapFilter) -> Result<Vec<SwapEvent>> {
+		let (conditions, values) = where_clause(filter);
+		let connection = self.connection.lock().unwrap();
+		let mut statement = connection.prepare_cached(&format!(
+			"SELECT block_number, tx_hash, log_index, sender, receiver, direction, \
+			 token0_amount, token1_amount, pool, pair FROM swaps{} ORDER BY block_number, log_index",
+			conditions
+		))?;
+		let rows = statement.query_map(params_from_iter(values), |row| {
+			Ok((
+				row.get::<_, Option<u64>>(0)?,
+				row.get::<_, Option<String>>(1)?,
+				row.get::<_, Option<u64>>(2)?,
+				row.get::<_, String>(3)?,
+				row.get::<_, String>(4)?,
+				row.get::<_, String>(5)?,
+				row.get::<_, String>(6)?,
+				row.get::<_, String>(7)?,
+				// Swaps stored before the pool was recorded
+				row.get::<_, Option<String>>(8)?,
+				// Swaps stored before the pair was recorded are of the DAI/USDC pool
+				row.get::<_, Option<String>>(9)?,
+			))
+		})?;
+
+		let mut events = Vec::new();
+		for row in rows {
+			let (
+				block_number,
+				tx_hash,
+				log_index,
+				sender,
+				receiver,
+				direction,
+				token0,
+				token1,
+				pool,
+				pair,
+			) = row?;
+			let pair = match pair {
+				Some(pair) => Arc::new(serde_json::from_str::<TokenPair>(&pair)?),
+				None => TokenPair::dai_usdc(),
+			};
+			let event = SwapEvent {
+				block_number,
+				transaction_hash: tx_hash.map(|hash| hash.parse()).transpose()?,
+				log_index,
+				timestamp: None,
+				pool: pool.map(|pool| pool.parse()).transpose()?.unwrap_or_default(),
+				sender: sender.parse()?,
+				receiver: receiver.parse()?,
+				direction: direction.parse()?,
+				amounts: SwapAmounts {
+					token0_amount: token0.parse()?,
+					token1_amount: token1.parse()?,
+					pair,
+				},
+				sqrt_price_x96: U256::zero(),
+				liquidity: 0,
+				tick: 0,
+			};
+			if filter.matches(&event) {
+				events.push(event);
+			}
+		}
+
+		Ok(events)
+	}
+
+	fn count(&self) -> Result<u64> {
+		let count =
+			self.connection
+				.lock()
+				.unwrap()
+				.query_row("SELECT COUNT(*) FROM swaps", [], |row| row.get(0))?;
+
+		Ok(count)
+	}
+}
+
+// Conditions of the filter with their parameters, empty without any. Amounts are stored as text
+// and compared as REAL, which may let through amounts within rounding of a bound, so the returned
+// rows still need to be matched exactly.
+fn where_clause(filter: &SwapFilter) -> (String, Vec<String>) {
+	let mut conditions = Vec::new();
+	let mut values = Vec::new();
+
+	if let Some(min) = filter.min_token0_amount {
+		conditions.push("CAST(token0_amount AS REAL) >= CAST(? AS REAL)".to_string());
+		values.push(min.to_string());
+	}
+	if let Some(max) = filter.max_token0_amount {
+		conditions.push("CAST(token0_amount AS REAL) <= CAST(? AS REAL)".to_string());
+		values.push(max.to_string());
+	}
+	if let Some(direction) = &filter.direction {
+		conditions.push("direction = ?".to_string());
+		values.push(direction.as_str().to_string());
+	}
+	for (column, whitelist) in
+		[("sender", &filter.sender_whitelist), ("receiver", &filter.receiver_whitelist)]
+	{
+		if let Some(addresses) = whitelist {
+			conditions.push(format!("{} IN ({})", column, vec!["?"; addresses.len()].join(", ")));
+			values.extend(addresses.iter().map(|address| format!("{:?}", address)));
+		}
+	}
+
+	match conditions.is_empty() {
+		true => (String::new(), values),
+		false => (format!(" WHERE {}", conditions.join(" AND ")), values),
+	}
+}
+
+fn insert(connection: &mut Connection, events: &[SwapEvent]) -> Result<()> {
+	let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+	let transaction = connection.transaction()?;
+	{
+		let mut statement = transaction.prepare_cached(
+			"INSERT INTO swaps (block_number, tx_hash, log_index, sender, receiver, direction, \
+			 token0_amount, token1_amount, exchange_rate, created_at, pool, pair) \
+			 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+		)?;
+
+		for event in events {
+			statement.execute(params![
+				event.block_number,
+				event.transaction_hash.map(|hash| format!("{:?}", hash)),
+				event.log_index,
+				format!("{:?}", event.sender),
+				format!("{:?}", event.receiver),
+				event.direction.as_str(),
+				event.amounts.token0_amount.to_string(),
+				event.amounts.token1_amount.to_string(),
+				event.exchange_rate().map(|rate| rate.to_string()),
+				created_at,
+				format!("{:?}", event.pool),
+				serde_json::to_string(&*event.amounts.pair)?,
+			])?;
+		}
+	}
+	transaction.commit()?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::{SwapDirection, SwapEventBuilder};
+	use rust_decimal::Decimal;
+
+	fn store() -> SqliteEventStore {
+		SqliteEventStore::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+	}
+
+	mod migrate {
+		use super::*;
+
+		#[test]
+		fn latest_version() {
+			let store = store();
+			let version: usize = store
+				.connection
+				.lock()
+				.unwrap()
+				.query_row("PRAGMA user_version", [], |row| row.get(0))
+				.unwrap();
+
+			assert_eq!(version, MIGRATIONS.len());
+		}
+
+		#[test]
+		fn dai_usdc_rows() {
+			let connection = Connection::open_in_memory().unwrap();
+			connection.execute_batch(MIGRATIONS[0]).unwrap();
+			connection.execute_batch(MIGRATIONS[1]).unwrap();
+			connection.pragma_update(None, "user_version", 2).unwrap();
+			connection
+				.execute(
+					"INSERT INTO swaps (block_number, log_index, sender, receiver, direction, \
+					 dai_amount, usdc_amount) VALUES (10, 1, ?1, ?1, 'usdc_to_dai', '4.00', '5.00')",
+					[format!("{:?}", web3::types::Address::zero())],
+				)
+				.unwrap();
+
+			let events = SqliteEventStore::from_connection(connection)
+				.unwrap()
+				.query(&SwapFilter::default())
+				.unwrap();
+
+			assert_eq!(events.len(), 1);
+			assert_eq!(events[0].direction, SwapDirection::Token1ToToken0);
+			assert_eq!(
+				events[0].amounts,
+				SwapAmounts::dai_usdc(Decimal::new(400, 2), Decimal::new(500, 2))
+			);
+		}
+
+		#[test]
+		fn idempotent() {
+			let mut store = store();
+			SqliteEventStore::migrate(store.connection.get_mut().unwrap()).unwrap();
+
+			assert_eq!(store.last_block_number().unwrap(), None);
+		}
+	}
+
+	mod insert_events {
+		use super::*;
+
+		#[test]
+		fn stored_columns() {
+			let mut store = store();
+			store
+				.insert_events(&[SwapEventBuilder::default()
+					.block_number(15_000_000)
+					.log_index(7)
+					.direction(SwapDirection::Token1ToToken0)
+					.token0_amount(Decimal::new(400, 2))
+					.token1_amount(Decimal::new(500, 2))
+					.build()])
+				.unwrap();
+
+			let row: (u64, Option<String>, u64, String, String, String, String) = store
+				.connection
+				.lock()
+				.unwrap()
+				.query_row(
+					"SELECT block_number, tx_hash, log_index, direction, token0_amount, \
+					 token1_amount, exchange_rate FROM swaps",
+					[],
+					|row| {
+						Ok((
+							row.get(0)?,
+							row.get(1)?,
+							row.get(2)?,
+							row.get(3)?,
+							row.get(4)?,
+							row.get(5)?,
+							row.get(6)?,
+						))
+					},
+				)
+				.unwrap();
+
+			assert_eq!(
+				row,
+				(
+					15_000_000,
+					None,
+					7,
+					"token1_to_token0".to_string(),
+					"4.00".to_string(),
+					"5.00".to_string(),
+					"1.25".to_string()
+				)
+			);
+		}
+	}
+
+	mod last_block_number {
+		use super::*;
+
+		#[test]
+		fn empty() {
+			assert_eq!(store().last_block_number().unwrap(), None);
+		}
+
+		#[test]
+		fn highest() {
+			let mut store = store();
+			let event =
+				|block_number| SwapEventBuilder::default().block_number(block_number).build();
+			store.insert_events(&[event(12), event(10)]).unwrap();
+			store.insert_events(&[event(11)]).unwrap();
+
+			assert_eq!(store.last_block_number().unwrap(), Some(12));
+		}
+	}
+
+	mod event_store {
+		use super::*;
+		use crate::event::TokenInfo;
+		use web3::types::{Address, H256};
+
+		fn event(block_number: u64, direction: SwapDirection, token0_amount: i64) -> SwapEvent {
+			SwapEventBuilder::default()
+				.block_number(block_number)
+				.transaction_hash(H256::from_low_u64_be(block_number))
+				.log_index(1)
+				.pool(Address::repeat_byte(0xa))
+				.sender(Address::repeat_byte(1))
+				.receiver(Address::repeat_byte(2))
+				.direction(direction)
+				.token0_amount(Decimal::new(token0_amount, 2))
+				.token1_amount(Decimal::new(token0_amount - 1, 2))
+				.build()
+		}
+
+		// Stores swaps out of block order and queries the ones of at least 100 of token0
+		fn query_filtered(store: &dyn EventStore) -> Vec<SwapEvent> {
+			store.store(&event(11, SwapDirection::Token1ToToken0, 20_000)).unwrap();
+			store.store(&event(10, SwapDirection::Token0ToToken1, 10_000)).unwrap();
+			store.store(&event(12, SwapDirection::Token0ToToken1, 5_000)).unwrap();
+
+			let filter =
+				SwapFilter { min_token0_amount: Some(Decimal::from(100)), ..SwapFilter::default() };
+			store.query(&filter).unwrap()
+		}
+
+		#[test]
+		fn sqlite() {
+			let store = store();
+
+			assert_eq!(
+				query_filtered(&store),
+				vec![
+					event(10, SwapDirection::Token0ToToken1, 10_000),
+					event(11, SwapDirection::Token1ToToken0, 20_000)
+				]
+			);
+			assert_eq!(store.count().unwrap(), 3);
+			assert_eq!(store.query(&SwapFilter::default()).unwrap().len(), 3);
+		}
+
+		#[test]
+		fn in_memory() {
+			let store = InMemoryEventStore::new();
+
+			assert_eq!(
+				query_filtered(&store),
+				vec![
+					event(10, SwapDirection::Token0ToToken1, 10_000),
+					event(11, SwapDirection::Token1ToToken0, 20_000)
+				]
+			);
+			assert_eq!(store.count().unwrap(), 3);
+		}
+
+		#[test]
+		fn sqlite_conditions() {
+			let store = store();
+			let other = Address::repeat_byte(3);
+			store.store(&event(10, SwapDirection::Token0ToToken1, 10_000)).unwrap();
+			store.store(&event(11, SwapDirection::Token1ToToken0, 10_000)).unwrap();
+			store
+				.store(&SwapEvent {
+					sender: other,
+					..event(12, SwapDirection::Token0ToToken1, 10_000)
+				})
+				.unwrap();
+			store.store(&event(13, SwapDirection::Token0ToToken1, 10_001)).unwrap();
+
+			let filter = SwapFilter {
+				min_token0_amount: Some(Decimal::from(100)),
+				max_token0_amount: Some(Decimal::new(10_000, 2)),
+				direction: Some(SwapDirection::Token0ToToken1),
+				sender_whitelist: Some(vec![Address::repeat_byte(1)]),
+				receiver_whitelist: None,
+			};
+			let block_numbers = |filter: &SwapFilter| {
+				let events = store.query(filter).unwrap();
+				events.into_iter().filter_map(|event| event.block_number).collect::<Vec<_>>()
+			};
+
+			assert_eq!(block_numbers(&filter), vec![10]);
+			assert_eq!(
+				block_numbers(&SwapFilter { sender_whitelist: Some(vec![]), ..filter.clone() }),
+				Vec::<u64>::new()
+			);
+			assert_eq!(
+				block_numbers(&SwapFilter {
+					receiver_whitelist: Some(vec![Address::repeat_byte(2), other]),
+					..SwapFilter::default()
+				}),
+				vec![10, 11, 12, 13]
+			);
+		}
+
+		#[test]
+		fn token_pair() {
+			let store = store();
+			let pair = Arc::new(TokenPair {
+				token0: TokenInfo { symbol: "USDC".to_string(), decimals: 6 },
+				token1: TokenInfo { symbol: "WETH".to_string(), decimals: 18 },
+			});
+			let mut event = event(10, SwapDirection::Token0ToToken1, 10_000);
+			event.amounts.pair = pair.clone();
+			store.store(&event).unwrap();
+
+			assert_eq!(store.query(&SwapFilter::default()).unwrap()[0].amounts.pair, pair);
+		}
+
+		#[test]
+		fn empty() {
+			assert_eq!(store().count().unwrap(), 0);
+			assert_eq!(InMemoryEventStore::new().query(&SwapFilter::default()).unwrap(), vec![]);
+		}
+	}
+}
@@ -0,0 +1,245 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tracing::info_span;
+use web3::types::{Filter, FilterBuilder, Log, H160, H256};
+
+use crate::{
+	abi::ContractAbi,
+	analytics::{self, VolumeSnapshot},
+	buffer::{ReorganizingBuffer, ReorganizingBufferError},
+	config::PoolConfig,
+	erc20::{TransactionContext, TransactionGrouper},
+	event::{SwapEvent, TokenPair},
+	parser::{EventParser, ParseContext},
+	transport::BlockHead,
+};
+
+// Confirmed block of a pool with its swaps grouped by transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockContext {
+	pub block_number: u64,
+	pub block_hash: H256,
+	pub timestamp: u64,
+	// In log order, swaps without a transaction hash are left out
+	pub transactions: Vec<TransactionContext>,
+	pub volume_snapshot: VolumeSnapshot,
+}
+
+impl BlockContext {
+	pub fn new(head: BlockHead, mut events: Vec<SwapEvent>) -> BlockContext {
+		events.sort();
+		let volume_snapshot =
+			VolumeSnapshot { block_number: head.number, ..analytics::aggregate(&events) };
+
+		let mut grouper = TransactionGrouper::new();
+		let mut transactions: Vec<_> =
+			events.into_iter().filter_map(|event| grouper.push_swap(event)).collect();
+		transactions.extend(grouper.finish());
+
+		BlockContext {
+			block_number: head.number,
+			block_hash: head.hash,
+			timestamp: head.timestamp,
+			transactions,
+			volume_snapshot,
+		}
+	}
+
+	// Swaps of all transactions in log order
+	pub fn swaps(&self) -> impl Iterator<Item = &SwapEvent> {
+		self.transactions.iter().flat_map(|transaction| &transaction.swaps)
+	}
+}
+
+// Swaps of a single pool, held back until their block is confirmed
+pub struct PoolSubscription {
+	pub pool_config: PoolConfig,
+	address: H160,
+	pair: Arc<TokenPair>,
+	abi: ContractAbi,
+	buffer: ReorganizingBuffer<SwapEvent>,
+	parser: &'static dyn EventParser,
+}
+
+impl PoolSubscription {
+	pub fn new(
+		pool_config: PoolConfig,
+		abi: ContractAbi,
+		confirmation_depth: usize,
+		parser: &'static dyn EventParser,
+	) -> Result<PoolSubscription> {
+		Ok(PoolSubscription {
+			address: pool_config.contract_address()?,
+			pair: pool_config.token_pair(),
+			pool_config,
+			abi,
+			buffer: ReorganizingBuffer::new(confirmation_depth),
+			parser,
+		})
+	}
+
+	pub fn address(&self) -> H160 {
+		self.address
+	}
+
+	pub fn pair(&self) -> &Arc<TokenPair> {
+		&self.pair
+	}
+
+	pub fn swap_topic(&self) -> H256 {
+		self.abi.swap_event.signature()
+	}
+
+	// Whether the log is a swap of this pool, as defined by the pool's ABI
+	pub fn matches(&self, log: &Log) -> bool {
+		log.address == self.address && log.topics.first() == Some(&self.swap_topic())
+	}
+
+	pub fn filter(&self, builder: FilterBuilder) -> Filter {
+		builder
+			.address(vec![self.address])
+			.topics(Some(vec![self.swap_topic()]), None, None, None)
+			.build()
+	}
+
+	pub fn parse(&self, log: Log, timestamp: Option<u64>) -> Result<SwapEvent> {
+		let _entered = info_span!("parse_log", log_index = ?log.log_index).entered();
+		let context = ParseContext::of(&log);
+		let event = self.parser.parse(log, &self.abi.swap_event, &self.pair).map_err(context)?;
+		Ok(SwapEvent { timestamp, ..event })
+	}
+
+	// Returns the block confirmed by this one, if any
+	pub fn push(
+		&mut self,
+		block_number: u64,
+		events: Vec<SwapEvent>,
+	) -> Result<Option<(u64, Vec<SwapEvent>)>, ReorganizingBufferError> {
+		self.buffer.push((block_number, events))
+	}
+
+	// Continues with the blocks that waited for confirmation before a restart
+	pub fn restore(
+		&mut self,
+		blocks: Vec<(u64, Vec<SwapEvent>)>,
+	) -> Result<(), ReorganizingBufferError> {
+		self.buffer = ReorganizingBuffer::from_snapshot(self.buffer.depth, blocks)?;
+		Ok(())
+	}
+
+	// Number of blocks waiting for confirmation
+	pub fn pending_blocks(&self) -> usize {
+		self.buffer.len()
+	}
+
+	pub fn first_pending_block(&self) -> Option<u64> {
+		self.buffer.first_queued_block()
+	}
+
+	pub fn pending_events(&self) -> usize {
+		self.buffer.window().map(|(_, events)| events.len()).sum()
+	}
+
+	pub fn snapshot(&self) -> Vec<(u64, Vec<SwapEvent>)> {
+		self.buffer.snapshot()
+	}
+
+	pub fn drain(&mut self) -> Vec<(u64, Vec<SwapEvent>)> {
+		self.buffer.drain()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		parser::Protocol,
+		testing::{swap_event_abi, SwapLogBuilder},
+	};
+
+	fn subscription() -> PoolSubscription {
+		PoolSubscription::new(
+			PoolConfig::default(),
+			ContractAbi::embedded(Protocol::V3).unwrap(),
+			1,
+			Protocol::V3.parser(),
+		)
+		.unwrap()
+	}
+
+	mod parse {
+		use super::*;
+		use crate::{event::SwapDirection, network::Network};
+		use rust_decimal::Decimal;
+
+		#[test]
+		fn error_context() {
+			let log = SwapLogBuilder::new(&swap_event_abi())
+				.block_number(123)
+				.transaction_hash(H256::from_low_u64_be(0xabcd))
+				.amount0(1)
+				.amount1(-1)
+				.build();
+
+			let err = subscription().parse(log, None).unwrap_err();
+
+			assert_eq!(err.to_string(), "Failed to parse swap in block 123, tx 0x0000…abcd");
+		}
+
+		// USDC is token0 of the Polygon pool, so amount0 has 6 decimals
+		#[test]
+		fn polygon_token_order() {
+			let subscription = PoolSubscription::new(
+				Network::Polygon.config().unwrap().pool,
+				ContractAbi::embedded(Protocol::V3).unwrap(),
+				1,
+				Protocol::V3.parser(),
+			)
+			.unwrap();
+			let log = SwapLogBuilder::new(&swap_event_abi())
+				.amount0(1_000_000_000)
+				.amount1(-999_500_000_000_000_000_000)
+				.build();
+
+			let event = subscription.parse(log, None).unwrap();
+			let (token_in, token_out) = event.direction.tokens(&event.amounts.pair);
+
+			assert_eq!(event.direction, SwapDirection::Token0ToToken1);
+			assert_eq!((token_in.symbol.as_str(), token_out.symbol.as_str()), ("USDC", "DAI"));
+			assert_eq!(event.amounts.token0_amount, Decimal::from(1000));
+			assert_eq!(event.amounts.token1_amount, Decimal::new(9995, 1));
+		}
+	}
+
+	mod block_context {
+		use super::*;
+		use crate::event::SwapEventBuilder;
+
+		#[test]
+		fn groups_transactions() {
+			let swap = |transaction_hash: u64, log_index| {
+				SwapEventBuilder::default()
+					.transaction_hash(H256::from_low_u64_be(transaction_hash))
+					.log_index(log_index)
+					.build()
+			};
+			let head =
+				BlockHead { number: 100, hash: H256::from([1; 32]), timestamp: 1_700_000_000 };
+
+			let context = BlockContext::new(head, vec![swap(2, 2), swap(1, 0), swap(1, 1)]);
+
+			assert_eq!(context.block_number, 100);
+			assert_eq!(context.block_hash, H256::from([1; 32]));
+			assert_eq!(context.timestamp, 1_700_000_000);
+			assert_eq!(context.transactions.len(), 2);
+			assert_eq!(context.transactions[0].transaction_hash, H256::from_low_u64_be(1));
+			assert_eq!(context.transactions[0].swaps.len(), 2);
+			assert_eq!(context.volume_snapshot.block_number, 100);
+			assert_eq!(context.volume_snapshot.swap_count, 3);
+			assert_eq!(
+				context.swaps().filter_map(|swap| swap.log_index).collect::<Vec<_>>(),
+				vec![0, 1, 2]
+			);
+		}
+	}
+}
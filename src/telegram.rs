@@ -0,0 +1,322 @@
+use anyhow::{Context, Result};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde_json::json;
+use std::{collections::BTreeMap, time::Duration};
+use tokio::sync::broadcast::{
+	self,
+	error::{RecvError, TryRecvError},
+};
+use tracing::warn;
+
+use crate::{
+	analytics::WhaleDetector,
+	event::{SwapDirection, SwapEvent},
+};
+
+pub const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// Sends whale trades among the confirmed events to a Telegram chat, one message per block
+pub struct TelegramNotifier {
+	client: reqwest::Client,
+	// sendMessage endpoint of the bot
+	url: String,
+	chat_id: String,
+	whale_detector: WhaleDetector,
+	retry_delay: Duration,
+}
+
+impl TelegramNotifier {
+	pub fn new(
+		api_url: &str,
+		bot_token: &str,
+		chat_id: String,
+		whale_detector: WhaleDetector,
+	) -> TelegramNotifier {
+		TelegramNotifier {
+			client: reqwest::Client::new(),
+			url: format!("{}/bot{}/sendMessage", api_url, bot_token),
+			chat_id,
+			whale_detector,
+			retry_delay: RETRY_DELAY,
+		}
+	}
+
+	// Runs until the event channel is closed. Events already waiting are sent along with the
+	// received one, so the events of a block share a message.
+	pub async fn run(self, mut events: broadcast::Receiver<SwapEvent>) {
+		loop {
+			let mut whales = Vec::new();
+			let mut closed = false;
+
+			match events.recv().await {
+				Ok(event) => whales.push(event),
+				Err(RecvError::Lagged(skipped)) =>
+					warn!(skipped, "Telegram notifier lagging, skipped events"),
+				Err(RecvError::Closed) => return,
+			}
+			loop {
+				match events.try_recv() {
+					Ok(event) => whales.push(event),
+					Err(TryRecvError::Lagged(skipped)) =>
+						warn!(skipped, "Telegram notifier lagging, skipped events"),
+					Err(TryRecvError::Empty) => break,
+					Err(TryRecvError::Closed) => {
+						closed = true;
+						break;
+					},
+				}
+			}
+			whales.retain(|event| self.whale_detector.is_whale(event));
+
+			for text in messages(&whales) {
+				if let Err(err) = self.send(&text).await {
+					warn!(error = %err, "Failed to send swaps to Telegram");
+				}
+			}
+			if closed {
+				return;
+			}
+		}
+	}
+
+	// Retries once
+	async fn send(&self, text: &str) -> Result<()> {
+		if let Err(err) = self.try_send(text).await {
+			warn!(error = %err, "Failed to send swaps to Telegram, retrying");
+			tokio::time::sleep(self.retry_delay).await;
+			self.try_send(text).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn try_send(&self, text: &str) -> Result<()> {
+		self.client
+			.post(&self.url)
+			.json(&json!({ "chat_id": self.chat_id, "text": text }))
+			.send()
+			.await
+			.and_then(|response| response.error_for_status())
+			.context("Failed to call Telegram sendMessage")?;
+
+		Ok(())
+	}
+}
+
+// One message per block, with a line per swap
+pub fn messages(events: &[SwapEvent]) -> Vec<String> {
+	let mut blocks = BTreeMap::<Option<u64>, Vec<String>>::new();
+	for event in events {
+		blocks.entry(event.block_number).or_default().push(line(event));
+	}
+
+	blocks.into_values().map(|lines| lines.join("\n")).collect()
+}
+
+// e.g. 🟢 $15,851.87 DAI → $15,850.37 USDC | Block 18234567 | 0x1234…abcd
+pub fn line(event: &SwapEvent) -> String {
+	let token0 =
+		format!("{} {}", usd(event.amounts.token0_amount), event.amounts.pair.token0.symbol);
+	let token1 =
+		format!("{} {}", usd(event.amounts.token1_amount), event.amounts.pair.token1.symbol);
+	let swap = match event.direction {
+		SwapDirection::Token0ToToken1 => format!("🟢 {} → {}", token0, token1),
+		SwapDirection::Token1ToToken0 => format!("🔴 {} → {}", token1, token0),
+	};
+
+	let mut line = swap;
+	if let Some(block_number) = event.block_number {
+		line += &format!(" | Block {}", block_number);
+	}
+	if let Some(hash) = event.transaction_hash {
+		let hash = format!("{:?}", hash);
+		line += &format!(" | {}…{}", &hash[..6], &hash[hash.len() - 4..]);
+	}
+	line
+}
+
+// Dollar amount with two decimal places and thousands separators
+fn usd(amount: Decimal) -> String {
+	let amount = amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+	let formatted = format!("{:.2}", amount.abs());
+	let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, "00"));
+
+	let mut grouped = String::new();
+	for (i, digit) in integer.chars().enumerate() {
+		if i > 0 && (integer.len() - i) % 3 == 0 {
+			grouped.push(',');
+		}
+		grouped.push(digit);
+	}
+
+	let sign = if amount.is_sign_negative() && !amount.is_zero() { "-" } else { "" };
+	format!("{}${}.{}", sign, grouped, fraction)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+	use std::{
+		net::SocketAddr,
+		sync::{Arc, Mutex},
+	};
+	use web3::types::H256;
+
+	fn whale_detector() -> WhaleDetector {
+		WhaleDetector { token1_threshold: Decimal::new(10_000, 0) }
+	}
+
+	fn swap(block_number: u64, usdc: i64) -> SwapEvent {
+		SwapEventBuilder::default()
+			.block_number(block_number)
+			.direction(SwapDirection::Token0ToToken1)
+			.token0_amount(Decimal::new(usdc, 2))
+			.token1_amount(Decimal::new(usdc, 2))
+			.build()
+	}
+
+	// Records the messages sent by the bot, failing the first `failures` requests
+	async fn serve(failures: usize) -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+		#[derive(Clone)]
+		struct Bot {
+			failures: Arc<Mutex<usize>>,
+			messages: Arc<Mutex<Vec<serde_json::Value>>>,
+		}
+
+		let bot = Bot {
+			failures: Arc::new(Mutex::new(failures)),
+			messages: Arc::new(Mutex::new(Vec::new())),
+		};
+		let app = Router::new()
+			.route(
+				"/botTOKEN/sendMessage",
+				post(|State(bot): State<Bot>, Json(payload): Json<serde_json::Value>| async move {
+					let mut failures = bot.failures.lock().unwrap();
+					if *failures > 0 {
+						*failures -= 1;
+						return StatusCode::TOO_MANY_REQUESTS;
+					}
+					bot.messages.lock().unwrap().push(payload);
+					StatusCode::OK
+				}),
+			)
+			.with_state(bot.clone());
+
+		let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+			.serve(app.into_make_service());
+		let addr = server.local_addr();
+		tokio::spawn(server);
+
+		(format!("http://{}", addr), bot.messages)
+	}
+
+	mod line {
+		use super::*;
+
+		#[test]
+		fn token0_to_token1() {
+			let event = SwapEventBuilder::default()
+				.block_number(18_234_567)
+				.transaction_hash(H256::from_low_u64_be(0xabcd))
+				.direction(SwapDirection::Token0ToToken1)
+				.token0_amount(Decimal::new(1_585_187, 2))
+				.token1_amount(Decimal::new(1_585_037, 2))
+				.build();
+
+			assert_eq!(
+				line(&event),
+				"🟢 $15,851.87 DAI → $15,850.37 USDC | Block 18234567 | 0x0000…abcd"
+			);
+		}
+
+		#[test]
+		fn token1_to_token0() {
+			let event = SwapEventBuilder::default()
+				.direction(SwapDirection::Token1ToToken0)
+				.token0_amount(Decimal::new(1_000_000_005, 3))
+				.token1_amount(Decimal::new(999, 0))
+				.build();
+
+			assert_eq!(line(&event), "🔴 $999.00 USDC → $1,000,000.01 DAI");
+		}
+	}
+
+	mod messages {
+		use super::*;
+
+		#[test]
+		fn per_block() {
+			let events = [swap(2, 1_000_000), swap(1, 2_000_000), swap(2, 3_000_000)];
+
+			assert_eq!(
+				messages(&events),
+				vec![
+					"🟢 $20,000.00 DAI → $20,000.00 USDC | Block 1".to_string(),
+					"🟢 $10,000.00 DAI → $10,000.00 USDC | Block 2\n🟢 $30,000.00 DAI → $30,000.00 \
+					 USDC | Block 2"
+						.to_string(),
+				]
+			);
+		}
+	}
+
+	mod run {
+		use super::*;
+
+		fn notifier(api_url: &str) -> TelegramNotifier {
+			TelegramNotifier {
+				retry_delay: Duration::ZERO,
+				..TelegramNotifier::new(api_url, "TOKEN", "42".to_string(), whale_detector())
+			}
+		}
+
+		#[tokio::test]
+		async fn batches_whales() {
+			let (url, messages) = serve(0).await;
+			let (sender, receiver) = broadcast::channel(16);
+
+			sender.send(swap(1, 2_000_000)).unwrap();
+			sender.send(swap(1, 100)).unwrap();
+			sender.send(swap(1, 3_000_000)).unwrap();
+			drop(sender);
+			notifier(&url).run(receiver).await;
+
+			let messages = messages.lock().unwrap();
+			assert_eq!(messages.len(), 1);
+			assert_eq!(messages[0]["chat_id"], "42");
+			assert_eq!(messages[0]["text"].as_str().unwrap().lines().count(), 2);
+		}
+
+		#[tokio::test]
+		async fn retries_once() {
+			let (url, messages) = serve(1).await;
+			let (sender, receiver) = broadcast::channel(16);
+
+			sender.send(swap(1, 2_000_000)).unwrap();
+			drop(sender);
+			notifier(&url).run(receiver).await;
+
+			assert_eq!(messages.lock().unwrap().len(), 1);
+		}
+
+		#[tokio::test]
+		async fn gives_up_after_retry() {
+			let (url, messages) = serve(2).await;
+			let (sender, receiver) = broadcast::channel(16);
+
+			sender.send(swap(1, 2_000_000)).unwrap();
+			sender.send(swap(2, 2_000_000)).unwrap();
+			drop(sender);
+			notifier(&url).run(receiver).await;
+
+			// Both attempts of the first block fail, the second block is still sent
+			let messages = messages.lock().unwrap();
+			assert_eq!(messages.len(), 1);
+			assert!(messages[0]["text"].as_str().unwrap().contains("Block 2"));
+		}
+	}
+}
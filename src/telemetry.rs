@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+const SERVICE_NAME: &str = "uniswap-monitor";
+
+// Exports spans in batches over OTLP/gRPC, must be created within a Tokio runtime
+pub fn otlp_layer<S>(endpoint: &str) -> Result<impl Layer<S>>
+where
+	S: Subscriber + for<'span> LookupSpan<'span>,
+{
+	let tracer = opentelemetry_otlp::new_pipeline()
+		.tracing()
+		.with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+		.with_trace_config(
+			trace::config()
+				.with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)])),
+		)
+		.install_batch(runtime::Tokio)
+		.with_context(|| format!("Failed to set up OTLP exporter for '{}'", endpoint))?;
+
+	Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+// Flushes spans that are still waiting in the batch exporter
+pub fn shutdown() {
+	opentelemetry::global::shutdown_tracer_provider();
+}
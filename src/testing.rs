@@ -0,0 +1,600 @@
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use jsonrpc_core::{Call, Params};
+use std::{
+	collections::{HashMap, VecDeque},
+	io::{self, Write},
+	pin::Pin,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Mutex,
+	},
+	task::{Context, Poll},
+};
+use web3::{
+	ethabi::{self, Contract, Event, Token},
+	helpers,
+	types::{
+		Address, BlockHeader, BlockNumber, Bytes, CallRequest, Filter, Log, TransactionReceipt,
+		H256, U256, U64,
+	},
+	RequestId, Transport,
+};
+
+use crate::transport::{BlockHead, BlockSource};
+
+const POOL_ABI: &[u8] = include_bytes!("contracts/uniswap_pool_abi.json");
+const FACTORY_ABI: &[u8] = include_bytes!("contracts/uniswap_factory_abi.json");
+const V2_PAIR_ABI: &[u8] = include_bytes!("contracts/uniswap_v2_pair_abi.json");
+const ERC20_ABI: &[u8] = include_bytes!("contracts/erc20_abi.json");
+
+// Replays pre-configured block headers and answers log queries from an in-memory store
+#[derive(Debug, Default)]
+pub struct MockWeb3Provider {
+	headers: VecDeque<BlockHeader>,
+	logs: HashMap<H256, Vec<Log>>,
+	// Latest queued block of each number, served by `block_head`
+	heads: HashMap<u64, BlockHead>,
+	// Last block returned by `next_block`
+	current: Option<u64>,
+	// Blocks the safe and finalized blocks are behind the current one
+	finality_lag: u64,
+	// `eth_call` outputs by contract and function selector, like in `MockTransport`
+	calls: HashMap<(Address, [u8; 4]), Vec<u8>>,
+	// Whether `next_block` waits forever once all blocks were returned, like a live node
+	endless: bool,
+}
+
+impl MockWeb3Provider {
+	pub fn new() -> MockWeb3Provider {
+		MockWeb3Provider::default()
+	}
+
+	// Queues a block whose logs get their block number and hash filled in from the header
+	pub fn with_block(mut self, header: BlockHeader, logs: Vec<Log>) -> MockWeb3Provider {
+		let hash = header.hash.expect("Mock block header without hash");
+		let logs = logs
+			.into_iter()
+			.map(|log| Log { block_hash: Some(hash), block_number: header.number, ..log })
+			.collect();
+
+		self.logs.insert(hash, logs);
+		if let Some(number) = header.number {
+			let head =
+				BlockHead { number: number.as_u64(), hash, timestamp: header.timestamp.as_u64() };
+			self.heads.insert(head.number, head);
+		}
+		self.headers.push_back(header);
+		self
+	}
+
+	pub fn with_finality_lag(mut self, finality_lag: u64) -> MockWeb3Provider {
+		self.finality_lag = finality_lag;
+		self
+	}
+
+	pub fn endless(mut self) -> MockWeb3Provider {
+		self.endless = true;
+		self
+	}
+
+	pub fn with_call(
+		mut self,
+		to: Address,
+		selector: [u8; 4],
+		output: Vec<u8>,
+	) -> MockWeb3Provider {
+		self.calls.insert((to, selector), output);
+		self
+	}
+
+	// Answers the ERC-20 `symbol` and `decimals` calls of the token
+	pub fn with_token(self, token: Address, symbol: &str, decimals: u32) -> MockWeb3Provider {
+		let abi = Contract::load(ERC20_ABI).unwrap();
+		let selector = |name| abi.function(name).unwrap().short_signature();
+
+		self.with_call(token, selector("symbol"), ethabi::encode(&[Token::String(symbol.into())]))
+			.with_call(token, selector("decimals"), ethabi::encode(&[Token::Uint(decimals.into())]))
+	}
+
+	fn logs_in_range(&self, from_block: u64, to_block: u64) -> Vec<Log> {
+		let mut logs: Vec<Log> = self
+			.logs
+			.values()
+			.flatten()
+			.filter(|log| {
+				log.block_number
+					.is_some_and(|number| (from_block..=to_block).contains(&number.as_u64()))
+			})
+			.cloned()
+			.collect();
+		logs.sort_by_key(|log| (log.block_number, log.log_index));
+		logs
+	}
+}
+
+impl Stream for MockWeb3Provider {
+	type Item = BlockHeader;
+
+	fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<BlockHeader>> {
+		Poll::Ready(self.headers.pop_front())
+	}
+}
+
+impl BlockSource for MockWeb3Provider {
+	async fn next_block(&mut self) -> Option<BlockHead> {
+		let header = match self.next().await {
+			Some(header) => header,
+			None if self.endless => std::future::pending().await,
+			None => return None,
+		};
+		self.current = header.number.map(|number| number.as_u64());
+
+		Some(BlockHead {
+			number: header.number?.as_u64(),
+			hash: header.hash?,
+			timestamp: header.timestamp.as_u64(),
+		})
+	}
+
+	// Only the block hash or block range and the addresses of the filter are taken into account
+	async fn logs(&mut self, filter: Filter) -> Result<Vec<Log>> {
+		let filter = serde_json::to_value(filter)?;
+		let addresses: Option<Vec<Address>> = match &filter["address"] {
+			serde_json::Value::Null => None,
+			serde_json::Value::Array(_) => Some(serde_json::from_value(filter["address"].clone())?),
+			address => Some(vec![serde_json::from_value(address.clone())?]),
+		};
+		let matches = |log: &Log| {
+			addresses.as_ref().map_or(true, |addresses| addresses.contains(&log.address))
+		};
+		let block_number = |key: &str| -> Result<Option<u64>> {
+			filter[key]
+				.as_str()
+				.map(|number| Ok(u64::from_str_radix(number.trim_start_matches("0x"), 16)?))
+				.transpose()
+		};
+
+		let logs = match filter["blockHash"].as_str() {
+			Some(hash) => self.logs.get(&hash.parse()?).cloned().unwrap_or_default(),
+			None => self.logs_in_range(
+				block_number("fromBlock")?.unwrap_or(0),
+				block_number("toBlock")?.unwrap_or(u64::MAX),
+			),
+		};
+		Ok(logs.into_iter().filter(matches).collect())
+	}
+
+	async fn latest_block(&mut self) -> Result<u64> {
+		Ok(self
+			.headers
+			.iter()
+			.filter_map(|header| header.number)
+			.max()
+			.unwrap_or_default()
+			.as_u64())
+	}
+
+	async fn block_head(&mut self, number: u64) -> Result<BlockHead> {
+		self.heads
+			.get(&number)
+			.cloned()
+			.ok_or_else(|| anyhow::anyhow!("Block {} not found", number))
+	}
+
+	async fn tagged_block_head(&mut self, tag: BlockNumber) -> Result<BlockHead> {
+		let number = match tag {
+			BlockNumber::Number(number) => Some(number.as_u64()),
+			BlockNumber::Latest => self.current,
+			BlockNumber::Safe | BlockNumber::Finalized =>
+				self.current.and_then(|current| current.checked_sub(self.finality_lag)),
+			_ => None,
+		};
+
+		number
+			.and_then(|number| self.heads.get(&number))
+			.cloned()
+			.ok_or_else(|| anyhow::anyhow!("Block {:?} not found", tag))
+	}
+
+	// Calls of unknown functions return no data
+	async fn call(&mut self, request: CallRequest) -> Result<Bytes> {
+		let to = request.to.unwrap_or_default();
+		let selector = request.data.as_ref().and_then(|data| data.0.get(..4));
+		Ok(Bytes(
+			selector
+				.and_then(|selector| self.calls.get(&(to, selector.try_into().unwrap())))
+				.cloned()
+				.unwrap_or_default(),
+		))
+	}
+}
+
+// Answers `eth_call` requests with outputs registered per contract and function selector,
+// calls of unknown functions return no data like calls of accounts without code. Receipts of
+// unknown transactions are null.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+	calls: HashMap<(Address, [u8; 4]), Vec<u8>>,
+	receipts: HashMap<H256, TransactionReceipt>,
+	call_count: Arc<AtomicUsize>,
+	receipt_count: Arc<AtomicUsize>,
+}
+
+impl MockTransport {
+	pub fn with_call(mut self, to: Address, selector: [u8; 4], output: Vec<u8>) -> MockTransport {
+		self.calls.insert((to, selector), output);
+		self
+	}
+
+	pub fn with_receipt(mut self, receipt: TransactionReceipt) -> MockTransport {
+		self.receipts.insert(receipt.transaction_hash, receipt);
+		self
+	}
+
+	// Number of `eth_call` requests sent through this transport or any of its clones
+	pub fn call_count(&self) -> usize {
+		self.call_count.load(Ordering::SeqCst)
+	}
+
+	// Number of `eth_getTransactionReceipt` requests, like `call_count`
+	pub fn receipt_count(&self) -> usize {
+		self.receipt_count.load(Ordering::SeqCst)
+	}
+
+	fn receipt(&self, params: &[serde_json::Value]) -> web3::Result<serde_json::Value> {
+		let transaction_hash: H256 = params
+			.first()
+			.and_then(|hash| serde_json::from_value(hash.clone()).ok())
+			.ok_or_else(|| {
+				web3::Error::InvalidResponse(
+					"Invalid eth_getTransactionReceipt request".to_string(),
+				)
+			})?;
+		Ok(serde_json::to_value(self.receipts.get(&transaction_hash))?)
+	}
+
+	fn eth_call(&self, params: &[serde_json::Value]) -> web3::Result<serde_json::Value> {
+		let request: CallRequest = params
+			.first()
+			.and_then(|request| serde_json::from_value(request.clone()).ok())
+			.ok_or_else(|| web3::Error::InvalidResponse("Invalid eth_call request".to_string()))?;
+		let data = request.data.unwrap_or_default().0;
+
+		let output = match (request.to, data.get(..4)) {
+			(Some(to), Some(selector)) =>
+				self.calls.get(&(to, selector.try_into().unwrap())).cloned().unwrap_or_default(),
+			_ => Vec::new(),
+		};
+		Ok(serde_json::to_value(Bytes(output))?)
+	}
+}
+
+impl Transport for MockTransport {
+	type Out = futures::future::Ready<web3::Result<serde_json::Value>>;
+
+	fn prepare(&self, method: &str, params: Vec<serde_json::Value>) -> (RequestId, Call) {
+		(0, helpers::build_request(0, method, params))
+	}
+
+	fn send(&self, _: RequestId, request: Call) -> Self::Out {
+		let result = match request {
+			Call::MethodCall(call) if call.method == "eth_call" => {
+				self.call_count.fetch_add(1, Ordering::SeqCst);
+				match call.params {
+					Params::Array(params) => self.eth_call(&params),
+					_ => Err(web3::Error::InvalidResponse("Invalid eth_call params".to_string())),
+				}
+			},
+			Call::MethodCall(call) if call.method == "eth_getTransactionReceipt" => {
+				self.receipt_count.fetch_add(1, Ordering::SeqCst);
+				match call.params {
+					Params::Array(params) => self.receipt(&params),
+					_ => Err(web3::Error::InvalidResponse(
+						"Invalid eth_getTransactionReceipt params".to_string(),
+					)),
+				}
+			},
+			_ => Err(web3::Error::Unreachable),
+		};
+		futures::future::ready(result)
+	}
+}
+
+pub fn block_header(number: u64, hash: H256, timestamp: u64) -> BlockHeader {
+	BlockHeader {
+		hash: Some(hash),
+		parent_hash: H256::zero(),
+		uncles_hash: H256::zero(),
+		author: Address::zero(),
+		state_root: H256::zero(),
+		transactions_root: H256::zero(),
+		receipts_root: H256::zero(),
+		number: Some(U64::from(number)),
+		gas_used: U256::zero(),
+		gas_limit: U256::zero(),
+		base_fee_per_gas: None,
+		extra_data: Bytes::default(),
+		logs_bloom: Default::default(),
+		timestamp: U256::from(timestamp),
+		difficulty: U256::zero(),
+		mix_hash: None,
+		nonce: None,
+	}
+}
+
+pub fn swap_event_abi() -> Event {
+	Contract::load(POOL_ABI).unwrap().event("Swap").unwrap().clone()
+}
+
+pub fn pool_created_abi() -> Event {
+	Contract::load(FACTORY_ABI).unwrap().event("PoolCreated").unwrap().clone()
+}
+
+// PoolCreated log emitted by the Uniswap V3 factory
+pub fn pool_created_log(
+	abi: &Event,
+	token0: Address,
+	token1: Address,
+	fee: u32,
+	tick_spacing: i32,
+	pool: Address,
+) -> Log {
+	let topic = |token| H256::from_slice(&ethabi::encode(&[token]));
+
+	Log {
+		address: crate::factory::UNI_V3_FACTORY.parse().unwrap(),
+		topics: vec![
+			abi.signature(),
+			topic(Token::Address(token0)),
+			topic(Token::Address(token1)),
+			topic(Token::Uint(U256::from(fee))),
+		],
+		data: Bytes(ethabi::encode(&[
+			Token::Int(int256(tick_spacing.into())),
+			Token::Address(pool),
+		])),
+		block_hash: None,
+		block_number: None,
+		transaction_hash: None,
+		transaction_index: None,
+		log_index: None,
+		transaction_log_index: None,
+		log_type: None,
+		removed: None,
+	}
+}
+
+pub fn v2_swap_event_abi() -> Event {
+	Contract::load(V2_PAIR_ABI).unwrap().event("Swap").unwrap().clone()
+}
+
+pub fn v2_sync_event_abi() -> Event {
+	Contract::load(V2_PAIR_ABI).unwrap().event("Sync").unwrap().clone()
+}
+
+// Swap log emitted by a Uniswap V2 pair, amounts are in and out amounts of token0 and token1
+pub fn v2_swap_log(
+	abi: &Event,
+	pair: Address,
+	sender: Address,
+	to: Address,
+	[amount0_in, amount1_in, amount0_out, amount1_out]: [u128; 4],
+) -> Log {
+	let topic = |token| H256::from_slice(&ethabi::encode(&[token]));
+
+	Log {
+		address: pair,
+		topics: vec![abi.signature(), topic(Token::Address(sender)), topic(Token::Address(to))],
+		data: Bytes(ethabi::encode(&[
+			Token::Uint(amount0_in.into()),
+			Token::Uint(amount1_in.into()),
+			Token::Uint(amount0_out.into()),
+			Token::Uint(amount1_out.into()),
+		])),
+		block_hash: None,
+		block_number: None,
+		transaction_hash: None,
+		transaction_index: None,
+		log_index: None,
+		transaction_log_index: None,
+		log_type: None,
+		removed: None,
+	}
+}
+
+// Sync log emitted by a Uniswap V2 pair after every change of its reserves
+pub fn v2_sync_log(abi: &Event, pair: Address, reserve0: u128, reserve1: u128) -> Log {
+	Log {
+		address: pair,
+		topics: vec![abi.signature()],
+		data: Bytes(ethabi::encode(&[Token::Uint(reserve0.into()), Token::Uint(reserve1.into())])),
+		block_hash: None,
+		block_number: None,
+		transaction_hash: None,
+		transaction_index: None,
+		log_index: None,
+		transaction_log_index: None,
+		log_type: None,
+		removed: None,
+	}
+}
+
+// Transfer log emitted by an ERC-20 token
+pub fn transfer_log(abi: &Event, token: Address, from: Address, to: Address, value: u128) -> Log {
+	let topic = |token| H256::from_slice(&ethabi::encode(&[token]));
+
+	Log {
+		address: token,
+		topics: vec![abi.signature(), topic(Token::Address(from)), topic(Token::Address(to))],
+		data: Bytes(ethabi::encode(&[Token::Uint(value.into())])),
+		block_hash: None,
+		block_number: None,
+		transaction_hash: None,
+		transaction_index: None,
+		log_index: None,
+		transaction_log_index: None,
+		log_type: None,
+		removed: None,
+	}
+}
+
+// ABI-encodes Swap logs from high-level parameters, ethabi 18 has no `Event::encode_log` yet.
+// Defaults to a swap of 1 DAI for 1 USDC in the DAI/USDC pool.
+#[derive(Debug, Clone)]
+pub struct SwapLogBuilder<'a> {
+	abi: &'a Event,
+	address: Address,
+	block_number: Option<u64>,
+	transaction_hash: Option<H256>,
+	log_index: Option<u64>,
+	sender: Address,
+	recipient: Address,
+	amount0: i128,
+	amount1: i128,
+	sqrt_price_x96: U256,
+	liquidity: u128,
+	tick: i32,
+}
+
+impl<'a> SwapLogBuilder<'a> {
+	pub fn new(abi: &'a Event) -> SwapLogBuilder<'a> {
+		SwapLogBuilder {
+			abi,
+			address: "5777d92f208679db4b9778590fa3cab3ac9e2168".parse().unwrap(),
+			block_number: None,
+			transaction_hash: None,
+			log_index: None,
+			sender: Address::zero(),
+			recipient: Address::zero(),
+			amount0: 1_000_000_000_000_000_000,
+			amount1: -1_000_000,
+			sqrt_price_x96: U256::from_dec_str("79228162514264337593543").unwrap(),
+			liquidity: 0,
+			tick: -276_324,
+		}
+	}
+
+	pub fn address(mut self, address: Address) -> Self {
+		self.address = address;
+		self
+	}
+
+	pub fn block_number(mut self, block_number: u64) -> Self {
+		self.block_number = Some(block_number);
+		self
+	}
+
+	pub fn transaction_hash(mut self, transaction_hash: H256) -> Self {
+		self.transaction_hash = Some(transaction_hash);
+		self
+	}
+
+	pub fn log_index(mut self, log_index: u64) -> Self {
+		self.log_index = Some(log_index);
+		self
+	}
+
+	pub fn sender(mut self, sender: Address) -> Self {
+		self.sender = sender;
+		self
+	}
+
+	pub fn recipient(mut self, recipient: Address) -> Self {
+		self.recipient = recipient;
+		self
+	}
+
+	// Raw amount in the smallest token unit, positive amounts flow into the pool
+	pub fn amount0(mut self, amount0: i128) -> Self {
+		self.amount0 = amount0;
+		self
+	}
+
+	pub fn amount1(mut self, amount1: i128) -> Self {
+		self.amount1 = amount1;
+		self
+	}
+
+	pub fn sqrt_price_x96(mut self, sqrt_price_x96: U256) -> Self {
+		self.sqrt_price_x96 = sqrt_price_x96;
+		self
+	}
+
+	pub fn liquidity(mut self, liquidity: u128) -> Self {
+		self.liquidity = liquidity;
+		self
+	}
+
+	pub fn tick(mut self, tick: i32) -> Self {
+		self.tick = tick;
+		self
+	}
+
+	pub fn build(self) -> Log {
+		let mut tokens = HashMap::from([
+			("sender", Token::Address(self.sender)),
+			("recipient", Token::Address(self.recipient)),
+			("amount0", Token::Int(int256(self.amount0))),
+			("amount1", Token::Int(int256(self.amount1))),
+			("sqrtPriceX96", Token::Uint(self.sqrt_price_x96)),
+			("liquidity", Token::Uint(U256::from(self.liquidity))),
+			("tick", Token::Int(int256(self.tick.into()))),
+		]);
+
+		let mut topics = vec![self.abi.signature()];
+		let mut data = Vec::new();
+		for input in &self.abi.inputs {
+			let token = tokens.remove(input.name.as_str()).expect("Unknown Swap event parameter");
+			if input.indexed {
+				topics.push(H256::from_slice(&ethabi::encode(&[token])));
+			} else {
+				data.push(token);
+			}
+		}
+
+		Log {
+			address: self.address,
+			topics,
+			data: Bytes(ethabi::encode(&data)),
+			block_hash: None,
+			block_number: self.block_number.map(U64::from),
+			transaction_hash: self.transaction_hash,
+			transaction_index: None,
+			log_index: self.log_index.map(U256::from),
+			transaction_log_index: None,
+			log_type: None,
+			removed: None,
+		}
+	}
+}
+
+// Sign-extends `n` to 256 bits like the ABI encoding of signed integers
+pub fn int256(n: i128) -> U256 {
+	let magnitude = U256::from(n.unsigned_abs());
+	if n < 0 {
+		(!magnitude).overflowing_add(U256::one()).0
+	} else {
+		magnitude
+	}
+}
+
+// In-memory writer whose contents stay readable after it has been handed to the pipeline
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+	pub fn contents(&self) -> String {
+		String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+	}
+}
+
+impl Write for SharedBuffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
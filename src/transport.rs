@@ -0,0 +1,695 @@
+use anyhow::{anyhow, bail, Context, Result};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::{
+	collections::VecDeque,
+	future::Future,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+use tracing::{info, warn};
+use web3::{
+	api::SubscriptionStream,
+	transports::{Http, WebSocket},
+	types::{Block, BlockHeader, BlockId, BlockNumber, Bytes, CallRequest, Filter, Log, H256, U64},
+	Transport, Web3,
+};
+
+use crate::{health::HealthState, rate_limit::SharedRateLimiter, retry::RetryPolicy};
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+type BlockStream = SubscriptionStream<WebSocket, BlockHeader>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+	#[default]
+	Ws,
+	Http,
+}
+
+// Which blocks are followed: the latest ones, whose events are confirmed once buried under a number
+// of blocks, or the ones the consensus layer marked safe or finalized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finality {
+	Probabilistic { confirmations: usize },
+	Safe,
+	Finalized,
+}
+
+impl Finality {
+	// Tag of the followed block, `None` for the latest block
+	pub fn block_tag(&self) -> Option<BlockNumber> {
+		match self {
+			Finality::Probabilistic { .. } => None,
+			Finality::Safe => Some(BlockNumber::Safe),
+			Finality::Finalized => Some(BlockNumber::Finalized),
+		}
+	}
+
+	// Safe and finalized blocks are not expected to be reorganized, so their events are confirmed
+	// right away
+	pub fn confirmation_depth(&self) -> usize {
+		match self {
+			Finality::Probabilistic { confirmations } => *confirmations,
+			Finality::Safe | Finality::Finalized => 0,
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalityKind {
+	#[default]
+	Probabilistic,
+	Safe,
+	Finalized,
+}
+
+impl FinalityKind {
+	// Confirmations only apply to probabilistic finality
+	pub fn with_confirmations(self, confirmations: usize) -> Finality {
+		match self {
+			FinalityKind::Probabilistic => Finality::Probabilistic { confirmations },
+			FinalityKind::Safe => Finality::Safe,
+			FinalityKind::Finalized => Finality::Finalized,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHead {
+	pub number: u64,
+	pub hash: H256,
+	pub timestamp: u64,
+}
+
+pub trait BlockSource {
+	// Returns `None` once the source has no more blocks to offer
+	fn next_block(&mut self) -> impl Future<Output = Option<BlockHead>>;
+
+	fn logs(&mut self, filter: Filter) -> impl Future<Output = Result<Vec<Log>>>;
+
+	fn latest_block(&mut self) -> impl Future<Output = Result<u64>>;
+
+	// Header of a past block, for blocks that weren't received through `next_block`
+	fn block_head(&mut self, number: u64) -> impl Future<Output = Result<BlockHead>>;
+
+	// Header of the block a tag like `safe` or `finalized` currently refers to
+	fn tagged_block_head(&mut self, tag: BlockNumber) -> impl Future<Output = Result<BlockHead>>;
+
+	// Output of a contract call at the latest block
+	fn call(&mut self, request: CallRequest) -> impl Future<Output = Result<Bytes>>;
+}
+
+// Contract calls at the latest block, through a block source or a plain connection before any
+// source is set up
+pub trait ContractCaller {
+	fn call_contract(&mut self, request: CallRequest) -> impl Future<Output = Result<Bytes>>;
+}
+
+impl<S: BlockSource> ContractCaller for S {
+	async fn call_contract(&mut self, request: CallRequest) -> Result<Bytes> {
+		self.call(request).await
+	}
+}
+
+impl<T: Transport> ContractCaller for Web3<T> {
+	async fn call_contract(&mut self, request: CallRequest) -> Result<Bytes> {
+		RetryPolicy::default()
+			.execute(|| async { Ok(self.eth().call(request.clone(), None).await?) })
+			.await
+	}
+}
+
+// WebSocket endpoints used in round-robin order, so a rate-limited or unavailable endpoint is
+// followed by the next one
+#[derive(Debug)]
+pub struct RpcConnectionPool {
+	endpoints: Vec<String>,
+	// Endpoint of the next connection, modulo the number of endpoints
+	current: AtomicUsize,
+}
+
+impl RpcConnectionPool {
+	pub fn new(endpoints: Vec<String>) -> Result<RpcConnectionPool> {
+		if endpoints.is_empty() {
+			bail!("RPC connection pool without endpoints");
+		}
+
+		Ok(RpcConnectionPool { endpoints, current: AtomicUsize::new(0) })
+	}
+
+	pub fn endpoints(&self) -> &[String] {
+		&self.endpoints
+	}
+
+	// Connects to the next endpoint, failed endpoints are skipped until each was tried once
+	pub async fn next_web3(&self) -> Result<Web3<WebSocket>> {
+		for _ in 0..self.endpoints.len() {
+			let endpoint = &self.endpoints
+				[self.current.fetch_add(1, Ordering::Relaxed) % self.endpoints.len()];
+
+			match WebSocket::new(endpoint).await {
+				Ok(transport) => return Ok(Web3::new(transport)),
+				Err(err) => warn!(error = %err, endpoint, "Failed to connect to RPC endpoint"),
+			}
+		}
+
+		Err(anyhow!("Failed to connect to any of {} RPC endpoints", self.endpoints.len()))
+	}
+}
+
+pub struct WebSocketSource {
+	pool: Arc<RpcConnectionPool>,
+	web3: Web3<WebSocket>,
+	block_stream: BlockStream,
+	rate_limiter: Option<SharedRateLimiter>,
+	health: Option<Arc<HealthState>>,
+}
+
+impl WebSocketSource {
+	pub async fn connect(pool: Arc<RpcConnectionPool>) -> WebSocketSource {
+		let (web3, block_stream) = reconnecting_stream(&pool).await;
+		WebSocketSource { pool, web3, block_stream, rate_limiter: None, health: None }
+	}
+
+	// Limits the requests for logs and blocks, the block subscription is not limited
+	pub fn with_rate_limit(mut self, rate_limiter: SharedRateLimiter) -> WebSocketSource {
+		self.rate_limiter = Some(rate_limiter);
+		self
+	}
+
+	// Reports the connection as lost while reconnecting
+	pub fn with_health(mut self, health: Arc<HealthState>) -> WebSocketSource {
+		health.set_connected(true);
+		self.health = Some(health);
+		self
+	}
+
+	async fn throttle(&self) {
+		if let Some(rate_limiter) = &self.rate_limiter {
+			rate_limiter.acquire().await;
+		}
+	}
+
+	async fn reconnect(&mut self) {
+		set_connected(&self.health, false);
+		(self.web3, self.block_stream) = reconnecting_stream(&self.pool).await;
+		set_connected(&self.health, true);
+	}
+}
+
+impl BlockSource for WebSocketSource {
+	async fn next_block(&mut self) -> Option<BlockHead> {
+		loop {
+			match self.block_stream.next().await {
+				Some(Ok(BlockHeader {
+					number: Some(number), hash: Some(hash), timestamp, ..
+				})) => {
+					record_head(&self.health);
+					return Some(BlockHead {
+						number: number.as_u64(),
+						hash,
+						timestamp: timestamp.as_u64(),
+					});
+				},
+				Some(_) => continue,
+				None => {
+					warn!("Block subscription closed");
+					self.reconnect().await;
+				},
+			}
+		}
+	}
+
+	async fn logs(&mut self, filter: Filter) -> Result<Vec<Log>> {
+		loop {
+			self.throttle().await;
+			match self.web3.eth().logs(filter.clone()).await {
+				Ok(logs) => return Ok(logs),
+				Err(err) => {
+					warn!(error = %err, "Failed to fetch logs");
+					self.reconnect().await;
+				},
+			}
+		}
+	}
+
+	async fn latest_block(&mut self) -> Result<u64> {
+		loop {
+			self.throttle().await;
+			match self.web3.eth().block_number().await {
+				Ok(number) => return Ok(number.as_u64()),
+				Err(err) => {
+					warn!(error = %err, "Failed to fetch latest block number");
+					self.reconnect().await;
+				},
+			}
+		}
+	}
+
+	async fn block_head(&mut self, number: u64) -> Result<BlockHead> {
+		self.tagged_block_head(BlockNumber::Number(U64::from(number))).await
+	}
+
+	async fn tagged_block_head(&mut self, tag: BlockNumber) -> Result<BlockHead> {
+		loop {
+			self.throttle().await;
+			match self.web3.eth().block(BlockId::Number(tag)).await {
+				Ok(block) => return block_head(tag, block),
+				Err(err) => {
+					warn!(error = %err, "Failed to fetch block header");
+					self.reconnect().await;
+				},
+			}
+		}
+	}
+
+	async fn call(&mut self, request: CallRequest) -> Result<Bytes> {
+		loop {
+			self.throttle().await;
+			match self.web3.eth().call(request.clone(), None).await {
+				Ok(output) => return Ok(output),
+				// Reverted calls fail the same way after reconnecting
+				Err(err @ web3::Error::Rpc(_)) => return Err(err.into()),
+				Err(err) => {
+					warn!(error = %err, "Failed to call contract");
+					self.reconnect().await;
+				},
+			}
+		}
+	}
+}
+
+pub struct PollingSource {
+	web3: Web3<Http>,
+	interval: Duration,
+	last: Option<BlockHead>,
+	pending: VecDeque<BlockHead>,
+	rate_limiter: Option<SharedRateLimiter>,
+	health: Option<Arc<HealthState>>,
+}
+
+impl PollingSource {
+	pub fn new(http_url: &str, interval: Duration) -> Result<PollingSource> {
+		let web3 = Web3::new(Http::new(http_url)?);
+		Ok(PollingSource {
+			web3,
+			interval,
+			last: None,
+			pending: VecDeque::new(),
+			rate_limiter: None,
+			health: None,
+		})
+	}
+
+	// Limits all requests, including polls of the latest block
+	pub fn with_rate_limit(mut self, rate_limiter: SharedRateLimiter) -> PollingSource {
+		self.rate_limiter = Some(rate_limiter);
+		self
+	}
+
+	// Reports the connection as lost while polls fail
+	pub fn with_health(mut self, health: Arc<HealthState>) -> PollingSource {
+		self.health = Some(health);
+		self
+	}
+
+	async fn throttle(&self) {
+		if let Some(rate_limiter) = &self.rate_limiter {
+			rate_limiter.acquire().await;
+		}
+	}
+
+	async fn poll(&mut self) -> Result<()> {
+		let latest = self.fetch_block(BlockNumber::Latest).await?;
+
+		match &self.last {
+			Some(last) if last.hash == latest.hash => (),
+			Some(last) if latest.number > last.number + 1 => {
+				// Fill in blocks produced since the previous poll
+				for number in last.number + 1..latest.number {
+					let block = self.fetch_block(BlockNumber::Number(U64::from(number))).await?;
+					self.pending.push_back(block);
+				}
+				self.pending.push_back(latest);
+			},
+			_ => self.pending.push_back(latest),
+		}
+
+		Ok(())
+	}
+
+	async fn fetch_block(&mut self, number: BlockNumber) -> Result<BlockHead> {
+		self.throttle().await;
+		block_head(number, self.web3.eth().block(BlockId::Number(number)).await?)
+	}
+}
+
+impl BlockSource for PollingSource {
+	async fn next_block(&mut self) -> Option<BlockHead> {
+		let mut backoff = INITIAL_BACKOFF;
+
+		loop {
+			if let Some(head) = self.pending.pop_front() {
+				record_head(&self.health);
+				self.last = Some(head.clone());
+				return Some(head);
+			}
+
+			let result = self.poll().await;
+			set_connected(&self.health, result.is_ok());
+
+			match result {
+				Ok(()) if self.pending.is_empty() => tokio::time::sleep(self.interval).await,
+				Ok(()) => backoff = INITIAL_BACKOFF,
+				Err(err) => {
+					warn!(error = %err, "Failed to poll latest block");
+					tokio::time::sleep(backoff).await;
+					backoff = next_backoff(backoff);
+				},
+			}
+		}
+	}
+
+	async fn logs(&mut self, filter: Filter) -> Result<Vec<Log>> {
+		let mut backoff = INITIAL_BACKOFF;
+
+		loop {
+			self.throttle().await;
+			match self.web3.eth().logs(filter.clone()).await {
+				Ok(logs) => return Ok(logs),
+				Err(err) => {
+					warn!(error = %err, "Failed to fetch logs");
+					tokio::time::sleep(backoff).await;
+					backoff = next_backoff(backoff);
+				},
+			}
+		}
+	}
+
+	async fn latest_block(&mut self) -> Result<u64> {
+		let mut backoff = INITIAL_BACKOFF;
+
+		loop {
+			self.throttle().await;
+			match self.web3.eth().block_number().await {
+				Ok(number) => return Ok(number.as_u64()),
+				Err(err) => {
+					warn!(error = %err, "Failed to fetch latest block number");
+					tokio::time::sleep(backoff).await;
+					backoff = next_backoff(backoff);
+				},
+			}
+		}
+	}
+
+	async fn block_head(&mut self, number: u64) -> Result<BlockHead> {
+		self.tagged_block_head(BlockNumber::Number(U64::from(number))).await
+	}
+
+	async fn tagged_block_head(&mut self, tag: BlockNumber) -> Result<BlockHead> {
+		let mut backoff = INITIAL_BACKOFF;
+
+		loop {
+			self.throttle().await;
+			match self.web3.eth().block(BlockId::Number(tag)).await {
+				Ok(block) => return block_head(tag, block),
+				Err(err) => {
+					warn!(error = %err, "Failed to fetch block header");
+					tokio::time::sleep(backoff).await;
+					backoff = next_backoff(backoff);
+				},
+			}
+		}
+	}
+
+	async fn call(&mut self, request: CallRequest) -> Result<Bytes> {
+		let mut backoff = INITIAL_BACKOFF;
+
+		loop {
+			self.throttle().await;
+			match self.web3.eth().call(request.clone(), None).await {
+				Ok(output) => return Ok(output),
+				// Reverted calls fail the same way when retried
+				Err(err @ web3::Error::Rpc(_)) => return Err(err.into()),
+				Err(err) => {
+					warn!(error = %err, "Failed to call contract");
+					tokio::time::sleep(backoff).await;
+					backoff = next_backoff(backoff);
+				},
+			}
+		}
+	}
+}
+
+// Follows the block of a tag like `finalized` instead of the latest block, the tag is looked up
+// whenever the wrapped source receives a block. Blocks the tag skipped over are filled in.
+pub struct FinalizedSource<S: BlockSource> {
+	source: S,
+	tag: BlockNumber,
+	// Last block queued to be returned
+	last: Option<u64>,
+	pending: VecDeque<BlockHead>,
+}
+
+impl<S: BlockSource> FinalizedSource<S> {
+	pub fn new(source: S, tag: BlockNumber) -> FinalizedSource<S> {
+		FinalizedSource { source, tag, last: None, pending: VecDeque::new() }
+	}
+
+	async fn poll(&mut self) -> Result<()> {
+		let head = self.source.tagged_block_head(self.tag).await?;
+
+		match self.last {
+			Some(last) if head.number <= last => return Ok(()),
+			Some(last) =>
+				for number in last + 1..head.number {
+					let block = self.source.block_head(number).await?;
+					self.last = Some(number);
+					self.pending.push_back(block);
+				},
+			None => (),
+		}
+		self.last = Some(head.number);
+		self.pending.push_back(head);
+
+		Ok(())
+	}
+}
+
+impl<S: BlockSource> BlockSource for FinalizedSource<S> {
+	async fn next_block(&mut self) -> Option<BlockHead> {
+		loop {
+			if let Some(head) = self.pending.pop_front() {
+				return Some(head);
+			}
+
+			self.source.next_block().await?;
+			if let Err(err) = self.poll().await {
+				warn!(error = %err, tag = ?self.tag, "Failed to fetch tagged block");
+			}
+		}
+	}
+
+	async fn logs(&mut self, filter: Filter) -> Result<Vec<Log>> {
+		self.source.logs(filter).await
+	}
+
+	// The block the tag refers to, later blocks are not final yet
+	async fn latest_block(&mut self) -> Result<u64> {
+		Ok(self.source.tagged_block_head(self.tag).await?.number)
+	}
+
+	async fn block_head(&mut self, number: u64) -> Result<BlockHead> {
+		self.source.block_head(number).await
+	}
+
+	async fn tagged_block_head(&mut self, tag: BlockNumber) -> Result<BlockHead> {
+		self.source.tagged_block_head(tag).await
+	}
+
+	async fn call(&mut self, request: CallRequest) -> Result<Bytes> {
+		self.source.call(request).await
+	}
+}
+
+fn block_head(number: BlockNumber, block: Option<Block<H256>>) -> Result<BlockHead> {
+	match block.with_context(|| format!("Block {:?} not found", number))? {
+		Block { number: Some(number), hash: Some(hash), timestamp, .. } =>
+			Ok(BlockHead { number: number.as_u64(), hash, timestamp: timestamp.as_u64() }),
+		_ => Err(anyhow!("Block {:?} is pending", number)),
+	}
+}
+
+async fn reconnecting_stream(pool: &RpcConnectionPool) -> (Web3<WebSocket>, BlockStream) {
+	let mut backoff = INITIAL_BACKOFF;
+	let mut attempt = 1;
+
+	loop {
+		info!(attempt, "Connecting to WebSocket endpoint");
+
+		match subscribe(pool).await {
+			Ok(connection) => return connection,
+			Err(err) => {
+				warn!(error = %err, retry_in = ?backoff, "Connection failed");
+				tokio::time::sleep(backoff).await;
+				backoff = next_backoff(backoff);
+				attempt += 1;
+			},
+		}
+	}
+}
+
+async fn subscribe(pool: &RpcConnectionPool) -> Result<(Web3<WebSocket>, BlockStream)> {
+	let web3 = pool.next_web3().await?;
+	let block_stream = web3.eth_subscribe().subscribe_new_heads().await?;
+
+	Ok((web3, block_stream))
+}
+
+fn set_connected(health: &Option<Arc<HealthState>>, is_connected: bool) {
+	if let Some(health) = health {
+		health.set_connected(is_connected);
+	}
+}
+
+fn record_head(health: &Option<Arc<HealthState>>) {
+	if let Some(health) = health {
+		health.record_head();
+	}
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+	(backoff * 2).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	mod rpc_connection_pool {
+		use super::*;
+		use tokio::net::TcpListener;
+
+		// Accepts WebSocket connections, returns the endpoint and the number of accepted ones
+		async fn serve() -> (String, Arc<AtomicUsize>) {
+			let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let addr = listener.local_addr().unwrap();
+			let connections = Arc::new(AtomicUsize::new(0));
+
+			let accepted = connections.clone();
+			tokio::spawn(async move {
+				while let Ok((stream, _)) = listener.accept().await {
+					accepted.fetch_add(1, Ordering::SeqCst);
+					tokio::spawn(async move {
+						let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+						while let Some(Ok(_)) = socket.next().await {}
+					});
+				}
+			});
+
+			(format!("ws://{}", addr), connections)
+		}
+
+		#[tokio::test]
+		async fn round_robin() {
+			let (a, a_connections) = serve().await;
+			let (b, b_connections) = serve().await;
+			let pool = RpcConnectionPool::new(vec![a, b]).unwrap();
+
+			for _ in 0..3 {
+				pool.next_web3().await.unwrap();
+			}
+
+			assert_eq!(a_connections.load(Ordering::SeqCst), 2);
+			assert_eq!(b_connections.load(Ordering::SeqCst), 1);
+		}
+
+		#[tokio::test]
+		async fn skips_unavailable_endpoint() {
+			let (endpoint, connections) = serve().await;
+			let pool =
+				RpcConnectionPool::new(vec!["ws://127.0.0.1:1".to_string(), endpoint]).unwrap();
+
+			pool.next_web3().await.unwrap();
+
+			assert_eq!(connections.load(Ordering::SeqCst), 1);
+		}
+
+		#[tokio::test]
+		async fn all_unavailable() {
+			let pool = RpcConnectionPool::new(vec!["ws://127.0.0.1:1".to_string()]).unwrap();
+
+			let err = pool.next_web3().await.unwrap_err();
+
+			assert_eq!(err.to_string(), "Failed to connect to any of 1 RPC endpoints");
+		}
+
+		#[test]
+		fn without_endpoints() {
+			assert!(RpcConnectionPool::new(Vec::new()).is_err());
+		}
+	}
+
+	mod finalized_source {
+		use super::*;
+		use crate::testing::{block_header, MockWeb3Provider};
+
+		fn provider(blocks: std::ops::Range<u64>) -> MockWeb3Provider {
+			blocks.fold(MockWeb3Provider::new().with_finality_lag(2), |provider, number| {
+				provider.with_block(block_header(number, H256::from_low_u64_be(number), 0), vec![])
+			})
+		}
+
+		#[tokio::test]
+		async fn follows_tag() {
+			let mut source = FinalizedSource::new(provider(100..106), BlockNumber::Finalized);
+
+			let mut numbers = Vec::new();
+			while let Some(block) = source.next_block().await {
+				numbers.push(block.number);
+			}
+
+			// Finalized two blocks after they were received
+			assert_eq!(numbers, vec![100, 101, 102, 103]);
+		}
+
+		#[tokio::test]
+		async fn fills_skipped_blocks() {
+			let mut source = FinalizedSource::new(provider(100..106), BlockNumber::Safe);
+			source.last = Some(99);
+			source.source.next_block().await;
+			source.source.next_block().await;
+			source.source.next_block().await;
+
+			assert_eq!(source.next_block().await.map(|block| block.number), Some(100));
+			assert_eq!(source.pending.len(), 1);
+			assert_eq!(source.latest_block().await.unwrap(), 101);
+		}
+	}
+
+	mod next_backoff {
+		use super::*;
+
+		#[test]
+		fn doubles() {
+			assert_eq!(next_backoff(INITIAL_BACKOFF), Duration::from_secs(2));
+			assert_eq!(next_backoff(Duration::from_secs(16)), Duration::from_secs(32));
+		}
+
+		#[test]
+		fn capped() {
+			assert_eq!(next_backoff(Duration::from_secs(32)), MAX_BACKOFF);
+			assert_eq!(next_backoff(MAX_BACKOFF), MAX_BACKOFF);
+		}
+	}
+}
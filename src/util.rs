@@ -0,0 +1,183 @@
+use rust_decimal::Decimal;
+use web3::{
+	signing::keccak256,
+	types::{H160, U256},
+};
+
+// Converts a signed ABI integer of `int_bits` bits to a decimal with `display_precision` decimal
+// places, for a token amount with `base` decimals. Digits beyond the precision are truncated.
+//
+// Signed integers are stored as two's complement: values with the highest of their `int_bits`
+// bits set are negative and equal that bit pattern minus 2^int_bits. The ABI sign-extends them
+// to 256 bits, bits beyond `int_bits` are ignored so extended and plain forms give the same value.
+//
+// Returns `None` if `int_bits` is not within 1..=256 or the result does not fit into a `Decimal`,
+// e.g. for amounts near the int128 bounds with few decimals or any magnitude beyond int128
+pub fn u256_to_signed_decimal(
+	n: U256,
+	int_bits: u32,
+	base: u32,
+	display_precision: u32,
+) -> Option<Decimal> {
+	if !(1..=256).contains(&int_bits) {
+		return None;
+	}
+
+	let mask = if int_bits == 256 { U256::MAX } else { (U256::one() << int_bits) - 1 };
+	let n = n & mask;
+
+	let is_negative = n.bit(int_bits as usize - 1);
+	let magnitude = if is_negative { (!n & mask) + 1 } else { n };
+
+	// Divisors beyond 256 bits truncate every magnitude to zero
+	let magnitude = if base >= display_precision {
+		U256::from(10)
+			.checked_pow((base - display_precision).into())
+			.map_or(U256::zero(), |divisor| magnitude / divisor)
+	} else {
+		U256::from(10)
+			.checked_pow((display_precision - base).into())
+			.and_then(|factor| magnitude.checked_mul(factor))?
+	};
+
+	// Anything beyond `i128::MAX` is far beyond the 96 bit mantissa of a `Decimal` anyway
+	if magnitude > U256::from(i128::MAX) {
+		return None;
+	}
+	let n = magnitude.as_u128() as i128;
+	let n = if is_negative { -n } else { n };
+
+	Decimal::try_from_i128_with_scale(n, display_precision).ok()
+}
+
+// EIP-55 mixed-case encoding, letters are capitalized where the matching nibble of the Keccak-256
+// hash of the lowercase hex address is 8 or higher
+pub fn checksum_address(addr: &H160) -> String {
+	let hex = format!("{:x}", addr);
+	let hash = keccak256(hex.as_bytes());
+
+	let checksummed: String = hex
+		.chars()
+		.enumerate()
+		.map(|(i, c)| {
+			let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0xf;
+			match nibble >= 8 {
+				true => c.to_ascii_uppercase(),
+				false => c,
+			}
+		})
+		.collect();
+	format!("0x{}", checksummed)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::int256;
+
+	mod u256_to_signed_decimal {
+		use super::*;
+
+		#[test]
+		fn int128_bounds() {
+			for n in [i128::MIN, i128::MIN + 1, -1, 0, 1, i128::MAX] {
+				let expected = Decimal::from_i128_with_scale(n / 10i128.pow(16), 2);
+
+				assert_eq!(u256_to_signed_decimal(int256(n), 128, 18, 2), Some(expected));
+			}
+		}
+
+		#[test]
+		fn without_sign_extension() {
+			let n = U256::from(u128::MAX - 9_999);
+
+			assert_eq!(u256_to_signed_decimal(n, 128, 6, 2), Some(Decimal::new(-1, 2)));
+		}
+
+		#[test]
+		fn int24() {
+			assert_eq!(
+				u256_to_signed_decimal(int256(-276_324), 24, 0, 0),
+				Some(Decimal::from(-276_324))
+			);
+			assert_eq!(
+				u256_to_signed_decimal(U256::from(0x80_0000), 24, 0, 0),
+				Some(Decimal::from(-8_388_608))
+			);
+		}
+
+		#[test]
+		fn full_width() {
+			let n = U256::MAX - 1_234_499;
+
+			assert_eq!(u256_to_signed_decimal(n, 256, 4, 2), Some(Decimal::new(-12345, 2)));
+		}
+
+		#[test]
+		fn more_places_than_decimals() {
+			assert_eq!(
+				u256_to_signed_decimal(U256::from(5), 128, 0, 2),
+				Some(Decimal::new(500, 2))
+			);
+		}
+
+		// Beyond the 96 bit mantissa of a `Decimal`
+		#[test]
+		fn out_of_range() {
+			for n in [i128::MIN, i128::MAX] {
+				assert_eq!(u256_to_signed_decimal(int256(n), 128, 6, 2), None);
+			}
+		}
+
+		#[test]
+		fn beyond_int128() {
+			let n = U256::one() << 200;
+
+			assert_eq!(u256_to_signed_decimal(n, 256, 18, 2), None);
+			assert_eq!(u256_to_signed_decimal(!n + 1, 256, 18, 2), None);
+		}
+
+		// Scaling up to more places than the token has decimals overflows 256 bits
+		#[test]
+		fn scaling_overflow() {
+			let n = U256::one() << 250;
+
+			assert_eq!(u256_to_signed_decimal(n, 256, 0, 2), None);
+			assert_eq!(u256_to_signed_decimal(U256::one(), 256, 0, 100), None);
+		}
+
+		#[test]
+		fn divisor_overflow() {
+			assert_eq!(u256_to_signed_decimal(U256::MAX >> 1, 256, 100, 0), Some(Decimal::ZERO));
+		}
+
+		#[test]
+		fn invalid_bits() {
+			assert_eq!(u256_to_signed_decimal(U256::one(), 0, 0, 0), None);
+			assert_eq!(u256_to_signed_decimal(U256::one(), 257, 0, 0), None);
+		}
+	}
+
+	mod checksum_address {
+		use super::*;
+
+		#[test]
+		fn eip55_examples() {
+			for expected in [
+				"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+				"0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+				"0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+				"0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+			] {
+				let addr: H160 = expected.to_lowercase().parse().unwrap();
+
+				assert_eq!(checksum_address(&addr), expected);
+			}
+		}
+
+		#[test]
+		fn without_letters() {
+			assert_eq!(checksum_address(&H160::zero()), format!("0x{}", "0".repeat(40)));
+		}
+	}
+}
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use web3::{
+	ethabi::{Address, Event, RawLog},
+	types::{Log, H256, U256},
+};
+
+use crate::{
+	event::{SwapAmounts, SwapEvent, TokenPair},
+	parser::{EventParser, ParseError, SwapParser},
+	util::u256_to_signed_decimal,
+};
+
+// All Uniswap V2 pairs charge 0.3%, in hundredths of a basis point like V3 fee tiers
+pub const V2_FEE_TIER: u32 = 3000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V2SwapEvent {
+	pub block_number: Option<u64>,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub pair: Address,
+	pub sender: Address,
+	pub to: Address,
+	// Raw amounts in the smallest token unit
+	pub amount0_in: U256,
+	pub amount1_in: U256,
+	pub amount0_out: U256,
+	pub amount1_out: U256,
+}
+
+impl V2SwapEvent {
+	// Net amounts flowing into the pair, like the signed amounts of V3 swaps
+	pub fn amounts(&self, pair: Arc<TokenPair>) -> Result<SwapAmounts, ParseError> {
+		// Both sides are uint112, so the two's complement of their difference fits an int256
+		let net = |amount_in: U256, amount_out: U256, decimals| {
+			let net = amount_in.overflowing_sub(amount_out).0;
+			u256_to_signed_decimal(net, 256, decimals, SwapParser::DECIMAL_PRECISION)
+				.ok_or(ParseError::AmountOutOfRange(net))
+		};
+
+		Ok(SwapAmounts {
+			token0_amount: net(self.amount0_in, self.amount0_out, pair.token0.decimals)?,
+			token1_amount: net(self.amount1_in, self.amount1_out, pair.token1.decimals)?,
+			pair,
+		})
+	}
+
+	// V2 pairs have no concentrated liquidity, so the pool state fields stay zero
+	pub fn to_swap_event(&self, pair: Arc<TokenPair>) -> Result<SwapEvent, ParseError> {
+		let amounts = self.amounts(pair)?;
+		SwapParser::validate(&amounts.abs())?;
+
+		Ok(SwapEvent {
+			block_number: self.block_number,
+			transaction_hash: self.transaction_hash,
+			log_index: self.log_index,
+			timestamp: None,
+			pool: self.pair,
+			sender: self.sender,
+			receiver: self.to,
+			direction: SwapParser::get_direction(&amounts)?,
+			amounts: amounts.abs(),
+			sqrt_price_x96: U256::zero(),
+			liquidity: 0,
+			tick: 0,
+		})
+	}
+}
+
+// Reserves of a V2 pair, emitted after every swap, mint and burn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V2SyncEvent {
+	pub block_number: Option<u64>,
+	pub pair: Address,
+	pub reserve0: u128,
+	pub reserve1: u128,
+}
+
+pub struct V2SwapParser;
+
+impl V2SwapParser {
+	pub fn parse_swap(log: Log, abi: &Event) -> Result<V2SwapEvent, ParseError> {
+		let block_number = log.block_number.map(|n| n.as_u64());
+		let transaction_hash = log.transaction_hash;
+		let log_index = log.log_index.map(|i| i.as_u64());
+		let pair = log.address;
+
+		let log = &abi.parse_log(RawLog { topics: log.topics, data: log.data.0 })?;
+
+		Ok(V2SwapEvent {
+			block_number,
+			transaction_hash,
+			log_index,
+			pair,
+			sender: SwapParser::get_address(log, "sender")?,
+			to: SwapParser::get_address(log, "to")?,
+			amount0_in: SwapParser::get_uint(log, "amount0In")?,
+			amount1_in: SwapParser::get_uint(log, "amount1In")?,
+			amount0_out: SwapParser::get_uint(log, "amount0Out")?,
+			amount1_out: SwapParser::get_uint(log, "amount1Out")?,
+		})
+	}
+
+	pub fn parse_sync(log: Log, abi: &Event) -> Result<V2SyncEvent, ParseError> {
+		let block_number = log.block_number.map(|n| n.as_u64());
+		let pair = log.address;
+
+		let log = &abi.parse_log(RawLog { topics: log.topics, data: log.data.0 })?;
+
+		Ok(V2SyncEvent {
+			block_number,
+			pair,
+			// uint112
+			reserve0: SwapParser::get_uint(log, "reserve0")?.low_u128(),
+			reserve1: SwapParser::get_uint(log, "reserve1")?.low_u128(),
+		})
+	}
+}
+
+impl EventParser for V2SwapParser {
+	fn parse(&self, log: Log, abi: &Event, pair: &Arc<TokenPair>) -> Result<SwapEvent, ParseError> {
+		Self::parse_swap(log, abi)?.to_swap_event(pair.clone())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		event::SwapDirection,
+		testing::{
+			swap_event_abi, v2_swap_event_abi, v2_swap_log, v2_sync_event_abi, v2_sync_log,
+			SwapLogBuilder,
+		},
+	};
+	use rust_decimal::Decimal;
+
+	mod parse_swap {
+		use super::*;
+
+		#[test]
+		fn ok() {
+			let abi = v2_swap_event_abi();
+			let log = Log {
+				block_number: Some(123.into()),
+				transaction_hash: Some(H256::from([5; 32])),
+				log_index: Some(7.into()),
+				..v2_swap_log(
+					&abi,
+					Address::from([4; 20]),
+					Address::from([1; 20]),
+					Address::from([2; 20]),
+					[1_234_560_000_000_000_000_000, 0, 0, 1_233_000_001],
+				)
+			};
+
+			assert_eq!(
+				V2SwapParser::parse_swap(log, &abi).unwrap(),
+				V2SwapEvent {
+					block_number: Some(123),
+					transaction_hash: Some(H256::from([5; 32])),
+					log_index: Some(7),
+					pair: Address::from([4; 20]),
+					sender: Address::from([1; 20]),
+					to: Address::from([2; 20]),
+					amount0_in: U256::from(1_234_560_000_000_000_000_000u128),
+					amount1_in: U256::zero(),
+					amount0_out: U256::zero(),
+					amount1_out: U256::from(1_233_000_001),
+				}
+			);
+		}
+
+		#[test]
+		fn v3_log() {
+			let log = SwapLogBuilder::new(&swap_event_abi()).build();
+
+			assert!(matches!(
+				V2SwapParser::parse_swap(log, &v2_swap_event_abi()),
+				Err(ParseError::LogParseFailure(_))
+			));
+		}
+	}
+
+	mod parse_sync {
+		use super::*;
+
+		#[test]
+		fn ok() {
+			let abi = v2_sync_event_abi();
+			let log = v2_sync_log(&abi, Address::from([4; 20]), 5_000_000, 6_000);
+
+			assert_eq!(
+				V2SwapParser::parse_sync(log, &abi).unwrap(),
+				V2SyncEvent {
+					block_number: None,
+					pair: Address::from([4; 20]),
+					reserve0: 5_000_000,
+					reserve1: 6_000,
+				}
+			);
+		}
+	}
+
+	mod parse {
+		use super::*;
+		use crate::event::TokenInfo;
+
+		fn parse(amounts: [u128; 4]) -> Result<SwapEvent, ParseError> {
+			let abi = v2_swap_event_abi();
+			let log = v2_swap_log(
+				&abi,
+				Address::from([4; 20]),
+				Address::from([1; 20]),
+				Address::from([2; 20]),
+				amounts,
+			);
+
+			V2SwapParser.parse(log, &abi, &TokenPair::dai_usdc())
+		}
+
+		#[test]
+		fn token0_to_token1() {
+			let event = parse([1_234_560_000_000_000_000_000, 0, 0, 1_233_000_001]).unwrap();
+
+			assert_eq!(event.pool, Address::from([4; 20]));
+			assert_eq!(event.sender, Address::from([1; 20]));
+			assert_eq!(event.receiver, Address::from([2; 20]));
+			assert_eq!(event.direction, SwapDirection::Token0ToToken1);
+			assert_eq!(
+				event.amounts,
+				SwapAmounts::dai_usdc(Decimal::new(123456, 2), Decimal::new(123300, 2))
+			);
+			assert_eq!(event.sqrt_price_x96, U256::zero());
+		}
+
+		#[test]
+		fn token1_to_token0() {
+			let event = parse([0, 500_100_000, 500_000_000_000_000_000_000, 0]).unwrap();
+
+			assert_eq!(event.direction, SwapDirection::Token1ToToken0);
+			assert_eq!(
+				event.amounts,
+				SwapAmounts::dai_usdc(Decimal::new(50000, 2), Decimal::new(50010, 2))
+			);
+		}
+
+		// Flash swaps may send a token out and have part of it paid back
+		#[test]
+		fn net_amounts() {
+			let event = parse([1_000_000_000_000_000_000, 1_000_000, 3_000_000_000_000_000_000, 0])
+				.unwrap();
+
+			assert_eq!(event.direction, SwapDirection::Token1ToToken0);
+			assert_eq!(
+				event.amounts,
+				SwapAmounts::dai_usdc(Decimal::new(200, 2), Decimal::new(100, 2))
+			);
+		}
+
+		// Reserves are uint112, whose maximum exceeds the decimal range with 6 decimals
+		#[test]
+		fn uint112_max() {
+			let usdc_pair = Arc::new(TokenPair {
+				token0: TokenInfo { symbol: "USDC".to_string(), decimals: 6 },
+				token1: TokenInfo { symbol: "WETH".to_string(), decimals: 18 },
+			});
+			let abi = v2_swap_event_abi();
+			let uint112_max = (1u128 << 112) - 1;
+			let log = v2_swap_log(
+				&abi,
+				Address::from([4; 20]),
+				Address::from([1; 20]),
+				Address::from([2; 20]),
+				[uint112_max, 0, 0, 1],
+			);
+
+			let err = V2SwapParser.parse(log, &abi, &usdc_pair).unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				format!("Amount {:#x} exceeds the decimal range", U256::from(uint112_max))
+			);
+		}
+
+		#[test]
+		fn only_in() {
+			let err = parse([1_000_000_000_000_000_000, 1_000_000, 0, 0]).unwrap_err();
+
+			assert_eq!(
+				err.to_string(),
+				"Swap amounts must have distinct signs, but both are positive"
+			);
+		}
+	}
+}
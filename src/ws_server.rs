@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use std::{future::Future, net::SocketAddr};
+use tokio::{
+	net::{TcpListener, TcpStream},
+	sync::broadcast::{self, error::RecvError},
+};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+
+use crate::{
+	event::SwapEvent, network::Network, output::JsonSwapEvent, queue::MemoryBoundedEventQueue,
+	server,
+};
+
+// Size of the serialized confirmed events replayed to clients connecting after they were emitted,
+// a few thousand events
+pub const WS_REPLAY_CAPACITY_BYTES: usize = 1024 * 1024;
+
+// Confirmed events buffered for each connected client
+const CLIENT_CHANNEL_CAPACITY: usize = 1000;
+
+// Transactions are linked on the network's block explorer
+pub async fn serve(
+	addr: SocketAddr,
+	events: broadcast::Sender<SwapEvent>,
+	network: Network,
+) -> Result<impl Future<Output = Result<()>>> {
+	let listener = server::bind(addr)
+		.and_then(|listener| Ok(TcpListener::from_std(listener)?))
+		.with_context(|| format!("Failed to serve WebSocket on {}", addr))?;
+
+	Ok(accept_clients(listener, events.subscribe(), network, WS_REPLAY_CAPACITY_BYTES))
+}
+
+// Recording events and subscribing clients happen on the same task,
+// so every event reaches a client either through the replay or the broadcast, never both
+async fn accept_clients(
+	listener: TcpListener,
+	mut events: broadcast::Receiver<SwapEvent>,
+	network: Network,
+	capacity_bytes: usize,
+) -> Result<()> {
+	let mut replay = MemoryBoundedEventQueue::new(capacity_bytes);
+	let (clients, _) = broadcast::channel(CLIENT_CHANNEL_CAPACITY);
+
+	loop {
+		tokio::select! {
+			// Pending events go first, so clients never miss events confirmed before they connected
+			biased;
+
+			event = events.recv() => match event {
+				Ok(event) => {
+					match message(&event, network) {
+						Ok(message) => replay.push(event.clone(), message.len()),
+						Err(err) => warn!(%err, "Failed to serialize event for replay"),
+					}
+					// Sending only fails while no client is connected
+					let _ = clients.send(event);
+				},
+				Err(RecvError::Lagged(skipped)) =>
+					warn!(skipped, "WebSocket server lagging, skipped events"),
+				Err(RecvError::Closed) => return Ok(()),
+			},
+			accepted = listener.accept() => match accepted {
+				Ok((stream, addr)) => {
+					let replay = replay.iter().cloned().collect();
+					tokio::spawn(handle_client(stream, addr, replay, clients.subscribe(), network));
+				},
+				Err(err) => warn!(%err, "Failed to accept WebSocket connection"),
+			},
+		}
+	}
+}
+
+async fn handle_client(
+	stream: TcpStream,
+	addr: SocketAddr,
+	replay: Vec<SwapEvent>,
+	events: broadcast::Receiver<SwapEvent>,
+	network: Network,
+) {
+	info!(%addr, "WebSocket client connected");
+
+	match send_events(stream, replay, events, network).await {
+		Ok(()) => info!(%addr, "WebSocket client disconnected"),
+		Err(err) => info!(%addr, %err, "WebSocket client disconnected"),
+	}
+}
+
+async fn send_events(
+	stream: TcpStream,
+	replay: Vec<SwapEvent>,
+	mut events: broadcast::Receiver<SwapEvent>,
+	network: Network,
+) -> Result<()> {
+	let mut socket = tokio_tungstenite::accept_async(stream).await?;
+
+	for event in &replay {
+		socket.send(message(event, network)?).await?;
+	}
+
+	loop {
+		tokio::select! {
+			event = events.recv() => match event {
+				Ok(event) => socket.send(message(&event, network)?).await?,
+				Err(RecvError::Lagged(skipped)) =>
+					warn!(skipped, "WebSocket client lagging, skipped events"),
+				Err(RecvError::Closed) => return Ok(socket.close(None).await?),
+			},
+			// Messages from clients are ignored, pings are answered by tungstenite itself
+			message = socket.next() => match message {
+				Some(Ok(_)) => (),
+				Some(Err(err)) => return Err(err.into()),
+				None => return Ok(()),
+			},
+		}
+	}
+}
+
+fn message(event: &SwapEvent, network: Network) -> Result<Message> {
+	Ok(Message::Text(serde_json::to_string(&JsonSwapEvent::with_network(event, &network))?))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::SwapEventBuilder;
+	use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+	type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+	fn event(block_number: u64) -> SwapEvent {
+		SwapEventBuilder::default().block_number(block_number).build()
+	}
+
+	// Replays up to `capacity` events like the ones of `event`
+	async fn start(events: &broadcast::Sender<SwapEvent>, capacity: usize) -> SocketAddr {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let capacity_bytes = capacity * message(&event(1), Network::Mainnet).unwrap().len();
+		tokio::spawn(accept_clients(
+			listener,
+			events.subscribe(),
+			Network::Mainnet,
+			capacity_bytes,
+		));
+		addr
+	}
+
+	async fn connect(addr: SocketAddr) -> Client {
+		connect_async(format!("ws://{}", addr)).await.unwrap().0
+	}
+
+	async fn receive_block_number(client: &mut Client) -> u64 {
+		let message = client.next().await.unwrap().unwrap();
+		let event: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+		event["block_number"].as_u64().unwrap()
+	}
+
+	mod accept_clients {
+		use super::*;
+
+		#[tokio::test]
+		async fn replays_then_streams() {
+			let (events, _) = broadcast::channel(16);
+			let addr = start(&events, 2).await;
+
+			for block_number in 1..=3 {
+				events.send(event(block_number)).unwrap();
+			}
+			let mut client = connect(addr).await;
+			events.send(event(4)).unwrap();
+
+			assert_eq!(receive_block_number(&mut client).await, 2);
+			assert_eq!(receive_block_number(&mut client).await, 3);
+			assert_eq!(receive_block_number(&mut client).await, 4);
+		}
+
+		#[tokio::test]
+		async fn survives_disconnected_client() {
+			let (events, _) = broadcast::channel(16);
+			let addr = start(&events, 10).await;
+
+			let mut leaving = connect(addr).await;
+			leaving.close(None).await.unwrap();
+			drop(leaving);
+			events.send(event(1)).unwrap();
+
+			let mut client = connect(addr).await;
+			events.send(event(2)).unwrap();
+
+			assert_eq!(receive_block_number(&mut client).await, 1);
+			assert_eq!(receive_block_number(&mut client).await, 2);
+		}
+	}
+}